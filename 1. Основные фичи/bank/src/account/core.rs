@@ -0,0 +1,102 @@
+use super::{AccountId, errors::AccountError};
+use crate::{
+    Name,
+    clock::{Clock, SystemClock},
+};
+
+/// # Счет пользователя
+///
+/// Имя уникально в рамках хранилища, а `id` не меняется
+/// даже после закрытия и повторного открытия счета.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Account {
+    id: AccountId,
+    name: Name,
+    created_at: u64,
+    closed: bool,
+}
+
+impl Account {
+    /// Создание нового открытого счета
+    pub(crate) fn new(id: AccountId, name: Name) -> Self {
+        Self::load(id, name, SystemClock.now(), false)
+    }
+
+    /// Полная загрузка счета
+    pub fn load(id: AccountId, name: Name, created_at: u64, closed: bool) -> Self {
+        Self {
+            id,
+            name,
+            created_at,
+            closed,
+        }
+    }
+
+    pub fn id(&self) -> AccountId {
+        self.id
+    }
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+    pub fn created_at(&self) -> u64 {
+        self.created_at
+    }
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    pub(crate) fn set_closed(&mut self, closed: bool) {
+        self.closed = closed;
+    }
+}
+
+impl From<&Account> for String {
+    fn from(account: &Account) -> Self {
+        format!(
+            "{};{};{};{}",
+            account.id, account.name, account.created_at, account.closed
+        )
+    }
+}
+
+impl TryFrom<String> for Account {
+    type Error = AccountError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let parts: Vec<&str> = value.trim().split(';').collect();
+        let [id, name, created_at, closed] = parts.as_slice() else {
+            return Err(AccountError::ParseError(value));
+        };
+
+        let id = id
+            .parse::<AccountId>()
+            .map_err(|_| AccountError::ParseError(format!("Неверный id: {}", id)))?;
+        let created_at = created_at
+            .parse::<u64>()
+            .map_err(|_| AccountError::ParseError(format!("Неверное время: {}", created_at)))?;
+        let closed = closed
+            .parse::<bool>()
+            .map_err(|_| AccountError::ParseError(format!("Неверный флаг: {}", closed)))?;
+
+        Ok(Account::load(id, name.to_string(), created_at, closed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_load_save() {
+        let account = Account::load(7, "Julia".into(), 1764444526, true);
+        let line = String::from(&account);
+        assert_eq!(line, "7;Julia;1764444526;true");
+        assert_eq!(Account::try_from(line), Ok(account));
+    }
+
+    #[test]
+    fn test_account_try_from_error() {
+        assert!(Account::try_from("7;Julia;1764444526".to_string()).is_err());
+        assert!(Account::try_from("x;Julia;1764444526;false".to_string()).is_err());
+    }
+}
@@ -0,0 +1,6 @@
+/// Ошибки работы со счетами
+#[derive(Debug, PartialEq)]
+pub enum AccountError {
+    /// Ошибка парсинга
+    ParseError(String),
+}
@@ -0,0 +1,11 @@
+mod core;
+pub mod errors;
+mod registry;
+
+pub use core::Account;
+pub use registry::AccountRegistry;
+
+/// Стабильный числовой идентификатор счета.
+///
+/// `0` зарезервирован форматами `parser` под "нет пользователя".
+pub type AccountId = u64;
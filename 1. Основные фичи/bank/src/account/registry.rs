@@ -0,0 +1,126 @@
+use super::{Account, AccountId};
+use crate::Name;
+use std::collections::HashMap;
+
+/// # Реестр счетов
+///
+/// Двусторонняя связь `id <-> имя`. Закрытые счета остаются в реестре,
+/// чтобы их id не переиспользовались.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountRegistry {
+    accounts: HashMap<AccountId, Account>,
+    ids: HashMap<Name, AccountId>,
+    next_id: AccountId,
+}
+
+impl Default for AccountRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AccountRegistry {
+    /// Создание пустого реестра
+    pub fn new() -> Self {
+        Self {
+            accounts: HashMap::new(),
+            ids: HashMap::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Открыть счет: новый, либо ранее закрытый с тем же именем
+    pub(crate) fn open(&mut self, name: &Name) -> &Account {
+        let id = match self.ids.get(name) {
+            Some(id) => *id,
+            None => {
+                let id = self.next_id;
+                self.next_id += 1;
+                self.ids.insert(name.clone(), id);
+                self.accounts.insert(id, Account::new(id, name.clone()));
+                id
+            }
+        };
+        let account = self
+            .accounts
+            .get_mut(&id)
+            .expect("Реестр рассинхронизирован");
+        account.set_closed(false);
+        account
+    }
+
+    /// Закрыть счет
+    pub(crate) fn close(&mut self, name: &Name) -> Option<&Account> {
+        let id = self.ids.get(name)?;
+        let account = self.accounts.get_mut(id)?;
+        account.set_closed(true);
+        Some(account)
+    }
+
+    /// Добавить уже существующий счет (загрузка, журнал)
+    pub(crate) fn insert(&mut self, account: Account) {
+        if let Some(old_id) = self.ids.insert(account.name().clone(), account.id())
+            && old_id != account.id()
+        {
+            self.accounts.remove(&old_id);
+        }
+        self.next_id = self.next_id.max(account.id() + 1);
+        self.accounts.insert(account.id(), account);
+    }
+
+    /// Получить счет по id
+    pub fn get(&self, id: AccountId) -> Option<&Account> {
+        self.accounts.get(&id)
+    }
+
+    /// Получить счет по имени
+    pub fn get_by_name(&self, name: &str) -> Option<&Account> {
+        self.ids.get(name).and_then(|id| self.accounts.get(id))
+    }
+
+    /// id счета по имени
+    pub fn id_of(&self, name: &str) -> Option<AccountId> {
+        self.ids.get(name).copied()
+    }
+
+    /// Имя счета по id
+    pub fn name_of(&self, id: AccountId) -> Option<&Name> {
+        self.accounts.get(&id).map(|a| a.name())
+    }
+
+    /// Все счета реестра, в порядке id
+    pub fn all(&self) -> Vec<&Account> {
+        let mut accounts: Vec<&Account> = self.accounts.values().collect();
+        accounts.sort_by_key(|a| a.id());
+        accounts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_open_close() {
+        let mut registry = AccountRegistry::new();
+        let alice = registry.open(&"Alice".into()).id();
+        let bob = registry.open(&"Bob".into()).id();
+        assert_ne!(alice, bob);
+        assert_eq!(registry.id_of("Alice"), Some(alice));
+        assert_eq!(registry.name_of(bob), Some(&"Bob".to_string()));
+
+        assert!(registry.close(&"Alice".into()).unwrap().is_closed());
+        // Повторное открытие сохраняет id
+        let reopened = registry.open(&"Alice".into());
+        assert_eq!(reopened.id(), alice);
+        assert!(!reopened.is_closed());
+    }
+
+    #[test]
+    fn test_registry_insert_keeps_next_id() {
+        let mut registry = AccountRegistry::new();
+        registry.insert(Account::load(10, "Ivan".into(), 0, false));
+        assert_eq!(registry.open(&"Julia".into()).id(), 11);
+        assert_eq!(registry.get(10).map(|a| a.name().as_str()), Some("Ivan"));
+    }
+}
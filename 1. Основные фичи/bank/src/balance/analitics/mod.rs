@@ -0,0 +1,388 @@
+mod report;
+mod suspicious;
+
+use super::{
+    Balance, BalanceSize,
+    operations::{Operation, OperationAmount, OperationStatus, OperationType},
+    scheduler::DAY,
+};
+use crate::{Name, clock::Timestamp, storage::Storage};
+use std::collections::{BTreeMap, HashMap};
+
+pub use report::{Counterparty, DailyFlow, Suspicion, SuspicionKind, Turnover};
+pub use suspicious::SuspicionRules;
+
+/// Движение средств успешной операции: (зачислено, списано).
+/// У закрытия счета суммы нет
+fn flow(op: &Operation) -> Option<(OperationAmount, OperationAmount)> {
+    if op.status != OperationStatus::SUCCESS {
+        return None;
+    }
+    match op.tx_type {
+        OperationType::Deposit(v)
+        | OperationType::Interest(v)
+        | OperationType::Transfer(_, v, true)
+        | OperationType::StandingOrder(_, v, true) => Some((v, 0)),
+        OperationType::Withdraw(v)
+        | OperationType::Fee(v)
+        | OperationType::Transfer(_, v, false)
+        | OperationType::StandingOrder(_, v, false) => Some((0, v)),
+        OperationType::Close => None,
+    }
+}
+
+/// # Аналитика баланса
+pub struct Analitic;
+
+impl Analitic {
+    /// Наиболее активный пользователь
+    pub fn find_most_active(storage: &Storage) -> Option<(String, &Balance)> {
+        let accounts = storage.get_all();
+        if accounts.is_empty() {
+            return None;
+        }
+        let result = accounts
+            .into_iter()
+            .map(|(n, b)| {
+                let a: u64 = b
+                    .get_history()
+                    .iter()
+                    .map(|op| match op.tx_type {
+                        OperationType::Deposit(v) => v,
+                        OperationType::Withdraw(v) => v,
+                        OperationType::Transfer(_, v, _) => v,
+                        _ => 0,
+                    })
+                    .sum();
+                (a, (n, b))
+            })
+            .max_by(|a, b| a.0.cmp(&b.0))
+            .unwrap()
+            .1
+            .clone();
+
+        Some(result)
+    }
+
+    /// Наиболее богатый
+    pub fn find_most_rich(storage: &Storage) -> Option<(String, &Balance)> {
+        let accounts = storage.get_all();
+        if accounts.is_empty() {
+            return None;
+        }
+        let result = accounts
+            .into_iter()
+            .max_by(|a, b| a.1.get_value().cmp(&b.1.get_value()))
+            .unwrap();
+        Some(result)
+    }
+
+    /// Баланс счета на момент `timestamp`, восстановленный по истории.
+    ///
+    /// Начальный баланс - текущий за вычетом всех операций истории,
+    /// а если счет закрывался - `0`.
+    pub fn balance_at(storage: &Storage, name: &Name, timestamp: Timestamp) -> Option<BalanceSize> {
+        let balance = storage.get_balance(name)?;
+        let history = balance.get_history();
+
+        let is_close = |op: &Operation| {
+            op.tx_type == OperationType::Close && op.status == OperationStatus::SUCCESS
+        };
+        let net = |op: &Operation| {
+            flow(op).map_or(0, |(credit, debit)| {
+                BalanceSize::from(credit) - BalanceSize::from(debit)
+            })
+        };
+
+        let mut value = if history.iter().any(is_close) {
+            0
+        } else {
+            balance.get_value() - history.iter().map(net).sum::<BalanceSize>()
+        };
+        for op in history.iter().filter(|op| op.timestamp() <= timestamp) {
+            if is_close(op) {
+                value = 0;
+            } else {
+                value += net(op);
+            }
+        }
+        Some(value)
+    }
+
+    /// Обороты счета за период `[from, to)`
+    pub fn turnover(
+        storage: &Storage,
+        name: &Name,
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Option<Turnover> {
+        let mut turnover = Turnover {
+            name: name.clone(),
+            from,
+            to,
+            credit: 0,
+            debit: 0,
+            operations: 0,
+        };
+        for op in storage.get_balance(name)?.get_history() {
+            if !(from..to).contains(&op.timestamp()) || op.status != OperationStatus::SUCCESS {
+                continue;
+            }
+            turnover.operations += 1;
+            if let Some((credit, debit)) = flow(op) {
+                turnover.credit += BalanceSize::from(credit);
+                turnover.debit += BalanceSize::from(debit);
+            }
+        }
+        Some(turnover)
+    }
+
+    /// Первые `n` контрагентов по объему переводов, включая постоянные поручения
+    pub fn top_counterparties(
+        storage: &Storage,
+        name: &Name,
+        n: usize,
+    ) -> Option<Vec<Counterparty>> {
+        let mut counterparties: HashMap<&Name, Counterparty> = HashMap::new();
+        for op in storage.get_balance(name)?.get_history() {
+            let (OperationType::Transfer(other, v, is_to)
+            | OperationType::StandingOrder(other, v, is_to)) = &op.tx_type
+            else {
+                continue;
+            };
+            if op.status != OperationStatus::SUCCESS {
+                continue;
+            }
+            let counterparty = counterparties.entry(other).or_insert_with(|| Counterparty {
+                name: other.clone(),
+                sent: 0,
+                received: 0,
+                transfers: 0,
+            });
+            counterparty.transfers += 1;
+            if *is_to {
+                counterparty.received += BalanceSize::from(*v);
+            } else {
+                counterparty.sent += BalanceSize::from(*v);
+            }
+        }
+
+        let mut counterparties: Vec<Counterparty> = counterparties.into_values().collect();
+        counterparties.sort_unstable_by(|a, b| {
+            b.volume()
+                .cmp(&a.volume())
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        counterparties.truncate(n);
+        Some(counterparties)
+    }
+
+    /// Движение средств счета по суткам (UTC) за период `[from, to)`.
+    /// Только сутки с операциями: период может быть сколь угодно длинным
+    pub fn daily_flow(
+        storage: &Storage,
+        name: &Name,
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Option<Vec<DailyFlow>> {
+        let history = storage.get_balance(name)?.get_history();
+
+        let mut days: BTreeMap<Timestamp, DailyFlow> = BTreeMap::new();
+        for op in history
+            .iter()
+            .filter(|op| (from..to).contains(&op.timestamp()))
+        {
+            let Some((credit, debit)) = flow(op) else {
+                continue;
+            };
+            let start = op.timestamp() / DAY * DAY;
+            let day = days.entry(start).or_insert_with(|| DailyFlow {
+                day: start,
+                credit: 0,
+                debit: 0,
+                net: 0,
+            });
+            day.credit += BalanceSize::from(credit);
+            day.debit += BalanceSize::from(debit);
+            day.net = day.credit - day.debit;
+        }
+        Some(days.into_values().collect())
+    }
+
+    /// Подозрительная активность: много мелких снятий и переводы туда-обратно
+    pub fn find_suspicious(storage: &Storage, rules: &SuspicionRules) -> Vec<Suspicion> {
+        suspicious::find(storage, rules)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::manager::BalanceManager;
+    use super::*;
+
+    fn get_storage() -> Storage {
+        let mut storage = Storage::new();
+        storage.add_user("a".into());
+        storage.add_user("b".into());
+
+        let _ = storage.deposit(&"a".into(), 15);
+        let _ = storage.deposit(&"b".into(), 20);
+        let _ = storage.withdraw(&"a".into(), 15);
+        storage
+    }
+
+    #[test]
+    fn test_find_most_active_none() {
+        let storage = super::Storage::new();
+        assert_eq!(Analitic::find_most_active(&storage), None);
+    }
+
+    #[test]
+    fn test_find_most_active_some() {
+        let storage = get_storage();
+        let balance = storage.get_balance(&"a".into()).unwrap();
+        assert_eq!(
+            Analitic::find_most_active(&storage),
+            Some(("a".into(), balance))
+        );
+    }
+
+    #[test]
+    fn test_find_most_rich_none() {
+        let storage = super::Storage::new();
+        assert_eq!(Analitic::find_most_rich(&storage), None);
+    }
+
+    /// Хранилище с одним счетом и операциями в заданное время
+    fn timed(name: &str, ops: Vec<Operation>) -> Storage {
+        let mut storage = Storage::new();
+        storage.add_user(name.into());
+        for op in ops {
+            storage.apply_operation(&name.into(), op).unwrap();
+        }
+        storage
+    }
+
+    fn op(id: u64, timestamp: Timestamp, tx_type: OperationType) -> Operation {
+        Operation::load(id, timestamp, tx_type, OperationStatus::PENDING, None)
+    }
+
+    #[test]
+    fn test_balance_at() {
+        let storage = timed(
+            "a",
+            vec![
+                op(1, 10, OperationType::Deposit(100)),
+                op(2, 20, OperationType::Withdraw(30)),
+                op(3, 30, OperationType::Close),
+                op(4, 40, OperationType::Deposit(5)),
+            ],
+        );
+        let at = |ts| Analitic::balance_at(&storage, &"a".into(), ts);
+        assert_eq!(at(0), Some(0));
+        assert_eq!(at(15), Some(100));
+        assert_eq!(at(25), Some(70));
+        assert_eq!(at(35), Some(0));
+        assert_eq!(at(45), Some(5));
+        assert_eq!(Analitic::balance_at(&storage, &"b".into(), 0), None);
+
+        // Без закрытия учитывается начальный баланс
+        let storage = get_storage();
+        let a = storage.get_balance(&"b".into()).unwrap().get_value();
+        assert_eq!(
+            Analitic::balance_at(&storage, &"b".into(), u64::MAX),
+            Some(a)
+        );
+        assert_eq!(Analitic::balance_at(&storage, &"b".into(), 0), Some(0));
+    }
+
+    #[test]
+    fn test_turnover_and_daily_flow() {
+        let storage = timed(
+            "a",
+            vec![
+                op(1, 10, OperationType::Deposit(100)),
+                op(2, DAY + 1, OperationType::Withdraw(30)),
+                op(3, DAY + 2, OperationType::Interest(5)),
+                op(4, 3 * DAY, OperationType::Fee(1)),
+            ],
+        );
+        let turnover = Analitic::turnover(&storage, &"a".into(), 0, 3 * DAY).unwrap();
+        assert_eq!((turnover.credit, turnover.debit), (105, 30));
+        assert_eq!(turnover.operations, 3);
+        assert_eq!(turnover.net(), 75);
+
+        let days = Analitic::daily_flow(&storage, &"a".into(), 0, 3 * DAY).unwrap();
+        let net: Vec<_> = days.iter().map(|d| (d.day, d.net)).collect();
+        assert_eq!(net, [(0, 100), (DAY, -25)]);
+        assert!(
+            Analitic::daily_flow(&storage, &"a".into(), 10, 10)
+                .unwrap()
+                .is_empty()
+        );
+
+        // Огромный период не выделяет память на пустые сутки
+        let days = Analitic::daily_flow(&storage, &"a".into(), 0, u64::MAX).unwrap();
+        let net: Vec<_> = days.iter().map(|d| (d.day, d.net)).collect();
+        assert_eq!(net, [(0, 100), (DAY, -25), (3 * DAY, -1)]);
+    }
+
+    #[test]
+    fn test_top_counterparties() {
+        let mut storage = get_storage();
+        storage.add_user("c".into());
+        storage.transfer(&"b".into(), &"a".into(), 5).unwrap();
+        storage.transfer(&"b".into(), &"c".into(), 7).unwrap();
+        storage.transfer(&"a".into(), &"b".into(), 3).unwrap();
+
+        let top = Analitic::top_counterparties(&storage, &"b".into(), 1).unwrap();
+        assert_eq!(
+            top,
+            [Counterparty {
+                name: "a".into(),
+                sent: 5,
+                received: 3,
+                transfers: 2,
+            }]
+        );
+        assert_eq!(
+            Analitic::top_counterparties(&storage, &"b".into(), 5)
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_find_suspicious() {
+        let mut storage = get_storage();
+        for _ in 0..5 {
+            storage.withdraw(&"b".into(), 1).unwrap();
+        }
+        for _ in 0..2 {
+            storage.transfer(&"a".into(), &"b".into(), 1).unwrap();
+            storage.transfer(&"b".into(), &"a".into(), 1).unwrap();
+        }
+
+        let found = Analitic::find_suspicious(&storage, &SuspicionRules::default());
+        let kinds: Vec<_> = found.iter().map(|s| (s.kind, s.name.as_str())).collect();
+        // Пара a-b найдена один раз
+        assert_eq!(
+            kinds,
+            [
+                (SuspicionKind::BackAndForth, "a"),
+                (SuspicionKind::SmallWithdrawals, "b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_most_rich_some() {
+        let storage = get_storage();
+        let balance = storage.get_balance(&"b".into()).unwrap();
+        assert_eq!(
+            Analitic::find_most_rich(&storage),
+            Some(("b".into(), balance))
+        );
+    }
+}
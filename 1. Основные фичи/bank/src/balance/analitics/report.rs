@@ -0,0 +1,82 @@
+use crate::{Name, balance::BalanceSize, clock::Timestamp};
+use serde::Serialize;
+
+/// ## Обороты счета за период `[from, to)`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Turnover {
+    pub name: Name,
+    pub from: Timestamp,
+    pub to: Timestamp,
+    /// Сумма зачислений
+    pub credit: BalanceSize,
+    /// Сумма списаний
+    pub debit: BalanceSize,
+    /// Количество успешных операций
+    pub operations: usize,
+}
+
+impl Turnover {
+    /// Чистый поток: зачисления минус списания
+    pub fn net(&self) -> BalanceSize {
+        self.credit - self.debit
+    }
+}
+
+/// ## Контрагент счета по переводам
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Counterparty {
+    pub name: Name,
+    /// Переведено контрагенту
+    pub sent: BalanceSize,
+    /// Получено от контрагента
+    pub received: BalanceSize,
+    /// Количество переводов в обе стороны
+    pub transfers: usize,
+}
+
+impl Counterparty {
+    /// Объем переводов в обе стороны
+    pub fn volume(&self) -> BalanceSize {
+        self.sent + self.received
+    }
+}
+
+/// ## Движение средств счета за сутки (UTC)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DailyFlow {
+    /// Начало суток
+    pub day: Timestamp,
+    pub credit: BalanceSize,
+    pub debit: BalanceSize,
+    /// Зачисления минус списания
+    pub net: BalanceSize,
+}
+
+/// ## Вид подозрительной активности
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuspicionKind {
+    /// Много мелких снятий за короткое время
+    SmallWithdrawals,
+    /// Переводы туда и обратно с одним контрагентом
+    BackAndForth,
+}
+
+/// ## Найденная подозрительная активность
+///
+/// Плоская структура, чтобы одинаково выводиться в JSON и CSV.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Suspicion {
+    pub kind: SuspicionKind,
+    pub name: Name,
+    /// Контрагент для [SuspicionKind::BackAndForth]
+    pub counterparty: Option<Name>,
+    /// Количество операций в окне
+    pub count: usize,
+    /// Сумма операций в окне
+    pub total: BalanceSize,
+    /// Время первой операции окна
+    pub from: Timestamp,
+    /// Время последней операции окна
+    pub to: Timestamp,
+}
@@ -0,0 +1,230 @@
+use super::{
+    flow,
+    report::{Suspicion, SuspicionKind},
+};
+use crate::{
+    Name,
+    balance::{
+        BalanceSize,
+        operations::{Operation, OperationAmount, OperationStatus, OperationType},
+        scheduler::DAY,
+    },
+    clock::Timestamp,
+    storage::Storage,
+};
+use std::collections::{HashMap, HashSet};
+
+/// ## Пороги поиска подозрительной активности
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuspicionRules {
+    /// Снятие не больше этой суммы считается мелким
+    pub small_amount: OperationAmount,
+    /// Сколько мелких снятий в окне подозрительно
+    pub small_count: usize,
+    /// Сколько смен направления переводов с одним контрагентом в окне подозрительно
+    pub back_and_forth: usize,
+    /// Длина окна в секундах
+    pub window: Timestamp,
+}
+
+impl Default for SuspicionRules {
+    fn default() -> Self {
+        Self {
+            small_amount: 100,
+            small_count: 5,
+            back_and_forth: 3,
+            window: DAY,
+        }
+    }
+}
+
+/// Найти подозрительную активность по всем счетам, в порядке имен
+pub(super) fn find(storage: &Storage, rules: &SuspicionRules) -> Vec<Suspicion> {
+    let mut accounts = storage.get_all();
+    accounts.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+    let mut found = vec![];
+    // Пары уже найдены со стороны другого счета
+    let mut pairs: HashSet<(Name, Name)> = HashSet::new();
+    for (name, balance) in accounts {
+        let history = balance.get_history();
+        found.extend(small_withdrawals(&name, history, rules));
+
+        for suspicion in back_and_forth(&name, history, rules) {
+            let counterparty = suspicion.counterparty.clone().unwrap_or_default();
+            let pair = if name < counterparty {
+                (name.clone(), counterparty)
+            } else {
+                (counterparty, name.clone())
+            };
+            if pairs.insert(pair) {
+                found.push(suspicion);
+            }
+        }
+    }
+    found
+}
+
+/// Окно с наибольшим числом операций: (начало, конец включительно)
+fn densest_window(stamps: &[Timestamp], window: Timestamp) -> Option<(usize, usize)> {
+    let mut best: Option<(usize, usize)> = None;
+    let mut l = 0;
+    for r in 0..stamps.len() {
+        while stamps[r] - stamps[l] >= window {
+            l += 1;
+        }
+        if best.is_none_or(|(bl, br)| r - l > br - bl) {
+            best = Some((l, r));
+        }
+    }
+    best
+}
+
+fn small_withdrawals(
+    name: &Name,
+    history: &[Operation],
+    rules: &SuspicionRules,
+) -> Option<Suspicion> {
+    let mut small: Vec<(Timestamp, OperationAmount)> = history
+        .iter()
+        .filter(|op| op.status == OperationStatus::SUCCESS)
+        .filter_map(|op| match op.tx_type {
+            OperationType::Withdraw(v) if v <= rules.small_amount => Some((op.timestamp(), v)),
+            _ => None,
+        })
+        .collect();
+    small.sort_unstable();
+
+    let stamps: Vec<Timestamp> = small.iter().map(|(ts, _)| *ts).collect();
+    let (l, r) = densest_window(&stamps, rules.window)?;
+    let count = r - l + 1;
+    (count >= rules.small_count).then(|| Suspicion {
+        kind: SuspicionKind::SmallWithdrawals,
+        name: name.clone(),
+        counterparty: None,
+        count,
+        total: small[l..=r]
+            .iter()
+            .map(|(_, v)| BalanceSize::from(*v))
+            .sum(),
+        from: stamps[l],
+        to: stamps[r],
+    })
+}
+
+fn back_and_forth(name: &Name, history: &[Operation], rules: &SuspicionRules) -> Vec<Suspicion> {
+    // Переводы по контрагентам: (время, сумма, входящий)
+    let mut transfers: HashMap<&Name, Vec<(Timestamp, OperationAmount, bool)>> = HashMap::new();
+    for op in history.iter().filter(|op| flow(op).is_some()) {
+        if let OperationType::Transfer(other, v, is_to) = &op.tx_type {
+            transfers
+                .entry(other)
+                .or_default()
+                .push((op.timestamp(), *v, *is_to));
+        }
+    }
+    let mut transfers: Vec<_> = transfers.into_iter().collect();
+    transfers.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+    let mut found = vec![];
+    for (other, mut ops) in transfers {
+        // Стабильно: операции в одну секунду остаются в порядке истории
+        ops.sort_by_key(|op| op.0);
+        // Смены направления до операции включительно
+        let flips: Vec<usize> = ops
+            .iter()
+            .enumerate()
+            .scan(0, |flips, (i, op)| {
+                if i > 0 && ops[i - 1].2 != op.2 {
+                    *flips += 1;
+                }
+                Some(*flips)
+            })
+            .collect();
+
+        let mut best: Option<(usize, usize)> = None;
+        let mut l = 0;
+        for r in 0..ops.len() {
+            while ops[r].0 - ops[l].0 >= rules.window {
+                l += 1;
+            }
+            if best.is_none_or(|(bl, br)| flips[r] - flips[l] > flips[br] - flips[bl]) {
+                best = Some((l, r));
+            }
+        }
+        let Some((l, r)) = best else {
+            continue;
+        };
+        if flips[r] - flips[l] < rules.back_and_forth {
+            continue;
+        }
+        found.push(Suspicion {
+            kind: SuspicionKind::BackAndForth,
+            name: name.clone(),
+            counterparty: Some(other.clone()),
+            count: r - l + 1,
+            total: ops[l..=r].iter().map(|op| BalanceSize::from(op.1)).sum(),
+            from: ops[l].0,
+            to: ops[r].0,
+        });
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(id: u64, timestamp: Timestamp, tx_type: OperationType) -> Operation {
+        Operation::load(id, timestamp, tx_type, OperationStatus::SUCCESS, None)
+    }
+
+    #[test]
+    fn test_densest_window() {
+        assert_eq!(densest_window(&[], 10), None);
+        assert_eq!(densest_window(&[0, 5, 20, 21, 22, 40], 10), Some((2, 4)));
+    }
+
+    #[test]
+    fn test_small_withdrawals() {
+        let rules = SuspicionRules {
+            small_count: 3,
+            ..Default::default()
+        };
+        let history = [
+            op(1, 0, OperationType::Withdraw(10)),
+            op(2, 10, OperationType::Withdraw(500)),
+            op(3, 20, OperationType::Withdraw(20)),
+            op(4, 30, OperationType::Withdraw(30)),
+            op(5, DAY + 40, OperationType::Withdraw(40)),
+        ];
+        let found = small_withdrawals(&"a".into(), &history, &rules).unwrap();
+        assert_eq!((found.count, found.total), (3, 60));
+        assert_eq!((found.from, found.to), (0, 30));
+
+        let rules = SuspicionRules {
+            small_count: 4,
+            ..rules
+        };
+        assert_eq!(small_withdrawals(&"a".into(), &history, &rules), None);
+    }
+
+    #[test]
+    fn test_back_and_forth() {
+        let rules = SuspicionRules::default();
+        let history = [
+            op(1, 0, OperationType::Transfer("b".into(), 100, false)),
+            op(2, 60, OperationType::Transfer("b".into(), 100, true)),
+            op(3, 120, OperationType::Transfer("b".into(), 100, false)),
+            op(4, 180, OperationType::Transfer("b".into(), 100, true)),
+            op(5, 240, OperationType::Transfer("c".into(), 100, true)),
+        ];
+        let found = back_and_forth(&"a".into(), &history, &rules);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].counterparty.as_deref(), Some("b"));
+        assert_eq!((found[0].count, found[0].total), (4, 400));
+
+        // Переводы в одну сторону не подозрительны
+        assert!(back_and_forth(&"a".into(), &history[..2], &rules).is_empty());
+    }
+}
@@ -1,11 +1,15 @@
-use super::{BalanceSize, errors::BalanceError, operations::Operation};
+use super::{
+    BalanceSize,
+    errors::BalanceError,
+    operations::{Operation, OperationStatus, OperationType},
+};
 use std::fmt::Display;
 
 /// # Баланс
 #[derive(Debug, Clone, PartialEq)]
 pub struct Balance {
-    pub(super) value: BalanceSize,
-    pub(super) history: Vec<Operation>,
+    pub(crate) value: BalanceSize,
+    pub(crate) history: Vec<Operation>,
 }
 
 impl Display for Balance {
@@ -61,6 +65,8 @@ impl TryFrom<String> for Balance {
         let len_history = history.len();
         let history = history[1..len_history - 1]
             .split('|')
+            // Пустая история сохраняется как `[]`
+            .filter(|op| !op.is_empty())
             .map(|op| {
                 Operation::try_from(op.to_string()).map_err(BalanceError::InvalidParseOperation)
             })
@@ -85,6 +91,29 @@ impl Balance {
         &self.history
     }
 
+    /// Восстановить операцию из журнала: добавить в историю и,
+    /// если она успешна, применить ее к значению без проверок
+    pub(crate) fn replay(&mut self, op: Operation) {
+        if op.status == OperationStatus::SUCCESS {
+            match op.tx_type {
+                OperationType::Deposit(v)
+                | OperationType::Interest(v)
+                | OperationType::Transfer(_, v, true)
+                | OperationType::StandingOrder(_, v, true) => {
+                    self.value = self.value.saturating_add(v.into());
+                }
+                OperationType::Withdraw(v)
+                | OperationType::Fee(v)
+                | OperationType::Transfer(_, v, false)
+                | OperationType::StandingOrder(_, v, false) => {
+                    self.value = self.value.saturating_sub(v.into());
+                }
+                OperationType::Close => self.value = 0,
+            }
+        }
+        self.history.push(op);
+    }
+
     pub(crate) fn save(&self) -> String {
         let history = self
             .history
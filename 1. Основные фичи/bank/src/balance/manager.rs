@@ -1,12 +1,17 @@
 use super::operations::{OperationAmount, OperationError};
-use crate::Name;
-use std::fmt::Display;
+use crate::{
+    Name,
+    clock::{Clock, SystemClock},
+};
+use std::{fmt::Display, io};
 
 /// Ошибка работы с балансом
 #[derive(Debug)]
 pub enum BalanceManagerError {
     UserNotFound(Name),
     OperationError(OperationError),
+    /// Журнал не записан: операция применена в памяти и ждет следующей записи
+    JournalError(io::Error),
 }
 
 impl Display for BalanceManagerError {
@@ -16,21 +21,54 @@ impl Display for BalanceManagerError {
             BalanceManagerError::OperationError(oper) => {
                 write!(f, "Ошибка операции. {:?}", oper)
             }
+            BalanceManagerError::JournalError(e) => write!(f, "Ошибка записи журнала: {}", e),
         }
     }
 }
 
 pub trait BalanceManager {
+    /// Пополнение баланса со временем операции из `clock`
+    fn deposit_at(
+        &mut self,
+        clock: &dyn Clock,
+        name: &Name,
+        amount: OperationAmount,
+    ) -> Result<(), BalanceManagerError>;
+    /// Списание баланса со временем операции из `clock`
+    fn withdraw_at(
+        &mut self,
+        clock: &dyn Clock,
+        name: &Name,
+        amount: OperationAmount,
+    ) -> Result<(), BalanceManagerError>;
+    /// Перевод между пользователями со временем операции из `clock`
+    fn transfer_at(
+        &mut self,
+        clock: &dyn Clock,
+        from: &Name,
+        to: &Name,
+        amount: OperationAmount,
+    ) -> Result<(), BalanceManagerError>;
+
     /// Пополнение баланса
-    fn deposit(&mut self, name: &Name, amount: OperationAmount) -> Result<(), BalanceManagerError>;
+    fn deposit(&mut self, name: &Name, amount: OperationAmount) -> Result<(), BalanceManagerError> {
+        self.deposit_at(&SystemClock, name, amount)
+    }
     /// Списание баланса
-    fn withdraw(&mut self, name: &Name, amount: OperationAmount)
-    -> Result<(), BalanceManagerError>;
+    fn withdraw(
+        &mut self,
+        name: &Name,
+        amount: OperationAmount,
+    ) -> Result<(), BalanceManagerError> {
+        self.withdraw_at(&SystemClock, name, amount)
+    }
     /// Перевод между пользователями
     fn transfer(
         &mut self,
         from: &Name,
         to: &Name,
         amount: OperationAmount,
-    ) -> Result<(), BalanceManagerError>;
+    ) -> Result<(), BalanceManagerError> {
+        self.transfer_at(&SystemClock, from, to, amount)
+    }
 }
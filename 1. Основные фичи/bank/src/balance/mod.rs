@@ -3,6 +3,7 @@ mod core;
 pub mod errors;
 pub mod manager;
 pub mod operations;
+pub mod scheduler;
 
 pub use core::Balance;
 
@@ -2,7 +2,7 @@ use super::super::Balance;
 use super::{OperationError, OperationStatus, OperationType};
 use crate::Name;
 use crate::balance::BalanceSize;
-use std::time::{SystemTime, UNIX_EPOCH};
+use crate::clock::{Clock, SystemClock};
 
 /// Операция баланса
 #[derive(Debug, Clone, PartialEq)]
@@ -40,13 +40,19 @@ impl Operation {
         }
     }
 
-    /// Создание операции
+    /// Создание операции с текущим системным временем
     pub fn new(id: u64, tx_type: OperationType, description: Option<String>) -> Self {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("Установите актуальное время")
-            .as_secs();
+        Self::new_at(&SystemClock, id, tx_type, description)
+    }
 
+    /// Создание операции со временем из `clock`
+    pub fn new_at(
+        clock: &dyn Clock,
+        id: u64,
+        tx_type: OperationType,
+        description: Option<String>,
+    ) -> Self {
+        let timestamp = clock.now();
         Self {
             id,
             tx_type,
@@ -76,6 +82,21 @@ impl Operation {
         Self::new(id, OperationType::Close, None)
     }
 
+    /// Создает операцию начисления процентов
+    pub fn interest(id: u64, amount: u64) -> Self {
+        Self::new(id, OperationType::Interest(amount), None)
+    }
+
+    /// Создает операцию списания комиссии
+    pub fn fee(id: u64, amount: u64) -> Self {
+        Self::new(id, OperationType::Fee(amount), None)
+    }
+
+    /// Создает операцию перевода по постоянному поручению
+    pub fn standing_order(id: u64, name: Name, amount: u64, is_to: bool) -> Self {
+        Self::new(id, OperationType::StandingOrder(name, amount, is_to), None)
+    }
+
     /// Устанавливает статус операции
     pub fn set_status(&mut self, status: OperationStatus) {
         self.status = status;
@@ -88,7 +109,7 @@ impl Operation {
         }
 
         let result = match self.tx_type {
-            OperationType::Deposit(b) => {
+            OperationType::Deposit(b) | OperationType::Interest(b) => {
                 if let Some(res) = balance.value.checked_add(b.into()) {
                     balance.value = res;
                     Ok(())
@@ -107,7 +128,7 @@ impl Operation {
                     })
                 }
             }
-            OperationType::Transfer(_, b, f) => {
+            OperationType::Transfer(_, b, f) | OperationType::StandingOrder(_, b, f) => {
                 if !f {
                     if let Some(res) = balance.value.checked_sub(b.into()) {
                         balance.value = res;
@@ -125,6 +146,14 @@ impl Operation {
                     Err(OperationError::OverLimitSize)
                 }
             }
+            OperationType::Fee(b) => {
+                if let Some(res) = balance.value.checked_sub(b.into()) {
+                    balance.value = res;
+                    Ok(())
+                } else {
+                    Err(OperationError::OverLimitSize)
+                }
+            }
             OperationType::Close => {
                 balance.value = 0;
                 Ok(())
@@ -279,6 +308,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_balance_op_apply_scheduled() {
+        let mut balance = Balance::new(10, vec![]);
+        Operation::interest(1, 5).apply(&mut balance).unwrap();
+        Operation::standing_order(2, "to".into(), 20, false)
+            .apply(&mut balance)
+            .unwrap();
+        // Комиссия списывается и с отрицательного баланса
+        Operation::fee(3, 1).apply(&mut balance).unwrap();
+        assert_eq!(balance.value, -6);
+        assert_eq!(balance.history.len(), 3);
+    }
+
+    #[test]
+    fn test_balance_op_new_at() {
+        let clock = crate::clock::ManualClock::new(1764444526);
+        let op = Operation::new_at(&clock, 1, OperationType::Deposit(1), None);
+        assert_eq!(op.timestamp(), 1764444526);
+    }
+
     #[test]
     fn test_balance_op_apply_invalid_status() {
         let mut balance = Balance::new(100, vec![]);
@@ -14,6 +14,12 @@ pub enum OperationType {
     /// from - true, текущий пользователь-получатель
     Transfer(String, OperationAmount, bool),
     Close,
+    /// Начисленные на положительный баланс проценты
+    Interest(OperationAmount),
+    /// Комиссия, списывается даже в минус (овердрафт)
+    Fee(OperationAmount),
+    /// Перевод по постоянному поручению, поля как у [OperationType::Transfer]
+    StandingOrder(String, OperationAmount, bool),
 }
 
 impl Display for OperationType {
@@ -23,6 +29,9 @@ impl Display for OperationType {
             OperationType::Withdraw(v) => format!("Withdraw({})", v),
             OperationType::Transfer(n, v, f) => format!("Transfer({}, {}, {})", n, v, f),
             OperationType::Close => "Close".to_string(),
+            OperationType::Interest(v) => format!("Interest({})", v),
+            OperationType::Fee(v) => format!("Fee({})", v),
+            OperationType::StandingOrder(n, v, f) => format!("StandingOrder({}, {}, {})", n, v, f),
         };
         write!(f, "{label}")
     }
@@ -35,6 +44,9 @@ impl Debug for OperationType {
             OperationType::Withdraw(v) => format!("W{}", v),
             OperationType::Transfer(n, v, f) => format!("T({}:{}:{})", n, v, f),
             OperationType::Close => "C".to_string(),
+            OperationType::Interest(v) => format!("I{}", v),
+            OperationType::Fee(v) => format!("F{}", v),
+            OperationType::StandingOrder(n, v, f) => format!("S({}:{}:{})", n, v, f),
         };
         write!(f, "{label}")
     }
@@ -47,6 +59,9 @@ impl From<OperationType> for String {
             OperationType::Withdraw(v) => format!("W{}", v),
             OperationType::Transfer(n, v, f) => format!("T({}:{}:{})", n, v, f),
             OperationType::Close => "C".to_string(),
+            OperationType::Interest(v) => format!("I{}", v),
+            OperationType::Fee(v) => format!("F{}", v),
+            OperationType::StandingOrder(n, v, f) => format!("S({}:{}:{})", n, v, f),
         }
     }
 }
@@ -68,6 +83,8 @@ impl TryFrom<String> for OperationType {
             match op {
                 "D" => Ok(OperationType::Deposit(v)),
                 "W" => Ok(OperationType::Withdraw(v)),
+                "I" => Ok(OperationType::Interest(v)),
+                "F" => Ok(OperationType::Fee(v)),
                 _ => Err(OperationError::InvalidOperation(text)),
             }
         } else {
@@ -82,6 +99,7 @@ impl TryFrom<String> for OperationType {
                 let flag = *flag == "true";
                 return match op {
                     "T" => Ok(OperationType::Transfer(name.to_string(), value, flag)),
+                    "S" => Ok(OperationType::StandingOrder(name.to_string(), value, flag)),
                     _ => Err(OperationError::InvalidOperation(text)),
                 };
             }
@@ -89,3 +107,25 @@ impl TryFrom<String> for OperationType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operation_type_load_save() {
+        for tx_type in [
+            OperationType::Deposit(1),
+            OperationType::Withdraw(2),
+            OperationType::Transfer("Julia".into(), 3, true),
+            OperationType::Close,
+            OperationType::Interest(4),
+            OperationType::Fee(5),
+            OperationType::StandingOrder("Ivan".into(), 6, false),
+        ] {
+            let text = String::from(tx_type.clone());
+            assert_eq!(OperationType::try_from(text), Ok(tx_type));
+        }
+        assert!(OperationType::try_from("X(Julia:1:true)".to_string()).is_err());
+    }
+}
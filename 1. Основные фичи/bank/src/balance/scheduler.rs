@@ -0,0 +1,446 @@
+use super::{
+    BalanceSize,
+    operations::{Operation, OperationAmount, OperationStatus, OperationType},
+};
+use crate::{
+    Name,
+    clock::{Clock, SystemClock, Timestamp},
+    storage::Storage,
+};
+use std::{collections::HashMap, fmt::Display};
+
+/// Сутки в секундах - период начислений по умолчанию
+pub const DAY: Timestamp = 24 * 60 * 60;
+
+/// ## Ставки счета
+///
+/// Проценты в базисных пунктах (1/100 процента) за период начисления,
+/// начисляются только на положительный баланс с округлением вниз.
+/// Комиссия за овердрафт списывается за каждый период с отрицательным балансом.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rates {
+    pub interest_bp: u32,
+    pub overdraft_fee: OperationAmount,
+}
+
+/// ## Постоянное поручение
+///
+/// Перевод `amount` от `from` к `to` каждые `every` секунд, начиная с `next_at`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StandingOrder {
+    pub from: Name,
+    pub to: Name,
+    pub amount: OperationAmount,
+    pub every: Timestamp,
+    pub next_at: Timestamp,
+}
+
+/// Ошибка добавления постоянного поручения
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderError {
+    /// Перевод самому себе
+    SameAccount(Name),
+    /// Нулевой период повторения
+    ZeroPeriod,
+}
+
+impl Display for OrderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderError::SameAccount(name) => {
+                write!(
+                    f,
+                    "Поручение не может переводить со счета {} на него же",
+                    name
+                )
+            }
+            OrderError::ZeroPeriod => write!(f, "Период поручения должен быть больше 0"),
+        }
+    }
+}
+
+/// Плановое событие
+enum Event {
+    Accrual,
+    Order(usize),
+}
+
+/// # Планировщик начислений и постоянных поручений
+///
+/// При каждом [Scheduler::run] выполняет все события, время которых
+/// по часам `clock` уже наступило, в порядке их времени. Операции получают
+/// время события, а не время запуска, поэтому пропущенные периоды
+/// наверстываются с правильными отметками.
+///
+/// ```
+/// use bank::{balance::scheduler::{DAY, Rates, Scheduler}, clock::ManualClock, storage::Storage};
+/// use bank::balance::manager::BalanceManager;
+///
+/// let clock = ManualClock::new(0);
+/// let mut storage = Storage::new();
+/// storage.add_user("Julia".into());
+/// storage.deposit_at(&clock, &"Julia".into(), 10_000).unwrap();
+///
+/// let mut scheduler = Scheduler::new(&clock, DAY);
+/// scheduler.set_rates("Julia".into(), Rates { interest_bp: 10, overdraft_fee: 0 });
+///
+/// clock.advance(DAY);
+/// scheduler.run(&mut storage);
+/// assert_eq!(storage.get_balance(&"Julia".into()).unwrap().get_value(), 10_010);
+/// ```
+pub struct Scheduler<C: Clock = SystemClock> {
+    clock: C,
+    /// Период начислений процентов и комиссий
+    period: Timestamp,
+    next_accrual: Timestamp,
+    default_rates: Rates,
+    rates: HashMap<Name, Rates>,
+    orders: Vec<StandingOrder>,
+}
+
+impl<C: Clock> Scheduler<C> {
+    /// Планировщик с периодом начислений `period`, первое начисление -
+    /// через период от текущего времени
+    pub fn new(clock: C, period: Timestamp) -> Self {
+        assert!(period > 0, "Период начислений должен быть больше 0");
+        let next_accrual = clock.now() + period;
+        Self {
+            clock,
+            period,
+            next_accrual,
+            default_rates: Rates::default(),
+            rates: HashMap::new(),
+            orders: vec![],
+        }
+    }
+
+    /// Ставки для счетов без своих ставок
+    pub fn set_default_rates(&mut self, rates: Rates) {
+        self.default_rates = rates;
+    }
+
+    /// Ставки счета
+    pub fn set_rates(&mut self, name: Name, rates: Rates) {
+        self.rates.insert(name, rates);
+    }
+
+    /// Действующие ставки счета
+    pub fn rates(&self, name: &Name) -> Rates {
+        self.rates.get(name).copied().unwrap_or(self.default_rates)
+    }
+
+    /// Добавить постоянное поручение
+    pub fn add_order(&mut self, order: StandingOrder) -> Result<(), OrderError> {
+        if order.every == 0 {
+            return Err(OrderError::ZeroPeriod);
+        }
+        if order.from == order.to {
+            return Err(OrderError::SameAccount(order.from));
+        }
+        self.orders.push(order);
+        Ok(())
+    }
+
+    /// Действующие постоянные поручения
+    pub fn orders(&self) -> &[StandingOrder] {
+        &self.orders
+    }
+
+    /// Выполнить наступившие события.
+    ///
+    /// Возвращает созданные операции с итоговым статусом, для перевода -
+    /// обе половины. Неуспешное начисление остается в истории со статусом
+    /// [OperationStatus::FAILURE], неуспешный перевод по поручению откатывается
+    /// и в результат не попадает. Ошибки не останавливают остальные события.
+    /// Поручения удаленных пользователей отменяются.
+    pub fn run(&mut self, storage: &mut Storage) -> Vec<(Name, Operation)> {
+        let now = self.clock.now();
+        let mut done = vec![];
+
+        while let Some((at, event)) = self.next_event(now) {
+            match event {
+                Event::Accrual => {
+                    self.accrue(storage, at, &mut done);
+                    self.next_accrual += self.period;
+                }
+                Event::Order(i) => {
+                    let order = &self.orders[i];
+                    if storage.get_balance(&order.from).is_none()
+                        || storage.get_balance(&order.to).is_none()
+                    {
+                        self.orders.remove(i);
+                        continue;
+                    }
+                    execute(storage, order, at, &mut done);
+                    let order = &mut self.orders[i];
+                    order.next_at += order.every;
+                }
+            }
+        }
+        done
+    }
+
+    /// Самое раннее наступившее событие, начисления - первыми
+    fn next_event(&self, now: Timestamp) -> Option<(Timestamp, Event)> {
+        let order = self
+            .orders
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, o)| o.next_at)
+            .map(|(i, o)| (o.next_at, Event::Order(i)));
+        let event = match order {
+            Some((at, _)) if at < self.next_accrual => order,
+            _ => Some((self.next_accrual, Event::Accrual)),
+        };
+        event.filter(|(at, _)| *at <= now)
+    }
+
+    /// Начислить проценты и комиссии всем счетам, в порядке имен
+    fn accrue(&self, storage: &mut Storage, at: Timestamp, done: &mut Vec<(Name, Operation)>) {
+        let mut accounts: Vec<(Name, BalanceSize)> = storage
+            .get_all()
+            .into_iter()
+            .map(|(name, balance)| (name, balance.get_value()))
+            .collect();
+        accounts.sort_unstable();
+
+        for (name, value) in accounts {
+            let rates = self.rates(&name);
+            let tx_type = if value > 0 {
+                let interest = value * BalanceSize::from(rates.interest_bp) / 10_000;
+                match OperationAmount::try_from(interest) {
+                    Ok(0) => continue,
+                    Ok(amount) => OperationType::Interest(amount),
+                    Err(_) => OperationType::Interest(OperationAmount::MAX),
+                }
+            } else if value < 0 && rates.overdraft_fee > 0 {
+                OperationType::Fee(rates.overdraft_fee)
+            } else {
+                continue;
+            };
+            apply(storage, &name, tx_type, at, done);
+        }
+    }
+}
+
+/// Создать операцию на время `at` и применить ее к счету
+fn apply(
+    storage: &mut Storage,
+    name: &Name,
+    tx_type: OperationType,
+    at: Timestamp,
+    done: &mut Vec<(Name, Operation)>,
+) {
+    let id = storage._get_id_balance();
+    let operation = Operation::load(id, at, tx_type, OperationStatus::PENDING, None);
+    // Ошибка уже в истории операции со статусом FAILURE
+    let _ = storage.apply_operation(name, operation);
+    push_recorded(storage, name, id, done);
+}
+
+/// Выполнить перевод по поручению
+fn execute(
+    storage: &mut Storage,
+    order: &StandingOrder,
+    at: Timestamp,
+    done: &mut Vec<(Name, Operation)>,
+) {
+    let id = storage._get_id_balance();
+    let half = |name: &Name, is_to| {
+        let tx_type = OperationType::StandingOrder(name.clone(), order.amount, is_to);
+        Operation::load(id, at, tx_type, OperationStatus::PENDING, None)
+    };
+    let _ = storage.apply_transfer(
+        (&order.from, half(&order.to, false)),
+        (&order.to, half(&order.from, true)),
+    );
+    push_recorded(storage, &order.from, id, done);
+    push_recorded(storage, &order.to, id, done);
+}
+
+/// Сохранить операцию `id`, если она записана в историю счета.
+/// Отклоненная или откаченная операция в историю не попадает
+fn push_recorded(storage: &Storage, name: &Name, id: u64, done: &mut Vec<(Name, Operation)>) {
+    if let Some(op) = storage
+        .get_balance(name)
+        .and_then(|b| b.get_history().last())
+        .filter(|op| op.id() == id)
+    {
+        done.push((name.clone(), op.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{balance::manager::BalanceManager, clock::ManualClock};
+
+    /// Пополнение по тем же часам, что и у планировщика
+    fn get_storage(clock: &ManualClock) -> Storage {
+        let mut storage = Storage::new();
+        storage.add_user("a".into());
+        storage.add_user("b".into());
+        storage.deposit_at(clock, &"a".into(), 10_000).unwrap();
+        storage
+    }
+
+    fn value(storage: &Storage, name: &str) -> BalanceSize {
+        storage.get_balance(&name.into()).unwrap().get_value()
+    }
+
+    #[test]
+    fn test_scheduler_interest() {
+        let clock = ManualClock::new(1000);
+        let mut storage = get_storage(&clock);
+        let mut scheduler = Scheduler::new(&clock, DAY);
+        scheduler.set_default_rates(Rates {
+            interest_bp: 100,
+            overdraft_fee: 0,
+        });
+
+        // Период еще не прошел
+        assert!(scheduler.run(&mut storage).is_empty());
+
+        // Три пропущенных периода наверстываются по очереди
+        clock.advance(3 * DAY);
+        let done = scheduler.run(&mut storage);
+        let stamps: Vec<_> = done.iter().map(|(_, op)| op.timestamp()).collect();
+        assert_eq!(stamps, [1000 + DAY, 1000 + 2 * DAY, 1000 + 3 * DAY]);
+        assert_eq!(value(&storage, "a"), 10_303);
+        // На нулевой баланс проценты не начисляются
+        assert_eq!(value(&storage, "b"), 0);
+        assert!(scheduler.run(&mut storage).is_empty());
+    }
+
+    #[test]
+    fn test_scheduler_standing_order_and_fee() {
+        let clock = ManualClock::new(0);
+        let mut storage = get_storage(&clock);
+        let mut scheduler = Scheduler::new(&clock, DAY);
+        scheduler.set_rates(
+            "b".into(),
+            Rates {
+                interest_bp: 0,
+                overdraft_fee: 7,
+            },
+        );
+        scheduler
+            .add_order(StandingOrder {
+                from: "b".into(),
+                to: "a".into(),
+                amount: 50,
+                every: DAY,
+                next_at: 10,
+            })
+            .unwrap();
+
+        clock.set(DAY + 10);
+        let done = scheduler.run(&mut storage);
+        let types: Vec<_> = done
+            .iter()
+            .map(|(name, op)| (name.as_str(), op.tx_type.clone(), op.timestamp()))
+            .collect();
+        // Поручение в 10, начисление в DAY (комиссия за минус), поручение в DAY + 10
+        assert_eq!(
+            types,
+            [
+                ("b", OperationType::StandingOrder("a".into(), 50, false), 10),
+                ("a", OperationType::StandingOrder("b".into(), 50, true), 10),
+                ("b", OperationType::Fee(7), DAY),
+                (
+                    "b",
+                    OperationType::StandingOrder("a".into(), 50, false),
+                    DAY + 10
+                ),
+                (
+                    "a",
+                    OperationType::StandingOrder("b".into(), 50, true),
+                    DAY + 10
+                ),
+            ]
+        );
+        assert_eq!(value(&storage, "a"), 10_100);
+        assert_eq!(value(&storage, "b"), -107);
+        // Ручные операции и операции планировщика идут по одним часам
+        let stamps: Vec<_> = storage
+            .get_balance(&"a".into())
+            .unwrap()
+            .get_history()
+            .iter()
+            .map(|op| op.timestamp())
+            .collect();
+        assert_eq!(stamps, [0, 10, DAY + 10]);
+        assert!(
+            done.iter()
+                .all(|(_, op)| op.status == OperationStatus::SUCCESS)
+        );
+    }
+
+    #[test]
+    fn test_scheduler_drops_orders_of_removed_users() {
+        let clock = ManualClock::new(0);
+        let mut storage = get_storage(&clock);
+        let mut scheduler = Scheduler::new(&clock, DAY);
+        scheduler
+            .add_order(StandingOrder {
+                from: "a".into(),
+                to: "b".into(),
+                amount: 1,
+                every: 10,
+                next_at: 10,
+            })
+            .unwrap();
+        storage.remove_user(&"b".into());
+
+        clock.set(100);
+        assert!(scheduler.run(&mut storage).is_empty());
+        assert!(scheduler.orders().is_empty());
+        assert_eq!(value(&storage, "a"), 10_000);
+    }
+
+    #[test]
+    fn test_execute_reports_only_recorded_operations() {
+        let clock = ManualClock::new(0);
+        let mut storage = get_storage(&clock);
+        storage.remove_user(&"b".into());
+        let order = StandingOrder {
+            from: "a".into(),
+            to: "b".into(),
+            amount: 1,
+            every: 10,
+            next_at: 10,
+        };
+
+        // Перевод не записан, прошлое пополнение "a" не выдается за него
+        let mut done = vec![];
+        execute(&mut storage, &order, 10, &mut done);
+        assert!(done.is_empty());
+        assert_eq!(value(&storage, "a"), 10_000);
+    }
+
+    #[test]
+    fn test_add_order_validation() {
+        let clock = ManualClock::new(0);
+        let mut scheduler = Scheduler::new(&clock, DAY);
+        let order = StandingOrder {
+            from: "a".into(),
+            to: "a".into(),
+            amount: 1,
+            every: 10,
+            next_at: 10,
+        };
+        assert_eq!(
+            scheduler.add_order(order.clone()),
+            Err(OrderError::SameAccount("a".into()))
+        );
+        assert_eq!(
+            scheduler.add_order(StandingOrder {
+                to: "b".into(),
+                every: 0,
+                ..order
+            }),
+            Err(OrderError::ZeroPeriod)
+        );
+        assert!(scheduler.orders().is_empty());
+    }
+}
@@ -1,13 +1,20 @@
 use bank::{
     Name,
-    balance::manager::BalanceManager,
+    balance::{
+        analitics::{Analitic, SuspicionRules},
+        manager::BalanceManager,
+        scheduler::DAY,
+    },
+    clock::{Clock, SystemClock},
     storage::Storage,
     transaction::{Deposit, Transaction, Transfer, Withdraw},
 };
 use std::io::{self, BufRead, Write};
 
 fn main() {
-    let mut storage = Storage::load_data("balance.csv").unwrap_or_default();
+    // Каждая операция сразу дописывается в журнал balance.csv.wal
+    let mut storage =
+        Storage::open_journaled("balance.csv", 100).expect("Не удалось открыть хранилище");
 
     println!("=== Bank CLI Utils ===");
     println!("Команды:");
@@ -18,6 +25,8 @@ fn main() {
     println!("  balance <name>                  - показать баланс");
     println!("  transfer <name> <name> <amount> - перевести средства");
     println!("  list                            - показать список пользователей");
+    println!("  report <name>                   - обороты за сутки и основные контрагенты");
+    println!("  suspicious                      - подозрительная активность");
     println!("  exit                            - выйти");
 
     let stdin = io::stdin();
@@ -54,7 +63,6 @@ fn main() {
                 if storage.add_user(name.clone()).is_some() {
                     let _ = storage.deposit(&name, balance);
                     println!("Пользователь {} добавлен с балансом {}", name, balance);
-                    storage.save("balance.csv");
                 } else {
                     println!("Пользователь {} уже существует", name);
                 }
@@ -67,7 +75,6 @@ fn main() {
                 let name = args[1];
                 if storage.remove_user(&name.to_string()).is_some() {
                     println!("Пользователь {} удалён", name);
-                    storage.save("balance.csv");
                 } else {
                     println!("Пользователь {} не найден", name);
                 }
@@ -91,7 +98,6 @@ fn main() {
                 match tx.apply(&mut storage) {
                     Ok(_) => {
                         println!("Транзакция: депозит {} на {}", name, amount);
-                        storage.save("balance.csv");
                     }
                     Err(e) => println!("Ошибка транзакции: {:?}", e),
                 }
@@ -113,7 +119,6 @@ fn main() {
                 match tx.apply(&mut storage) {
                     Ok(_) => {
                         println!("С баланса пользователя {} снято {}", name, amount);
-                        storage.save("balance.csv");
                     }
                     Err(_) => println!("Ошибка списания"),
                 }
@@ -149,7 +154,6 @@ fn main() {
                 match tx.apply(&mut storage) {
                     Ok(_) => {
                         println!("Транзакция: перевод от {} к {} выполнена", from, to);
-                        storage.save("balance.csv");
                     }
                     Err(e) => println!("Ошибка транзакции: {:?}", e),
                 }
@@ -160,10 +164,50 @@ fn main() {
                     println!("{}: {}", name, balance);
                 }
             }
+            "report" => {
+                if args.len() != 2 {
+                    println!("Пример: report John");
+                    continue;
+                }
+                let name = args[1].to_string();
+                let now = SystemClock.now();
+                let Some(turnover) = Analitic::turnover(&storage, &name, now - DAY, now + 1) else {
+                    println!("Пользователь {} не найден", name);
+                    continue;
+                };
+                println!(
+                    "За сутки: зачислено {}, списано {}, операций {}",
+                    turnover.credit, turnover.debit, turnover.operations
+                );
+                let top = Analitic::top_counterparties(&storage, &name, 3).unwrap_or_default();
+                for c in top {
+                    println!(
+                        "  {}: отправлено {}, получено {}, переводов {}",
+                        c.name, c.sent, c.received, c.transfers
+                    );
+                }
+            }
+            "suspicious" => {
+                let found = Analitic::find_suspicious(&storage, &SuspicionRules::default());
+                if found.is_empty() {
+                    println!("Подозрительной активности нет");
+                }
+                for s in found {
+                    println!(
+                        "{:?} {} {}: операций {} на сумму {}",
+                        s.kind,
+                        s.name,
+                        s.counterparty.unwrap_or_default(),
+                        s.count,
+                        s.total
+                    );
+                }
+            }
             "exit" => break,
             _ => println!("Неизвестная команда"),
         }
     }
 
+    storage.compact().expect("Не удалось сохранить снимок");
     println!("Выход из CLI, все изменения сохранены.");
 }
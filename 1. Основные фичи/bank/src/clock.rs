@@ -0,0 +1,75 @@
+use std::{
+    cell::Cell,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Время в секундах с начала эпохи Unix
+pub type Timestamp = u64;
+
+/// # Источник времени
+///
+/// Операции и планировщик берут время отсюда, а не из [SystemTime::now],
+/// чтобы в тестах время можно было подставить.
+pub trait Clock {
+    /// Текущее время
+    fn now(&self) -> Timestamp;
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Timestamp {
+        (**self).now()
+    }
+}
+
+/// Системное время
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Установите актуальное время")
+            .as_secs()
+    }
+}
+
+/// Логические часы, время двигается только вручную
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock(Cell<Timestamp>);
+
+impl ManualClock {
+    pub fn new(now: Timestamp) -> Self {
+        Self(Cell::new(now))
+    }
+
+    /// Установить время
+    pub fn set(&self, now: Timestamp) {
+        self.0.set(now);
+    }
+
+    /// Сдвинуть время вперед
+    pub fn advance(&self, secs: Timestamp) {
+        self.0.set(self.0.get() + secs);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Timestamp {
+        self.0.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::new(10);
+        clock.advance(5);
+        assert_eq!(clock.now(), 15);
+        clock.set(3);
+        assert_eq!(clock.now(), 3);
+    }
+}
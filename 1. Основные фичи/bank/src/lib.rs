@@ -1,6 +1,11 @@
+// Для `#[derive(Transaction)]`, который ссылается на `::bank`
+extern crate self as bank;
+
+pub mod account;
 pub mod balance;
+pub mod clock;
 pub mod storage;
 pub mod transaction;
 
-/// Потом надеюсь замениться на User структуру, пока так .-.
+/// Уникальное имя счета, сам счет и его id - [account::Account]
 pub type Name = String;
@@ -1,5 +1,9 @@
-use super::Storage;
-use crate::{Name, balance::Balance};
+use super::{JournalRecord, Storage, UndoEntry};
+use crate::{
+    Name,
+    account::{Account, AccountId, AccountRegistry},
+    balance::Balance,
+};
 use std::collections::HashMap;
 
 impl Default for Storage {
@@ -13,7 +17,11 @@ impl Storage {
     pub fn new() -> Self {
         Storage {
             accounts: HashMap::new(),
+            registry: AccountRegistry::new(),
             __id_balance_gen: 1,
+            undo_log: Vec::new(),
+            savepoints: 0,
+            journal: None,
         }
     }
 
@@ -22,15 +30,26 @@ impl Storage {
         if self.accounts.contains_key(&name) {
             None
         } else {
+            let account = self.registry.open(&name).clone();
             self.accounts.insert(name.clone(), 0.into());
+            self.record(UndoEntry::Added(name.clone()));
+            self.journal(JournalRecord::Add(account));
+            // При ошибке записи остаются в очереди: ее вернет следующая операция или flush_journal
+            let _ = self.sync_journal();
 
             Some(self.accounts.get(&name).unwrap())
         }
     }
 
-    /// Удалить пользователя
+    /// Удалить пользователя. Счет остается в реестре закрытым
     pub fn remove_user(&mut self, name: &Name) -> Option<Balance> {
-        self.accounts.remove(name)
+        let balance = self.accounts.remove(name)?;
+        self.registry.close(name);
+        self.record(UndoEntry::Removed(name.clone(), balance.clone()));
+        self.journal(JournalRecord::Remove(name.clone()));
+        // При ошибке записи остаются в очереди: ее вернет следующая операция или flush_journal
+        let _ = self.sync_journal();
+        Some(balance)
     }
 
     /// Получить баланс
@@ -38,6 +57,21 @@ impl Storage {
         self.accounts.get(name)
     }
 
+    /// Получить счет по имени
+    pub fn get_account(&self, name: &Name) -> Option<&Account> {
+        self.registry.get_by_name(name)
+    }
+
+    /// Получить счет по id
+    pub fn get_account_by_id(&self, id: AccountId) -> Option<&Account> {
+        self.registry.get(id)
+    }
+
+    /// Реестр счетов
+    pub fn registry(&self) -> &AccountRegistry {
+        &self.registry
+    }
+
     /// Получить всех пользователей и их балансы
     pub fn get_all(&self) -> Vec<(Name, &Balance)> {
         self.accounts.iter().map(|(n, b)| (n.clone(), b)).collect()
@@ -60,4 +94,20 @@ mod tests {
         assert_eq!(storage.add_user("Alice".to_string()), Some(&0.into())); // новый пользователь
         assert_eq!(storage.add_user("Alice".to_string()), None); // уже существует
     }
+
+    #[test]
+    fn test_account_ids_are_stable() {
+        let mut storage = Storage::new();
+        storage.add_user("Alice".to_string());
+        storage.add_user("Bob".to_string());
+        let alice = storage.get_account(&"Alice".to_string()).unwrap().id();
+
+        storage.remove_user(&"Alice".to_string());
+        assert!(storage.get_account_by_id(alice).unwrap().is_closed());
+
+        storage.add_user("Alice".to_string());
+        let account = storage.get_account(&"Alice".to_string()).unwrap();
+        assert_eq!(account.id(), alice);
+        assert!(!account.is_closed());
+    }
 }
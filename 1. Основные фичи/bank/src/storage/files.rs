@@ -1,5 +1,11 @@
-use super::Storage;
-use crate::balance::{Balance, errors::BalanceError};
+use super::{
+    Storage,
+    journal::{journal_path, read_journal},
+};
+use crate::{
+    account::Account,
+    balance::{Balance, errors::BalanceError},
+};
 use std::{
     fs::{self, File},
     io::{self, BufRead},
@@ -13,13 +19,22 @@ impl Storage {
             .and_modify(|b| *b = balance.clone())
             .or_insert(balance);
     }
+    /// Загрузить хранилище из снимка и, если он есть, журнала рядом с ним
     pub fn load_data(file: &str) -> Result<Storage, std::io::Error> {
+        let journal = journal_path(file);
         let mut storage = Storage::new();
-        if !Path::new(file).exists() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                "Файл не найден",
-            ));
+        storage.load_registry(&accounts_path(file))?;
+        if !Path::new(&journal).exists() {
+            if !Path::new(file).exists() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "Файл не найден",
+                ));
+            }
+        } else if !Path::new(file).exists() {
+            // Снимка еще не было, все состояние в журнале
+            storage.replay_journal(&journal)?;
+            return Ok(storage);
         }
 
         let file = File::open(file)?;
@@ -50,18 +65,75 @@ impl Storage {
             }
         }
 
+        storage.replay_journal(&journal)?;
         Ok(storage)
     }
 
-    pub fn save(&self, file: &str) {
+    /// Загрузить реестр счетов, если он был сохранен
+    fn load_registry(&mut self, file: &str) -> Result<(), std::io::Error> {
+        if !Path::new(file).exists() {
+            return Ok(());
+        }
+        for line in fs::read_to_string(file)?.lines() {
+            let account = Account::try_from(line.to_string()).map_err(|e| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Неверный формат счета: {:?}", e),
+                )
+            })?;
+            self.registry.insert(account);
+        }
+        Ok(())
+    }
+
+    /// Содержимое реестра в формате `Id;Name;CreatedAt;Closed`
+    pub(crate) fn registry_data(&self) -> String {
+        self.registry
+            .all()
+            .into_iter()
+            .map(|account| format!("{}\n", String::from(account)))
+            .collect()
+    }
+
+    /// Применить журнал поверх загруженного снимка
+    fn replay_journal(&mut self, journal: &str) -> Result<(), std::io::Error> {
+        let (records, _) = read_journal(Path::new(journal))?;
+        for record in records {
+            self.replay(record);
+        }
+
+        // Новые операции должны получать id больше уже сохраненных
+        let max_id = self
+            .accounts
+            .values()
+            .flat_map(|b| b.history.iter().map(|op| op.id()))
+            .max()
+            .unwrap_or(0);
+        self.__id_balance_gen = self.__id_balance_gen.max(max_id + 1);
+        Ok(())
+    }
+
+    /// Содержимое снимка в формате `Name;Balance`
+    pub(crate) fn snapshot_data(&self) -> String {
         let mut data = String::new();
         for (name, balance) in self.get_all() {
             data.push_str(&format!("{};{}\n", name, balance.save()));
         }
-        fs::write(file, data).expect("Не удалось записать файл");
+        data
+    }
+
+    /// Сохранить снимок и реестр счетов рядом с ним
+    pub fn save(&self, file: &str) {
+        fs::write(file, self.snapshot_data()).expect("Не удалось записать файл");
+        fs::write(accounts_path(file), self.registry_data()).expect("Не удалось записать файл");
     }
 }
 
+/// Путь к реестру счетов для файла снимка
+pub fn accounts_path(snapshot: &str) -> String {
+    format!("{}.accounts", snapshot)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -100,6 +172,25 @@ Julia;400,[2,1764444530,D600,success,Record number #2|3,1764444535,T(Ivan:200:fa
         assert_eq!(a_balance.get_value(), 400);
     }
 
+    #[test]
+    fn test_save_load_keeps_account_ids() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("bank.csv");
+        let path = path.to_str().unwrap();
+
+        let mut storage = Storage::new();
+        storage.add_user("Ivan".into());
+        storage.add_user("Julia".into());
+        storage.remove_user(&"Ivan".into());
+        storage.save(path);
+
+        let loaded = Storage::load_data(path).unwrap();
+        assert_eq!(loaded.registry(), storage.registry());
+        let julia = loaded.get_account(&"Julia".into()).unwrap();
+        assert_eq!(julia.id(), 2);
+        assert!(loaded.get_account_by_id(1).unwrap().is_closed());
+    }
+
     #[test]
     fn test_load_data_not_existing_file() {
         let mut file = NamedTempFile::new().unwrap();
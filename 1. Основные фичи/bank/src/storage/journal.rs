@@ -0,0 +1,501 @@
+use super::{Storage, files::accounts_path};
+use crate::{
+    Name,
+    account::Account,
+    balance::operations::{Operation, OperationError},
+};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// Запись журнала
+///
+/// В файле хранится строкой `<checksum>;<payload>\n`, где payload:
+/// - `A;<name>;<id>,<created_at>` - открыт счет
+/// - `R;<name>` - удален пользователь
+/// - `O;<name>;<operation>` - операция попала в историю счета
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum JournalRecord {
+    Add(Account),
+    Remove(Name),
+    Operation(Name, Operation),
+}
+
+impl JournalRecord {
+    fn encode(&self) -> String {
+        let payload = match self {
+            JournalRecord::Add(account) => format!(
+                "A;{};{},{}",
+                account.name(),
+                account.id(),
+                account.created_at()
+            ),
+            JournalRecord::Remove(name) => format!("R;{}", name),
+            JournalRecord::Operation(name, op) => format!("O;{};{}", name, String::from(op)),
+        };
+        format!("{:08x};{}\n", checksum(payload.as_bytes()), payload)
+    }
+
+    fn decode(payload: &str) -> Result<Self, OperationError> {
+        let mut parts = payload.splitn(3, ';');
+        let kind = parts.next().unwrap_or_default();
+        let name = parts
+            .next()
+            .ok_or(OperationError::ParseError(payload.to_string()))?
+            .to_string();
+        match (kind, parts.next()) {
+            ("A", Some(meta)) => {
+                let account = meta
+                    .split_once(',')
+                    .and_then(|(id, created_at)| Some((id.parse().ok()?, created_at.parse().ok()?)))
+                    .map(|(id, created_at)| Account::load(id, name, created_at, false))
+                    .ok_or(OperationError::ParseError(payload.to_string()))?;
+                Ok(JournalRecord::Add(account))
+            }
+            ("R", None) => Ok(JournalRecord::Remove(name)),
+            ("O", Some(op)) => Ok(JournalRecord::Operation(
+                name,
+                Operation::try_from(op.to_string())?,
+            )),
+            _ => Err(OperationError::ParseError(payload.to_string())),
+        }
+    }
+}
+
+/// FNV-1a, для обнаружения оборванных записей
+fn checksum(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+/// Путь к журналу для файла снимка
+pub fn journal_path(snapshot: &str) -> String {
+    format!("{}.wal", snapshot)
+}
+
+/// Прочитать журнал.
+///
+/// Возвращает записи и длину корректной части файла в байтах.
+/// Оборванная последняя запись (без перевода строки или с неверной
+/// контрольной суммой) отбрасывается, испорченная запись в середине - ошибка.
+pub(crate) fn read_journal(path: &Path) -> io::Result<(Vec<JournalRecord>, u64)> {
+    if !path.exists() {
+        return Ok((vec![], 0));
+    }
+
+    let data = fs::read(path)?;
+    let mut records = vec![];
+    let mut valid_len = 0;
+
+    let mut rest = data.as_slice();
+    while let Some(end) = rest.iter().position(|b| *b == b'\n') {
+        let is_last = end + 1 == rest.len();
+        let line = &rest[..end];
+
+        let record = std::str::from_utf8(line)
+            .ok()
+            .and_then(|line| line.split_once(';'))
+            .filter(|(sum, payload)| {
+                u32::from_str_radix(sum, 16).ok() == Some(checksum(payload.as_bytes()))
+            });
+
+        match record {
+            Some((_, payload)) => {
+                let record = JournalRecord::decode(payload).map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Неверная запись журнала: {:?}", e),
+                    )
+                })?;
+                records.push(record);
+            }
+            None if is_last => break,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Журнал поврежден на смещении {}", valid_len),
+                ));
+            }
+        }
+
+        valid_len += end as u64 + 1;
+        rest = &rest[end + 1..];
+    }
+
+    Ok((records, valid_len))
+}
+
+/// Записать файл через временный файл и атомарную подмену
+fn write_atomic(path: &Path, data: &str) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(data.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)
+}
+
+/// Журнал операций хранилища
+#[derive(Debug)]
+pub(crate) struct Journal {
+    /// Файл снимка, в который пишется компактизация
+    snapshot: PathBuf,
+    file: File,
+    /// Записи, ожидающие закрытия точки сохранения
+    pub(crate) pending: Vec<JournalRecord>,
+    /// Количество записей в файле журнала
+    records: usize,
+    /// Через сколько записей делать компактизацию, 0 - никогда
+    compact_every: usize,
+    /// Ошибка последней компактизации
+    compaction_error: Option<io::Error>,
+}
+
+impl Storage {
+    /// Открыть хранилище в режиме журнала.
+    ///
+    /// Состояние восстанавливается из снимка `file` и журнала рядом с ним,
+    /// после чего каждая операция дописывается в журнал, а раз в
+    /// `compact_every` записей журнал сворачивается в новый снимок.
+    pub fn open_journaled(file: &str, compact_every: usize) -> io::Result<Storage> {
+        let journal = journal_path(file);
+        let mut storage = if Path::new(file).exists() || Path::new(&journal).exists() {
+            Storage::load_data(file)?
+        } else {
+            Storage::new()
+        };
+
+        // Обрезаем оборванный хвост, чтобы дописывать после целой записи
+        let (records, valid_len) = read_journal(Path::new(&journal))?;
+        let file_handle = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&journal)?;
+        file_handle.set_len(valid_len)?;
+
+        storage.journal = Some(Journal {
+            snapshot: PathBuf::from(file),
+            file: file_handle,
+            pending: vec![],
+            records: records.len(),
+            compact_every,
+            compaction_error: None,
+        });
+        Ok(storage)
+    }
+
+    /// Свернуть журнал в снимок.
+    ///
+    /// Снимок пишется во временный файл и атомарно подменяет старый,
+    /// после чего журнал очищается. Если падение случится между этими
+    /// шагами, повторное применение журнала пропустит уже учтенные операции.
+    pub fn compact(&mut self) -> io::Result<()> {
+        let Some(snapshot) = self.journal.as_ref().map(|j| j.snapshot.clone()) else {
+            return Ok(());
+        };
+
+        let snapshot_str = snapshot.to_string_lossy().to_string();
+        write_atomic(
+            &PathBuf::from(accounts_path(&snapshot_str)),
+            &self.registry_data(),
+        )?;
+        write_atomic(&snapshot, &self.snapshot_data())?;
+
+        if let Some(journal) = self.journal.as_mut() {
+            journal.file.set_len(0)?;
+            journal.file.sync_all()?;
+            journal.records = 0;
+        }
+        Ok(())
+    }
+
+    /// Добавить запись в журнал, если он включен
+    pub(crate) fn journal(&mut self, record: JournalRecord) {
+        if let Some(journal) = self.journal.as_mut() {
+            journal.pending.push(record);
+        }
+    }
+
+    /// Добавить в журнал последнюю операцию счета
+    pub(crate) fn journal_last_operation(&mut self, name: &Name) {
+        let Some(op) = self
+            .accounts
+            .get(name)
+            .and_then(|b| b.history.last())
+            .cloned()
+        else {
+            return;
+        };
+        self.journal(JournalRecord::Operation(name.clone(), op));
+    }
+
+    /// Записать накопленные записи на диск.
+    ///
+    /// Пока открыта точка сохранения, записи копятся в памяти. При ошибке
+    /// записи хвост файла обрезается до прежней длины, а записи остаются
+    /// в очереди и пишутся следующим вызовом: состояние в памяти уже изменено.
+    /// Ошибка компактизации ничего не теряет - записи уже в журнале. Она
+    /// сохраняется в [Storage::last_compaction_error] и повторяется при следующей записи.
+    pub(crate) fn sync_journal(&mut self) -> io::Result<()> {
+        if self.savepoints > 0 {
+            return Ok(());
+        }
+        let Some(journal) = self.journal.as_mut() else {
+            return Ok(());
+        };
+        if journal.pending.is_empty() {
+            return Ok(());
+        }
+
+        let data: String = journal.pending.iter().map(JournalRecord::encode).collect();
+        let len = journal.file.metadata()?.len();
+        if let Err(e) = journal
+            .file
+            .write_all(data.as_bytes())
+            .and_then(|_| journal.file.sync_data())
+        {
+            // Оборванную запись нельзя оставлять перед повтором
+            let _ = journal.file.set_len(len);
+            return Err(e);
+        }
+        journal.records += journal.pending.len();
+        journal.pending.clear();
+
+        if journal.compact_every > 0 && journal.records >= journal.compact_every {
+            let result = self.compact();
+            if let Some(journal) = self.journal.as_mut() {
+                journal.compaction_error = result.err();
+            }
+        }
+        Ok(())
+    }
+
+    /// Записать в журнал записи, оставшиеся после ошибки записи
+    pub fn flush_journal(&mut self) -> io::Result<()> {
+        self.sync_journal()
+    }
+
+    /// Ошибка последней неудачной компактизации, `None` - после успешной
+    pub fn last_compaction_error(&self) -> Option<&io::Error> {
+        self.journal.as_ref()?.compaction_error.as_ref()
+    }
+
+    /// Применить запись журнала при восстановлении.
+    ///
+    /// Операции, id которых не больше последнего id в истории счета,
+    /// уже есть в снимке и пропускаются.
+    pub(crate) fn replay(&mut self, record: JournalRecord) {
+        match record {
+            JournalRecord::Add(account) => {
+                let name = account.name().clone();
+                self.registry.insert(account);
+                self.accounts.entry(name).or_default();
+            }
+            JournalRecord::Remove(name) => {
+                self.registry.close(&name);
+                self.accounts.remove(&name);
+            }
+            JournalRecord::Operation(name, op) => {
+                if let Some(balance) = self.accounts.get_mut(&name)
+                    && balance
+                        .history
+                        .last()
+                        .is_none_or(|last| last.id() < op.id())
+                {
+                    balance.replay(op);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::balance::manager::{BalanceManager, BalanceManagerError};
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    fn paths(dir: &TempDir) -> (String, String) {
+        let snapshot = dir.path().join("bank.csv").to_str().unwrap().to_string();
+        let journal = journal_path(&snapshot);
+        (snapshot, journal)
+    }
+
+    #[test]
+    fn test_journal_replay() {
+        let dir = TempDir::new().unwrap();
+        let (snapshot, _) = paths(&dir);
+
+        let mut storage = Storage::open_journaled(&snapshot, 0).unwrap();
+        storage.add_user("a".into());
+        storage.add_user("b".into());
+        storage.deposit(&"a".into(), 100).unwrap();
+        storage.transfer(&"a".into(), &"b".into(), 30).unwrap();
+        assert!(storage.withdraw(&"b".into(), 50).is_err());
+        drop(storage);
+
+        let storage = Storage::load_data(&snapshot).unwrap();
+        let a = storage.get_balance(&"a".into()).unwrap();
+        let b = storage.get_balance(&"b".into()).unwrap();
+        assert_eq!(a.get_value(), 70);
+        assert_eq!(b.get_value(), 30);
+        assert_eq!(a.get_history().len(), 2);
+        assert_eq!(b.get_history().len(), 2);
+        assert_eq!(storage.get_account(&"b".into()).unwrap().id(), 2);
+    }
+
+    #[test]
+    fn test_journal_torn_tail() {
+        let dir = TempDir::new().unwrap();
+        let (snapshot, journal) = paths(&dir);
+
+        let mut storage = Storage::open_journaled(&snapshot, 0).unwrap();
+        storage.add_user("a".into());
+        storage.deposit(&"a".into(), 100).unwrap();
+        drop(storage);
+
+        let mut file = OpenOptions::new().append(true).open(&journal).unwrap();
+        write!(file, "0badf00d;O;a;3,17644").unwrap();
+        drop(file);
+
+        let mut storage = Storage::open_journaled(&snapshot, 0).unwrap();
+        assert_eq!(storage.get_balance(&"a".into()).unwrap().get_value(), 100);
+        storage.deposit(&"a".into(), 1).unwrap();
+        drop(storage);
+
+        let storage = Storage::load_data(&snapshot).unwrap();
+        assert_eq!(storage.get_balance(&"a".into()).unwrap().get_value(), 101);
+    }
+
+    #[test]
+    fn test_journal_corrupted_middle() {
+        let dir = TempDir::new().unwrap();
+        let (snapshot, journal) = paths(&dir);
+        fs::write(&journal, "00000000;A;a\n00000000;A;b\n").unwrap();
+
+        assert!(Storage::load_data(&snapshot).is_err());
+    }
+
+    #[test]
+    fn test_journal_compaction() {
+        let dir = TempDir::new().unwrap();
+        let (snapshot, journal) = paths(&dir);
+
+        let mut storage = Storage::open_journaled(&snapshot, 3).unwrap();
+        storage.add_user("a".into());
+        storage.deposit(&"a".into(), 10).unwrap();
+        storage.deposit(&"a".into(), 20).unwrap();
+        assert_eq!(fs::read(&journal).unwrap().len(), 0);
+        storage.deposit(&"a".into(), 30).unwrap();
+        drop(storage);
+
+        let storage = Storage::load_data(&snapshot).unwrap();
+        assert_eq!(storage.get_balance(&"a".into()).unwrap().get_value(), 60);
+        assert_eq!(
+            storage
+                .get_balance(&"a".into())
+                .unwrap()
+                .get_history()
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_journal_compaction_failure() {
+        let dir = TempDir::new().unwrap();
+        let (snapshot, journal) = paths(&dir);
+
+        let mut storage = Storage::open_journaled(&snapshot, 2).unwrap();
+        // Снимок не подменить: на его месте непустой каталог
+        fs::create_dir_all(Path::new(&snapshot).join("busy")).unwrap();
+        storage.add_user("a".into());
+        storage.deposit(&"a".into(), 10).unwrap();
+        assert!(!fs::read(&journal).unwrap().is_empty());
+        assert!(storage.last_compaction_error().is_some());
+
+        // Следующая запись повторяет компактизацию
+        fs::remove_dir_all(&snapshot).unwrap();
+        storage.deposit(&"a".into(), 20).unwrap();
+        assert_eq!(fs::read(&journal).unwrap().len(), 0);
+        assert!(storage.last_compaction_error().is_none());
+        drop(storage);
+
+        let storage = Storage::load_data(&snapshot).unwrap();
+        assert_eq!(storage.get_balance(&"a".into()).unwrap().get_value(), 30);
+    }
+
+    #[test]
+    fn test_journal_write_failure() {
+        let dir = TempDir::new().unwrap();
+        let (snapshot, journal) = paths(&dir);
+
+        let mut storage = Storage::open_journaled(&snapshot, 0).unwrap();
+        storage.add_user("a".into());
+        storage.add_user("b".into());
+        // Файл, открытый только на чтение, не принимает записи
+        let writable = std::mem::replace(
+            &mut storage.journal.as_mut().unwrap().file,
+            File::open(&journal).unwrap(),
+        );
+        assert!(matches!(
+            storage.deposit(&"a".into(), 10),
+            Err(BalanceManagerError::JournalError(_))
+        ));
+        assert!(matches!(
+            storage.transfer(&"a".into(), &"b".into(), 4),
+            Err(BalanceManagerError::JournalError(_))
+        ));
+
+        // Записи не потеряны и уходят в журнал, когда запись снова возможна
+        storage.journal.as_mut().unwrap().file = writable;
+        storage.flush_journal().unwrap();
+        drop(storage);
+
+        let storage = Storage::load_data(&snapshot).unwrap();
+        assert_eq!(storage.get_balance(&"a".into()).unwrap().get_value(), 6);
+        assert_eq!(storage.get_balance(&"b".into()).unwrap().get_value(), 4);
+    }
+
+    #[test]
+    fn test_journal_replay_after_compaction_is_idempotent() {
+        let dir = TempDir::new().unwrap();
+        let (snapshot, journal) = paths(&dir);
+
+        let mut storage = Storage::open_journaled(&snapshot, 0).unwrap();
+        storage.add_user("a".into());
+        storage.deposit(&"a".into(), 10).unwrap();
+        let records = fs::read(&journal).unwrap();
+        storage.compact().unwrap();
+        drop(storage);
+
+        // Падение между подменой снимка и очисткой журнала
+        fs::write(&journal, records).unwrap();
+        let storage = Storage::load_data(&snapshot).unwrap();
+        assert_eq!(storage.get_balance(&"a".into()).unwrap().get_value(), 10);
+    }
+
+    #[test]
+    fn test_journal_rollback() {
+        let dir = TempDir::new().unwrap();
+        let (snapshot, _) = paths(&dir);
+
+        let mut storage = Storage::open_journaled(&snapshot, 0).unwrap();
+        storage.add_user("a".into());
+        let result: Result<(), ()> = storage.atomic(|s| {
+            s.deposit(&"a".into(), 10).unwrap();
+            Err(())
+        });
+        assert!(result.is_err());
+        drop(storage);
+
+        let storage = Storage::load_data(&snapshot).unwrap();
+        let a = storage.get_balance(&"a".into()).unwrap();
+        assert_eq!(a.get_value(), 0);
+        assert_eq!(a.get_history().len(), 1);
+    }
+}
@@ -3,64 +3,111 @@ use crate::{
     Name,
     balance::{
         manager::{BalanceManager, BalanceManagerError},
-        operations::{Operation, OperationAmount},
+        operations::{Operation, OperationAmount, OperationStatus, OperationType},
     },
+    clock::Clock,
 };
 
-impl BalanceManager for Storage {
-    fn deposit(&mut self, name: &Name, amount: OperationAmount) -> Result<(), BalanceManagerError> {
-        let id = self._get_id_balance();
+impl Storage {
+    /// Применить операцию к счету и записать ее в журнал
+    pub(crate) fn apply_operation(
+        &mut self,
+        name: &Name,
+        operation: Operation,
+    ) -> Result<(), BalanceManagerError> {
+        self.record_change(name);
         let Some(balance) = self.accounts.get_mut(name) else {
             Err(BalanceManagerError::UserNotFound(name.clone()))?
         };
 
-        Operation::deposit(id, amount)
+        // Операция попадает в историю и при ошибке, поэтому журналируем всегда
+        let result = operation
             .apply(balance)
-            .map_err(BalanceManagerError::OperationError)?;
+            .map_err(BalanceManagerError::OperationError);
+        self.journal_last_operation(name);
+        self.sync_journal()
+            .map_err(BalanceManagerError::JournalError)?;
+
+        result
+    }
+
+    /// Применить обе половины перевода атомарно
+    pub(crate) fn apply_transfer(
+        &mut self,
+        (from, operation_from): (&Name, Operation),
+        (to, operation_to): (&Name, Operation),
+    ) -> Result<(), BalanceManagerError> {
+        if !self.accounts.contains_key(from) {
+            return Err(BalanceManagerError::UserNotFound(from.clone()));
+        }
+        if !self.accounts.contains_key(to) {
+            return Err(BalanceManagerError::UserNotFound(to.clone()));
+        }
 
-        Ok(())
+        // Списание и зачисление должны пройти вместе
+        self.atomic(|storage| {
+            storage.record_change(from);
+            storage.record_change(to);
+            let [Some(balance_from), Some(balance_to)] =
+                storage.accounts.get_disjoint_mut([from, to])
+            else {
+                unreachable!("Счета проверены выше");
+            };
+            operation_from
+                .apply(balance_from)
+                .map_err(BalanceManagerError::OperationError)?;
+            operation_to
+                .apply(balance_to)
+                .map_err(BalanceManagerError::OperationError)?;
+
+            storage.journal_last_operation(from);
+            storage.journal_last_operation(to);
+            Ok(())
+        })?;
+        // Журнал пишется при закрытии точки сохранения, здесь - только его ошибка
+        self.sync_journal()
+            .map_err(BalanceManagerError::JournalError)
     }
+}
 
-    fn withdraw(
+impl BalanceManager for Storage {
+    fn deposit_at(
         &mut self,
+        clock: &dyn Clock,
         name: &Name,
         amount: OperationAmount,
     ) -> Result<(), BalanceManagerError> {
         let id = self._get_id_balance();
-        let Some(balance) = self.accounts.get_mut(name) else {
-            Err(BalanceManagerError::UserNotFound(name.clone()))?
-        };
-
-        Operation::withdraw(id, amount)
-            .apply(balance)
-            .map_err(BalanceManagerError::OperationError)?;
+        let operation = Operation::new_at(clock, id, OperationType::Deposit(amount), None);
+        self.apply_operation(name, operation)
+    }
 
-        Ok(())
+    fn withdraw_at(
+        &mut self,
+        clock: &dyn Clock,
+        name: &Name,
+        amount: OperationAmount,
+    ) -> Result<(), BalanceManagerError> {
+        let id = self._get_id_balance();
+        let operation = Operation::new_at(clock, id, OperationType::Withdraw(amount), None);
+        self.apply_operation(name, operation)
     }
 
-    fn transfer(
+    fn transfer_at(
         &mut self,
+        clock: &dyn Clock,
         from: &Name,
         to: &Name,
         amount: OperationAmount,
     ) -> Result<(), BalanceManagerError> {
         let id = self._get_id_balance();
-        if let [Some(balance_from), Some(balance_to)] = self.accounts.get_disjoint_mut([from, to]) {
-            let operation_from = Operation::transfer(id, to.clone(), amount, false);
-            let operation_to = Operation::transfer(id, from.clone(), amount, true);
-            operation_from
-                .apply(balance_from)
-                .map_err(BalanceManagerError::OperationError)?;
-            operation_to
-                .apply(balance_to)
-                .map_err(BalanceManagerError::OperationError)?;
-
-            Ok(())
-        } else if self.accounts.contains_key(from) {
-            Err(BalanceManagerError::UserNotFound(to.clone()))
-        } else {
-            Err(BalanceManagerError::UserNotFound(from.clone()))
-        }
+        // Обе половины получают одно время
+        let now = clock.now();
+        let half = |name: &Name, is_to| {
+            let tx_type = OperationType::Transfer(name.clone(), amount, is_to);
+            Operation::load(id, now, tx_type, OperationStatus::PENDING, None)
+        };
+        self.apply_transfer((from, half(to, false)), (to, half(from, true)))
     }
 }
 
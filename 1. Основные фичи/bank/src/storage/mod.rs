@@ -1,15 +1,35 @@
 mod core;
 pub mod files;
+mod journal;
 pub mod manager;
-use crate::{Name, balance::Balance};
+mod savepoint;
+use crate::{Name, account::AccountRegistry, balance::Balance};
+use journal::{Journal, JournalRecord};
+use savepoint::UndoEntry;
 use std::collections::HashMap;
 
+pub use files::accounts_path;
+pub use journal::journal_path;
+pub use savepoint::Savepoint;
+
 /// Структура хранилища
 #[derive(Debug)]
 pub struct Storage {
     /// Поле для хранения пользовательских балансов
     accounts: HashMap<Name, Balance>,
 
+    /// Реестр счетов: id, имена и метаданные
+    registry: AccountRegistry,
+
     /// поле для генерации уникальных id для баланса
     __id_balance_gen: u64,
+
+    /// Журнал отката открытых точек сохранения
+    undo_log: Vec<UndoEntry>,
+
+    /// Количество открытых точек сохранения
+    savepoints: usize,
+
+    /// Журнал операций, если хранилище открыто в режиме журнала
+    journal: Option<Journal>,
 }
@@ -0,0 +1,222 @@
+use super::{JournalRecord, Storage};
+use crate::{
+    Name,
+    balance::{Balance, BalanceSize, operations::OperationStatus},
+};
+
+/// Запись журнала отката
+#[derive(Debug, Clone)]
+pub(crate) enum UndoEntry {
+    /// Пользователь был добавлен
+    Added(Name),
+    /// Пользователь был удален вместе с балансом
+    Removed(Name, Balance),
+    /// Баланс был изменен: значение и длина истории до изменения
+    Changed {
+        name: Name,
+        value: BalanceSize,
+        history_len: usize,
+    },
+}
+
+/// Точка сохранения хранилища.
+///
+/// Получается через [Storage::begin] и должна быть закрыта
+/// через [Storage::commit] или [Storage::rollback].
+#[derive(Debug, PartialEq)]
+#[must_use = "точку сохранения нужно закрыть через commit или rollback"]
+pub struct Savepoint {
+    /// Длина журнала отката
+    undo: usize,
+    /// Количество записей журнала операций, ожидающих записи
+    journal: usize,
+}
+
+impl Storage {
+    /// Открыть точку сохранения.
+    ///
+    /// Пока открыта хотя бы одна точка, все изменения хранилища
+    /// записываются в журнал отката.
+    pub fn begin(&mut self) -> Savepoint {
+        self.savepoints += 1;
+        Savepoint {
+            undo: self.undo_log.len(),
+            journal: self.journal.as_ref().map_or(0, |j| j.pending.len()),
+        }
+    }
+
+    /// Зафиксировать изменения, сделанные после точки сохранения
+    pub fn commit(&mut self, savepoint: Savepoint) {
+        debug_assert!(savepoint.undo <= self.undo_log.len());
+        self.close_savepoint();
+    }
+
+    /// Откатить изменения, сделанные после точки сохранения.
+    ///
+    /// Значения балансов восстанавливаются, а операции, попавшие в историю
+    /// после точки, остаются в ней со статусом [OperationStatus::FAILURE].
+    pub fn rollback(&mut self, savepoint: Savepoint) {
+        // Счета и длина истории, с которой операции помечены как FAILURE
+        let mut failed: Vec<(Name, usize)> = vec![];
+
+        while self.undo_log.len() > savepoint.undo {
+            let Some(entry) = self.undo_log.pop() else {
+                break;
+            };
+            match entry {
+                UndoEntry::Added(name) => {
+                    self.accounts.remove(&name);
+                    // id уже выдан, поэтому счет просто закрывается
+                    self.registry.close(&name);
+                }
+                UndoEntry::Removed(name, balance) => {
+                    self.registry.open(&name);
+                    self.accounts.insert(name, balance);
+                }
+                UndoEntry::Changed {
+                    name,
+                    value,
+                    history_len,
+                } => {
+                    if let Some(balance) = self.accounts.get_mut(&name) {
+                        balance.value = value;
+                        balance
+                            .history
+                            .iter_mut()
+                            .skip(history_len)
+                            .for_each(|op| op.set_status(OperationStatus::FAILURE));
+                    }
+                    match failed.iter_mut().find(|(n, _)| *n == name) {
+                        Some((_, len)) => *len = (*len).min(history_len),
+                        None => failed.push((name, history_len)),
+                    }
+                }
+            }
+        }
+
+        // В журнал попадают только итоговые, уже проваленные операции
+        if let Some(journal) = self.journal.as_mut() {
+            journal.pending.truncate(savepoint.journal);
+            for (name, history_len) in failed {
+                let Some(balance) = self.accounts.get(&name) else {
+                    continue;
+                };
+                for op in balance.history.iter().skip(history_len) {
+                    journal
+                        .pending
+                        .push(JournalRecord::Operation(name.clone(), op.clone()));
+                }
+            }
+        }
+        self.close_savepoint();
+    }
+
+    /// Закрыть точку сохранения
+    fn close_savepoint(&mut self) {
+        self.savepoints -= 1;
+        // Внешняя точка еще может откатить вложенные изменения
+        if self.savepoints == 0 {
+            self.undo_log.clear();
+            // Ошибку записи вернет вызывающая операция следующей попыткой
+            let _ = self.sync_journal();
+        }
+    }
+
+    /// Выполнить `f` атомарно: при ошибке все изменения откатываются
+    pub fn atomic<T, E>(&mut self, f: impl FnOnce(&mut Storage) -> Result<T, E>) -> Result<T, E> {
+        let savepoint = self.begin();
+        let result = f(self);
+        match result {
+            Ok(_) => self.commit(savepoint),
+            Err(_) => self.rollback(savepoint),
+        }
+        result
+    }
+
+    /// Записать изменение в журнал, если открыта точка сохранения
+    pub(crate) fn record(&mut self, entry: UndoEntry) {
+        if self.savepoints > 0 {
+            self.undo_log.push(entry);
+        }
+    }
+
+    /// Запомнить состояние баланса перед его изменением
+    pub(crate) fn record_change(&mut self, name: &Name) {
+        if self.savepoints == 0 {
+            return;
+        }
+        if let Some(balance) = self.accounts.get(name) {
+            let entry = UndoEntry::Changed {
+                name: name.clone(),
+                value: balance.value,
+                history_len: balance.history.len(),
+            };
+            self.undo_log.push(entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::balance::manager::BalanceManager;
+
+    #[test]
+    fn test_rollback_restores_value() {
+        let mut storage = Storage::new();
+        storage.add_user("a".into());
+        storage.deposit(&"a".into(), 100).unwrap();
+
+        let savepoint = storage.begin();
+        storage.withdraw(&"a".into(), 30).unwrap();
+        storage.rollback(savepoint);
+
+        let balance = storage.get_balance(&"a".into()).unwrap();
+        assert_eq!(balance.get_value(), 100);
+        assert_eq!(balance.get_history().len(), 2);
+        assert_eq!(balance.get_history()[1].status, OperationStatus::FAILURE);
+    }
+
+    #[test]
+    fn test_rollback_users() {
+        let mut storage = Storage::new();
+        storage.add_user("a".into());
+
+        let savepoint = storage.begin();
+        storage.add_user("b".into());
+        storage.remove_user(&"a".into());
+        storage.rollback(savepoint);
+
+        assert!(storage.get_balance(&"a".into()).is_some());
+        assert!(storage.get_balance(&"b".into()).is_none());
+    }
+
+    #[test]
+    fn test_nested_commit_rolled_back_by_outer() {
+        let mut storage = Storage::new();
+        storage.add_user("a".into());
+
+        let outer = storage.begin();
+        let inner = storage.begin();
+        storage.deposit(&"a".into(), 10).unwrap();
+        storage.commit(inner);
+        storage.rollback(outer);
+
+        assert_eq!(storage.get_balance(&"a".into()).unwrap().get_value(), 0);
+        assert!(storage.undo_log.is_empty());
+    }
+
+    #[test]
+    fn test_atomic_commit() {
+        let mut storage = Storage::new();
+        storage.add_user("a".into());
+
+        let result: Result<(), ()> = storage.atomic(|s| {
+            s.deposit(&"a".into(), 10).map_err(|_| ())?;
+            Ok(())
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(storage.get_balance(&"a".into()).unwrap().get_value(), 10);
+        assert!(storage.undo_log.is_empty());
+    }
+}
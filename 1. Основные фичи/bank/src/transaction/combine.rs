@@ -23,9 +23,10 @@ impl<T1: Transaction, T2: Transaction> TxCombinator<T1, T2> {
 
 impl<T1: Transaction, T2: Transaction> Transaction for TxCombinator<T1, T2> {
     fn apply(&self, storage: &mut Storage) -> Result<(), TxError> {
-        self.t1.apply(storage)?;
-        self.t2.apply(storage)?;
-        Ok(())
+        storage.atomic(|storage| {
+            self.t1.apply(storage)?;
+            self.t2.apply(storage)
+        })
     }
 }
 
@@ -39,9 +40,18 @@ impl<T1: Transaction, T2: Transaction, Rhs: Transaction> Add<Rhs> for TxCombinat
 
 #[cfg(test)]
 mod tests {
-    use super::super::{Deposit, Withdraw};
+    use super::super::{Deposit, Transfer, Withdraw};
     use super::*;
+    use crate::balance::operations::{OperationError, OperationStatus};
     use assert_matches::assert_matches;
+    use macros::Transaction;
+
+    #[derive(Transaction)]
+    #[transaction("withdraw")]
+    struct Fee {
+        account: String,
+        amount: u64,
+    }
 
     #[test]
     fn test_tx_combinator_invalid() {
@@ -74,4 +84,49 @@ mod tests {
         let t: TxCombinator<Deposit, Withdraw> = t1 + t2;
         assert_eq!(t.apply(&mut Storage::new()), Err(TxError::InvalidAccount));
     }
+
+    #[test]
+    fn test_tx_combinator_rollback() {
+        let mut storage = Storage::new();
+        storage.add_user("a".into());
+        storage.add_user("b".into());
+
+        let t = Deposit::new("a".into(), 10)
+            + Transfer::new("a".into(), "b".into(), 5)
+            + Withdraw::new("a".into(), 50);
+        assert_eq!(
+            t.apply(&mut storage),
+            Err(TxError::OperationError(OperationError::NotEnoughMoney {
+                required: 50,
+                available: 5,
+            }))
+        );
+
+        let a = storage.get_balance(&"a".into()).unwrap();
+        let b = storage.get_balance(&"b".into()).unwrap();
+        assert_eq!(a.get_value(), 0);
+        assert_eq!(b.get_value(), 0);
+        assert_eq!(a.get_history().len(), 3);
+        assert_eq!(b.get_history().len(), 1);
+        assert!(
+            a.get_history()
+                .iter()
+                .chain(b.get_history())
+                .all(|op| op.status == OperationStatus::FAILURE)
+        );
+    }
+
+    #[test]
+    fn test_tx_combinator_rollback_derived() {
+        let mut storage = Storage::new();
+        storage.add_user("a".into());
+
+        let fee = Fee {
+            account: "a".into(),
+            amount: 15,
+        };
+        let t = TxCombinator::new(Deposit::new("a".into(), 10), fee);
+        assert_matches!(t.apply(&mut storage), Err(TxError::OperationError(_)));
+        assert_eq!(storage.get_balance(&"a".into()).unwrap().get_value(), 0);
+    }
 }
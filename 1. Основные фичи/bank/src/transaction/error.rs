@@ -4,9 +4,11 @@ use crate::balance::operations::OperationError;
 /// - [TxError::InsufficientFunds] - Недостаточно средств
 /// - [TxError::InvalidAccount] - Не найден счет
 /// - [TxError::ManageError] - Ошибка работы с балансом
+/// - [TxError::JournalError] - Ошибка записи журнала
 #[derive(Debug, PartialEq)]
 pub enum TxError {
     InsufficientFunds,
     InvalidAccount,
     OperationError(OperationError),
+    JournalError(String),
 }
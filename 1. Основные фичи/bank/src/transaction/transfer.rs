@@ -26,6 +26,7 @@ impl Transaction for Transfer {
             .map_err(|e| match e {
                 BalanceManagerError::OperationError { .. } => TxError::InsufficientFunds,
                 BalanceManagerError::UserNotFound(_) => TxError::InvalidAccount,
+                BalanceManagerError::JournalError(e) => TxError::JournalError(e.to_string()),
             })?;
 
         Ok(())
@@ -25,6 +25,7 @@ impl Transaction for Withdraw {
             .map_err(|e| match e {
                 BalanceManagerError::OperationError(err) => TxError::OperationError(err),
                 BalanceManagerError::UserNotFound(_) => TxError::InvalidAccount,
+                BalanceManagerError::JournalError(e) => TxError::JournalError(e.to_string()),
             })?;
         Ok(())
     }
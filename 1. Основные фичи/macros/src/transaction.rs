@@ -22,33 +22,30 @@ pub fn transaction_derive(input: TokenStream) -> TokenStream {
         }
     }
 
+    // Производные транзакции делегируют встроенным, чтобы изменения
+    // проходили через журнал отката хранилища
     let body = match kind {
         "deposit" => quote! {
-            *storage.accounts.entry(self.account.clone()).or_insert(0) += self.amount;
+            ::bank::transaction::Deposit::new(self.account.clone(), self.amount).apply(storage)
         },
         "withdraw" => quote! {
-            let bal = storage.accounts.entry(self.account.clone()).or_insert(0);
-            if *bal < self.amount {
-                return Err(TxError::InsufficientFunds);
-            }
-            *bal -= self.amount;
+            ::bank::transaction::Withdraw::new(self.account.clone(), self.amount).apply(storage)
         },
         "transfer" => quote! {
-            let from_bal = storage.accounts.entry(self.from.clone()).or_insert(0);
-            if *from_bal < self.amount {
-                return Err(TxError::InsufficientFunds);
-            }
-            *from_bal -= self.amount;
-            *storage.accounts.entry(self.to.clone()).or_insert(0) += self.amount;
+            ::bank::transaction::Transfer::new(self.from.clone(), self.to.clone(), self.amount)
+                .apply(storage)
         },
         _ => panic!("Unknown transaction kind"),
     };
 
     let expanded = quote! {
-        impl Transaction for #name {
-            fn apply(&self, storage: &mut Storage) -> Result<(), TxError> {
+        impl ::bank::transaction::Transaction for #name {
+            fn apply(
+                &self,
+                storage: &mut ::bank::storage::Storage,
+            ) -> Result<(), ::bank::transaction::TxError> {
+                use ::bank::transaction::Transaction as _;
                 #body
-                Ok(())
             }
         }
     };
@@ -0,0 +1,118 @@
+use crate::errors::ParseFileError;
+use bank::{
+    Name,
+    account::{AccountId, AccountRegistry},
+};
+
+/// ## Сопоставление пользователей и их id в файлах
+///
+/// Форматы хранят пользователей числовыми id, а операции банка - именами.
+/// `0` в файлах означает "нет пользователя" и никогда не сопоставляется.
+pub trait AccountResolver {
+    /// id пользователя по имени
+    fn id_of(&self, name: &str) -> Option<AccountId>;
+    /// Имя пользователя по id
+    fn name_of(&self, id: AccountId) -> Option<Name>;
+}
+
+/// ## Имена пользователей и есть их id
+///
+/// Поведение по умолчанию: имя `"42"` записывается как id `42` и обратно.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NumericIds;
+
+impl AccountResolver for NumericIds {
+    fn id_of(&self, name: &str) -> Option<AccountId> {
+        name.parse().ok()
+    }
+
+    fn name_of(&self, id: AccountId) -> Option<Name> {
+        Some(id.to_string())
+    }
+}
+
+impl AccountResolver for AccountRegistry {
+    fn id_of(&self, name: &str) -> Option<AccountId> {
+        AccountRegistry::id_of(self, name)
+    }
+
+    fn name_of(&self, id: AccountId) -> Option<Name> {
+        AccountRegistry::name_of(self, id).cloned()
+    }
+}
+
+impl<T: AccountResolver + ?Sized> AccountResolver for &T {
+    fn id_of(&self, name: &str) -> Option<AccountId> {
+        (**self).id_of(name)
+    }
+
+    fn name_of(&self, id: AccountId) -> Option<Name> {
+        (**self).name_of(id)
+    }
+}
+
+/// id пользователя для записи в файл, `None` - "нет пользователя" (`0`)
+pub(crate) fn resolve_id(
+    accounts: &impl AccountResolver,
+    name: Option<&str>,
+) -> Result<AccountId, ParseFileError> {
+    match name {
+        None => Ok(0),
+        Some(name) => accounts.id_of(name).ok_or(ParseFileError::SerializeError(
+            "Пользователь должен иметь ID",
+        )),
+    }
+}
+
+/// Имя пользователя по id из файла
+pub(crate) fn resolve_name(
+    accounts: &impl AccountResolver,
+    id: AccountId,
+) -> Result<Name, ParseFileError> {
+    accounts.name_of(id).ok_or(ParseFileError::DeSerializeError(
+        "Неизвестный id пользователя",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{OperationName, from::FromFile, to::ToFile, types::FileType};
+    use bank::{balance::manager::BalanceManager, storage::Storage};
+
+    #[test]
+    fn test_transfer_round_trip_with_registry() {
+        let mut storage = Storage::new();
+        storage.add_user("Alice".into());
+        storage.add_user("Bob".into());
+        storage.deposit(&"Alice".into(), 100).unwrap();
+        storage
+            .transfer(&"Alice".into(), &"Bob".into(), 40)
+            .unwrap();
+
+        let bob = storage.get_balance(&"Bob".into()).unwrap();
+        let operations: Vec<OperationName> = bob
+            .get_history()
+            .iter()
+            .map(|op| OperationName::new(op.clone(), "Bob".into()))
+            .collect();
+
+        for file_type in [FileType::CSV, FileType::TXT, FileType::BIN] {
+            let mut buf = Vec::new();
+            ToFile::operations_with(&mut buf, &operations, file_type.clone(), storage.registry())
+                .unwrap();
+            let parsed =
+                FromFile::operations_with(&mut buf.as_slice(), file_type, storage.registry())
+                    .unwrap();
+            assert_eq!(parsed, operations);
+        }
+    }
+
+    #[test]
+    fn test_unknown_name() {
+        let registry = AccountRegistry::new();
+        assert!(resolve_id(&registry, Some("Alice")).is_err());
+        assert!(resolve_name(&registry, 1).is_err());
+        assert_eq!(resolve_id(&registry, None).unwrap(), 0);
+    }
+}
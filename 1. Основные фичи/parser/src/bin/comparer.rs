@@ -1,7 +1,15 @@
-use anyhow::{Result, bail};
-use clap::Parser;
-use parser::{from::FromFile, types::FileType};
-use std::{fs::File, io::BufReader};
+use anyhow::{Context, Result};
+use clap::{Parser, ValueEnum};
+use parser::{
+    diff::{DiffReport, diff},
+    from::RecordReader,
+    types::FileType,
+};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    process::ExitCode,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -10,7 +18,7 @@ struct Cli {
     #[arg(long)]
     file1: String,
 
-    /// Тип файла 1
+    /// Тип файла 1 (по умолчанию - по расширению)
     #[arg(long)]
     file1_type: Option<FileType>,
 
@@ -18,67 +26,144 @@ struct Cli {
     #[arg(long)]
     file2: String,
 
-    /// Тип файла 2
+    /// Тип файла 2 (по умолчанию - по расширению)
     #[arg(long)]
     file2_type: Option<FileType>,
+
+    /// Формат отчета
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    format: ReportFormat,
+
+    /// Файл отчета (по умолчанию - stdout)
+    #[arg(short, long)]
+    output: Option<String>,
 }
 
-fn main() -> Result<()> {
-    let Cli {
-        file1: f1,
-        file1_type: f1_tp,
-        file2: f2,
-        file2_type: f2_tp,
-    } = Cli::parse();
-
-    let f1_tp_default = FileType::try_from(f1.split('.').next_back().unwrap().to_string()).ok();
-    let f2_tp_default = FileType::try_from(f2.split('.').next_back().unwrap().to_string()).ok();
-
-    if f1_tp.is_none() && f1_tp_default.is_none() {
-        bail!("Типы входных файлов не указаны");
-    } else if f2_tp.is_none() || f2_tp_default.is_none() {
-        bail!("Тип выходного файла не указан");
-    }
+/// ## Формат отчета о различиях
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum ReportFormat {
+    /// Построчно для человека
+    Text,
+    /// Итоги и различия одним объектом
+    Json,
+    /// Только различия, по строке на различие
+    Csv,
+}
 
-    if (f1_tp != f1_tp_default) || (f2_tp != f2_tp_default) {
-        println!("Типы не соответствуют названию первого и второго файлов");
-        println!("Вы точно уверены? Y/N");
-        let mut answ = String::new();
-        std::io::stdin().read_line(&mut answ).unwrap();
-        let answ = answ.trim().to_lowercase();
-        if answ == "n\n" || answ == "n" {
-            return Ok(());
+/// Файлы совпадают
+const EXIT_EQUAL: u8 = 0;
+/// Найдены различия
+const EXIT_DIFF: u8 = 1;
+/// Сравнение не выполнено
+const EXIT_ERROR: u8 = 2;
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(true) => ExitCode::from(EXIT_EQUAL),
+        Ok(false) => ExitCode::from(EXIT_DIFF),
+        Err(e) => {
+            eprintln!("Ошибка: {e:#}");
+            ExitCode::from(EXIT_ERROR)
         }
     }
+}
 
-    let f1_tp = f1_tp.unwrap_or(f1_tp_default.unwrap());
-    let f2_tp = f2_tp.unwrap_or(f2_tp_default.unwrap());
-
-    let mut buf_r: BufReader<File> = BufReader::new(File::open(f1)?);
-    let operations1 = FromFile::operations(&mut buf_r, f1_tp)?;
+/// Сравнить файлы и вывести отчет, `true` - файлы совпадают
+fn run(cli: Cli) -> Result<bool> {
+    let f1_tp = file_type(&cli.file1, cli.file1_type)?;
+    let f2_tp = file_type(&cli.file2, cli.file2_type)?;
 
-    let mut buf_r: BufReader<File> = BufReader::new(File::open(f2)?);
-    let operations2 = FromFile::operations(&mut buf_r, f2_tp)?;
+    let open = |path: &str, file_type: FileType| -> Result<_> {
+        let file = File::open(path).with_context(|| format!("не удалось открыть {path}"))?;
+        Ok(RecordReader::new(BufReader::new(file), file_type))
+    };
+    let report = diff(open(&cli.file1, f1_tp)?, open(&cli.file2, f2_tp)?)?;
 
-    if operations1 != operations2 {
-        println!("Файлы не равны");
+    match cli.output {
+        Some(path) => write_report(BufWriter::new(File::create(path)?), &report, cli.format)?,
+        None => write_report(std::io::stdout().lock(), &report, cli.format)?,
     }
+    Ok(report.summary.is_equal())
+}
 
-    println!("Хотите построчный вывод? Y/N");
-    let mut answ = String::new();
-    std::io::stdin().read_line(&mut answ).unwrap();
-    let answ = answ.trim().to_lowercase();
-    if answ == "n\n" || answ == "n" {
-        return Ok(());
+/// Тип файла: указанный явно или по расширению
+fn file_type(path: &str, file_type: Option<FileType>) -> Result<FileType> {
+    match file_type {
+        Some(file_type) => Ok(file_type),
+        None => path
+            .rsplit_once('.')
+            .and_then(|(_, ext)| FileType::try_from(ext.to_string()).ok())
+            .with_context(|| format!("тип файла {path} не указан и не определен по расширению")),
     }
+}
 
-    for (op1, op2) in operations1.iter().zip(operations2.iter()) {
-        if op1 != op2 {
-            println!("{:?} != {:?}", op1, op2);
+fn write_report<W: Write>(mut w: W, report: &DiffReport, format: ReportFormat) -> Result<()> {
+    match format {
+        ReportFormat::Text => {
+            for entry in &report.entries {
+                let pos = |p: Option<usize>| p.map_or("-".to_string(), |p| p.to_string());
+                write!(
+                    w,
+                    "{:?} TX_ID={} [{}:{}]",
+                    entry.kind,
+                    entry.tx_id,
+                    pos(entry.left_position),
+                    pos(entry.right_position)
+                )?;
+                if let (Some(field), Some(left), Some(right)) =
+                    (entry.field, &entry.left, &entry.right)
+                {
+                    write!(w, " {field:?}: {left:?} != {right:?}")?;
+                }
+                writeln!(w)?;
+            }
+            let s = &report.summary;
+            writeln!(
+                w,
+                "Записей: {} / {}, совпало по TX_ID: {}, с различиями: {}, переставлено: {}, только в файле 1: {}, только в файле 2: {}",
+                s.left_records,
+                s.right_records,
+                s.matched,
+                s.mismatched,
+                s.moved,
+                s.only_left,
+                s.only_right
+            )?;
+            writeln!(
+                w,
+                "{}",
+                if s.is_equal() {
+                    "Файлы равны"
+                } else {
+                    "Файлы не равны"
+                }
+            )?;
+        }
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(&mut w, report)?;
+            writeln!(w)?;
+        }
+        ReportFormat::Csv => {
+            let mut w_csv = csv::Writer::from_writer(&mut w);
+            // Заголовок пишется с первой записью, пустой отчет тоже должен его иметь
+            if report.entries.is_empty() {
+                w_csv.write_record([
+                    "kind",
+                    "tx_id",
+                    "left_position",
+                    "right_position",
+                    "field",
+                    "left",
+                    "right",
+                ])?;
+            }
+            for entry in &report.entries {
+                w_csv.serialize(entry)?;
+            }
+            w_csv.flush()?;
         }
     }
-    println!("Сравнение окончено");
-
+    w.flush()?;
     Ok(())
 }
 
@@ -89,20 +174,54 @@ mod tests {
     const PATH_CSV: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test.csv");
     const PATH_BIN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test.bin");
 
+    fn open(path: &str, file_type: FileType) -> RecordReader<BufReader<File>> {
+        RecordReader::new(BufReader::new(File::open(path).unwrap()), file_type)
+    }
+
     #[test]
     fn text_compare_bin_txt_csv() {
-        let mut buf_r: BufReader<File> = BufReader::new(File::open(PATH_CSV).unwrap());
-        let res_csv = FromFile::operations(&mut buf_r, FileType::CSV).unwrap();
+        let report = diff(open(PATH_CSV, FileType::CSV), open(PATH_TXT, FileType::TXT)).unwrap();
+        assert!(report.summary.is_equal());
+        assert_eq!(report.summary.matched, 1000);
 
-        let mut buf_r: BufReader<File> = BufReader::new(File::open(PATH_BIN).unwrap());
-        let res_bin = FromFile::operations(&mut buf_r, FileType::BIN).unwrap();
+        let report = diff(open(PATH_CSV, FileType::CSV), open(PATH_BIN, FileType::BIN)).unwrap();
+        assert!(report.summary.is_equal());
+    }
 
-        let mut buf_r: BufReader<File> = BufReader::new(File::open(PATH_TXT).unwrap());
-        let res_txt = FromFile::operations(&mut buf_r, FileType::TXT).unwrap();
+    #[test]
+    fn text_compare_report_formats() {
+        let report = diff(
+            open(PATH_CSV, FileType::CSV).take(10),
+            open(PATH_BIN, FileType::BIN).skip(2).take(10),
+        )
+        .unwrap();
+        assert_eq!(report.summary.only_left, 2);
+        assert_eq!(report.summary.only_right, 2);
+
+        let mut json = Vec::new();
+        write_report(&mut json, &report, ReportFormat::Json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["summary"]["matched"], 8);
+        assert_eq!(json["entries"][0]["kind"], "only_left");
+
+        let mut csv = Vec::new();
+        write_report(&mut csv, &report, ReportFormat::Csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("kind,tx_id,left_position,right_position,field,left,right")
+        );
+        assert_eq!(lines.count(), 4);
+    }
 
-        for i in 0..res_csv.len() {
-            assert_eq!(res_csv[i], res_txt[i]);
-            assert_eq!(res_csv[i], res_bin[i]);
-        }
+    #[test]
+    fn text_file_type_by_extension() {
+        assert_eq!(file_type("a/b.csv", None).unwrap(), FileType::CSV);
+        assert_eq!(
+            file_type("a/b.csv", Some(FileType::BIN)).unwrap(),
+            FileType::BIN
+        );
+        assert!(file_type("a/b", None).is_err());
     }
 }
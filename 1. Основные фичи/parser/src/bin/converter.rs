@@ -1,9 +1,14 @@
-use anyhow::{Result, bail};
+use anyhow::{Context, Result};
 use clap::Parser;
-use parser::{from::FromFile, to::ToFile, types::FileType};
+use parser::{
+    errors::ParseFileError,
+    from::RecordReader,
+    to::RecordWriter,
+    types::{FileType, ParseMode},
+};
 use std::{
     fs::File,
-    io::{BufReader, BufWriter},
+    io::{BufRead, BufReader, BufWriter, Read, Write},
 };
 
 #[derive(Parser, Debug)]
@@ -13,7 +18,7 @@ struct Cli {
     #[arg(short, long)]
     input: String,
 
-    /// Тип входного файла
+    /// Тип входного файла (по умолчанию - по содержимому, затем по расширению)
     #[arg(long)]
     input_type: Option<FileType>,
 
@@ -21,9 +26,26 @@ struct Cli {
     #[arg(short, long)]
     output: String,
 
-    /// Тип выходного файла
+    /// Тип выходного файла (по умолчанию - по расширению)
     #[arg(long)]
     output_type: Option<FileType>,
+
+    /// Режим разбора: strict - остановиться на первой ошибке,
+    /// lenient - пропустить ошибочные записи
+    #[arg(long, value_enum, default_value_t = ParseMode::Strict)]
+    mode: ParseMode,
+
+    /// Файл отчета о пропущенных записях (CSV)
+    #[arg(long)]
+    skipped_report: Option<String>,
+}
+
+/// Результат конвертации
+struct Converted {
+    /// Записано операций
+    count: usize,
+    /// Пропущенные записи
+    skipped: Vec<ParseFileError>,
 }
 
 fn main() -> Result<()> {
@@ -34,47 +56,107 @@ fn main() -> Result<()> {
         input_type,
         output,
         output_type,
+        mode,
+        skipped_report,
     } = Cli::parse();
 
-    let input_type_default =
-        FileType::try_from(input.split('.').next_back().unwrap().to_string()).ok();
-    let output_type_default =
-        FileType::try_from(output.split('.').next_back().unwrap().to_string()).ok();
-
-    if input_type.is_none() && input_type_default.is_none() {
-        bail!("Типы входных файлов не указаны");
-    } else if output_type.is_none() || output_type_default.is_none() {
-        bail!("Тип выходного файла не указан");
-    }
-
-    if (input_type != input_type_default) || (output_type_default != output_type) {
-        println!("Типы не соответствуют названию первого и второго файлов");
-        println!("Вы точно уверены? Y/N");
-        let mut answ = String::new();
-        std::io::stdin().read_line(&mut answ).unwrap();
-        let answ = answ.trim().to_lowercase();
-        if answ == "n\n" || answ == "n" {
-            return Ok(());
+    let mut buf_r = BufReader::new(File::open(&input)?);
+    // Тип по содержимому важнее расширения
+    let detected = FileType::detect(buf_r.fill_buf()?);
+    let input_type = match (input_type, detected) {
+        (Some(input_type), Some(detected)) if input_type != detected => {
+            println!("Тип {input_type:?} не соответствует содержимому файла ({detected:?})");
+            if !confirm()? {
+                return Ok(());
+            }
+            input_type
         }
+        (Some(input_type), _) => input_type,
+        (None, Some(detected)) => detected,
+        (None, None) => by_extension(&input).context("Тип входного файла не указан")?,
+    };
+    let output_type = output_type
+        .or_else(|| by_extension(&output))
+        .context("Тип выходного файла не указан")?;
+
+    let buf_w = BufWriter::new(File::create(output)?);
+    let Converted { count, skipped } = convert(buf_r, input_type, buf_w, output_type, mode)?;
+
+    println!("Выполено успешно, записей: {count}");
+    if !skipped.is_empty() {
+        for e in &skipped {
+            eprintln!("Пропущена {e}");
+        }
+        println!("Пропущено записей: {}", skipped.len());
+    }
+    if let Some(path) = skipped_report {
+        write_skipped(BufWriter::new(File::create(path)?), &skipped)?;
     }
+    Ok(())
+}
 
-    let input_type = input_type.unwrap_or(input_type_default.unwrap());
-    let output_type = output_type.unwrap_or(output_type_default.unwrap());
+/// Отчет о пропущенных записях: номер, смещение, строка и причина
+fn write_skipped<W: Write>(w: W, skipped: &[ParseFileError]) -> Result<()> {
+    let mut w_csv = csv::Writer::from_writer(w);
+    w_csv.write_record(["RECORD", "OFFSET", "LINE", "ERROR"])?;
+    for e in skipped {
+        let (position, reason) = match e {
+            ParseFileError::Record { position, source } => (position, source.to_string()),
+            e => {
+                w_csv.write_record(["", "", "", &e.to_string()])?;
+                continue;
+            }
+        };
+        let line = position.line.map(|l| l.to_string()).unwrap_or_default();
+        w_csv.write_record([
+            position.record.to_string(),
+            position.offset.to_string(),
+            line,
+            reason,
+        ])?;
+    }
+    w_csv.flush()?;
+    Ok(())
+}
 
-    let mut buf_r: BufReader<File> = BufReader::new(File::open(input)?);
-    let operations = FromFile::operations(&mut buf_r, input_type)?;
+/// Тип файла по расширению
+fn by_extension(path: &str) -> Option<FileType> {
+    let (_, ext) = path.rsplit_once('.')?;
+    FileType::try_from(ext.to_string()).ok()
+}
 
-    let mut buf_w = BufWriter::new(File::create(output)?);
-    ToFile::operations(&mut buf_w, &operations, output_type)?;
+fn confirm() -> Result<bool> {
+    println!("Вы точно уверены? Y/N");
+    let mut answ = String::new();
+    std::io::stdin().read_line(&mut answ)?;
+    Ok(answ.trim().to_lowercase() != "n")
+}
 
-    println!("Выполено успешно");
-    Ok(())
+/// Конвертирует операции по одной, не загружая файл в память
+fn convert<R: Read, W: Write>(
+    r: R,
+    input_type: FileType,
+    w: W,
+    output_type: FileType,
+    mode: ParseMode,
+) -> Result<Converted> {
+    let mut reader = RecordReader::new(r, input_type).with_mode(mode);
+    let mut writer = RecordWriter::new(w, output_type);
+    let mut count = 0;
+    for operation in reader.by_ref() {
+        writer.write(&operation?)?;
+        count += 1;
+    }
+    writer.finish()?;
+    Ok(Converted {
+        count,
+        skipped: reader.into_skipped(),
+    })
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use std::io::Read;
 
     const PATH_TXT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test.txt");
     const PATH_CSV: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test.csv");
@@ -82,14 +164,16 @@ mod test {
 
     #[test]
     fn test_convert_txt_to_bin() {
-        let mut buf_r: BufReader<File> = BufReader::new(File::open(PATH_TXT).unwrap());
-        let res = FromFile::operations(&mut buf_r, FileType::TXT);
-        let operations = res.unwrap();
-
-        let mut buf_w = BufWriter::new(Vec::new());
-        let _ = ToFile::operations(&mut buf_w, &operations, FileType::BIN);
-
-        let bytes = buf_w.into_inner().unwrap();
+        let buf_r = BufReader::new(File::open(PATH_TXT).unwrap());
+        let mut bytes = Vec::new();
+        convert(
+            buf_r,
+            FileType::TXT,
+            &mut bytes,
+            FileType::BIN,
+            ParseMode::Strict,
+        )
+        .unwrap();
 
         let mut answer = Vec::new();
         File::open(PATH_BIN)
@@ -101,14 +185,16 @@ mod test {
 
     #[test]
     fn test_convert_csv_to_bin() {
-        let mut buf_r: BufReader<File> = BufReader::new(File::open(PATH_CSV).unwrap());
-        let res = FromFile::operations(&mut buf_r, FileType::CSV);
-        let operations = res.unwrap();
-
-        let mut buf_w = BufWriter::new(Vec::new());
-        let _ = ToFile::operations(&mut buf_w, &operations, FileType::BIN);
-
-        let bytes = buf_w.into_inner().unwrap();
+        let buf_r = BufReader::new(File::open(PATH_CSV).unwrap());
+        let mut bytes = Vec::new();
+        convert(
+            buf_r,
+            FileType::CSV,
+            &mut bytes,
+            FileType::BIN,
+            ParseMode::Strict,
+        )
+        .unwrap();
 
         let mut answer = Vec::new();
         File::open(PATH_BIN)
@@ -123,14 +209,16 @@ mod test {
     #[ignore = "Так как конвертация в csv файл убирает кавычки в description, то тест не проходит. 
                 Либо убирать кавычки с входных файлов, либо убирать тесты"]
     fn test_convert_bin_to_csv() {
-        let mut buf_r: BufReader<File> = BufReader::new(File::open(PATH_BIN).unwrap());
-        let res = FromFile::operations(&mut buf_r, FileType::BIN);
-        let operations = res.unwrap();
-
-        let mut buf_w = BufWriter::new(Vec::new());
-        let _ = ToFile::operations(&mut buf_w, &operations, FileType::CSV);
-
-        let bytes = buf_w.into_inner().unwrap();
+        let buf_r = BufReader::new(File::open(PATH_BIN).unwrap());
+        let mut bytes = Vec::new();
+        convert(
+            buf_r,
+            FileType::BIN,
+            &mut bytes,
+            FileType::CSV,
+            ParseMode::Strict,
+        )
+        .unwrap();
         let csv = String::from_utf8(bytes).unwrap();
 
         let mut answer = String::new();
@@ -149,14 +237,16 @@ mod test {
     #[ignore = "Так как конвертация в csv файл убирает кавычки в description, то тест не проходит. 
                 Либо убирать кавычки с входных файлов, либо убирать тесты"]
     fn test_convert_txt_to_csv() {
-        let mut buf_r: BufReader<File> = BufReader::new(File::open(PATH_TXT).unwrap());
-        let res = FromFile::operations(&mut buf_r, FileType::TXT);
-        let operations = res.unwrap();
-
-        let mut buf_w = BufWriter::new(Vec::new());
-        let _ = ToFile::operations(&mut buf_w, &operations, FileType::CSV);
-
-        let bytes = buf_w.into_inner().unwrap();
+        let buf_r = BufReader::new(File::open(PATH_TXT).unwrap());
+        let mut bytes = Vec::new();
+        convert(
+            buf_r,
+            FileType::TXT,
+            &mut bytes,
+            FileType::CSV,
+            ParseMode::Strict,
+        )
+        .unwrap();
         let csv = String::from_utf8(bytes).unwrap();
 
         let mut answer = String::new();
@@ -170,4 +260,90 @@ mod test {
             assert_eq!(line, answer.split('\n').nth(i).unwrap());
         }
     }
+
+    #[test]
+    fn test_detect_by_content() {
+        for (path, file_type) in [
+            (PATH_TXT, FileType::TXT),
+            (PATH_CSV, FileType::CSV),
+            (PATH_BIN, FileType::BIN),
+        ] {
+            let mut buf_r = BufReader::new(File::open(path).unwrap());
+            assert_eq!(FileType::detect(buf_r.fill_buf().unwrap()), Some(file_type));
+        }
+    }
+
+    #[test]
+    fn test_convert_through_all_formats() {
+        let mut answer = Vec::new();
+        File::open(PATH_BIN)
+            .unwrap()
+            .read_to_end(&mut answer)
+            .unwrap();
+
+        let mut bytes = answer.clone();
+        let mut file_type = FileType::BIN;
+        for next in [
+            FileType::JSON,
+            FileType::JSONL,
+            FileType::MT940,
+            FileType::TXT,
+            FileType::CSV,
+            FileType::BIN,
+        ] {
+            let mut out = Vec::new();
+            let Converted { count, skipped } = convert(
+                bytes.as_slice(),
+                file_type,
+                &mut out,
+                next.clone(),
+                ParseMode::Strict,
+            )
+            .unwrap();
+            assert!(skipped.is_empty());
+            assert_eq!(count, 1000);
+            assert_eq!(FileType::detect(&out).as_ref(), Some(&next));
+            bytes = out;
+            file_type = next;
+        }
+        assert_eq!(bytes, answer);
+    }
+
+    #[test]
+    fn test_convert_lenient() {
+        let csv = std::fs::read_to_string(PATH_CSV).unwrap();
+        // Портим третью запись (строка 4 файла)
+        let mut lines: Vec<&str> = csv.lines().collect();
+        lines[3] = "1000000000000002,DEPOSIT,0,x";
+        let broken = lines.join("\n");
+
+        let res = convert(
+            broken.as_bytes(),
+            FileType::CSV,
+            Vec::new(),
+            FileType::JSONL,
+            ParseMode::Strict,
+        );
+        let err = res.err().unwrap();
+        assert!(err.to_string().contains("запись 2"));
+
+        let mut out = Vec::new();
+        let Converted { count, skipped } = convert(
+            broken.as_bytes(),
+            FileType::CSV,
+            &mut out,
+            FileType::JSONL,
+            ParseMode::Lenient,
+        )
+        .unwrap();
+        assert_eq!(count, 999);
+        assert_eq!(skipped.len(), 1);
+
+        let mut report = Vec::new();
+        write_skipped(&mut report, &skipped).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        let mut report = report.lines().skip(1);
+        assert!(report.next().unwrap().starts_with("2,"));
+        assert!(report.next().is_none());
+    }
 }
@@ -0,0 +1,391 @@
+use crate::{OperationName, errors::ParseFileError};
+use bank::balance::operations::OperationType;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+
+/// ## Вид различия
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffKind {
+    /// Запись есть только в левом файле
+    OnlyLeft,
+    /// Запись есть только в правом файле
+    OnlyRight,
+    /// Поле записи отличается
+    Mismatch,
+    /// Запись стоит не на своем месте относительно остальных
+    Moved,
+}
+
+/// ## Сравниваемое поле записи
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffField {
+    /// Тип операции без суммы (для перевода - с контрагентом и направлением)
+    Type,
+    Amount,
+    Status,
+    Timestamp,
+    Description,
+    /// Пользователь, в чьей истории операция
+    User,
+}
+
+/// ## Одно различие между файлами
+///
+/// Позиции - порядковые номера записей в файлах, начиная с 0.
+/// Плоская структура, чтобы одинаково выводиться в JSON и CSV.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct DiffEntry {
+    pub kind: DiffKind,
+    pub tx_id: u64,
+    pub left_position: Option<usize>,
+    pub right_position: Option<usize>,
+    pub field: Option<DiffField>,
+    pub left: Option<String>,
+    pub right: Option<String>,
+}
+
+/// ## Итоги сравнения
+#[derive(Debug, PartialEq, Clone, Default, Serialize)]
+pub struct DiffSummary {
+    /// Записей в левом файле
+    pub left_records: usize,
+    /// Записей в правом файле
+    pub right_records: usize,
+    /// Записей, найденных в обоих файлах
+    pub matched: usize,
+    /// Найденных в обоих файлах, но с различием полей
+    pub mismatched: usize,
+    /// Найденных в обоих файлах, но в другом порядке
+    pub moved: usize,
+    pub only_left: usize,
+    pub only_right: usize,
+}
+
+impl DiffSummary {
+    /// Файлы совпадают
+    pub fn is_equal(&self) -> bool {
+        self.mismatched == 0 && self.moved == 0 && self.only_left == 0 && self.only_right == 0
+    }
+}
+
+/// ## Отчет сравнения двух файлов
+#[derive(Debug, PartialEq, Clone, Default, Serialize)]
+pub struct DiffReport {
+    pub summary: DiffSummary,
+    pub entries: Vec<DiffEntry>,
+}
+
+/// Запись, еще не найденная во втором файле
+type Pending = HashMap<u64, VecDeque<(usize, OperationName)>>;
+
+/// ## Сравнение записей двух файлов по TX_ID
+///
+/// Файлы читаются параллельно, целиком в памяти держатся только записи без пары.
+/// От каждой сопоставленной записи остаются позиции и TX_ID для поиска
+/// перестановок, поэтому память растет линейно с числом записей, но на пару
+/// это три числа, а не сама запись.
+/// Повторяющиеся TX_ID сопоставляются по порядку появления.
+///
+/// ### Arguments
+/// * `left` - операции первого файла (например, [crate::from::RecordReader])
+/// * `right` - операции второго файла
+pub fn diff<L, R>(mut left: L, mut right: R) -> Result<DiffReport, ParseFileError>
+where
+    L: Iterator<Item = Result<OperationName, ParseFileError>>,
+    R: Iterator<Item = Result<OperationName, ParseFileError>>,
+{
+    let mut report = DiffReport::default();
+    let mut pending_left = Pending::new();
+    let mut pending_right = Pending::new();
+    // Позиции пар (слева, справа, TX_ID) для поиска перестановок
+    let mut pairs: Vec<(usize, usize, u64)> = vec![];
+
+    loop {
+        let l = left.next().transpose()?;
+        let r = right.next().transpose()?;
+        if l.is_none() && r.is_none() {
+            break;
+        }
+
+        if let Some(op) = l {
+            let pos = report.summary.left_records;
+            report.summary.left_records += 1;
+            match take(&mut pending_right, op.operation().id()) {
+                Some((pos_r, other)) => {
+                    compare(&mut report, (pos, &op), (pos_r, &other));
+                    pairs.push((pos, pos_r, op.operation().id()));
+                }
+                None => put(&mut pending_left, pos, op),
+            }
+        }
+        if let Some(op) = r {
+            let pos = report.summary.right_records;
+            report.summary.right_records += 1;
+            match take(&mut pending_left, op.operation().id()) {
+                Some((pos_l, other)) => {
+                    compare(&mut report, (pos_l, &other), (pos, &op));
+                    pairs.push((pos_l, pos, op.operation().id()));
+                }
+                None => put(&mut pending_right, pos, op),
+            }
+        }
+    }
+
+    report.summary.matched = pairs.len();
+    report_moved(&mut report, pairs);
+    report_only(&mut report, pending_left, DiffKind::OnlyLeft);
+    report_only(&mut report, pending_right, DiffKind::OnlyRight);
+    Ok(report)
+}
+
+fn take(pending: &mut Pending, id: u64) -> Option<(usize, OperationName)> {
+    let queue = pending.get_mut(&id)?;
+    let res = queue.pop_front();
+    if queue.is_empty() {
+        pending.remove(&id);
+    }
+    res
+}
+
+fn put(pending: &mut Pending, pos: usize, op: OperationName) {
+    pending
+        .entry(op.operation().id())
+        .or_default()
+        .push_back((pos, op));
+}
+
+/// Сумма операции, у закрытия ее нет
+fn amount(tx_type: &OperationType) -> u64 {
+    match tx_type {
+        OperationType::Deposit(amount)
+        | OperationType::Withdraw(amount)
+        | OperationType::Transfer(_, amount, _)
+        | OperationType::Interest(amount)
+        | OperationType::Fee(amount)
+        | OperationType::StandingOrder(_, amount, _) => *amount,
+        OperationType::Close => 0,
+    }
+}
+
+/// Тип операции без суммы
+fn kind(tx_type: &OperationType) -> String {
+    match tx_type {
+        OperationType::Deposit(_) => "DEPOSIT".to_string(),
+        OperationType::Withdraw(_) => "WITHDRAWAL".to_string(),
+        OperationType::Transfer(name, _, true) => format!("TRANSFER({name})"),
+        OperationType::Transfer(name, _, false) => format!("TRANSFER_OUT({name})"),
+        OperationType::Close => "CLOSE".to_string(),
+        OperationType::Interest(_) => "INTEREST".to_string(),
+        OperationType::Fee(_) => "FEE".to_string(),
+        OperationType::StandingOrder(name, _, true) => format!("STANDING_ORDER({name})"),
+        OperationType::StandingOrder(name, _, false) => format!("STANDING_ORDER_OUT({name})"),
+    }
+}
+
+/// Сравнить поля пары записей с одним TX_ID
+fn compare(
+    report: &mut DiffReport,
+    (pos_l, left): (usize, &OperationName),
+    (pos_r, right): (usize, &OperationName),
+) {
+    let (l, r) = (left.operation(), right.operation());
+    let fields = [
+        (DiffField::Type, kind(&l.tx_type), kind(&r.tx_type)),
+        (
+            DiffField::Amount,
+            amount(&l.tx_type).to_string(),
+            amount(&r.tx_type).to_string(),
+        ),
+        (
+            DiffField::Status,
+            format!("{:?}", l.status),
+            format!("{:?}", r.status),
+        ),
+        (
+            DiffField::Timestamp,
+            l.timestamp().to_string(),
+            r.timestamp().to_string(),
+        ),
+        (
+            DiffField::Description,
+            l.description.clone(),
+            r.description.clone(),
+        ),
+        (DiffField::User, left.name().clone(), right.name().clone()),
+    ];
+
+    let mut mismatched = false;
+    for (field, l_value, r_value) in fields {
+        if l_value != r_value {
+            mismatched = true;
+            report.entries.push(DiffEntry {
+                kind: DiffKind::Mismatch,
+                tx_id: l.id(),
+                left_position: Some(pos_l),
+                right_position: Some(pos_r),
+                field: Some(field),
+                left: Some(l_value),
+                right: Some(r_value),
+            });
+        }
+    }
+    if mismatched {
+        report.summary.mismatched += 1;
+    }
+}
+
+/// Найти перестановки: записи вне наибольшей общей последовательности
+/// сопоставленных записей считаются перемещенными
+fn report_moved(report: &mut DiffReport, mut pairs: Vec<(usize, usize, u64)>) {
+    pairs.sort_unstable();
+
+    // Наибольшая возрастающая подпоследовательность правых позиций
+    let mut tails: Vec<usize> = vec![];
+    let mut prev: Vec<Option<usize>> = vec![None; pairs.len()];
+    for (i, &(_, pos_r, _)) in pairs.iter().enumerate() {
+        let k = tails.partition_point(|&t| pairs[t].1 < pos_r);
+        prev[i] = k.checked_sub(1).map(|k| tails[k]);
+        if k == tails.len() {
+            tails.push(i);
+        } else {
+            tails[k] = i;
+        }
+    }
+    let mut in_order = vec![false; pairs.len()];
+    let mut cur = tails.last().copied();
+    while let Some(i) = cur {
+        in_order[i] = true;
+        cur = prev[i];
+    }
+
+    for (&(pos_l, pos_r, tx_id), in_order) in pairs.iter().zip(in_order) {
+        if in_order {
+            continue;
+        }
+        report.summary.moved += 1;
+        report.entries.push(DiffEntry {
+            kind: DiffKind::Moved,
+            tx_id,
+            left_position: Some(pos_l),
+            right_position: Some(pos_r),
+            field: None,
+            left: None,
+            right: None,
+        });
+    }
+}
+
+/// Записи без пары в порядке их позиций
+fn report_only(report: &mut DiffReport, pending: Pending, kind: DiffKind) {
+    let mut only: Vec<(usize, u64)> = pending
+        .into_values()
+        .flatten()
+        .map(|(pos, op)| (pos, op.operation().id()))
+        .collect();
+    only.sort_unstable();
+
+    for (pos, tx_id) in only {
+        let (left_position, right_position) = match kind {
+            DiffKind::OnlyLeft => {
+                report.summary.only_left += 1;
+                (Some(pos), None)
+            }
+            _ => {
+                report.summary.only_right += 1;
+                (None, Some(pos))
+            }
+        };
+        report.entries.push(DiffEntry {
+            kind,
+            tx_id,
+            left_position,
+            right_position,
+            field: None,
+            left: None,
+            right: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bank::balance::operations::{Operation, OperationStatus};
+
+    fn op(id: u64, amount: u64) -> OperationName {
+        OperationName::new(
+            Operation::load(
+                id,
+                1,
+                OperationType::Deposit(amount),
+                OperationStatus::SUCCESS,
+                None,
+            ),
+            "1".into(),
+        )
+    }
+
+    fn run(left: &[OperationName], right: &[OperationName]) -> DiffReport {
+        diff(left.iter().cloned().map(Ok), right.iter().cloned().map(Ok)).unwrap()
+    }
+
+    #[test]
+    fn test_diff_equal() {
+        let ops: Vec<_> = (0..5).map(|i| op(i, 10)).collect();
+        let report = run(&ops, &ops);
+        assert!(report.summary.is_equal());
+        assert_eq!(report.summary.matched, 5);
+        assert!(report.entries.is_empty());
+    }
+
+    #[test]
+    fn test_diff_only_one_side() {
+        let left = [op(1, 10), op(2, 10), op(3, 10)];
+        let right = [op(1, 10), op(3, 10), op(4, 10)];
+        let report = run(&left, &right);
+
+        assert_eq!(report.summary.only_left, 1);
+        assert_eq!(report.summary.only_right, 1);
+        // Сдвиг из-за пропущенной записи - не перестановка
+        assert_eq!(report.summary.moved, 0);
+        let kinds: Vec<_> = report.entries.iter().map(|e| (e.kind, e.tx_id)).collect();
+        assert_eq!(kinds, [(DiffKind::OnlyLeft, 2), (DiffKind::OnlyRight, 4)]);
+    }
+
+    #[test]
+    fn test_diff_fields() {
+        let mut changed = op(1, 20);
+        changed.0.description = "другое".into();
+        let report = run(&[op(1, 10)], &[changed]);
+
+        assert_eq!(report.summary.mismatched, 1);
+        let fields: Vec<_> = report.entries.iter().map(|e| e.field).collect();
+        assert_eq!(
+            fields,
+            [Some(DiffField::Amount), Some(DiffField::Description)]
+        );
+        assert_eq!(report.entries[0].left.as_deref(), Some("10"));
+        assert_eq!(report.entries[0].right.as_deref(), Some("20"));
+    }
+
+    #[test]
+    fn test_diff_moved() {
+        let left = [op(1, 10), op(2, 10), op(3, 10), op(4, 10)];
+        let right = [op(2, 10), op(3, 10), op(4, 10), op(1, 10)];
+        let report = run(&left, &right);
+
+        assert_eq!(report.summary.moved, 1);
+        assert_eq!(report.entries[0].kind, DiffKind::Moved);
+        assert_eq!(report.entries[0].tx_id, 1);
+    }
+
+    #[test]
+    fn test_diff_duplicate_ids() {
+        let report = run(&[op(1, 10), op(1, 20)], &[op(1, 10), op(1, 20), op(1, 30)]);
+        assert_eq!(report.summary.matched, 2);
+        assert_eq!(report.summary.mismatched, 0);
+        assert_eq!(report.summary.only_right, 1);
+    }
+}
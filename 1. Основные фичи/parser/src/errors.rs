@@ -1,3 +1,4 @@
+use std::fmt::Display;
 use thiserror::Error;
 
 /// ## Ошибки парсинга
@@ -6,6 +7,7 @@ use thiserror::Error;
 /// - [ParseFileError::IoError] - I/O ошибка
 /// - [ParseFileError::SerializeError] - Неверный формат операций (entity -> file)
 /// - [ParseFileError::DeSerializeError] - Неверный формат операций (file -> entity)
+/// - [ParseFileError::Record] - Ошибка конкретной записи входного файла
 #[derive(Debug, Error)]
 pub enum ParseFileError {
     #[error("I/O ошибка: {0}")]
@@ -16,4 +18,41 @@ pub enum ParseFileError {
 
     #[error("Неверный формат: {0}")]
     DeSerializeError(&'static str),
+
+    #[error("{position}: {source}")]
+    Record {
+        position: Position,
+        source: Box<ParseFileError>,
+    },
+}
+
+impl ParseFileError {
+    /// Место записи, на которой произошла ошибка
+    pub fn position(&self) -> Option<&Position> {
+        match self {
+            ParseFileError::Record { position, .. } => Some(position),
+            _ => None,
+        }
+    }
+}
+
+/// ## Место записи во входном файле
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Position {
+    /// Порядковый номер записи, с 0
+    pub record: usize,
+    /// Смещение начала записи в байтах
+    pub offset: u64,
+    /// Номер строки начала записи, с 1 (у BIN строк нет)
+    pub line: Option<u64>,
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "запись {}, байт {}", self.record, self.offset)?;
+        if let Some(line) = self.line {
+            write!(f, ", строка {line}")?;
+        }
+        Ok(())
+    }
 }
@@ -1,148 +1,291 @@
-use crate::{OperationName, errors::ParseFileError};
-use bank::balance::operations::{Operation, OperationStatus, OperationType};
+use super::tracked::Tracked;
+use crate::{
+    OperationName,
+    accounts::AccountResolver,
+    errors::ParseFileError,
+    record::{Record, RecordType},
+};
+use bank::balance::operations::{Operation, OperationStatus};
+use std::io::{ErrorKind, Read};
 
-/// Сериализует операцию в бинарном формате
-fn parse_body(body: &[u8]) -> Result<OperationName, ParseFileError> {
-    let mut i = 0;
-    let id = {
-        let end = i + 8;
-        let arr: [u8; 8] = body[i..end].try_into().expect("REASON");
-        i = end;
-        u64::from_be_bytes(arr)
-    };
-    let tx_type = {
-        let res = match body[i] {
-            0 => Ok(OperationType::Deposit(0)),
-            1 => Ok(OperationType::Transfer("".to_string(), 0, false)),
-            2 => Ok(OperationType::Withdraw(0)),
-            _ => Err(()),
-        };
-        i += 1;
+/// Магическое число начала записи
+const MAGIC: &[u8; 4] = b"YPBN";
+/// Длина тела записи без описания
+const BODY_MIN: u32 = 46;
 
-        res
+/// Последовательное чтение полей тела записи
+struct Body<'a> {
+    body: &'a [u8],
+    i: usize,
+}
+
+impl<'a> Body<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ParseFileError> {
+        let bytes = self
+            .body
+            .get(self.i..self.i + len)
+            .ok_or(ParseFileError::SerializeError("длина записи"))?;
+        self.i += len;
+        Ok(bytes)
     }
-    .or(Err(ParseFileError::SerializeError("Неверный тип операции")))?;
-    let from_user = {
-        let end = i + 8;
-        let arr: [u8; 8] = body[i..end].try_into().expect("REASON");
-        i = end;
-        u64::from_be_bytes(arr)
-    };
-    let to_user = {
-        let end = i + 8;
-        let arr: [u8; 8] = body[i..end].try_into().expect("REASON");
-        i = end;
-        u64::from_be_bytes(arr)
-    };
-    let amount = {
-        let end = i + 8;
-        let arr: [u8; 8] = body[i..end].try_into().expect("REASON");
-        i = end;
-        i64::from_be_bytes(arr)
-    };
-    let timestamp = {
-        let end = i + 8;
-        let arr: [u8; 8] = body[i..end].try_into().expect("REASON");
-        i = end;
-        u64::from_be_bytes(arr)
-    };
-    let status = {
-        let res = match body[i] {
-            0 => Ok(OperationStatus::SUCCESS),
-            1 => Ok(OperationStatus::FAILURE),
-            2 => Ok(OperationStatus::PENDING),
-            _ => Err(()),
-        };
-        i += 1;
-        res
-    }
-    .or(Err(ParseFileError::SerializeError(
-        "Неверный статус операции",
-    )))?;
-    let desc_len = {
-        let end = i + 4;
-        let arr: [u8; 4] = body[i..end].try_into().expect("REASON");
-        i = end;
-        u32::from_be_bytes(arr)
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ParseFileError> {
+        let mut arr = [0; N];
+        arr.copy_from_slice(self.take(N)?);
+        Ok(arr)
+    }
+
+    fn u8(&mut self) -> Result<u8, ParseFileError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, ParseFileError> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, ParseFileError> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+}
+
+/// Сериализует операцию в бинарном формате
+fn parse_body(
+    body: &[u8],
+    accounts: &impl AccountResolver,
+) -> Result<OperationName, ParseFileError> {
+    let mut body = Body { body, i: 0 };
+    let id = body.u64()?;
+    let tx_type = RecordType::from_code(body.u8()?)?;
+    let from_user = body.u64()?;
+    let to_user = body.u64()?;
+    let amount = i64::from_be_bytes(body.array()?);
+    let timestamp = body.u64()?;
+    let status = match body.u8()? {
+        0 => OperationStatus::SUCCESS,
+        1 => OperationStatus::FAILURE,
+        2 => OperationStatus::PENDING,
+        _ => {
+            return Err(ParseFileError::SerializeError("Неверный статус операции"));
+        }
     };
+    let desc_len = body.u32()?;
     let desc = {
-        let end = i + desc_len as usize;
-        let arr: Vec<u8> = body[i..end].to_vec();
-        String::from_utf8(arr)
-            .expect("REASON")
-            .trim_matches('"')
-            .to_string()
+        let bytes = body.take(desc_len as usize)?.to_vec();
+        let desc = String::from_utf8(bytes).or(Err(ParseFileError::SerializeError(
+            "описание ожидается в UTF-8",
+        )))?;
+        // Снимаем ровно одну пару кавычек, добавленную при записи
+        desc.strip_prefix('"')
+            .and_then(|d| d.strip_suffix('"'))
+            .map(str::to_string)
+            .unwrap_or(desc)
     };
 
-    let (tx_type, name) = match tx_type {
-        OperationType::Deposit(_) => (OperationType::Deposit(amount.unsigned_abs()), to_user),
-        OperationType::Withdraw(_) => (OperationType::Withdraw(amount.unsigned_abs()), from_user),
-        OperationType::Transfer(_, _, _) => (
-            OperationType::Transfer(from_user.to_string(), amount.unsigned_abs(), true),
-            to_user,
-        ),
-        OperationType::Close => (OperationType::Close, to_user),
+    let record = Record {
+        tx_type,
+        from: from_user,
+        to: to_user,
+        amount: amount.unsigned_abs(),
     };
+    let (tx_type, name) = record.into_operation_type(accounts)?;
     let operation = Operation::load(id, timestamp, tx_type, status, Some(desc));
-    Ok(OperationName(operation, name.to_string()))
+    Ok(OperationName(operation, name))
 }
 
-/// Преобразование bin-файла в список операций
-pub(super) fn parse_from_bin<R: std::io::Read>(
-    r: &mut R,
-) -> Result<Vec<OperationName>, ParseFileError> {
-    let mut operations: Vec<OperationName> = Vec::new();
-    let mut buf_magic = [0; 4];
+/// ## Потоковое чтение bin-файла
+///
+/// В памяти держится только текущая запись.
+pub(super) struct BinReader<R> {
+    r: Tracked<R>,
+    /// Файл закончился или оборван
+    done: bool,
+    /// Магическое число следующей записи уже прочитано при поиске
+    synced: bool,
+    /// Байты после испорченного магического числа, с них продолжается поиск
+    carry: Vec<u8>,
+    /// Смещение начала последней записи
+    start: u64,
+}
 
-    loop {
-        if r.read_exact(&mut buf_magic).is_err() {
-            break;
-        };
+impl<R: Read> BinReader<R> {
+    pub(super) fn new(r: R) -> Self {
+        Self {
+            r: Tracked::new(r),
+            done: false,
+            synced: false,
+            carry: vec![],
+            start: 0,
+        }
+    }
 
-        if str::from_utf8(&buf_magic).expect("REASON") != "YPBN" {
-            return Err(ParseFileError::SerializeError("магический символ"));
+    /// Следующая операция, `None` - конец файла
+    pub(super) fn next_record(
+        &mut self,
+        accounts: &impl AccountResolver,
+    ) -> Option<Result<OperationName, ParseFileError>> {
+        if self.done {
+            return None;
+        }
+        self.read_record(accounts).transpose()
+    }
+
+    /// Смещение начала последней записи
+    pub(super) fn start(&self) -> (u64, Option<u64>) {
+        (self.start, None)
+    }
+
+    /// Найти начало следующей записи после ошибки
+    pub(super) fn recover(&mut self) {
+        if self.done {
+            return;
+        }
+        let mut window = std::mem::take(&mut self.carry);
+        while !window.ends_with(MAGIC) {
+            match self.r.next_byte() {
+                Ok(Some(b)) => window.push(b),
+                _ => {
+                    self.done = true;
+                    return;
+                }
+            }
+            if window.len() > MAGIC.len() {
+                window.remove(0);
+            }
+        }
+        self.synced = true;
+    }
+
+    fn read_record(
+        &mut self,
+        accounts: &impl AccountResolver,
+    ) -> Result<Option<OperationName>, ParseFileError> {
+        if self.synced {
+            self.synced = false;
+            self.start = self.r.offset() - MAGIC.len() as u64;
+        } else {
+            self.start = self.r.offset();
+            let mut magic = [0; 4];
+            let read = self.read_full(&mut magic)?;
+            if read == 0 {
+                self.done = true;
+                return Ok(None);
+            }
+            if read < magic.len() {
+                return Err(self.truncated());
+            }
+            if &magic != MAGIC {
+                self.carry = magic[1..].to_vec();
+                return Err(ParseFileError::SerializeError("магический символ"));
+            }
         }
 
         let mut buf_size = [0; 4];
-        r.read_exact(&mut buf_size)
-            .map_err(ParseFileError::IoError)?;
+        if self.read_full(&mut buf_size)? < buf_size.len() {
+            return Err(self.truncated());
+        }
         let record_size = u32::from_be_bytes(buf_size);
-        if record_size < 46 {
+        if record_size < BODY_MIN {
             return Err(ParseFileError::SerializeError("длина записи (min 46)"));
         }
-        let mut buf_body = vec![0; record_size as usize];
-        r.read_exact(&mut buf_body)
-            .map_err(ParseFileError::IoError)?;
+        // Тело читается порциями, а не выделяется сразу по заявленной длине
+        let mut buf_body = vec![];
+        (&mut self.r)
+            .take(record_size as u64)
+            .read_to_end(&mut buf_body)
+            .map_err(|e| self.fatal(e))?;
+        if buf_body.len() < record_size as usize {
+            return Err(self.truncated());
+        }
 
-        let arr: [u8; 4] = buf_body[42..46].try_into().expect("REASON");
-        let desc_size = u32::from_be_bytes(arr);
-        if record_size != 46 + desc_size {
+        let desc_size = Body {
+            body: &buf_body,
+            i: 42,
+        }
+        .u32()?;
+        if record_size as u64 != BODY_MIN as u64 + desc_size as u64 {
             return Err(ParseFileError::SerializeError(
                 "длина записи (46 + desc_size)",
             ));
         };
-        let operation = parse_body(&buf_body)?;
-        operations.push(operation);
+        parse_body(&buf_body, accounts).map(Some)
+    }
+
+    /// Прочитать сколько получится до конца файла
+    fn read_full(&mut self, buf: &mut [u8]) -> Result<usize, ParseFileError> {
+        let mut read = 0;
+        while read < buf.len() {
+            match self.r.read(&mut buf[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(self.fatal(e)),
+            }
+        }
+        Ok(read)
+    }
 
-        buf_magic = [0; 4];
+    fn truncated(&mut self) -> ParseFileError {
+        self.done = true;
+        ParseFileError::SerializeError("оборванная запись")
     }
 
-    Ok(operations)
+    fn fatal(&mut self, e: std::io::Error) -> ParseFileError {
+        self.done = true;
+        ParseFileError::IoError(e)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::accounts::NumericIds;
     use std::{fs::File, io::BufReader};
     const PATH_TEST: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test.bin");
 
     #[test]
     fn test_parse_from_bin_success() {
         let file = File::open(PATH_TEST).unwrap();
-        let mut buf = BufReader::new(file);
-        let res = parse_from_bin(&mut buf);
-        assert!(res.is_ok());
-        let res = res.unwrap();
-        assert_eq!(res.len(), 1000);
+        let mut reader = BinReader::new(BufReader::new(file));
+        let mut count = 0;
+        while let Some(res) = reader.next_record(&NumericIds) {
+            assert!(res.is_ok());
+            count += 1;
+        }
+        assert_eq!(count, 1000);
+    }
+
+    #[test]
+    fn test_parse_from_bin_truncated() {
+        let mut data = std::fs::read(PATH_TEST).unwrap();
+        let first = 8 + u32::from_be_bytes(data[4..8].try_into().unwrap()) as usize;
+        data.truncate(first + 20);
+        let mut reader = BinReader::new(data.as_slice());
+        assert!(reader.next_record(&NumericIds).unwrap().is_ok());
+        // Обрыв не паникует и завершает чтение
+        assert!(reader.next_record(&NumericIds).unwrap().is_err());
+        assert!(reader.next_record(&NumericIds).is_none());
+    }
+
+    #[test]
+    fn test_parse_from_bin_recover() {
+        let data = std::fs::read(PATH_TEST).unwrap();
+        // Мусор перед второй записью
+        let first = 8 + u32::from_be_bytes(data[4..8].try_into().unwrap()) as usize;
+        let mut broken = data[..first].to_vec();
+        broken.extend_from_slice(b"XXY");
+        broken.extend_from_slice(&data[first..]);
+
+        let mut reader = BinReader::new(broken.as_slice());
+        assert!(reader.next_record(&NumericIds).unwrap().is_ok());
+        assert!(reader.next_record(&NumericIds).unwrap().is_err());
+        assert_eq!(reader.start(), (first as u64, None));
+
+        reader.recover();
+        let mut count = 1;
+        while let Some(res) = reader.next_record(&NumericIds) {
+            assert!(res.is_ok());
+            count += 1;
+        }
+        assert_eq!(count, 1000);
     }
 }
@@ -1,62 +1,150 @@
-use crate::{OperationName, errors::ParseFileError, types::CsvRecord};
-use bank::balance::operations::{Operation, OperationType};
-
-/// Преобразование csv-файла в список операций
-pub(super) fn parse_from_csv<R: std::io::Read>(
-    r: &mut R,
-) -> Result<Vec<OperationName>, ParseFileError> {
-    let mut rdr = csv::Reader::from_reader(r);
-
-    let operations: Vec<Result<OperationName, ParseFileError>> = rdr
-        .deserialize::<CsvRecord>()
-        .map(|row| {
-            let Ok(row) = row else {
-                return Err(ParseFileError::SerializeError("Не соответствует шаблону"));
-            };
-            let CsvRecord {
-                TX_ID,
-                TX_TYPE,
-                FROM_USER_ID,
-                TO_USER_ID,
-                AMOUNT,
-                TIMESTAMP,
-                STATUS,
-                DESCRIPTION,
-            } = row;
-
-            let (tx_type, name) = match TX_TYPE.as_str() {
-                "DEPOSIT" => Ok((OperationType::Deposit(AMOUNT), TO_USER_ID)),
-                "TRANSFER" => Ok((
-                    OperationType::Transfer(FROM_USER_ID.to_string(), AMOUNT, true),
-                    TO_USER_ID,
-                )),
-                "WITHDRAWAL" => Ok((OperationType::Withdraw(AMOUNT), FROM_USER_ID)),
-                _ => Err(ParseFileError::SerializeError(
-                    "tx_type ожидается: [DEPOSIT, WITHDRAWAL, TRANSFER]",
-                )),
-            }?;
-            let operation = Operation::load(TX_ID, TIMESTAMP, tx_type, STATUS, Some(DESCRIPTION));
-
-            Ok(OperationName(operation, name.to_string()))
-        })
-        .collect();
-
-    operations.into_iter().collect()
+use crate::{
+    OperationName,
+    accounts::AccountResolver,
+    errors::ParseFileError,
+    record::{Record, RecordType},
+    types::CsvRecord,
+};
+use bank::balance::operations::Operation;
+use csv::StringRecord;
+use std::io::Read;
+
+/// ## Потоковое чтение csv-файла
+///
+/// Строки десериализуются по одной, в памяти держится только текущая.
+pub(super) struct CsvReader<R> {
+    rdr: csv::Reader<R>,
+    headers: Option<StringRecord>,
+    row: StringRecord,
+    /// Смещение и строка начала последней записи
+    start: (u64, u64),
+    done: bool,
+}
+
+impl<R: Read> CsvReader<R> {
+    pub(super) fn new(r: R) -> Self {
+        Self {
+            rdr: csv::Reader::from_reader(r),
+            headers: None,
+            row: StringRecord::new(),
+            start: (0, 1),
+            done: false,
+        }
+    }
+
+    /// Следующая операция, `None` - конец файла
+    pub(super) fn next_record(
+        &mut self,
+        accounts: &impl AccountResolver,
+    ) -> Option<Result<OperationName, ParseFileError>> {
+        if self.done {
+            return None;
+        }
+        let headers = match &self.headers {
+            Some(headers) => headers,
+            None => match self.rdr.headers() {
+                Ok(headers) => self.headers.insert(headers.clone()),
+                Err(e) => return Some(Err(self.csv_error(e))),
+            },
+        };
+
+        match self.rdr.read_record(&mut self.row) {
+            Ok(false) => None,
+            Ok(true) => {
+                if let Some(pos) = self.row.position() {
+                    self.start = (pos.byte(), pos.line());
+                }
+                let row = self.row.deserialize::<CsvRecord>(Some(headers)).or(Err(
+                    ParseFileError::SerializeError("Не соответствует шаблону"),
+                ));
+                Some(row.and_then(|row| parse_record(row, accounts)))
+            }
+            Err(e) => Some(Err(self.csv_error(e))),
+        }
+    }
+
+    /// Смещение и строка начала последней записи
+    pub(super) fn start(&self) -> (u64, Option<u64>) {
+        (self.start.0, Some(self.start.1))
+    }
+
+    /// Ошибка чтения строки: I/O завершает чтение, остальные - только строку
+    fn csv_error(&mut self, e: csv::Error) -> ParseFileError {
+        if let Some(pos) = e.position() {
+            self.start = (pos.byte(), pos.line());
+        }
+        match e.into_kind() {
+            csv::ErrorKind::Io(e) => {
+                self.done = true;
+                ParseFileError::IoError(e)
+            }
+            _ => ParseFileError::SerializeError("Не соответствует шаблону"),
+        }
+    }
+}
+
+/// Преобразование записи в операцию (общее для CSV и JSON)
+pub(super) fn parse_record(
+    row: CsvRecord,
+    accounts: &impl AccountResolver,
+) -> Result<OperationName, ParseFileError> {
+    let CsvRecord {
+        TX_ID,
+        TX_TYPE,
+        FROM_USER_ID,
+        TO_USER_ID,
+        AMOUNT,
+        TIMESTAMP,
+        STATUS,
+        DESCRIPTION,
+    } = row;
+
+    let record = Record {
+        tx_type: RecordType::from_name(&TX_TYPE)?,
+        from: FROM_USER_ID,
+        to: TO_USER_ID,
+        amount: AMOUNT,
+    };
+    let (tx_type, name) = record.into_operation_type(accounts)?;
+    let operation = Operation::load(TX_ID, TIMESTAMP, tx_type, STATUS, Some(DESCRIPTION));
+
+    Ok(OperationName(operation, name))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::accounts::NumericIds;
     use std::{fs::File, io::BufReader};
     const PATH_TEST: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test.csv");
 
     #[test]
     fn test_parse_from_csv_success() {
         let file = File::open(PATH_TEST).unwrap();
-        let mut buf = BufReader::new(file);
-        let res = parse_from_csv(&mut buf);
-        assert!(res.is_ok());
-        let res = res.unwrap();
-        assert_eq!(res.len(), 1000);
+        let mut reader = CsvReader::new(BufReader::new(file));
+        let mut count = 0;
+        while let Some(res) = reader.next_record(&NumericIds) {
+            assert!(res.is_ok());
+            count += 1;
+        }
+        assert_eq!(count, 1000);
+    }
+
+    #[test]
+    fn test_parse_from_csv_bad_row() {
+        let data = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION
+1,DEPOSIT,0,7,100,1,SUCCESS,a
+2,DEPOSIT,0,7
+3,DEPOSIT,0,7,x,1,SUCCESS,a
+4,DEPOSIT,0,7,100,1,SUCCESS,a
+";
+        let mut reader = CsvReader::new(data.as_bytes());
+        assert!(reader.next_record(&NumericIds).unwrap().is_ok());
+        assert!(reader.next_record(&NumericIds).unwrap().is_err());
+        assert_eq!(reader.start(), (104, Some(3)));
+        assert!(reader.next_record(&NumericIds).unwrap().is_err());
+        assert_eq!(reader.start().1, Some(4));
+        assert!(reader.next_record(&NumericIds).unwrap().is_ok());
+        assert!(reader.next_record(&NumericIds).is_none());
     }
 }
@@ -0,0 +1,200 @@
+use super::{csv::parse_record, tracked::Tracked};
+use crate::{OperationName, accounts::AccountResolver, errors::ParseFileError, types::CsvRecord};
+use std::io::{BufRead, Read};
+
+/// Позиция в массиве
+enum State {
+    /// Ожидается `[`
+    Start,
+    /// Ожидается первая запись или `]`
+    First,
+    /// Ожидается `,` или `]`
+    Next,
+    /// Массив закончился или разметка нарушена
+    Done,
+}
+
+/// ## Потоковое чтение json-массива
+///
+/// Объекты массива читаются и десериализуются по одному, весь массив
+/// в память не читается. Ошибка внутри объекта не нарушает разметку массива.
+pub(super) struct JsonReader<R> {
+    r: Tracked<R>,
+    state: State,
+    /// Текст текущего объекта
+    object: Vec<u8>,
+    /// Смещение и строка начала последней записи
+    start: (u64, u64),
+}
+
+impl<R: Read> JsonReader<R> {
+    pub(super) fn new(r: R) -> Self {
+        Self {
+            r: Tracked::new(r),
+            state: State::Start,
+            object: vec![],
+            start: (0, 1),
+        }
+    }
+
+    /// Следующая операция, `None` - конец массива
+    pub(super) fn next_record(
+        &mut self,
+        accounts: &impl AccountResolver,
+    ) -> Option<Result<OperationName, ParseFileError>> {
+        match self.read_object() {
+            Ok(false) => None,
+            Ok(true) => {
+                let res = serde_json::from_slice::<CsvRecord>(&self.object)
+                    .map_err(json_error)
+                    .and_then(|row| parse_record(row, accounts));
+                Some(res)
+            }
+            Err(e) => {
+                self.state = State::Done;
+                Some(Err(e))
+            }
+        }
+    }
+
+    /// Смещение и строка начала последней записи
+    pub(super) fn start(&self) -> (u64, Option<u64>) {
+        (self.start.0, Some(self.start.1))
+    }
+
+    /// Прочитать текст следующего объекта массива, `false` - конец массива
+    fn read_object(&mut self) -> Result<bool, ParseFileError> {
+        loop {
+            let next = self.peek()?;
+            match (&self.state, next) {
+                (State::Done, _) => return Ok(false),
+                (State::Start, Some(b'[')) => {
+                    self.r.consume(1);
+                    self.state = State::First;
+                }
+                (State::First | State::Next, Some(b']')) => {
+                    self.r.consume(1);
+                    self.state = State::Done;
+                }
+                (State::Next, Some(b',')) => {
+                    self.r.consume(1);
+                    self.state = State::First;
+                }
+                (State::First, Some(b'{')) => {
+                    self.state = State::Next;
+                    self.start = (self.r.offset(), self.r.line());
+                    self.scan_object()?;
+                    return Ok(true);
+                }
+                (State::Start, None) => return Ok(false),
+                (State::Start, Some(_)) => {
+                    return Err(ParseFileError::SerializeError("ожидается json-массив"));
+                }
+                (State::First, _) => {
+                    return Err(ParseFileError::SerializeError("ожидается объект или `]`"));
+                }
+                (State::Next, _) => {
+                    return Err(ParseFileError::SerializeError("ожидается `,` или `]`"));
+                }
+            }
+        }
+    }
+
+    /// Скопировать объект до парной `}` с учетом строк и экранирования
+    fn scan_object(&mut self) -> Result<(), ParseFileError> {
+        self.object.clear();
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+        loop {
+            let Some(b) = self.r.next_byte()? else {
+                return Err(ParseFileError::SerializeError("оборванная запись"));
+            };
+            self.object.push(b);
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' if in_string => escaped = true,
+                b'"' => in_string = !in_string,
+                b'{' | b'[' if !in_string => depth += 1,
+                b'}' | b']' if !in_string => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Первый непробельный байт без его чтения, `None` - конец файла
+    fn peek(&mut self) -> Result<Option<u8>, ParseFileError> {
+        loop {
+            let buf = self.r.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            let spaces = buf.iter().take_while(|b| b.is_ascii_whitespace()).count();
+            if let Some(&b) = buf.get(spaces) {
+                self.r.consume(spaces);
+                return Ok(Some(b));
+            }
+            self.r.consume(spaces);
+        }
+    }
+}
+
+/// Ошибка json: I/O или несоответствие шаблону
+pub(super) fn json_error(e: serde_json::Error) -> ParseFileError {
+    if e.is_io() {
+        ParseFileError::IoError(e.into())
+    } else {
+        ParseFileError::SerializeError("Не соответствует шаблону")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::NumericIds;
+
+    #[test]
+    fn test_parse_from_json() {
+        let data = r#"[
+            {"TX_ID":1,"TX_TYPE":"DEPOSIT","FROM_USER_ID":0,"TO_USER_ID":7,"AMOUNT":100,
+             "TIMESTAMP":1,"STATUS":"SUCCESS","DESCRIPTION":"a"} ,
+            {"TX_ID":2,"TX_TYPE":"CLOSE","FROM_USER_ID":0,"TO_USER_ID":7,"AMOUNT":0,
+             "TIMESTAMP":2,"STATUS":"PENDING","DESCRIPTION":"]"}
+        ]"#;
+        let mut reader = JsonReader::new(data.as_bytes());
+        let first = reader.next_record(&NumericIds).unwrap().unwrap();
+        assert_eq!(first.operation().id(), 1);
+        let second = reader.next_record(&NumericIds).unwrap().unwrap();
+        assert_eq!(second.operation().description, "]");
+        assert!(reader.next_record(&NumericIds).is_none());
+    }
+
+    #[test]
+    fn test_parse_from_json_broken() {
+        // Ошибка внутри объекта не мешает читать следующие
+        let data = r#"[{"TX_ID":1, "X": "}"},
+            {"TX_ID":2,"TX_TYPE":"CLOSE","FROM_USER_ID":0,"TO_USER_ID":7,"AMOUNT":0,
+             "TIMESTAMP":2,"STATUS":"PENDING","DESCRIPTION":"\"{"} {"#;
+        let mut reader = JsonReader::new(data.as_bytes());
+        assert!(reader.next_record(&NumericIds).unwrap().is_err());
+        assert_eq!(reader.start(), (1, Some(1)));
+        assert!(reader.next_record(&NumericIds).unwrap().is_ok());
+        assert_eq!(reader.start().1, Some(2));
+        // Нарушенная разметка массива завершает чтение
+        assert!(reader.next_record(&NumericIds).unwrap().is_err());
+        assert!(reader.next_record(&NumericIds).is_none());
+
+        let mut reader = JsonReader::new("{}".as_bytes());
+        assert!(reader.next_record(&NumericIds).unwrap().is_err());
+        assert!(
+            JsonReader::new("".as_bytes())
+                .next_record(&NumericIds)
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use super::{csv::parse_record, json::json_error, tracked::Tracked};
+use crate::{OperationName, accounts::AccountResolver, errors::ParseFileError, types::CsvRecord};
+use std::io::{BufRead, Read};
+
+/// ## Потоковое чтение json lines
+///
+/// Каждая непустая строка - отдельный json-объект записи.
+pub(super) struct JsonlReader<R> {
+    r: Tracked<R>,
+    line: String,
+    /// Смещение и строка начала последней записи
+    start: (u64, u64),
+    done: bool,
+}
+
+impl<R: Read> JsonlReader<R> {
+    pub(super) fn new(r: R) -> Self {
+        Self {
+            r: Tracked::new(r),
+            line: String::new(),
+            start: (0, 1),
+            done: false,
+        }
+    }
+
+    /// Следующая операция, `None` - конец файла
+    pub(super) fn next_record(
+        &mut self,
+        accounts: &impl AccountResolver,
+    ) -> Option<Result<OperationName, ParseFileError>> {
+        if self.done {
+            return None;
+        }
+        loop {
+            self.start = (self.r.offset(), self.r.line());
+            self.line.clear();
+            match self.r.read_line(&mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(ParseFileError::IoError(e)));
+                }
+            }
+            if self.line.trim().is_empty() {
+                continue;
+            }
+            let res = serde_json::from_str::<CsvRecord>(&self.line)
+                .map_err(json_error)
+                .and_then(|row| parse_record(row, accounts));
+            return Some(res);
+        }
+    }
+
+    /// Смещение и строка начала последней записи
+    pub(super) fn start(&self) -> (u64, Option<u64>) {
+        (self.start.0, Some(self.start.1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::NumericIds;
+
+    #[test]
+    fn test_parse_from_jsonl() {
+        let data = r#"{"TX_ID":1,"TX_TYPE":"DEPOSIT","FROM_USER_ID":0,"TO_USER_ID":7,"AMOUNT":100,"TIMESTAMP":1,"STATUS":"SUCCESS","DESCRIPTION":"a"}
+
+{"TX_ID":2}
+"#;
+        let mut reader = JsonlReader::new(data.as_bytes());
+        assert_eq!(
+            reader.next_record(&NumericIds).unwrap().unwrap().name(),
+            "7"
+        );
+        // Испорченная строка не мешает читать следующие
+        assert!(reader.next_record(&NumericIds).unwrap().is_err());
+        assert_eq!(reader.start().1, Some(3));
+        assert!(reader.next_record(&NumericIds).is_none());
+    }
+}
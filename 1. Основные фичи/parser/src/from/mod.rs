@@ -1,9 +1,198 @@
 mod bin;
 mod csv;
+mod json;
+mod jsonl;
+mod mt940;
+mod tracked;
 mod txt;
 
-use crate::{OperationName, errors::ParseFileError, types::FileType};
-use tracing::{error, info, instrument};
+use crate::{
+    OperationName,
+    accounts::{AccountResolver, NumericIds},
+    errors::{ParseFileError, Position},
+    types::{FileType, ParseMode},
+};
+use std::io::Read;
+use tracing::{error, info, instrument, warn};
+
+/// ## Потоковое чтение операций
+///
+/// Итератор по операциям файла: записи читаются по одной,
+/// поэтому память не зависит от размера файла.
+///
+/// ### Пример
+/// ```
+/// use parser::{from::RecordReader, types::FileType};
+///
+/// let data = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION
+/// 1,DEPOSIT,0,7,100,1633036860000,SUCCESS,Пополнение
+/// ";
+/// let reader = RecordReader::new(data.as_bytes(), FileType::CSV);
+/// for op in reader {
+///     assert_eq!(op.unwrap().name(), "7");
+/// }
+/// ```
+///
+/// ### Режимы разбора
+/// В [ParseMode::Strict] (по умолчанию) первая ошибочная запись возвращается
+/// как [ParseFileError::Record] с местом в файле, и чтение завершается.
+/// В [ParseMode::Lenient] ошибочные записи пропускаются и доступны через
+/// [RecordReader::skipped], I/O ошибки по-прежнему возвращаются.
+/// ```
+/// use parser::{from::RecordReader, types::{FileType, ParseMode}};
+///
+/// let data = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION
+/// 1,DEPOSIT,0,7,сто,1633036860000,SUCCESS,Пополнение
+/// 2,DEPOSIT,0,7,100,1633036860000,SUCCESS,Пополнение
+/// ";
+/// let mut reader = RecordReader::new(data.as_bytes(), FileType::CSV).with_mode(ParseMode::Lenient);
+/// assert_eq!(reader.by_ref().count(), 1);
+/// let position = reader.skipped()[0].position().unwrap();
+/// assert_eq!((position.record, position.line), (0, Some(2)));
+/// ```
+pub struct RecordReader<R, A = NumericIds> {
+    format: Format<R>,
+    accounts: A,
+    mode: ParseMode,
+    /// Номер следующей записи
+    index: usize,
+    /// Пропущенные записи (в [ParseMode::Lenient])
+    skipped: Vec<ParseFileError>,
+    done: bool,
+}
+
+/// Чтение конкретного формата
+enum Format<R> {
+    Bin(bin::BinReader<R>),
+    Csv(csv::CsvReader<R>),
+    Txt(txt::TxtReader<R>),
+    Json(json::JsonReader<R>),
+    Jsonl(jsonl::JsonlReader<R>),
+    Mt940(mt940::Mt940Reader<R>),
+}
+
+impl<R: Read> RecordReader<R> {
+    /// ## Чтение операций, id пользователей - их имена
+    ///
+    /// ### Arguments
+    /// * `r` - reader
+    /// * `file_type` - тип файла
+    pub fn new(r: R, file_type: FileType) -> Self {
+        Self::with_accounts(r, file_type, NumericIds)
+    }
+}
+
+impl<R: Read, A: AccountResolver> RecordReader<R, A> {
+    /// ## Чтение операций, сопоставляя id пользователей с именами
+    ///
+    /// ### Arguments
+    /// * `r` - reader
+    /// * `file_type` - тип файла
+    /// * `accounts` - сопоставление id и имен (например, `&AccountRegistry`)
+    pub fn with_accounts(r: R, file_type: FileType, accounts: A) -> Self {
+        let format = match file_type {
+            FileType::BIN => Format::Bin(bin::BinReader::new(r)),
+            FileType::CSV => Format::Csv(csv::CsvReader::new(r)),
+            FileType::TXT => Format::Txt(txt::TxtReader::new(r)),
+            FileType::JSON => Format::Json(json::JsonReader::new(r)),
+            FileType::JSONL => Format::Jsonl(jsonl::JsonlReader::new(r)),
+            FileType::MT940 => Format::Mt940(mt940::Mt940Reader::new(r)),
+        };
+        Self {
+            format,
+            accounts,
+            mode: ParseMode::default(),
+            index: 0,
+            skipped: vec![],
+            done: false,
+        }
+    }
+
+    /// Режим разбора
+    pub fn with_mode(mut self, mode: ParseMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Ошибки пропущенных записей, каждая - [ParseFileError::Record]
+    pub fn skipped(&self) -> &[ParseFileError] {
+        &self.skipped
+    }
+
+    /// Забрать ошибки пропущенных записей после чтения
+    pub fn into_skipped(self) -> Vec<ParseFileError> {
+        self.skipped
+    }
+
+    fn next_record(&mut self) -> Option<Result<OperationName, ParseFileError>> {
+        match &mut self.format {
+            Format::Bin(r) => r.next_record(&self.accounts),
+            Format::Csv(r) => r.next_record(&self.accounts),
+            Format::Txt(r) => r.next_record(&self.accounts),
+            Format::Json(r) => r.next_record(&self.accounts),
+            Format::Jsonl(r) => r.next_record(&self.accounts),
+            Format::Mt940(r) => r.next_record(&self.accounts),
+        }
+    }
+
+    /// Место последней прочитанной записи
+    fn record_position(&self, record: usize) -> Position {
+        let (offset, line) = match &self.format {
+            Format::Bin(r) => r.start(),
+            Format::Csv(r) => r.start(),
+            Format::Txt(r) => r.start(),
+            Format::Json(r) => r.start(),
+            Format::Jsonl(r) => r.start(),
+            Format::Mt940(r) => r.start(),
+        };
+        Position {
+            record,
+            offset,
+            line,
+        }
+    }
+}
+
+impl<R: Read, A: AccountResolver> Iterator for RecordReader<R, A> {
+    type Item = Result<OperationName, ParseFileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.done {
+                return None;
+            }
+            let res = self.next_record()?;
+            let record = self.index;
+            self.index += 1;
+
+            let e = match res {
+                Ok(op) => return Some(Ok(op)),
+                Err(e @ ParseFileError::IoError(_)) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+                Err(e) => ParseFileError::Record {
+                    position: self.record_position(record),
+                    source: Box::new(e),
+                },
+            };
+            match self.mode {
+                ParseMode::Strict => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+                ParseMode::Lenient => {
+                    warn!("запись пропущена: {}", e);
+                    self.skipped.push(e);
+                    // Только в bin нужно искать начало следующей записи
+                    if let Format::Bin(r) = &mut self.format {
+                        r.recover();
+                    }
+                }
+            }
+        }
+    }
+}
 
 /// ## Парсит фаил в зависимости от его типа
 /// (пока только операции)
@@ -15,17 +204,83 @@ impl FromFile {
     /// ### Arguments
     /// * `r` - reader
     /// * `file_type` - тип файла
-    #[instrument(skip(r), name = "parse_operations_from_file")]
-    pub fn operations<R: std::io::Read>(
+    pub fn operations<R: Read>(
+        r: &mut R,
+        file_type: FileType,
+    ) -> Result<Vec<OperationName>, ParseFileError> {
+        Self::operations_with(r, file_type, &NumericIds)
+    }
+
+    /// ## Парсит операции, сопоставляя id пользователей с именами
+    ///
+    /// Все операции собираются в память, для больших файлов
+    /// используйте [RecordReader].
+    ///
+    /// ### Arguments
+    /// * `r` - reader
+    /// * `file_type` - тип файла
+    /// * `accounts` - сопоставление id и имен (например, реестр `Storage`)
+    #[instrument(skip(r, accounts), name = "parse_operations_from_file")]
+    pub fn operations_with<R: Read>(
         r: &mut R,
         file_type: FileType,
+        accounts: &impl AccountResolver,
     ) -> Result<Vec<OperationName>, ParseFileError> {
         info!("Парсим операции: {:?}", file_type);
-        match file_type {
-            FileType::BIN => bin::parse_from_bin(r),
-            FileType::CSV => csv::parse_from_csv(r),
-            FileType::TXT => txt::parse_from_txt(r),
-        }
-        .inspect_err(|e| error!("ошибка при парсинге операций: {}", e))
+        RecordReader::with_accounts(r, file_type, accounts)
+            .collect::<Result<Vec<_>, _>>()
+            .inspect_err(|e| error!("ошибка при парсинге операций: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &str = "# Record 1 (CLOSE)
+TX_ID: 1
+TX_TYPE: CLOSE
+FROM_USER_ID: 0
+TO_USER_ID: 7
+AMOUNT: 0
+TIMESTAMP: 1
+STATUS: BROKEN
+DESCRIPTION: \"\"
+
+# Record 2 (CLOSE)
+TX_ID: 2
+TX_TYPE: CLOSE
+FROM_USER_ID: 0
+TO_USER_ID: 7
+AMOUNT: 0
+TIMESTAMP: 1
+STATUS: SUCCESS
+DESCRIPTION: \"\"
+";
+
+    #[test]
+    fn test_strict_mode() {
+        let mut reader = RecordReader::new(DATA.as_bytes(), FileType::TXT);
+        let err = reader.next().unwrap().unwrap_err();
+        assert_eq!(
+            err.position(),
+            Some(&Position {
+                record: 0,
+                offset: 0,
+                line: Some(1)
+            })
+        );
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_lenient_mode() {
+        let mut reader =
+            RecordReader::new(DATA.as_bytes(), FileType::TXT).with_mode(ParseMode::Lenient);
+        let operations: Vec<_> = reader.by_ref().collect::<Result<_, _>>().unwrap();
+        assert_eq!(operations.len(), 1);
+        assert_eq!(operations[0].operation().id(), 2);
+        assert_eq!(reader.skipped().len(), 1);
+        assert_eq!(reader.skipped()[0].position().unwrap().record, 0);
     }
 }
@@ -0,0 +1,340 @@
+use super::{tracked::Tracked, txt::unescape};
+use crate::{
+    OperationName,
+    accounts::AccountResolver,
+    errors::ParseFileError,
+    record::{Record, RecordType},
+};
+use bank::{
+    account::AccountId,
+    balance::operations::{Operation, OperationStatus},
+};
+use std::io::{BufRead, Read};
+
+/// Строка `:61:` и поле `:86:` одной операции
+struct Entry {
+    /// Смещение и строка `:61:`
+    start: (u64, u64),
+    owner: AccountId,
+    line: String,
+    info: Option<String>,
+}
+
+/// ## Потоковое чтение выписки в стиле SWIFT MT940
+///
+/// Операция - пара `:61:` и `:86:`, пользователь берется из `:25:`.
+/// Остальные поля (`:20:`, `:28C:`, `:60F:`, `:62F:`, конверт SWIFT) пропускаются,
+/// продолжение `:86:` на следующих строках склеивается.
+pub(super) struct Mt940Reader<R> {
+    r: Tracked<R>,
+    line: String,
+    /// Смещение и строка начала текущей строки
+    line_start: (u64, u64),
+    /// Смещение и строка начала последней записи
+    start: (u64, u64),
+    done: bool,
+    /// Строка уже прочитана, но относится к следующей операции
+    peeked: bool,
+    owner: Option<AccountId>,
+    entry: Option<Entry>,
+}
+
+impl<R: Read> Mt940Reader<R> {
+    pub(super) fn new(r: R) -> Self {
+        Self {
+            r: Tracked::new(r),
+            line: String::new(),
+            line_start: (0, 1),
+            start: (0, 1),
+            done: false,
+            peeked: false,
+            owner: None,
+            entry: None,
+        }
+    }
+
+    /// Следующая операция, `None` - конец файла
+    pub(super) fn next_record(
+        &mut self,
+        accounts: &impl AccountResolver,
+    ) -> Option<Result<OperationName, ParseFileError>> {
+        if self.done {
+            return None;
+        }
+        loop {
+            if !self.peeked {
+                self.line_start = (self.r.offset(), self.r.line());
+                self.line.clear();
+                match self.r.read_line(&mut self.line) {
+                    Ok(0) => return self.entry.take().map(|e| self.parse_entry(e, accounts)),
+                    Ok(_) => {}
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(ParseFileError::IoError(e)));
+                    }
+                }
+            }
+            self.peeked = false;
+
+            let line = self.line.trim_end_matches(['\r', '\n']);
+            let tag = line
+                .strip_prefix(':')
+                .and_then(|l| l.split_once(':'))
+                .filter(|(tag, _)| tag.len() <= 3);
+
+            let Some((tag, value)) = tag else {
+                if line == "-" || line.starts_with('-') && line.ends_with('}') {
+                    // Конец выписки
+                    if let Some(entry) = self.entry.take() {
+                        self.peeked = true;
+                        return Some(self.parse_entry(entry, accounts));
+                    }
+                    self.owner = None;
+                } else if let Some(Entry {
+                    info: Some(info), ..
+                }) = self.entry.as_mut()
+                {
+                    info.push_str(line);
+                }
+                continue;
+            };
+
+            if tag == "86"
+                && let Some(entry) = self.entry.as_mut()
+                && entry.info.is_none()
+            {
+                entry.info = Some(value.to_string());
+                continue;
+            }
+            // Любое другое поле завершает текущую операцию
+            if let Some(entry) = self.entry.take() {
+                self.peeked = true;
+                return Some(self.parse_entry(entry, accounts));
+            }
+
+            match tag {
+                "25" => match value.trim().parse() {
+                    Ok(owner) => self.owner = Some(owner),
+                    Err(_) => {
+                        self.owner = None;
+                        self.start = self.line_start;
+                        return Some(Err(ParseFileError::SerializeError(
+                            ":25: ожидается id пользователя",
+                        )));
+                    }
+                },
+                "61" => {
+                    let Some(owner) = self.owner else {
+                        self.start = self.line_start;
+                        return Some(Err(ParseFileError::SerializeError(
+                            ":61: вне выписки (нет :25:)",
+                        )));
+                    };
+                    self.entry = Some(Entry {
+                        start: self.line_start,
+                        owner,
+                        line: value.to_string(),
+                        info: None,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Смещение и строка начала последней записи
+    pub(super) fn start(&self) -> (u64, Option<u64>) {
+        (self.start.0, Some(self.start.1))
+    }
+
+    fn parse_entry(
+        &mut self,
+        entry: Entry,
+        accounts: &impl AccountResolver,
+    ) -> Result<OperationName, ParseFileError> {
+        self.start = entry.start;
+        parse_entry(entry, accounts)
+    }
+}
+
+/// Разбор `:61:` - `ГГММДД[ММДД]<C|D><сумма>,<дробь>N<код><ссылка>//<TX_ID>`
+fn parse_statement_line(line: &str) -> Result<(char, u64, String, u64), ParseFileError> {
+    let rest = line
+        .get(6..)
+        .filter(|_| line[..6].bytes().all(|b| b.is_ascii_digit()))
+        .ok_or(ParseFileError::SerializeError(":61: ожидается дата ГГММДД"))?;
+    // Необязательная дата проводки ММДД
+    let rest = match rest.get(..4) {
+        Some(date) if date.bytes().all(|b| b.is_ascii_digit()) => &rest[4..],
+        _ => rest,
+    };
+
+    let mut chars = rest.chars();
+    let mark = chars
+        .next()
+        .filter(|c| *c == 'C' || *c == 'D')
+        .ok_or(ParseFileError::SerializeError(":61: ожидается C или D"))?;
+    let rest = chars.as_str();
+
+    let (amount, rest) = rest
+        .split_once(',')
+        .ok_or(ParseFileError::SerializeError(":61: ожидается сумма"))?;
+    let amount = amount
+        .parse::<u64>()
+        .or(Err(ParseFileError::SerializeError(
+            ":61: сумма ожидается u64",
+        )))?;
+    let fraction = rest.bytes().take_while(u8::is_ascii_digit).count();
+    if rest[..fraction].bytes().any(|b| b != b'0') {
+        return Err(ParseFileError::SerializeError(
+            ":61: дробные суммы не поддерживаются",
+        ));
+    }
+    let rest = &rest[fraction..];
+
+    let code =
+        rest.strip_prefix('N')
+            .and_then(|r| r.get(..3))
+            .ok_or(ParseFileError::SerializeError(
+                ":61: ожидается код операции",
+            ))?;
+    let tx_id = rest[4..]
+        .split_once("//")
+        .and_then(|(_, id)| id.trim().parse::<u64>().ok())
+        .ok_or(ParseFileError::SerializeError(":61: ожидается //TX_ID"))?;
+    Ok((mark, amount, code.to_string(), tx_id))
+}
+
+/// Значение подполя `:86:` вида `/KEY/value/`
+fn get_subfield<'a>(info: &'a str, key: &str) -> Option<&'a str> {
+    let start = info.find(&format!("/{key}/"))? + key.len() + 2;
+    let value = &info[start..];
+    Some(value.split_once('/').map_or(value, |(v, _)| v))
+}
+
+fn parse_entry(
+    entry: Entry,
+    accounts: &impl AccountResolver,
+) -> Result<OperationName, ParseFileError> {
+    let Entry {
+        owner, line, info, ..
+    } = entry;
+    let (mark, amount, code, tx_id) = parse_statement_line(&line)?;
+    let info = info.ok_or(ParseFileError::SerializeError(":61: без :86:"))?;
+
+    // Описание последнее и может содержать `/`
+    let (info, description) = info
+        .split_once("/DESC/")
+        .ok_or(ParseFileError::SerializeError(":86: ожидается /DESC/"))?;
+    let timestamp = get_subfield(info, "TS")
+        .and_then(|ts| ts.parse::<u64>().ok())
+        .ok_or(ParseFileError::SerializeError(":86: /TS/ ожидается u64"))?;
+    let status = match get_subfield(info, "STATUS") {
+        Some("PENDING") => OperationStatus::PENDING,
+        Some("SUCCESS") => OperationStatus::SUCCESS,
+        Some("FAILURE") => OperationStatus::FAILURE,
+        _ => {
+            return Err(ParseFileError::SerializeError(
+                ":86: /STATUS/ ожидается: [PENDING, SUCCESS, FAILURE]",
+            ));
+        }
+    };
+    let counterparty = match get_subfield(info, "CP") {
+        Some(cp) => cp
+            .parse::<AccountId>()
+            .or(Err(ParseFileError::SerializeError(
+                ":86: /CP/ ожидается u64",
+            )))?,
+        None => 0,
+    };
+
+    let (tx_type, from, to) = match (code.as_str(), mark) {
+        ("DEP", 'C') => (RecordType::Deposit, 0, owner),
+        ("WDL", 'D') => (RecordType::Withdrawal, owner, 0),
+        ("TRF", 'C') => (RecordType::Transfer, counterparty, owner),
+        ("TRF", 'D') => (RecordType::TransferOut, owner, counterparty),
+        ("CLS", 'C') => (RecordType::Close, 0, owner),
+        ("INT", 'C') => (RecordType::Interest, 0, owner),
+        ("CHG", 'D') => (RecordType::Fee, owner, 0),
+        ("STO", 'C') => (RecordType::StandingOrder, counterparty, owner),
+        ("STO", 'D') => (RecordType::StandingOrderOut, owner, counterparty),
+        _ => {
+            return Err(ParseFileError::SerializeError(
+                ":61: код ожидается: [DEP C, WDL D, TRF C/D, CLS C, INT C, CHG D, STO C/D]",
+            ));
+        }
+    };
+    let record = Record {
+        tx_type,
+        from,
+        to,
+        amount,
+    };
+    let (tx_type, name) = record.into_operation_type(accounts)?;
+    let operation = Operation::load(
+        tx_id,
+        timestamp,
+        tx_type,
+        status,
+        Some(unescape(description)),
+    );
+    Ok(OperationName(operation, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::NumericIds;
+    use bank::balance::operations::OperationType;
+
+    #[test]
+    fn test_parse_from_mt940() {
+        let data = "{1:F01BANKXXXX0000000000}{2:I940BANKXXXXN}{4:
+:20:STMT1
+:25:7
+:28C:1
+:60F:C211001EUR0,
+:61:2110011001C100,00NDEPNONREF//1
+:86:/TS/1633046400000/STATUS/SUCCESS/CP/0/DESC/\"a/b
+ c\"
+:61:211001D40,NTRFNONREF//2
+:86:/TS/1633046400000/STATUS/FAILURE/CP/3/DESC/\"\"
+:62F:C211001EUR60,
+-}
+";
+        let mut reader = Mt940Reader::new(data.as_bytes());
+        let first = reader.next_record(&NumericIds).unwrap().unwrap();
+        assert_eq!(first.name(), "7");
+        assert_eq!(first.operation().tx_type, OperationType::Deposit(100));
+        assert_eq!(first.operation().description, "a/b c");
+
+        let second = reader.next_record(&NumericIds).unwrap().unwrap();
+        assert_eq!(
+            second.operation().tx_type,
+            OperationType::Transfer("3".into(), 40, false)
+        );
+        assert_eq!(second.operation().status, OperationStatus::FAILURE);
+        assert!(reader.next_record(&NumericIds).is_none());
+    }
+
+    #[test]
+    fn test_parse_from_mt940_errors() {
+        assert!(parse_statement_line("211001X1,NDEPNONREF//1").is_err());
+        assert!(parse_statement_line("211001C1,50NDEPNONREF//1").is_err());
+        assert!(parse_statement_line("211001C1,NDEPNONREF").is_err());
+
+        let data = ":61:211001C1,NDEPNONREF//1\n:86:/TS/1/STATUS/SUCCESS/DESC/\"\"\n";
+        let mut reader = Mt940Reader::new(data.as_bytes());
+        assert!(reader.next_record(&NumericIds).unwrap().is_err());
+        assert_eq!(reader.start(), (0, Some(1)));
+
+        let data = ":25:7\n:61:211001X1,NDEPNONREF//1\n:86:\n:61:211001C1,NDEPNONREF//2\n";
+        let mut reader = Mt940Reader::new(data.as_bytes());
+        assert!(reader.next_record(&NumericIds).unwrap().is_err());
+        assert_eq!(reader.start(), (6, Some(2)));
+        // Запись без :86: - тоже ошибка, но только этой записи
+        assert!(reader.next_record(&NumericIds).unwrap().is_err());
+        assert_eq!(reader.start(), (38, Some(4)));
+        assert!(reader.next_record(&NumericIds).is_none());
+    }
+}
@@ -0,0 +1,83 @@
+use std::io::{BufRead, BufReader, Read, Result};
+
+/// ## Буферизованное чтение с подсчетом байт и строк
+///
+/// Нужно, чтобы указывать место ошибочной записи во входном файле.
+pub(super) struct Tracked<R> {
+    r: BufReader<R>,
+    offset: u64,
+    newlines: u64,
+}
+
+impl<R: Read> Tracked<R> {
+    pub(super) fn new(r: R) -> Self {
+        Self {
+            r: BufReader::new(r),
+            offset: 0,
+            newlines: 0,
+        }
+    }
+
+    /// Прочитано байт
+    pub(super) fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Номер текущей строки, с 1
+    pub(super) fn line(&self) -> u64 {
+        self.newlines + 1
+    }
+
+    /// Следующий байт, `None` - конец файла
+    pub(super) fn next_byte(&mut self) -> Result<Option<u8>> {
+        let Some(&b) = self.fill_buf()?.first() else {
+            return Ok(None);
+        };
+        self.consume(1);
+        Ok(Some(b))
+    }
+}
+
+impl<R: Read> Read for Tracked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let n = {
+            let available = self.fill_buf()?;
+            let n = available.len().min(buf.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read> BufRead for Tracked<R> {
+    fn fill_buf(&mut self) -> Result<&[u8]> {
+        self.r.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let consumed = &self.r.buffer()[..amt];
+        self.newlines += consumed.iter().filter(|&&b| b == b'\n').count() as u64;
+        self.offset += amt as u64;
+        self.r.consume(amt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracked_position() {
+        let mut r = Tracked::new("ab\ncd\n\nef".as_bytes());
+        let mut line = String::new();
+        r.read_line(&mut line).unwrap();
+        assert_eq!((r.offset(), r.line()), (3, 2));
+
+        let mut rest = String::new();
+        r.read_to_string(&mut rest).unwrap();
+        assert_eq!((r.offset(), r.line()), (9, 4));
+        assert_eq!(r.next_byte().unwrap(), None);
+    }
+}
@@ -1,111 +1,218 @@
-use crate::{OperationName, errors::ParseFileError};
-use bank::balance::operations::{Operation, OperationStatus, OperationType};
+use super::tracked::Tracked;
+use crate::{
+    OperationName,
+    accounts::AccountResolver,
+    errors::ParseFileError,
+    record::{Record, RecordType},
+};
+use bank::balance::operations::{Operation, OperationStatus};
+use std::io::{BufRead, Read};
 
 /// Получение значения атрибута строки
 fn get_atr(rows: &str, atr_name: &str) -> Option<String> {
-    let i_st = rows.find(atr_name)?;
-    let i_end = i_st + rows[i_st..].find('\n').unwrap_or(rows.len() - i_st);
-    let (_, value) = rows[i_st..i_end].split_once(":")?;
+    rows.lines().find_map(|row| {
+        let (name, value) = row.split_once(':')?;
+        (name.trim() == atr_name).then(|| value.trim().to_string())
+    })
+}
+
+/// Снятие кавычек и экранирования с описания
+pub(super) fn unescape(description: &str) -> String {
+    let description = description
+        .strip_prefix('"')
+        .and_then(|d| d.strip_suffix('"'))
+        .unwrap_or(description);
+
+    let mut res = String::with_capacity(description.len());
+    let mut chars = description.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => res.push('\n'),
+                Some('r') => res.push('\r'),
+                Some(c) => res.push(c),
+                None => res.push('\\'),
+            },
+            c => res.push(c),
+        }
+    }
+    res
+}
 
-    Some(value.trim().to_string())
+/// ## Потоковое чтение txt-файла
+///
+/// Записи разделены пустой строкой и читаются построчно,
+/// в памяти держится только текущая запись.
+pub(super) struct TxtReader<R> {
+    r: Tracked<R>,
+    line: String,
+    rows: String,
+    /// Смещение и строка начала последней записи
+    start: (u64, u64),
+    done: bool,
 }
 
-/// Преобразование txt-файла в список операций
-pub(super) fn parse_from_txt<R: std::io::Read>(
-    r: &mut R,
-) -> Result<Vec<OperationName>, ParseFileError> {
-    let mut buf = String::new();
-    r.read_to_string(&mut buf)
-        .map_err(ParseFileError::IoError)?;
-
-    let data: Vec<&str> = buf.split("\n\n").collect();
-
-    let mut operations: Vec<Result<OperationName, ParseFileError>> = data
-        .iter()
-        .map(|rows| {
-            let len_rows = rows.split("\n").count();
-            if len_rows != 9 {
-                return Err(ParseFileError::SerializeError(
-                    "rоличество строк не соответствует 9",
-                ));
+impl<R: Read> TxtReader<R> {
+    pub(super) fn new(r: R) -> Self {
+        Self {
+            r: Tracked::new(r),
+            line: String::new(),
+            rows: String::new(),
+            start: (0, 1),
+            done: false,
+        }
+    }
+
+    /// Следующая операция, `None` - конец файла
+    pub(super) fn next_record(
+        &mut self,
+        accounts: &impl AccountResolver,
+    ) -> Option<Result<OperationName, ParseFileError>> {
+        if self.done {
+            return None;
+        }
+        self.rows.clear();
+        let mut len_rows = 0;
+        loop {
+            let start = (self.r.offset(), self.r.line());
+            self.line.clear();
+            match self.r.read_line(&mut self.line) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(ParseFileError::IoError(e)));
+                }
+            }
+            if self.line.trim().is_empty() {
+                // Лишние пустые строки между записями пропускаем
+                if len_rows == 0 {
+                    continue;
+                }
+                break;
+            }
+            if len_rows == 0 {
+                self.start = start;
             }
+            self.rows.push_str(&self.line);
+            len_rows += 1;
+        }
+        if len_rows == 0 {
+            return None;
+        }
+        Some(parse_rows(&self.rows, len_rows, accounts))
+    }
+
+    /// Смещение и строка начала последней записи
+    pub(super) fn start(&self) -> (u64, Option<u64>) {
+        (self.start.0, Some(self.start.1))
+    }
+}
 
-            let tx_type = get_atr(rows, "TX_TYPE")
-                .ok_or(ParseFileError::SerializeError("missing tx_type"))?;
-            let to_user_id = get_atr(rows, "TO_USER_ID")
-                .ok_or(ParseFileError::SerializeError("missing to_user_id"))?;
-            let from_user_id = get_atr(rows, "FROM_USER_ID")
-                .ok_or(ParseFileError::SerializeError("missing from_user_id"))?;
-            let timestamp = get_atr(rows, "TIMESTAMP")
-                .ok_or(ParseFileError::SerializeError("missing timestamp"))?;
-            let description = get_atr(rows, "DESCRIPTION")
-                .ok_or(ParseFileError::SerializeError("missing description"))?
-                .trim_matches('"')
-                .to_string();
-            let tx_id =
-                get_atr(rows, "TX_ID").ok_or(ParseFileError::SerializeError("missing tx_id"))?;
-            let amount =
-                get_atr(rows, "AMOUNT").ok_or(ParseFileError::SerializeError("missing amount"))?;
-            let status =
-                get_atr(rows, "STATUS").ok_or(ParseFileError::SerializeError("missing status"))?;
-
-            let timestamp = timestamp
-                .parse::<u64>()
-                .or(Err(ParseFileError::SerializeError(
-                    "timestamp ожидается u64",
-                )))?;
-            let amount = amount
-                .parse::<u64>()
-                .or(Err(ParseFileError::SerializeError("amount ожидается u64")))?;
-            let tx_id = tx_id
-                .parse::<u64>()
-                .or(Err(ParseFileError::SerializeError("tx_id ожидается u64")))?;
-
-            let (tx_type, name) = match tx_type.as_str() {
-                "DEPOSIT" => Ok((OperationType::Deposit(amount), to_user_id)),
-                "WITHDRAWAL" => Ok((OperationType::Withdraw(amount), from_user_id)),
-                "TRANSFER" => Ok((
-                    OperationType::Transfer(from_user_id.clone(), amount, true),
-                    to_user_id,
-                )),
-                _ => Err(ParseFileError::SerializeError(
-                    "tx_type ожидается: [DEPOSIT, WITHDRAWAL, TRANSFER]",
-                )),
-            }?;
-
-            let status = match status.as_str() {
-                "PENDING" => Ok(OperationStatus::PENDING),
-                "SUCCESS" => Ok(OperationStatus::SUCCESS),
-                "FAILURE" => Ok(OperationStatus::FAILURE),
-                _ => Err(ParseFileError::SerializeError(
-                    "status ожидается: [PENDING, SUCCESS, FAILURE]",
-                )),
-            }?;
-            let operation = Operation::load(tx_id, timestamp, tx_type, status, Some(description));
-
-            Ok(OperationName(operation, name.to_string()))
-        })
-        .collect();
-    if let Some(op) = operations.last()
-        && op.is_err()
-    {
-        operations.pop();
+/// Преобразование строк одной записи в операцию
+fn parse_rows(
+    rows: &str,
+    len_rows: usize,
+    accounts: &impl AccountResolver,
+) -> Result<OperationName, ParseFileError> {
+    if len_rows != 9 {
+        return Err(ParseFileError::SerializeError(
+            "rоличество строк не соответствует 9",
+        ));
     }
-    operations.into_iter().collect()
+
+    let tx_type =
+        get_atr(rows, "TX_TYPE").ok_or(ParseFileError::SerializeError("missing tx_type"))?;
+    let to_user_id =
+        get_atr(rows, "TO_USER_ID").ok_or(ParseFileError::SerializeError("missing to_user_id"))?;
+    let from_user_id = get_atr(rows, "FROM_USER_ID")
+        .ok_or(ParseFileError::SerializeError("missing from_user_id"))?;
+    let timestamp =
+        get_atr(rows, "TIMESTAMP").ok_or(ParseFileError::SerializeError("missing timestamp"))?;
+    let description = get_atr(rows, "DESCRIPTION")
+        .ok_or(ParseFileError::SerializeError("missing description"))?;
+    let description = unescape(&description);
+    let tx_id = get_atr(rows, "TX_ID").ok_or(ParseFileError::SerializeError("missing tx_id"))?;
+    let amount = get_atr(rows, "AMOUNT").ok_or(ParseFileError::SerializeError("missing amount"))?;
+    let status = get_atr(rows, "STATUS").ok_or(ParseFileError::SerializeError("missing status"))?;
+
+    let timestamp = timestamp
+        .parse::<u64>()
+        .or(Err(ParseFileError::SerializeError(
+            "timestamp ожидается u64",
+        )))?;
+    let amount = amount
+        .parse::<u64>()
+        .or(Err(ParseFileError::SerializeError("amount ожидается u64")))?;
+    let tx_id = tx_id
+        .parse::<u64>()
+        .or(Err(ParseFileError::SerializeError("tx_id ожидается u64")))?;
+    let to_user_id = to_user_id
+        .parse::<u64>()
+        .or(Err(ParseFileError::SerializeError(
+            "to_user_id ожидается u64",
+        )))?;
+    let from_user_id = from_user_id
+        .parse::<u64>()
+        .or(Err(ParseFileError::SerializeError(
+            "from_user_id ожидается u64",
+        )))?;
+
+    let record = Record {
+        tx_type: RecordType::from_name(&tx_type)?,
+        from: from_user_id,
+        to: to_user_id,
+        amount,
+    };
+    let (tx_type, name) = record.into_operation_type(accounts)?;
+
+    let status = match status.as_str() {
+        "PENDING" => Ok(OperationStatus::PENDING),
+        "SUCCESS" => Ok(OperationStatus::SUCCESS),
+        "FAILURE" => Ok(OperationStatus::FAILURE),
+        _ => Err(ParseFileError::SerializeError(
+            "status ожидается: [PENDING, SUCCESS, FAILURE]",
+        )),
+    }?;
+    let operation = Operation::load(tx_id, timestamp, tx_type, status, Some(description));
+
+    Ok(OperationName(operation, name))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::{fs::File, io::BufReader};
+    use crate::accounts::NumericIds;
+    use std::fs::File;
     const PATH_TEST: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test.txt");
 
     #[test]
     fn test_parse_from_txt_success() {
         let file = File::open(PATH_TEST).unwrap();
-        let mut buf = BufReader::new(file);
-        let res = parse_from_txt(&mut buf);
-        assert!(res.is_ok());
-        assert_eq!(res.unwrap().len(), 1000);
+        let mut reader = TxtReader::new(file);
+        let mut count = 0;
+        while let Some(res) = reader.next_record(&NumericIds) {
+            assert!(res.is_ok());
+            count += 1;
+        }
+        assert_eq!(count, 1000);
+    }
+
+    #[test]
+    fn test_parse_from_txt_truncated() {
+        let data = "# Record 1 (CLOSE)\nTX_ID: 1\nTX_TYPE: CLOSE\n";
+        let mut reader = TxtReader::new(data.as_bytes());
+        assert!(reader.next_record(&NumericIds).unwrap().is_err());
+        assert!(reader.next_record(&NumericIds).is_none());
+    }
+
+    #[test]
+    fn test_parse_from_txt_position() {
+        let data = "\n# Record 1 (CLOSE)\nTX_ID: x\n\n\n# Record 2 (CLOSE)\n";
+        let mut reader = TxtReader::new(data.as_bytes());
+        assert!(reader.next_record(&NumericIds).unwrap().is_err());
+        assert_eq!(reader.start(), (1, Some(2)));
+        assert!(reader.next_record(&NumericIds).unwrap().is_err());
+        assert_eq!(reader.start(), (31, Some(6)));
     }
 }
@@ -1,11 +1,30 @@
 #![deny(unreachable_pub)]
+pub mod accounts;
+pub mod diff;
 pub mod errors;
 pub mod from;
+mod record;
 pub mod to;
 pub mod types;
 use bank::{Name, balance::operations::Operation};
 
-/// Внутрення обертка операции, сохраняющая дополнительно пользователя
-/// (Мб костыль до того, как Name перерастет в User)
+/// Внутрення обертка операции, сохраняющая дополнительно пользователя.
+/// Имя переводится в id файла через [accounts::AccountResolver]
 #[derive(Debug, PartialEq, Clone)]
 pub struct OperationName(Operation, Name);
+
+impl OperationName {
+    pub fn new(operation: Operation, name: Name) -> Self {
+        Self(operation, name)
+    }
+
+    /// Операция
+    pub fn operation(&self) -> &Operation {
+        &self.0
+    }
+
+    /// Пользователь, в чьей истории находится операция
+    pub fn name(&self) -> &Name {
+        &self.1
+    }
+}
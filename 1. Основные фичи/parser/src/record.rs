@@ -0,0 +1,234 @@
+use crate::{
+    OperationName,
+    accounts::{AccountResolver, resolve_id, resolve_name},
+    errors::ParseFileError,
+};
+use bank::{
+    Name,
+    account::AccountId,
+    balance::operations::{OperationAmount, OperationType},
+};
+
+/// ## Тип записи в файле
+///
+/// `TRANSFER` - перевод со стороны получателя, `TRANSFER_OUT` - со стороны
+/// отправителя. Так обе половины перевода сохраняются без потерь.
+/// Так же устроены `STANDING_ORDER` и `STANDING_ORDER_OUT`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum RecordType {
+    Deposit,
+    Transfer,
+    Withdrawal,
+    TransferOut,
+    Close,
+    Interest,
+    Fee,
+    StandingOrder,
+    StandingOrderOut,
+}
+
+impl RecordType {
+    /// Название типа в CSV и TXT
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            RecordType::Deposit => "DEPOSIT",
+            RecordType::Transfer => "TRANSFER",
+            RecordType::Withdrawal => "WITHDRAWAL",
+            RecordType::TransferOut => "TRANSFER_OUT",
+            RecordType::Close => "CLOSE",
+            RecordType::Interest => "INTEREST",
+            RecordType::Fee => "FEE",
+            RecordType::StandingOrder => "STANDING_ORDER",
+            RecordType::StandingOrderOut => "STANDING_ORDER_OUT",
+        }
+    }
+
+    /// Код типа в BIN
+    pub(crate) fn code(&self) -> u8 {
+        match self {
+            RecordType::Deposit => 0,
+            RecordType::Transfer => 1,
+            RecordType::Withdrawal => 2,
+            RecordType::TransferOut => 3,
+            RecordType::Close => 4,
+            RecordType::Interest => 5,
+            RecordType::Fee => 6,
+            RecordType::StandingOrder => 7,
+            RecordType::StandingOrderOut => 8,
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Result<Self, ParseFileError> {
+        match name {
+            "DEPOSIT" => Ok(RecordType::Deposit),
+            "TRANSFER" => Ok(RecordType::Transfer),
+            "WITHDRAWAL" => Ok(RecordType::Withdrawal),
+            "TRANSFER_OUT" => Ok(RecordType::TransferOut),
+            "CLOSE" => Ok(RecordType::Close),
+            "INTEREST" => Ok(RecordType::Interest),
+            "FEE" => Ok(RecordType::Fee),
+            "STANDING_ORDER" => Ok(RecordType::StandingOrder),
+            "STANDING_ORDER_OUT" => Ok(RecordType::StandingOrderOut),
+            _ => Err(ParseFileError::SerializeError(
+                "tx_type ожидается: [DEPOSIT, WITHDRAWAL, TRANSFER, TRANSFER_OUT, CLOSE, INTEREST, FEE, STANDING_ORDER, STANDING_ORDER_OUT]",
+            )),
+        }
+    }
+
+    pub(crate) fn from_code(code: u8) -> Result<Self, ParseFileError> {
+        match code {
+            0 => Ok(RecordType::Deposit),
+            1 => Ok(RecordType::Transfer),
+            2 => Ok(RecordType::Withdrawal),
+            3 => Ok(RecordType::TransferOut),
+            4 => Ok(RecordType::Close),
+            5 => Ok(RecordType::Interest),
+            6 => Ok(RecordType::Fee),
+            7 => Ok(RecordType::StandingOrder),
+            8 => Ok(RecordType::StandingOrderOut),
+            _ => Err(ParseFileError::SerializeError("Неверный тип операции")),
+        }
+    }
+}
+
+/// ## Поля записи, общие для всех форматов
+///
+/// `0` в `from`/`to` - "нет пользователя".
+#[derive(Debug, PartialEq)]
+pub(crate) struct Record {
+    pub(crate) tx_type: RecordType,
+    pub(crate) from: AccountId,
+    pub(crate) to: AccountId,
+    pub(crate) amount: OperationAmount,
+}
+
+impl Record {
+    /// Разложить операцию пользователя на поля записи
+    pub(crate) fn from_operation(
+        operation: &OperationName,
+        accounts: &impl AccountResolver,
+    ) -> Result<Self, ParseFileError> {
+        let OperationName(op, name) = operation;
+        let name = Some(name.as_str());
+
+        let (tx_type, amount, from, to) = match &op.tx_type {
+            OperationType::Deposit(amount) => (RecordType::Deposit, *amount, None, name),
+            OperationType::Withdraw(amount) => (RecordType::Withdrawal, *amount, name, None),
+            OperationType::Transfer(other, amount, true) => {
+                (RecordType::Transfer, *amount, Some(other.as_str()), name)
+            }
+            OperationType::Transfer(other, amount, false) => {
+                (RecordType::TransferOut, *amount, name, Some(other.as_str()))
+            }
+            OperationType::Close => (RecordType::Close, 0, None, name),
+            OperationType::Interest(amount) => (RecordType::Interest, *amount, None, name),
+            OperationType::Fee(amount) => (RecordType::Fee, *amount, name, None),
+            OperationType::StandingOrder(other, amount, true) => (
+                RecordType::StandingOrder,
+                *amount,
+                Some(other.as_str()),
+                name,
+            ),
+            OperationType::StandingOrder(other, amount, false) => (
+                RecordType::StandingOrderOut,
+                *amount,
+                name,
+                Some(other.as_str()),
+            ),
+        };
+
+        Ok(Record {
+            tx_type,
+            from: resolve_id(accounts, from)?,
+            to: resolve_id(accounts, to)?,
+            amount,
+        })
+    }
+
+    /// Собрать тип операции и владельца истории из полей записи
+    pub(crate) fn into_operation_type(
+        self,
+        accounts: &impl AccountResolver,
+    ) -> Result<(OperationType, Name), ParseFileError> {
+        let Record {
+            tx_type,
+            from,
+            to,
+            amount,
+        } = self;
+
+        let (tx_type, owner) = match tx_type {
+            RecordType::Deposit => (OperationType::Deposit(amount), to),
+            RecordType::Withdrawal => (OperationType::Withdraw(amount), from),
+            RecordType::Transfer => (
+                OperationType::Transfer(resolve_name(accounts, from)?, amount, true),
+                to,
+            ),
+            RecordType::TransferOut => (
+                OperationType::Transfer(resolve_name(accounts, to)?, amount, false),
+                from,
+            ),
+            RecordType::Close => (OperationType::Close, to),
+            RecordType::Interest => (OperationType::Interest(amount), to),
+            RecordType::Fee => (OperationType::Fee(amount), from),
+            RecordType::StandingOrder => (
+                OperationType::StandingOrder(resolve_name(accounts, from)?, amount, true),
+                to,
+            ),
+            RecordType::StandingOrderOut => (
+                OperationType::StandingOrder(resolve_name(accounts, to)?, amount, false),
+                from,
+            ),
+        };
+        Ok((tx_type, resolve_name(accounts, owner)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::NumericIds;
+    use bank::balance::operations::Operation;
+
+    #[test]
+    fn test_record_type_codes() {
+        for tx_type in [
+            RecordType::Deposit,
+            RecordType::Transfer,
+            RecordType::Withdrawal,
+            RecordType::TransferOut,
+            RecordType::Close,
+            RecordType::Interest,
+            RecordType::Fee,
+            RecordType::StandingOrder,
+            RecordType::StandingOrderOut,
+        ] {
+            assert_eq!(RecordType::from_code(tx_type.code()).unwrap(), tx_type);
+            assert_eq!(RecordType::from_name(tx_type.name()).unwrap(), tx_type);
+        }
+        assert!(RecordType::from_code(9).is_err());
+        assert!(RecordType::from_name("OTHER").is_err());
+    }
+
+    #[test]
+    fn test_record_transfer_out() {
+        let op = OperationName(
+            Operation::transfer(1, "7".into(), 50, false),
+            "3".to_string(),
+        );
+        let record = Record::from_operation(&op, &NumericIds).unwrap();
+        assert_eq!(
+            record,
+            Record {
+                tx_type: RecordType::TransferOut,
+                from: 3,
+                to: 7,
+                amount: 50,
+            }
+        );
+        assert_eq!(
+            record.into_operation_type(&NumericIds).unwrap(),
+            (OperationType::Transfer("7".into(), 50, false), "3".into())
+        );
+    }
+}
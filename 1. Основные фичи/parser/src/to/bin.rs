@@ -1,50 +1,44 @@
-use crate::{OperationName, errors::ParseFileError};
-use bank::balance::operations::{OperationStatus, OperationType};
+use crate::{OperationName, accounts::AccountResolver, errors::ParseFileError, record::Record};
+use bank::balance::operations::OperationStatus;
+use std::io::Write;
 
-pub(super) fn parse_to_bin<W: std::io::Write>(
-    w: &mut W,
-    operations: &[OperationName],
-) -> Result<(), ParseFileError> {
-    for op in operations {
-        w.write_all("YPBN".as_bytes())
-            .map_err(ParseFileError::IoError)?;
-
-        let mut body: Vec<u8> = vec![];
-        let OperationName(op, name) = op;
-
-        let (tx_type, amount, from_name, to_name) = match &op.tx_type {
-            OperationType::Deposit(amount) => (0, amount, "0", name.as_str()),
-            OperationType::Withdraw(amount) => (2, amount, name.as_str(), "0"),
+/// ## Потоковая запись bin-файла
+pub(super) struct BinWriter<W> {
+    w: W,
+}
 
-            // будем учитывать операцию перевода только с
-            // аккаунта-получателя
-            OperationType::Transfer(from_name, amount, true) => {
-                (1, amount, from_name.as_str(), name.as_str())
-            }
+impl<W: Write> BinWriter<W> {
+    pub(super) fn new(w: W) -> Self {
+        Self { w }
+    }
 
-            // пропускаем операции перевода с аккаунта-отправителя
-            OperationType::Transfer(_, _, false) => continue,
-            OperationType::Close => continue,
-        };
-        let from_name = from_name
-            .parse::<u64>()
-            .or(Err(ParseFileError::SerializeError(
-                "Пользователь должен иметь ID",
-            )))?;
-        let to_name = to_name
-            .parse::<u64>()
-            .or(Err(ParseFileError::SerializeError(
-                "Пользователь должен иметь ID",
-            )))?;
+    /// Записать одну операцию
+    pub(super) fn write_record(
+        &mut self,
+        op: &OperationName,
+        accounts: &impl AccountResolver,
+    ) -> Result<(), ParseFileError> {
+        let mut body: Vec<u8> = vec![];
+        let Record {
+            tx_type,
+            from,
+            to,
+            amount,
+        } = Record::from_operation(op, accounts)?;
+        let OperationName(op, _) = op;
+        // AMOUNT в формате знаковый
+        let amount = i64::try_from(amount).or(Err(ParseFileError::SerializeError(
+            "amount не помещается в i64",
+        )))?;
         let status: u8 = match &op.status {
             OperationStatus::SUCCESS => 0,
             OperationStatus::FAILURE => 1,
             OperationStatus::PENDING => 2,
         };
         body.extend_from_slice(&op.id().to_be_bytes());
-        body.push(tx_type);
-        body.extend_from_slice(&from_name.to_be_bytes());
-        body.extend_from_slice(&to_name.to_be_bytes());
+        body.push(tx_type.code());
+        body.extend_from_slice(&from.to_be_bytes());
+        body.extend_from_slice(&to.to_be_bytes());
         body.extend_from_slice(&amount.to_be_bytes());
         body.extend_from_slice(&op.timestamp().to_be_bytes());
         body.push(status);
@@ -55,20 +49,31 @@ pub(super) fn parse_to_bin<W: std::io::Write>(
         body.extend_from_slice(desc_bytes);
 
         let body_len = body.len() as u32;
-        w.write_all(&body_len.to_be_bytes())
+        self.w
+            .write_all("YPBN".as_bytes())
+            .map_err(ParseFileError::IoError)?;
+        self.w
+            .write_all(&body_len.to_be_bytes())
             .map_err(ParseFileError::IoError)?;
-        w.write_all(&body).map_err(ParseFileError::IoError)?;
+        self.w.write_all(&body).map_err(ParseFileError::IoError)?;
+        Ok(())
     }
-    w.flush()?;
 
-    Ok(())
+    pub(super) fn flush(&mut self) -> Result<(), ParseFileError> {
+        self.w.flush()?;
+        Ok(())
+    }
+
+    pub(super) fn into_inner(self) -> W {
+        self.w
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bank::balance::operations::Operation;
-    use std::io::BufWriter;
+    use crate::accounts::NumericIds;
+    use bank::balance::operations::{Operation, OperationType};
 
     #[test]
     fn test_parse_to_bin_success() {
@@ -88,9 +93,10 @@ mod tests {
             ),
             "9223372036854775807".to_string(),
         )];
-        let mut buf = BufWriter::new(Vec::new());
-        let res = parse_to_bin(&mut buf, &operations);
-        assert!(res.is_ok());
-        assert_eq!(buf.into_inner().unwrap(), answer);
+        let mut writer = BinWriter::new(Vec::new());
+        for op in &operations {
+            assert!(writer.write_record(op, &NumericIds).is_ok());
+        }
+        assert_eq!(writer.into_inner(), answer);
     }
 }
@@ -1,64 +1,80 @@
-use crate::{OperationName, errors::ParseFileError, types::CsvRecord};
-use bank::balance::operations::OperationType;
+use crate::{
+    OperationName, accounts::AccountResolver, errors::ParseFileError, record::Record,
+    types::CsvRecord,
+};
 use csv::Writer;
+use std::io::Write;
 
-pub(super) fn parse_to_csv<W: std::io::Write>(
-    w: &mut W,
-    operations: &[OperationName],
-) -> Result<(), ParseFileError> {
-    let mut w_csv = Writer::from_writer(w);
+/// ## Потоковая запись csv-файла
+///
+/// Заголовок пишется вместе с первой записью.
+pub(super) struct CsvWriter<W: Write> {
+    w: Writer<W>,
+}
 
-    for op in operations {
-        let OperationName(op, name) = op;
-        let (tx_type, amount, from_name, to_name) = match &op.tx_type {
-            OperationType::Deposit(amount) => ("DEPOSIT", amount, "0", name.as_str()),
-            OperationType::Withdraw(amount) => ("WITHDRAWAL", amount, name.as_str(), "0"),
-            // будем учитывать операцию перевода только с
-            // аккаунта-получателя
-            OperationType::Transfer(from_name, amount, true) => {
-                ("TRANSFER", amount, from_name.as_str(), name.as_str())
-            }
-            // пропускаем операции перевода с аккаунта-отправителя
-            OperationType::Transfer(_, _, false) => continue,
-            OperationType::Close => continue,
-        };
-        let from_name = from_name
-            .parse::<u64>()
-            .or(Err(ParseFileError::SerializeError(
-                "Пользователь должен иметь ID",
-            )))?;
-        let to_name = to_name
-            .parse::<u64>()
-            .or(Err(ParseFileError::SerializeError(
-                "Пользователь должен иметь ID",
-            )))?;
+impl<W: Write> CsvWriter<W> {
+    pub(super) fn new(w: W) -> Self {
+        Self {
+            w: Writer::from_writer(w),
+        }
+    }
 
-        w_csv
-            .serialize(CsvRecord {
-                TX_ID: op.id(),
-                TX_TYPE: tx_type.to_string(),
-                FROM_USER_ID: from_name,
-                TO_USER_ID: to_name,
-                AMOUNT: *amount,
-                TIMESTAMP: op.timestamp(),
-                STATUS: op.status.clone(),
-                DESCRIPTION: op.description.clone(),
-            })
+    /// Записать одну операцию
+    pub(super) fn write_record(
+        &mut self,
+        op: &OperationName,
+        accounts: &impl AccountResolver,
+    ) -> Result<(), ParseFileError> {
+        self.w
+            .serialize(to_record(op, accounts)?)
             .map_err(|e| match e.into_kind() {
                 csv::ErrorKind::Io(e) => ParseFileError::IoError(e),
                 _ => ParseFileError::SerializeError("Не соответствует шаблону"),
-            })?;
+            })
+    }
+
+    pub(super) fn flush(&mut self) -> Result<(), ParseFileError> {
+        self.w.flush()?;
+        Ok(())
+    }
+
+    pub(super) fn into_inner(self) -> Result<W, ParseFileError> {
+        self.w
+            .into_inner()
+            .map_err(|e| ParseFileError::IoError(e.into_error()))
     }
+}
+
+/// Преобразование операции в запись (общее для CSV и JSON)
+pub(super) fn to_record(
+    op: &OperationName,
+    accounts: &impl AccountResolver,
+) -> Result<CsvRecord, ParseFileError> {
+    let Record {
+        tx_type,
+        from,
+        to,
+        amount,
+    } = Record::from_operation(op, accounts)?;
+    let OperationName(op, _) = op;
 
-    w_csv.flush()?;
-    Ok(())
+    Ok(CsvRecord {
+        TX_ID: op.id(),
+        TX_TYPE: tx_type.name().to_string(),
+        FROM_USER_ID: from,
+        TO_USER_ID: to,
+        AMOUNT: amount,
+        TIMESTAMP: op.timestamp(),
+        STATUS: op.status.clone(),
+        DESCRIPTION: op.description.clone(),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bank::balance::operations::{Operation, OperationStatus};
-    use std::io::BufWriter;
+    use crate::accounts::NumericIds;
+    use bank::balance::operations::{Operation, OperationStatus, OperationType};
 
     #[test]
     fn test_parse_to_csv_success() {
@@ -75,10 +91,11 @@ mod tests {
             ),
             "9223372036854775807".to_string(),
         )];
-        let mut buf = BufWriter::new(Vec::new());
-        let res = parse_to_csv(&mut buf, &operations);
-        assert!(res.is_ok());
-        let bytes = buf.into_inner().unwrap();
+        let mut writer = CsvWriter::new(Vec::new());
+        for op in &operations {
+            assert!(writer.write_record(op, &NumericIds).is_ok());
+        }
+        let bytes = writer.into_inner().unwrap();
         let csv = String::from_utf8(bytes).unwrap();
         assert_eq!(csv, answer);
     }
@@ -0,0 +1,88 @@
+use super::csv::to_record;
+use crate::{OperationName, accounts::AccountResolver, errors::ParseFileError};
+use std::io::Write;
+
+/// ## Потоковая запись json-массива
+///
+/// Записи пишутся по одной, массив закрывается в [JsonWriter::finish].
+pub(super) struct JsonWriter<W> {
+    w: W,
+    count: usize,
+}
+
+impl<W: Write> JsonWriter<W> {
+    pub(super) fn new(w: W) -> Self {
+        Self { w, count: 0 }
+    }
+
+    /// Записать одну операцию
+    pub(super) fn write_record(
+        &mut self,
+        op: &OperationName,
+        accounts: &impl AccountResolver,
+    ) -> Result<(), ParseFileError> {
+        let record = to_record(op, accounts)?;
+        let sep: &[u8] = if self.count == 0 { b"[\n" } else { b",\n" };
+        self.w.write_all(sep)?;
+        serde_json::to_writer(&mut self.w, &record).map_err(json_error)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Закрыть массив
+    pub(super) fn finish(&mut self) -> Result<(), ParseFileError> {
+        let end: &[u8] = if self.count == 0 { b"[\n]\n" } else { b"\n]\n" };
+        self.w.write_all(end)?;
+        Ok(())
+    }
+
+    pub(super) fn flush(&mut self) -> Result<(), ParseFileError> {
+        self.w.flush()?;
+        Ok(())
+    }
+
+    pub(super) fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+/// Ошибка json: I/O или несоответствие шаблону
+pub(super) fn json_error(e: serde_json::Error) -> ParseFileError {
+    if e.is_io() {
+        ParseFileError::IoError(e.into())
+    } else {
+        ParseFileError::SerializeError("Не соответствует шаблону")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::NumericIds;
+    use bank::balance::operations::{Operation, OperationStatus, OperationType};
+
+    #[test]
+    fn test_parse_to_json() {
+        let mut writer = JsonWriter::new(Vec::new());
+        writer.finish().unwrap();
+        assert_eq!(writer.into_inner(), b"[\n]\n");
+
+        let mut writer = JsonWriter::new(Vec::new());
+        let op = OperationName(
+            Operation::load(
+                1,
+                2,
+                OperationType::Deposit(100),
+                OperationStatus::SUCCESS,
+                None,
+            ),
+            "7".into(),
+        );
+        writer.write_record(&op, &NumericIds).unwrap();
+        writer.write_record(&op, &NumericIds).unwrap();
+        writer.finish().unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&writer.into_inner()).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 2);
+        assert_eq!(json[0]["TO_USER_ID"], 7);
+    }
+}
@@ -0,0 +1,35 @@
+use super::{csv::to_record, json::json_error};
+use crate::{OperationName, accounts::AccountResolver, errors::ParseFileError};
+use std::io::Write;
+
+/// ## Потоковая запись json lines
+pub(super) struct JsonlWriter<W> {
+    w: W,
+}
+
+impl<W: Write> JsonlWriter<W> {
+    pub(super) fn new(w: W) -> Self {
+        Self { w }
+    }
+
+    /// Записать одну операцию
+    pub(super) fn write_record(
+        &mut self,
+        op: &OperationName,
+        accounts: &impl AccountResolver,
+    ) -> Result<(), ParseFileError> {
+        let record = to_record(op, accounts)?;
+        serde_json::to_writer(&mut self.w, &record).map_err(json_error)?;
+        self.w.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub(super) fn flush(&mut self) -> Result<(), ParseFileError> {
+        self.w.flush()?;
+        Ok(())
+    }
+
+    pub(super) fn into_inner(self) -> W {
+        self.w
+    }
+}
@@ -1,9 +1,125 @@
 mod bin;
 mod csv;
+mod json;
+mod jsonl;
+mod mt940;
 mod txt;
+use std::io::Write;
 use tracing::{error, instrument};
 
-use crate::{OperationName, errors::ParseFileError, types::FileType};
+use crate::{
+    OperationName,
+    accounts::{AccountResolver, NumericIds},
+    errors::ParseFileError,
+    types::FileType,
+};
+
+/// ## Потоковая запись операций
+///
+/// Операции записываются по одной, поэтому память не зависит
+/// от количества записей. После записи нужно вызвать [RecordWriter::finish].
+///
+/// ### Пример
+/// ```
+/// use bank::balance::operations::Operation;
+/// use parser::{OperationName, to::RecordWriter, types::FileType};
+///
+/// let mut writer = RecordWriter::new(Vec::new(), FileType::CSV);
+/// writer
+///     .write(&OperationName::new(Operation::deposit(1, 100), "7".into()))
+///     .unwrap();
+/// let csv = String::from_utf8(writer.finish().unwrap()).unwrap();
+/// assert_eq!(csv.lines().count(), 2);
+/// ```
+pub struct RecordWriter<W: Write, A = NumericIds> {
+    format: Format<W>,
+    accounts: A,
+}
+
+/// Запись конкретного формата
+enum Format<W: Write> {
+    Bin(bin::BinWriter<W>),
+    // csv::Writer держит внутренний буфер
+    Csv(Box<csv::CsvWriter<W>>),
+    Txt(txt::TxtWriter<W>),
+    Json(json::JsonWriter<W>),
+    Jsonl(jsonl::JsonlWriter<W>),
+    Mt940(mt940::Mt940Writer<W>),
+}
+
+impl<W: Write> RecordWriter<W> {
+    /// ## Запись операций, имена пользователей - их id
+    ///
+    /// ### Arguments
+    /// * `w` - writer
+    /// * `file_type` - тип файла
+    pub fn new(w: W, file_type: FileType) -> Self {
+        Self::with_accounts(w, file_type, NumericIds)
+    }
+}
+
+impl<W: Write, A: AccountResolver> RecordWriter<W, A> {
+    /// ## Запись операций, сопоставляя имена пользователей с id
+    ///
+    /// ### Arguments
+    /// * `w` - writer
+    /// * `file_type` - тип файла
+    /// * `accounts` - сопоставление id и имен (например, `&AccountRegistry`)
+    pub fn with_accounts(w: W, file_type: FileType, accounts: A) -> Self {
+        let format = match file_type {
+            FileType::BIN => Format::Bin(bin::BinWriter::new(w)),
+            FileType::CSV => Format::Csv(Box::new(csv::CsvWriter::new(w))),
+            FileType::TXT => Format::Txt(txt::TxtWriter::new(w)),
+            FileType::JSON => Format::Json(json::JsonWriter::new(w)),
+            FileType::JSONL => Format::Jsonl(jsonl::JsonlWriter::new(w)),
+            FileType::MT940 => Format::Mt940(mt940::Mt940Writer::new(w)),
+        };
+        Self { format, accounts }
+    }
+
+    /// Записать одну операцию
+    pub fn write(&mut self, operation: &OperationName) -> Result<(), ParseFileError> {
+        match &mut self.format {
+            Format::Bin(w) => w.write_record(operation, &self.accounts),
+            Format::Csv(w) => w.write_record(operation, &self.accounts),
+            Format::Txt(w) => w.write_record(operation, &self.accounts),
+            Format::Json(w) => w.write_record(operation, &self.accounts),
+            Format::Jsonl(w) => w.write_record(operation, &self.accounts),
+            Format::Mt940(w) => w.write_record(operation, &self.accounts),
+        }
+    }
+
+    /// Сбросить буферы во writer
+    pub fn flush(&mut self) -> Result<(), ParseFileError> {
+        match &mut self.format {
+            Format::Bin(w) => w.flush(),
+            Format::Csv(w) => w.flush(),
+            Format::Txt(w) => w.flush(),
+            Format::Json(w) => w.flush(),
+            Format::Jsonl(w) => w.flush(),
+            Format::Mt940(w) => w.flush(),
+        }
+    }
+
+    /// Завершить запись и вернуть writer
+    pub fn finish(mut self) -> Result<W, ParseFileError> {
+        // Закрывающая разметка формата
+        match &mut self.format {
+            Format::Json(w) => w.finish()?,
+            Format::Mt940(w) => w.finish()?,
+            _ => {}
+        }
+        self.flush()?;
+        match self.format {
+            Format::Bin(w) => Ok(w.into_inner()),
+            Format::Csv(w) => w.into_inner(),
+            Format::Txt(w) => Ok(w.into_inner()),
+            Format::Json(w) => Ok(w.into_inner()),
+            Format::Jsonl(w) => Ok(w.into_inner()),
+            Format::Mt940(w) => Ok(w.into_inner()),
+        }
+    }
+}
 
 /// ## Парсит файл в зависимости от его типа
 /// (пока только операции)
@@ -16,17 +132,136 @@ impl ToFile {
     /// * `w` - writer
     /// * `operations` - операции
     /// * `file_type` - тип файла
-    #[instrument(skip(w), name = "parse_operations_to_file")]
-    pub fn operations<W: std::io::Write>(
+    pub fn operations<W: Write>(
         w: &mut W,
         operations: &[OperationName],
         file_type: FileType,
     ) -> Result<(), ParseFileError> {
-        match file_type {
-            FileType::BIN => bin::parse_to_bin(w, operations),
-            FileType::CSV => csv::parse_to_csv(w, operations),
-            FileType::TXT => txt::parse_to_txt(w, operations),
+        Self::operations_with(w, operations, file_type, &NumericIds)
+    }
+
+    /// ## Парсит операции, сопоставляя имена пользователей с id
+    ///
+    /// ### Arguments
+    /// * `w` - writer
+    /// * `operations` - операции
+    /// * `file_type` - тип файла
+    /// * `accounts` - сопоставление id и имен (например, реестр `Storage`)
+    #[instrument(skip(w, operations, accounts), name = "parse_operations_to_file")]
+    pub fn operations_with<W: Write>(
+        w: &mut W,
+        operations: &[OperationName],
+        file_type: FileType,
+        accounts: &impl AccountResolver,
+    ) -> Result<(), ParseFileError> {
+        let mut writer = RecordWriter::with_accounts(w, file_type, accounts);
+        operations
+            .iter()
+            .try_for_each(|op| writer.write(op))
+            .and_then(|_| writer.finish().map(|_| ()))
+            .inspect_err(|e| error!("ошибка при парсинге операций: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from::FromFile;
+    use bank::balance::operations::{Operation, OperationStatus, OperationType};
+    use proptest::prelude::*;
+
+    /// Пользователь в файле - ненулевой id
+    fn name() -> impl Strategy<Value = String> {
+        (1..=u64::MAX).prop_map(|id| id.to_string())
+    }
+
+    fn tx_type() -> impl Strategy<Value = OperationType> {
+        // В BIN сумма знаковая
+        let amount = 0..=i64::MAX as u64;
+        prop_oneof![
+            amount.clone().prop_map(OperationType::Deposit),
+            amount.clone().prop_map(OperationType::Withdraw),
+            (name(), amount.clone(), any::<bool>())
+                .prop_map(|(name, amount, is_to)| OperationType::Transfer(name, amount, is_to)),
+            Just(OperationType::Close),
+            amount.clone().prop_map(OperationType::Interest),
+            amount.clone().prop_map(OperationType::Fee),
+            (name(), amount, any::<bool>()).prop_map(|(name, amount, is_to)| {
+                OperationType::StandingOrder(name, amount, is_to)
+            }),
+        ]
+    }
+
+    fn status() -> impl Strategy<Value = OperationStatus> {
+        prop_oneof![
+            Just(OperationStatus::SUCCESS),
+            Just(OperationStatus::FAILURE),
+            Just(OperationStatus::PENDING),
+        ]
+    }
+
+    fn operation() -> impl Strategy<Value = OperationName> {
+        (
+            any::<u64>(),
+            any::<u64>(),
+            tx_type(),
+            status(),
+            any::<String>(),
+            name(),
+        )
+            .prop_map(|(id, timestamp, tx_type, status, description, name)| {
+                OperationName(
+                    Operation::load(id, timestamp, tx_type, status, Some(description)),
+                    name,
+                )
+            })
+    }
+
+    fn round_trip(operations: &[OperationName], file_type: FileType) -> Vec<OperationName> {
+        let mut buf = Vec::new();
+        ToFile::operations(&mut buf, operations, file_type.clone()).unwrap();
+        FromFile::operations(&mut buf.as_slice(), file_type).unwrap()
+    }
+
+    proptest! {
+        #[test]
+        fn test_round_trip_bin(operations in prop::collection::vec(operation(), 0..20)) {
+            prop_assert_eq!(round_trip(&operations, FileType::BIN), operations);
+        }
+
+        #[test]
+        fn test_round_trip_csv(operations in prop::collection::vec(operation(), 0..20)) {
+            prop_assert_eq!(round_trip(&operations, FileType::CSV), operations);
+        }
+
+        #[test]
+        fn test_round_trip_txt(operations in prop::collection::vec(operation(), 0..20)) {
+            prop_assert_eq!(round_trip(&operations, FileType::TXT), operations);
         }
-        .inspect_err(|e| error!("ошибка при парсинге операций: {}", e))
+
+        #[test]
+        fn test_round_trip_json(operations in prop::collection::vec(operation(), 0..20)) {
+            prop_assert_eq!(round_trip(&operations, FileType::JSON), operations);
+        }
+
+        #[test]
+        fn test_round_trip_jsonl(operations in prop::collection::vec(operation(), 0..20)) {
+            prop_assert_eq!(round_trip(&operations, FileType::JSONL), operations);
+        }
+
+        #[test]
+        fn test_round_trip_mt940(operations in prop::collection::vec(operation(), 0..20)) {
+            prop_assert_eq!(round_trip(&operations, FileType::MT940), operations);
+        }
+    }
+
+    #[test]
+    fn test_bin_amount_overflow() {
+        let operations = vec![OperationName(
+            Operation::deposit(1, u64::MAX),
+            "1".to_string(),
+        )];
+        let res = ToFile::operations(&mut Vec::new(), &operations, FileType::BIN);
+        assert!(res.is_err());
     }
 }
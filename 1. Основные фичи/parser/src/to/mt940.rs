@@ -0,0 +1,189 @@
+use super::txt::escape;
+use crate::{
+    OperationName,
+    accounts::AccountResolver,
+    errors::ParseFileError,
+    record::{Record, RecordType},
+};
+use bank::{account::AccountId, balance::operations::OperationStatus};
+use std::io::Write;
+
+/// ## Потоковая запись выписки в стиле SWIFT MT940
+///
+/// Подряд идущие операции одного пользователя попадают в одну выписку:
+/// ```text
+/// :20:STMT1
+/// :25:<id пользователя>
+/// :28C:1
+/// :61:<ГГММДД><C|D><сумма>,N<код>NONREF//<TX_ID>
+/// :86:/TS/<время, мс>/STATUS/<статус>/CP/<id контрагента>/DESC/"<описание>"
+/// -
+/// ```
+/// Остатки `:60F:`/`:62F:` не пишутся: в операциях их нет.
+pub(super) struct Mt940Writer<W> {
+    w: W,
+    /// Пользователь открытой выписки
+    owner: Option<AccountId>,
+    /// Количество начатых выписок
+    statements: usize,
+}
+
+impl<W: Write> Mt940Writer<W> {
+    pub(super) fn new(w: W) -> Self {
+        Self {
+            w,
+            owner: None,
+            statements: 0,
+        }
+    }
+
+    /// Записать одну операцию
+    pub(super) fn write_record(
+        &mut self,
+        op: &OperationName,
+        accounts: &impl AccountResolver,
+    ) -> Result<(), ParseFileError> {
+        let Record {
+            tx_type,
+            from,
+            to,
+            amount,
+        } = Record::from_operation(op, accounts)?;
+        let OperationName(op, _) = op;
+
+        // Владелец выписки, контрагент, кредит/дебет и код операции
+        let (owner, counterparty, mark, code) = match tx_type {
+            RecordType::Deposit => (to, 0, 'C', "DEP"),
+            RecordType::Withdrawal => (from, 0, 'D', "WDL"),
+            RecordType::Transfer => (to, from, 'C', "TRF"),
+            RecordType::TransferOut => (from, to, 'D', "TRF"),
+            RecordType::Close => (to, 0, 'C', "CLS"),
+            RecordType::Interest => (to, 0, 'C', "INT"),
+            RecordType::Fee => (from, 0, 'D', "CHG"),
+            RecordType::StandingOrder => (to, from, 'C', "STO"),
+            RecordType::StandingOrderOut => (from, to, 'D', "STO"),
+        };
+
+        if self.owner != Some(owner) {
+            self.finish()?;
+            self.statements += 1;
+            let n = self.statements;
+            write!(self.w, ":20:STMT{n}\n:25:{owner}\n:28C:{n}\n")?;
+            self.owner = Some(owner);
+        }
+
+        let status = match &op.status {
+            OperationStatus::SUCCESS => "SUCCESS",
+            OperationStatus::FAILURE => "FAILURE",
+            OperationStatus::PENDING => "PENDING",
+        };
+        writeln!(
+            self.w,
+            ":61:{}{mark}{amount},N{code}NONREF//{}",
+            value_date(op.timestamp()),
+            op.id()
+        )?;
+        writeln!(
+            self.w,
+            ":86:/TS/{}/STATUS/{status}/CP/{counterparty}/DESC/\"{}\"",
+            op.timestamp(),
+            escape(&op.description)
+        )?;
+        Ok(())
+    }
+
+    /// Закрыть открытую выписку
+    pub(super) fn finish(&mut self) -> Result<(), ParseFileError> {
+        if self.owner.take().is_some() {
+            writeln!(self.w, "-")?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn flush(&mut self) -> Result<(), ParseFileError> {
+        self.w.flush()?;
+        Ok(())
+    }
+
+    pub(super) fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+/// Дата валютирования `ГГММДД` (UTC) по времени в миллисекундах
+fn value_date(timestamp: u64) -> String {
+    // Алгоритм civil_from_days (H. Hinnant)
+    let days = (timestamp / 1000 / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:02}{month:02}{day:02}", year.rem_euclid(100))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::NumericIds;
+    use bank::balance::operations::{Operation, OperationType};
+
+    #[test]
+    fn test_value_date() {
+        assert_eq!(value_date(0), "700101");
+        assert_eq!(value_date(1633036860000), "210930");
+        assert_eq!(value_date(951_782_400_000), "000229");
+    }
+
+    #[test]
+    fn test_parse_to_mt940() {
+        let answer = ":20:STMT1
+:25:7
+:28C:1
+:61:211001C100,NDEPNONREF//1
+:86:/TS/1633046400000/STATUS/SUCCESS/CP/0/DESC/\"a/b\"
+:61:211001D40,NTRFNONREF//2
+:86:/TS/1633046400000/STATUS/FAILURE/CP/3/DESC/\"\"
+-
+:20:STMT2
+:25:3
+:28C:2
+:61:211001C0,NCLSNONREF//3
+:86:/TS/1633046400000/STATUS/SUCCESS/CP/0/DESC/\"\"
+-
+";
+        let ts = 1633046400000;
+        let op = |id, tx_type, status, desc: &str, name: &str| {
+            OperationName(
+                Operation::load(id, ts, tx_type, status, Some(desc.into())),
+                name.into(),
+            )
+        };
+        let operations = [
+            op(
+                1,
+                OperationType::Deposit(100),
+                OperationStatus::SUCCESS,
+                "a/b",
+                "7",
+            ),
+            op(
+                2,
+                OperationType::Transfer("3".into(), 40, false),
+                OperationStatus::FAILURE,
+                "",
+                "7",
+            ),
+            op(3, OperationType::Close, OperationStatus::SUCCESS, "", "3"),
+        ];
+        let mut writer = Mt940Writer::new(Vec::new());
+        for op in &operations {
+            writer.write_record(op, &NumericIds).unwrap();
+        }
+        writer.finish().unwrap();
+        assert_eq!(String::from_utf8(writer.into_inner()).unwrap(), answer);
+    }
+}
@@ -1,35 +1,57 @@
-use crate::{OperationName, errors::ParseFileError};
-use bank::balance::operations::{OperationStatus, OperationType};
+use crate::{OperationName, accounts::AccountResolver, errors::ParseFileError, record::Record};
+use bank::balance::operations::OperationStatus;
+use std::io::Write;
 
-pub(super) fn parse_to_txt<W: std::io::Write>(
-    w: &mut W,
-    operations: &[OperationName],
-) -> Result<(), ParseFileError> {
-    for (i, op) in operations.iter().enumerate() {
-        let OperationName(op, name) = op;
-        let (tx_type, amount, from_name, to_name) = match &op.tx_type {
-            OperationType::Deposit(amount) => ("DEPOSIT", amount, "0", name.as_str()),
-            OperationType::Withdraw(amount) => ("WITHDRAWAL", amount, name.as_str(), "0"),
+/// Экранирование описания, чтобы оно оставалось одной строкой
+pub(super) fn escape(description: &str) -> String {
+    let mut res = String::with_capacity(description.len());
+    for c in description.chars() {
+        match c {
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            _ => res.push(c),
+        }
+    }
+    res
+}
 
-            // будем учитывать операцию перевода только с
-            // аккаунта-получателя
-            OperationType::Transfer(from_name, amount, true) => {
-                ("TRANSFER", amount, from_name.as_str(), name.as_str())
-            }
+/// ## Потоковая запись txt-файла
+pub(super) struct TxtWriter<W> {
+    w: W,
+    /// Номер последней записанной записи
+    indx: usize,
+}
 
-            // пропускаем операции перевода с аккаунта-отправителя
-            OperationType::Transfer(_, _, false) => continue,
-            OperationType::Close => continue,
-        };
+impl<W: Write> TxtWriter<W> {
+    pub(super) fn new(w: W) -> Self {
+        Self { w, indx: 0 }
+    }
+
+    /// Записать одну операцию
+    pub(super) fn write_record(
+        &mut self,
+        op: &OperationName,
+        accounts: &impl AccountResolver,
+    ) -> Result<(), ParseFileError> {
+        let Record {
+            tx_type,
+            from: from_name,
+            to: to_name,
+            amount,
+        } = Record::from_operation(op, accounts)?;
+        let OperationName(op, _) = op;
+        let tx_type = tx_type.name();
         let status = match &op.status {
             OperationStatus::SUCCESS => "SUCCESS",
             OperationStatus::FAILURE => "FAILURE",
             OperationStatus::PENDING => "PENDING",
         };
-        let indx = i + 1;
+        self.indx += 1;
+        let indx = self.indx;
         let id = op.id();
         let timestamp = op.timestamp();
-        let description = format!("\"{}\"", op.description);
+        let description = format!("\"{}\"", escape(&op.description));
         let data = format!(
             "# Record {indx} ({tx_type})
 TX_ID: {id}
@@ -41,17 +63,24 @@ TIMESTAMP: {timestamp}
 STATUS: {status}
 DESCRIPTION: {description}",
         );
-        writeln!(w, "{data}\n").or_else(|e| Err(ParseFileError::IoError(e)))?;
+        writeln!(self.w, "{data}\n").map_err(ParseFileError::IoError)
+    }
+
+    pub(super) fn flush(&mut self) -> Result<(), ParseFileError> {
+        self.w.flush()?;
+        Ok(())
+    }
+
+    pub(super) fn into_inner(self) -> W {
+        self.w
     }
-    w.flush()?;
-    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bank::balance::operations::Operation;
-    use std::io::BufWriter;
+    use crate::accounts::NumericIds;
+    use bank::balance::operations::{Operation, OperationType};
 
     #[test]
     fn test_parse_to_txt_success() {
@@ -76,10 +105,11 @@ DESCRIPTION: \"Record number 1\"
             ),
             "9223372036854775807".to_string(),
         )];
-        let mut buf = BufWriter::new(Vec::new());
-        let res = parse_to_txt(&mut buf, &operations);
-        assert!(res.is_ok());
-        let bytes = buf.into_inner().unwrap();
+        let mut writer = TxtWriter::new(Vec::new());
+        for op in &operations {
+            assert!(writer.write_record(op, &NumericIds).is_ok());
+        }
+        let bytes = writer.into_inner();
         let csv = String::from_utf8(bytes).unwrap();
         assert_eq!(csv, answer);
     }
@@ -13,6 +13,8 @@ use serde::{Deserialize, Serialize};
 /// - TIMESTAMP - Время операции
 /// - STATUS - Статус операции
 /// - DESCRIPTION - Описание
+///
+/// Те же поля у объектов JSON и JSON Lines.
 #[derive(Deserialize, Serialize)]
 #[allow(non_snake_case)]
 pub(crate) struct CsvRecord {
@@ -32,6 +34,9 @@ pub(crate) struct CsvRecord {
 /// - [FileType::CSV] - Csv формат файла
 /// - [FileType::TXT] - Txt формат файла
 /// - [FileType::BIN] - Bin формат файла
+/// - [FileType::JSON] - Json массив записей
+/// - [FileType::JSONL] - Json Lines, запись на строку
+/// - [FileType::MT940] - Выписка в стиле SWIFT MT940
 #[derive(Debug, PartialEq, Clone, ValueEnum)]
 pub enum FileType {
     /// Csv формат файла
@@ -40,6 +45,50 @@ pub enum FileType {
     TXT,
     /// Bin формат файла
     BIN,
+    /// Json массив записей
+    JSON,
+    /// Json Lines, запись на строку
+    JSONL,
+    /// Выписка в стиле SWIFT MT940
+    MT940,
+}
+
+impl FileType {
+    /// ## Определение типа по началу содержимого файла
+    ///
+    /// `head` - первые байты файла (например, `BufRead::fill_buf`).
+    /// `None`, если формат не распознан.
+    pub fn detect(head: &[u8]) -> Option<Self> {
+        if head.starts_with(b"YPBN") {
+            return Some(FileType::BIN);
+        }
+        let head = head.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(head);
+        let head = head.trim_ascii_start();
+        if head.starts_with(b"[") {
+            Some(FileType::JSON)
+        } else if head.starts_with(b"{1:") || head.starts_with(b":20:") {
+            // Выписка может быть в конверте SWIFT: {1:...}{2:...}{4:
+            Some(FileType::MT940)
+        } else if head.starts_with(b"{") {
+            Some(FileType::JSONL)
+        } else if head.starts_with(b"# Record") || head.starts_with(b"TX_ID:") {
+            Some(FileType::TXT)
+        } else if head.starts_with(b"TX_ID,") {
+            Some(FileType::CSV)
+        } else {
+            None
+        }
+    }
+}
+
+/// ## Режим разбора входного файла
+#[derive(Debug, Default, PartialEq, Clone, Copy, ValueEnum)]
+pub enum ParseMode {
+    /// Первая ошибочная запись завершает чтение
+    #[default]
+    Strict,
+    /// Ошибочные записи пропускаются и собираются в отчет
+    Lenient,
 }
 
 impl TryFrom<String> for FileType {
@@ -49,7 +98,27 @@ impl TryFrom<String> for FileType {
             "csv" => Ok(FileType::CSV),
             "txt" => Ok(FileType::TXT),
             "bin" => Ok(FileType::BIN),
+            "json" => Ok(FileType::JSON),
+            "jsonl" | "ndjson" => Ok(FileType::JSONL),
+            "mt940" | "sta" => Ok(FileType::MT940),
             _ => Err(()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(FileType::detect(b"YPBN\0\0"), Some(FileType::BIN));
+        assert_eq!(FileType::detect(b"\xEF\xBB\xBF\n [{"), Some(FileType::JSON));
+        assert_eq!(FileType::detect(b"{\"TX_ID\":1}"), Some(FileType::JSONL));
+        assert_eq!(FileType::detect(b"{1:F01BANK}{4:"), Some(FileType::MT940));
+        assert_eq!(FileType::detect(b":20:STMT1"), Some(FileType::MT940));
+        assert_eq!(FileType::detect(b"# Record 1"), Some(FileType::TXT));
+        assert_eq!(FileType::detect(b"TX_ID,TX_TYPE"), Some(FileType::CSV));
+        assert_eq!(FileType::detect(b""), None);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::RoomMetrics;
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Поле метрики, за которым следит правило
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    Temperature,
+    Humidity,
+    Pressure,
+    NoiseLevel,
+    DoorOpen,
+}
+
+impl Field {
+    // Значение поля, открытая дверь - 1.0
+    pub fn value(self, metrics: &RoomMetrics) -> f32 {
+        match self {
+            Field::Temperature => metrics.temperature,
+            Field::Humidity => metrics.humidity,
+            Field::Pressure => metrics.pressure,
+            Field::NoiseLevel => metrics.noise_level,
+            Field::DoorOpen => metrics.door_open as u8 as f32,
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Field::Temperature => "temperature",
+            Field::Humidity => "humidity",
+            Field::Pressure => "pressure",
+            Field::NoiseLevel => "noise_level",
+            Field::DoorOpen => "door_open",
+        };
+        f.write_str(name)
+    }
+}
+
+// Условие срабатывания. `hysteresis` - насколько значение должно вернуться
+// за порог, чтобы тревога снялась
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Condition {
+    Above {
+        limit: f32,
+        #[serde(default)]
+        hysteresis: f32,
+    },
+    Below {
+        limit: f32,
+        #[serde(default)]
+        hysteresis: f32,
+    },
+    // Скорость изменения по модулю, единиц в секунду
+    Rate {
+        limit: f32,
+        #[serde(default)]
+        hysteresis: f32,
+    },
+    // Поле не ноль дольше `seconds` (дверь открыта)
+    Duration {
+        seconds: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    pub field: Field,
+    #[serde(flatten)]
+    pub condition: Condition,
+}
+
+// Куда отправлять тревоги
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NotifierConfig {
+    Log,
+    // POST с JSON тревоги, только http://
+    Webhook { url: String },
+    // Тревоги строками JSON в конец файла
+    File { path: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+pub struct AlertConfig {
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    // Датчик молчит дольше - тревога
+    #[serde(default)]
+    pub silence_secs: Option<u64>,
+    #[serde(default)]
+    pub notifiers: Vec<NotifierConfig>,
+}
+
+impl AlertConfig {
+    // Формат по расширению: .json, остальное - TOML
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            Self::from_json(&text)
+        } else {
+            Self::from_toml(&text)
+        }
+    }
+
+    pub fn from_toml(text: &str) -> io::Result<Self> {
+        toml::from_str::<Self>(text)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+            .validated()
+    }
+
+    pub fn from_json(text: &str) -> io::Result<Self> {
+        serde_json::from_str::<Self>(text)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+            .validated()
+    }
+
+    fn validated(self) -> io::Result<Self> {
+        for rule in &self.rules {
+            let hysteresis = match rule.condition {
+                Condition::Above { hysteresis, .. }
+                | Condition::Below { hysteresis, .. }
+                | Condition::Rate { hysteresis, .. } => hysteresis,
+                Condition::Duration { .. } => 0.0,
+            };
+            if hysteresis < 0.0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("rule {}: negative hysteresis", rule.name),
+                ));
+            }
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_and_json() {
+        let config = AlertConfig::from_toml(
+            r#"
+            silence_secs = 30
+
+            [[rules]]
+            name = "hot"
+            field = "temperature"
+            kind = "above"
+            limit = 30.0
+            hysteresis = 1.5
+
+            [[rules]]
+            name = "door"
+            field = "door_open"
+            kind = "duration"
+            seconds = 60
+
+            [[notifiers]]
+            kind = "log"
+
+            [[notifiers]]
+            kind = "webhook"
+            url = "http://127.0.0.1:9000/alerts"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.silence_secs, Some(30));
+        assert_eq!(
+            config.rules[0].condition,
+            Condition::Above {
+                limit: 30.0,
+                hysteresis: 1.5
+            }
+        );
+        assert_eq!(config.rules[1].field, Field::DoorOpen);
+        assert_eq!(config.notifiers.len(), 2);
+
+        let json =
+            r#"{"rules": [{"name": "jump", "field": "pressure", "kind": "rate", "limit": 2.0}]}"#;
+        let config = AlertConfig::from_json(json).unwrap();
+        assert_eq!(
+            config.rules[0].condition,
+            Condition::Rate {
+                limit: 2.0,
+                hysteresis: 0.0
+            }
+        );
+
+        let bad = r#"{"rules": [{"name": "x", "field": "noise_level", "kind": "below", "limit": 1.0, "hysteresis": -1.0}]}"#;
+        assert!(AlertConfig::from_json(bad).is_err());
+    }
+
+    #[test]
+    fn test_example_config() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("alerts.toml");
+        let config = AlertConfig::load(&path).unwrap();
+        assert_eq!(config.rules.len(), 6);
+        assert_eq!(config.silence_secs, Some(10));
+    }
+}
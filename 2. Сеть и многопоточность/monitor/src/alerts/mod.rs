@@ -0,0 +1,429 @@
+//! Правила тревог над потоком метрик.
+//!
+//! Правила из [config::AlertConfig] проверяются для каждого датчика (адреса
+//! источника) отдельно. Тревога приходит один раз при срабатывании и один раз при
+//! снятии, повторные пакеты за порогом новых тревог не дают.
+
+pub mod config;
+pub mod notify;
+
+pub use config::{AlertConfig, Condition, Field, Rule};
+pub use notify::Notifier;
+
+use crate::RoomMetrics;
+use crate::error;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Имя правила для тревоги о молчащем датчике
+pub const SILENCE_RULE: &str = "silence";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Fired,
+    Resolved,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Alert {
+    pub rule: String,
+    pub source: SocketAddr,
+    pub state: AlertState,
+    pub timestamp: u64,
+    pub message: String,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.state {
+            AlertState::Fired => "🚨 ТРЕВОГА",
+            AlertState::Resolved => "✅ СНЯТА",
+        };
+        write!(
+            f,
+            "{} [{}] {}: {}",
+            state, self.rule, self.source, self.message
+        )
+    }
+}
+
+// Состояние правила для одного датчика
+#[derive(Debug, Default)]
+struct RuleState {
+    active: bool,
+    // Прошлое значение, для скорости изменения
+    last: Option<(u64, f32)>,
+    // С какого момента поле не ноль, для длительности
+    since: Option<u64>,
+}
+
+#[derive(Debug)]
+struct Source {
+    last_seen: Instant,
+    silent: bool,
+}
+
+pub struct AlertEngine {
+    rules: Vec<Rule>,
+    silence: Option<Duration>,
+    notifiers: Vec<Box<dyn Notifier>>,
+    states: HashMap<(usize, SocketAddr), RuleState>,
+    sources: HashMap<SocketAddr, Source>,
+}
+
+impl AlertEngine {
+    pub fn new(rules: Vec<Rule>, silence: Option<Duration>) -> Self {
+        Self {
+            rules,
+            silence,
+            notifiers: Vec::new(),
+            states: HashMap::new(),
+            sources: HashMap::new(),
+        }
+    }
+
+    // Правила и уведомления из конфига
+    pub fn from_config(config: AlertConfig) -> io::Result<Self> {
+        let mut engine = Self::new(config.rules, config.silence_secs.map(Duration::from_secs));
+        for notifier in &config.notifiers {
+            engine.add_notifier(notify::build(notifier)?);
+        }
+        Ok(engine)
+    }
+
+    pub fn add_notifier(&mut self, notifier: Box<dyn Notifier>) {
+        self.notifiers.push(notifier);
+    }
+
+    // Проверить метрику и разослать тревоги
+    pub fn handle(&mut self, metrics: &RoomMetrics, src: SocketAddr) -> Vec<Alert> {
+        let alerts = self.evaluate(metrics, src, Instant::now());
+        self.notify(&alerts);
+        alerts
+    }
+
+    // Найти замолчавшие датчики и разослать тревоги, вызывать периодически
+    pub fn tick(&mut self) -> Vec<Alert> {
+        let alerts = self.check_silence(Instant::now());
+        self.notify(&alerts);
+        alerts
+    }
+
+    fn notify(&mut self, alerts: &[Alert]) {
+        for alert in alerts {
+            for notifier in &mut self.notifiers {
+                if let Err(_e) = notifier.notify(alert) {
+                    error!("Ошибка отправки тревоги: {}", _e);
+                }
+            }
+        }
+    }
+
+    // Проверить метрику по всем правилам, `now` - время получения
+    pub fn evaluate(&mut self, metrics: &RoomMetrics, src: SocketAddr, now: Instant) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        let source = self.sources.entry(src).or_insert(Source {
+            last_seen: now,
+            silent: false,
+        });
+        source.last_seen = now;
+        if source.silent {
+            source.silent = false;
+            alerts.push(Alert {
+                rule: SILENCE_RULE.to_string(),
+                source: src,
+                state: AlertState::Resolved,
+                timestamp: metrics.timestamp,
+                message: "датчик снова на связи".to_string(),
+            });
+        }
+
+        for (i, rule) in self.rules.iter().enumerate() {
+            let state = self.states.entry((i, src)).or_default();
+            let value = rule.field.value(metrics);
+            let timestamp = metrics.timestamp;
+
+            let active = match rule.condition {
+                Condition::Above { limit, hysteresis } if state.active => {
+                    value > limit - hysteresis
+                }
+                Condition::Above { limit, .. } => value > limit,
+                Condition::Below { limit, hysteresis } if state.active => {
+                    value < limit + hysteresis
+                }
+                Condition::Below { limit, .. } => value < limit,
+                Condition::Rate { limit, hysteresis } => match state.last {
+                    // Пакеты в одну секунду скорость не меняют
+                    Some((last_time, last_value)) if timestamp > last_time => {
+                        let rate = (value - last_value).abs() / (timestamp - last_time) as f32;
+                        if state.active {
+                            rate > limit - hysteresis
+                        } else {
+                            rate > limit
+                        }
+                    }
+                    _ => state.active,
+                },
+                Condition::Duration { seconds } if value != 0.0 => {
+                    let since = *state.since.get_or_insert(timestamp);
+                    timestamp.saturating_sub(since) >= seconds
+                }
+                Condition::Duration { .. } => {
+                    state.since = None;
+                    false
+                }
+            };
+            // Точкой отсчета скорости остается первый пакет своей секунды
+            if state
+                .last
+                .is_none_or(|(last_time, _)| timestamp > last_time)
+            {
+                state.last = Some((timestamp, value));
+            }
+
+            if active != state.active {
+                state.active = active;
+                alerts.push(Alert {
+                    rule: rule.name.clone(),
+                    source: src,
+                    state: if active {
+                        AlertState::Fired
+                    } else {
+                        AlertState::Resolved
+                    },
+                    timestamp,
+                    message: describe(rule, value),
+                });
+            }
+        }
+        alerts
+    }
+
+    // Датчики, молчащие дольше `silence_secs` на момент `now`
+    pub fn check_silence(&mut self, now: Instant) -> Vec<Alert> {
+        let Some(timeout) = self.silence else {
+            return Vec::new();
+        };
+        let mut alerts = Vec::new();
+        for (src, source) in &mut self.sources {
+            if !source.silent && now.duration_since(source.last_seen) > timeout {
+                source.silent = true;
+                alerts.push(Alert {
+                    rule: SILENCE_RULE.to_string(),
+                    source: *src,
+                    state: AlertState::Fired,
+                    timestamp: unix_now(),
+                    message: format!("нет данных {} с", timeout.as_secs()),
+                });
+            }
+        }
+        alerts
+    }
+}
+
+fn describe(rule: &Rule, value: f32) -> String {
+    match rule.condition {
+        Condition::Above { limit, .. } => {
+            format!("{} = {:.1}, порог {:.1}", rule.field, value, limit)
+        }
+        Condition::Below { limit, .. } => {
+            format!("{} = {:.1}, минимум {:.1}", rule.field, value, limit)
+        }
+        Condition::Rate { limit, .. } => {
+            format!("{} = {:.1}, допустимо {:.1}/с", rule.field, value, limit)
+        }
+        Condition::Duration { seconds } => {
+            format!("{} = {:.0}, допустимо {} с", rule.field, value, seconds)
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn src(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn metrics(timestamp: u64, temperature: f32, door_open: bool) -> RoomMetrics {
+        RoomMetrics {
+            timestamp,
+            temperature,
+            humidity: 40.0,
+            pressure: 1000.0,
+            noise_level: 10.0,
+            door_open,
+        }
+    }
+
+    fn rule(name: &str, field: Field, condition: Condition) -> Rule {
+        Rule {
+            name: name.to_string(),
+            field,
+            condition,
+        }
+    }
+
+    // Состояния тревог по порядку температур
+    fn run(engine: &mut AlertEngine, temperatures: &[f32]) -> Vec<(u64, AlertState)> {
+        let now = Instant::now();
+        temperatures
+            .iter()
+            .enumerate()
+            .flat_map(|(t, &temp)| {
+                engine
+                    .evaluate(&metrics(t as u64, temp, false), src(1), now)
+                    .into_iter()
+                    .map(move |alert| (t as u64, alert.state))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_threshold_hysteresis() {
+        let mut engine = AlertEngine::new(
+            vec![rule(
+                "hot",
+                Field::Temperature,
+                Condition::Above {
+                    limit: 30.0,
+                    hysteresis: 2.0,
+                },
+            )],
+            None,
+        );
+        // Колебания около порога не снимают тревогу
+        let alerts = run(&mut engine, &[25.0, 31.0, 29.5, 30.5, 29.0, 27.5, 31.0]);
+        assert_eq!(
+            alerts,
+            [
+                (1, AlertState::Fired),
+                (5, AlertState::Resolved),
+                (6, AlertState::Fired)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rate() {
+        let mut engine = AlertEngine::new(
+            vec![rule(
+                "jump",
+                Field::Temperature,
+                Condition::Rate {
+                    limit: 2.0,
+                    hysteresis: 0.5,
+                },
+            )],
+            None,
+        );
+        let alerts = run(&mut engine, &[20.0, 21.0, 24.0, 25.8, 26.0]);
+        assert_eq!(alerts, [(2, AlertState::Fired), (4, AlertState::Resolved)]);
+    }
+
+    #[test]
+    fn test_rate_within_second() {
+        let mut engine = AlertEngine::new(
+            vec![rule(
+                "jump",
+                Field::Temperature,
+                Condition::Rate {
+                    limit: 5.0,
+                    hysteresis: 0.0,
+                },
+            )],
+            None,
+        );
+        let now = Instant::now();
+        let fired: Vec<_> = [(10, 20.0), (10, 30.0), (11, 30.0)]
+            .iter()
+            .flat_map(|&(t, temp)| engine.evaluate(&metrics(t, temp, false), src(1), now))
+            .map(|alert| (alert.timestamp, alert.state))
+            .collect();
+        assert_eq!(fired, [(11, AlertState::Fired)]);
+    }
+
+    #[test]
+    fn test_door_duration_per_source() {
+        let mut engine = AlertEngine::new(
+            vec![rule(
+                "door",
+                Field::DoorOpen,
+                Condition::Duration { seconds: 10 },
+            )],
+            None,
+        );
+        let now = Instant::now();
+        assert!(
+            engine
+                .evaluate(&metrics(100, 20.0, true), src(1), now)
+                .is_empty()
+        );
+        assert!(
+            engine
+                .evaluate(&metrics(105, 20.0, true), src(2), now)
+                .is_empty()
+        );
+        let fired = engine.evaluate(&metrics(110, 20.0, true), src(1), now);
+        assert_eq!(
+            (fired[0].source, fired[0].state),
+            (src(1), AlertState::Fired)
+        );
+        // У второго датчика дверь открыта только 5 секунд
+        assert!(
+            engine
+                .evaluate(&metrics(110, 20.0, true), src(2), now)
+                .is_empty()
+        );
+        let resolved = engine.evaluate(&metrics(111, 20.0, false), src(1), now);
+        assert_eq!(resolved[0].state, AlertState::Resolved);
+    }
+
+    #[test]
+    fn test_silence() {
+        let mut engine = AlertEngine::new(Vec::new(), Some(Duration::from_secs(5)));
+        let start = Instant::now();
+        engine.evaluate(&metrics(0, 20.0, false), src(1), start);
+        engine.evaluate(
+            &metrics(0, 20.0, false),
+            src(2),
+            start + Duration::from_secs(4),
+        );
+
+        let silent = engine.check_silence(start + Duration::from_secs(6));
+        assert_eq!(silent.len(), 1);
+        assert_eq!(
+            (silent[0].source, silent[0].state),
+            (src(1), AlertState::Fired)
+        );
+        // Повторно не срабатывает
+        assert!(
+            engine
+                .check_silence(start + Duration::from_secs(7))
+                .is_empty()
+        );
+
+        let back = engine.evaluate(
+            &metrics(8, 20.0, false),
+            src(1),
+            start + Duration::from_secs(8),
+        );
+        assert_eq!(
+            (back[0].rule.as_str(), back[0].state),
+            (SILENCE_RULE, AlertState::Resolved)
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use super::Alert;
+use super::config::NotifierConfig;
+use crate::logging::{ConsoleLogger, Logger};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Получатель тревог
+pub trait Notifier {
+    fn notify(&mut self, alert: &Alert) -> io::Result<()>;
+}
+
+// Уведомление из конфига
+pub fn build(config: &NotifierConfig) -> io::Result<Box<dyn Notifier>> {
+    Ok(match config {
+        NotifierConfig::Log => Box::new(LogNotifier::new(Box::new(ConsoleLogger::new(vec![])))),
+        NotifierConfig::Webhook { url } => Box::new(WebhookNotifier::new(url)?),
+        NotifierConfig::File { path } => Box::new(FileNotifier::open(path)?),
+    })
+}
+
+// Тревоги в лог
+pub struct LogNotifier {
+    logger: Box<dyn Logger>,
+}
+
+impl LogNotifier {
+    pub fn new(logger: Box<dyn Logger>) -> Self {
+        Self { logger }
+    }
+}
+
+impl Notifier for LogNotifier {
+    fn notify(&mut self, alert: &Alert) -> io::Result<()> {
+        self.logger.log(&alert.to_string());
+        Ok(())
+    }
+}
+
+// POST тревоги в JSON на http-адрес
+pub struct WebhookNotifier {
+    host: String,
+    path: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: &str) -> io::Result<Self> {
+        let rest = url.strip_prefix("http://").ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("webhook {}: поддерживается только http://", url),
+            )
+        })?;
+        let (host, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        Ok(Self {
+            host: host.to_string(),
+            path: path.to_string(),
+        })
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn notify(&mut self, alert: &Alert) -> io::Result<()> {
+        let body = serde_json::to_string(alert).map_err(io::Error::other)?;
+        let addr = self
+            .host
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, self.host.clone()))?;
+        let mut stream = TcpStream::connect_timeout(&addr, WEBHOOK_TIMEOUT)?;
+        stream.set_read_timeout(Some(WEBHOOK_TIMEOUT))?;
+        stream.set_write_timeout(Some(WEBHOOK_TIMEOUT))?;
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path,
+            self.host,
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes())?;
+
+        // Достаточно строки статуса: HTTP/1.1 200 OK
+        let mut status = String::new();
+        BufReader::new(stream).read_line(&mut status)?;
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(io::Error::other(format!(
+                "webhook {}: {}",
+                self.host,
+                status.trim()
+            ))),
+        }
+    }
+}
+
+// Тревоги строками JSON в конец файла
+pub struct FileNotifier {
+    file: File,
+}
+
+impl FileNotifier {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file })
+    }
+}
+
+impl Notifier for FileNotifier {
+    fn notify(&mut self, alert: &Alert) -> io::Result<()> {
+        let mut line = serde_json::to_vec(alert).map_err(io::Error::other)?;
+        line.push(b'\n');
+        self.file.write_all(&line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::AlertState;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn alert() -> Alert {
+        Alert {
+            rule: "hot".to_string(),
+            source: "127.0.0.1:5000".parse().unwrap(),
+            state: AlertState::Fired,
+            timestamp: 100,
+            message: "temperature = 31.0, порог 30.0".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_webhook() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/alerts", listener.local_addr().unwrap());
+        // Заглушка: принимает запрос и отвечает 200, потом 500
+        let stub = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for status in ["200 OK", "500 Internal Server Error"] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(value) = line.strip_prefix("Content-Length: ") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    request.push_str(&line);
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                request.push_str(&String::from_utf8(body).unwrap());
+                requests.push(request);
+                write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).unwrap();
+            }
+            requests
+        });
+
+        let mut webhook = WebhookNotifier::new(&url).unwrap();
+        webhook.notify(&alert()).unwrap();
+        assert!(webhook.notify(&alert()).is_err());
+        let requests = stub.join().unwrap();
+        assert!(requests[0].starts_with("POST /alerts HTTP/1.1"));
+        assert!(requests[0].ends_with(
+            r#""state":"fired","timestamp":100,"message":"temperature = 31.0, порог 30.0"}"#
+        ));
+
+        assert!(WebhookNotifier::new("https://example.com").is_err());
+    }
+
+    #[test]
+    fn test_file() {
+        let path = std::env::temp_dir().join(format!("monitor-alerts-{}.log", std::process::id()));
+        let mut file = FileNotifier::open(path.to_str().unwrap()).unwrap();
+        file.notify(&alert()).unwrap();
+        file.notify(&alert()).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.starts_with(r#"{"rule":"hot","source":"127.0.0.1:5000""#));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -18,6 +18,29 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
     let (receiver_handle, metrics_rx) = receiver.start_with_channel();
 
+    // MONITOR_DB=<файл>: метрики сохраняются в SQLite
+    #[cfg(feature = "sqlite")]
+    let mut sink = match std::env::var("MONITOR_DB") {
+        Ok(path) => {
+            use monitor::store::{MetricsSink, MetricsStore, Retention};
+            println!("Метрики сохраняются в {}", path);
+            let store = MetricsStore::open(std::path::Path::new(&path))?;
+            Some(MetricsSink::new(store).with_retention(Retention::default()))
+        }
+        Err(_) => None,
+    };
+
+    // MONITOR_ALERTS=<alerts.toml|alerts.json>: правила тревог
+    #[cfg(feature = "alerts")]
+    let mut alerts = match std::env::var("MONITOR_ALERTS") {
+        Ok(path) => {
+            let config = monitor::alerts::AlertConfig::load(std::path::Path::new(&path))?;
+            println!("Правила тревог: {} ({} правил)", path, config.rules.len());
+            Some(monitor::alerts::AlertEngine::from_config(config)?)
+        }
+        Err(_) => None,
+    };
+
     println!("Система мониторинга запущена. Ожидание данных...");
     println!("Нажмите Ctrl+C для остановки");
 
@@ -25,10 +48,24 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Основной цикл обработки данных
     loop {
-        match metrics_rx.recv() {
+        match metrics_rx.recv_timeout(std::time::Duration::from_secs(1)) {
             Ok((metrics, _src_addr)) => {
                 total_received += 1;
 
+                #[cfg(feature = "alerts")]
+                if let Some(alerts) = alerts.as_mut() {
+                    alerts.handle(&metrics, _src_addr);
+                }
+
+                // Ошибка записи не останавливает мониторинг: пачка останется и запишется позже,
+                // при долгой недоступности хранилища старые метрики отбрасываются
+                #[cfg(feature = "sqlite")]
+                if let Some(sink) = sink.as_mut()
+                    && let Err(e) = sink.push(metrics.clone(), _src_addr)
+                {
+                    println!("⚠️  Ошибка записи метрик: {}", e);
+                }
+
                 // Определяем статус тревоги
                 let alert_status = if metrics.door_open {
                     "🚨 ТРЕВОГА: ДВЕРЬ ОТКРЫТА!"
@@ -56,15 +93,39 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     alert_status
                 );
             }
-            Err(_) => {
+            // Датчики молчат: накопленная пачка все равно пишется по интервалу
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                #[cfg(feature = "sqlite")]
+                if let Some(sink) = sink.as_mut()
+                    && let Err(e) = sink.tick()
+                {
+                    println!("⚠️  Ошибка записи метрик: {}", e);
+                }
+            }
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
                 println!("🔌 Канал закрыт. Завершение работы.");
                 break;
             }
         }
+
+        // Ждем не дольше секунды, чтобы замечать молчащие датчики
+        #[cfg(feature = "alerts")]
+        if let Some(alerts) = alerts.as_mut() {
+            alerts.tick();
+        }
     }
 
     // Пытаемся дождаться завершения потока
     let _ = receiver_handle.join();
+    #[cfg(feature = "sqlite")]
+    if let Some(mut sink) = sink {
+        sink.flush()?;
+        println!(
+            "Сохранено {} метрик, отброшено {}",
+            sink.stored(),
+            sink.dropped()
+        );
+    }
 
     println!("Итог: получено {} пакетов данных", total_received);
     Ok(())
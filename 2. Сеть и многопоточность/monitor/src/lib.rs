@@ -1,7 +1,11 @@
+#[cfg(feature = "alerts")]
+pub mod alerts;
 pub mod logging;
 pub mod metrics;
 pub mod receiver;
 pub mod sender;
+#[cfg(feature = "sqlite")]
+pub mod store;
 pub use metrics::RoomMetrics;
 pub use receiver::MetricsReceiver;
 pub use sender::MetricsSender;
@@ -0,0 +1,477 @@
+use crate::RoomMetrics;
+use crate::{debug, error, info, warn};
+use rusqlite::{Connection, Transaction, params};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS metrics (
+    id          INTEGER PRIMARY KEY,
+    room        TEXT    NOT NULL,
+    timestamp   INTEGER NOT NULL,
+    temperature REAL    NOT NULL,
+    humidity    REAL    NOT NULL,
+    pressure    REAL    NOT NULL,
+    noise_level REAL    NOT NULL,
+    door_open   INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS metrics_room_time ON metrics (room, timestamp);
+CREATE TABLE IF NOT EXISTS metrics_downsampled (
+    room            TEXT    NOT NULL,
+    bucket          INTEGER NOT NULL,
+    samples         INTEGER NOT NULL,
+    temperature_min REAL    NOT NULL,
+    temperature_max REAL    NOT NULL,
+    temperature_sum REAL    NOT NULL,
+    humidity_sum    REAL    NOT NULL,
+    pressure_sum    REAL    NOT NULL,
+    noise_level_sum REAL    NOT NULL,
+    door_open_count INTEGER NOT NULL,
+    PRIMARY KEY (room, bucket)
+);
+";
+
+// Агрегаты по комнате за период
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoomAggregate {
+    pub room: String,
+    pub samples: u64,
+    pub temperature_min: f32,
+    pub temperature_max: f32,
+    pub temperature_avg: f32,
+    pub door_open_count: u64,
+}
+
+// Сколько хранить данные: сырые метрики старше `raw` сворачиваются в корзины
+// по `bucket`, корзины старше `keep` удаляются
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Retention {
+    pub raw: Duration,
+    pub bucket: Duration,
+    pub keep: Duration,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            raw: Duration::from_secs(24 * 60 * 60),
+            bucket: Duration::from_secs(60),
+            keep: Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+}
+
+// Хранилище метрик в SQLite, комната - адрес датчика
+pub struct MetricsStore {
+    conn: Connection,
+}
+
+impl MetricsStore {
+    pub fn open(path: &Path) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        // WAL: чтение не ждет записи пачки
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Self::with_connection(conn)
+    }
+
+    pub fn in_memory() -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> rusqlite::Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    // Записать пачку одной транзакцией
+    pub fn insert_batch(&mut self, batch: &[(String, RoomMetrics)]) -> rusqlite::Result<usize> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT INTO metrics (room, timestamp, temperature, humidity, pressure, noise_level, door_open)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            for (room, m) in batch {
+                insert.execute(params![
+                    room,
+                    m.timestamp as i64,
+                    m.temperature,
+                    m.humidity,
+                    m.pressure,
+                    m.noise_level,
+                    m.door_open
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(batch.len())
+    }
+
+    // Сырые метрики за [from, to], по времени; `room = None` - все комнаты
+    pub fn range(
+        &self,
+        room: Option<&str>,
+        from: u64,
+        to: u64,
+    ) -> rusqlite::Result<Vec<(String, RoomMetrics)>> {
+        let mut select = self.conn.prepare_cached(
+            "SELECT room, timestamp, temperature, humidity, pressure, noise_level, door_open
+             FROM metrics
+             WHERE timestamp BETWEEN ?1 AND ?2 AND (?3 IS NULL OR room = ?3)
+             ORDER BY timestamp, id",
+        )?;
+        let rows = select.query_map(params![from as i64, to as i64, room], |row| {
+            Ok((
+                row.get(0)?,
+                RoomMetrics {
+                    timestamp: row.get::<_, i64>(1)? as u64,
+                    temperature: row.get(2)?,
+                    humidity: row.get(3)?,
+                    pressure: row.get(4)?,
+                    noise_level: row.get(5)?,
+                    door_open: row.get(6)?,
+                },
+            ))
+        })?;
+        rows.collect()
+    }
+
+    // Агрегаты по комнатам за [from, to], вместе со свернутыми данными
+    pub fn aggregates(&self, from: u64, to: u64) -> rusqlite::Result<Vec<RoomAggregate>> {
+        let mut select = self.conn.prepare_cached(
+            "SELECT room, SUM(samples), MIN(t_min), MAX(t_max), SUM(t_sum) / SUM(samples), SUM(doors)
+             FROM (
+                 SELECT room, 1 AS samples, temperature AS t_min, temperature AS t_max,
+                        temperature AS t_sum, door_open AS doors
+                 FROM metrics WHERE timestamp BETWEEN ?1 AND ?2
+                 UNION ALL
+                 SELECT room, samples, temperature_min, temperature_max,
+                        temperature_sum, door_open_count
+                 FROM metrics_downsampled WHERE bucket BETWEEN ?1 AND ?2
+             )
+             GROUP BY room ORDER BY room",
+        )?;
+        let rows = select.query_map(params![from as i64, to as i64], |row| {
+            Ok(RoomAggregate {
+                room: row.get(0)?,
+                samples: row.get::<_, i64>(1)? as u64,
+                temperature_min: row.get::<_, f64>(2)? as f32,
+                temperature_max: row.get::<_, f64>(3)? as f32,
+                temperature_avg: row.get::<_, f64>(4)? as f32,
+                door_open_count: row.get::<_, i64>(5)? as u64,
+            })
+        })?;
+        rows.collect()
+    }
+
+    // Свернуть сырые метрики старше `before` в корзины по `bucket` секунд
+    pub fn downsample(&mut self, before: u64, bucket: u64) -> rusqlite::Result<usize> {
+        let bucket = bucket.max(1) as i64;
+        let tx = self.conn.transaction()?;
+        // Корзина могла появиться раньше: складываем с ней
+        tx.execute(
+            "INSERT INTO metrics_downsampled
+             SELECT room, (timestamp / ?2) * ?2, COUNT(*), MIN(temperature), MAX(temperature),
+                    SUM(temperature), SUM(humidity), SUM(pressure), SUM(noise_level), SUM(door_open)
+             FROM metrics WHERE timestamp < ?1
+             GROUP BY room, (timestamp / ?2) * ?2
+             ON CONFLICT (room, bucket) DO UPDATE SET
+                 samples = samples + excluded.samples,
+                 temperature_min = MIN(temperature_min, excluded.temperature_min),
+                 temperature_max = MAX(temperature_max, excluded.temperature_max),
+                 temperature_sum = temperature_sum + excluded.temperature_sum,
+                 humidity_sum = humidity_sum + excluded.humidity_sum,
+                 pressure_sum = pressure_sum + excluded.pressure_sum,
+                 noise_level_sum = noise_level_sum + excluded.noise_level_sum,
+                 door_open_count = door_open_count + excluded.door_open_count",
+            params![before as i64, bucket],
+        )?;
+        let removed = Self::delete_raw(&tx, before)?;
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    fn delete_raw(tx: &Transaction, before: u64) -> rusqlite::Result<usize> {
+        tx.execute(
+            "DELETE FROM metrics WHERE timestamp < ?1",
+            params![before as i64],
+        )
+    }
+
+    // Удалить все данные старше `before`
+    pub fn purge(&mut self, before: u64) -> rusqlite::Result<usize> {
+        let tx = self.conn.transaction()?;
+        let removed = Self::delete_raw(&tx, before)?
+            + tx.execute(
+                "DELETE FROM metrics_downsampled WHERE bucket < ?1",
+                params![before as i64],
+            )?;
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    // Применить политику хранения на момент `now`
+    pub fn apply_retention(&mut self, now: u64, retention: &Retention) -> rusqlite::Result<()> {
+        let _downsampled = self.downsample(
+            now.saturating_sub(retention.raw.as_secs()),
+            retention.bucket.as_secs(),
+        )?;
+        let _purged = self.purge(now.saturating_sub(retention.keep.as_secs()))?;
+        debug!("Retention: свернуто {}, удалено {}", _downsampled, _purged);
+        Ok(())
+    }
+}
+
+// Потребитель метрик из MetricsReceiver: копит пачку и пишет ее в хранилище,
+// когда она заполнилась или прошло `flush_interval`. Пока хранилище недоступно,
+// пачка ждет следующей попытки, но не больше `max_batch` метрик: старые отбрасываются
+pub struct MetricsSink {
+    store: MetricsStore,
+    batch: Vec<(String, RoomMetrics)>,
+    batch_size: usize,
+    max_batch: usize,
+    dropped: usize,
+    flush_interval: Duration,
+    last_flush: Instant,
+    retention: Option<Retention>,
+    last_retention: Instant,
+    stored: usize,
+}
+
+// Как часто применять политику хранения
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60);
+// Сколько метрик держать в памяти, пока хранилище недоступно
+const MAX_BATCH: usize = 10_000;
+
+impl MetricsSink {
+    pub fn new(store: MetricsStore) -> Self {
+        Self {
+            store,
+            batch: Vec::new(),
+            batch_size: 100,
+            max_batch: MAX_BATCH,
+            dropped: 0,
+            flush_interval: Duration::from_secs(1),
+            last_flush: Instant::now(),
+            retention: None,
+            last_retention: Instant::now(),
+            stored: 0,
+        }
+    }
+
+    pub fn with_batch(mut self, batch_size: usize, flush_interval: Duration) -> Self {
+        self.batch_size = batch_size.max(1);
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    pub fn with_max_batch(mut self, max_batch: usize) -> Self {
+        self.max_batch = max_batch.max(self.batch_size);
+        self
+    }
+
+    pub fn with_retention(mut self, retention: Retention) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    pub fn store(&self) -> &MetricsStore {
+        &self.store
+    }
+
+    // Сколько метрик записано
+    pub fn stored(&self) -> usize {
+        self.stored
+    }
+
+    // Сколько метрик отброшено из-за переполнения пачки
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    pub fn push(&mut self, metrics: RoomMetrics, src: SocketAddr) -> rusqlite::Result<()> {
+        if self.batch.len() >= self.max_batch {
+            let excess = self.batch.len() + 1 - self.max_batch;
+            self.batch.drain(..excess);
+            self.dropped += excess;
+            warn!("Хранилище недоступно, отброшено {} старых метрик", excess);
+        }
+        self.batch.push((src.to_string(), metrics));
+        self.tick()
+    }
+
+    // Записать пачку, если пора
+    pub fn tick(&mut self) -> rusqlite::Result<()> {
+        if self.batch.len() >= self.batch_size || self.last_flush.elapsed() >= self.flush_interval {
+            self.flush()?;
+        }
+        if let Some(retention) = self.retention
+            && self.last_retention.elapsed() >= RETENTION_INTERVAL
+        {
+            self.last_retention = Instant::now();
+            self.store.apply_retention(now(), &retention)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> rusqlite::Result<()> {
+        self.last_flush = Instant::now();
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        self.stored += self.store.insert_batch(&self.batch)?;
+        debug!("Записано {} метрик", self.batch.len());
+        self.batch.clear();
+        Ok(())
+    }
+
+    // Писать метрики из канала, пока он не закроется. Ошибка записи не
+    // останавливает прием: пачка остается и пишется следующей попыткой
+    pub fn run(mut self, rx: mpsc::Receiver<(RoomMetrics, SocketAddr)>) -> rusqlite::Result<usize> {
+        info!("Запись метрик в хранилище запущена");
+        loop {
+            let result = match rx.recv_timeout(self.flush_interval) {
+                Ok((metrics, src)) => self.push(metrics, src),
+                Err(mpsc::RecvTimeoutError::Timeout) => self.tick(),
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            };
+            if let Err(_e) = result {
+                error!("Ошибка записи метрик: {}", _e);
+            }
+        }
+        self.flush()?;
+        Ok(self.stored)
+    }
+
+    pub fn spawn(
+        self,
+        rx: mpsc::Receiver<(RoomMetrics, SocketAddr)>,
+    ) -> thread::JoinHandle<rusqlite::Result<usize>> {
+        thread::spawn(move || {
+            let result = self.run(rx);
+            if let Err(_e) = &result {
+                error!("Ошибка записи метрик: {}", _e);
+            }
+            result
+        })
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(timestamp: u64, temperature: f32, door_open: bool) -> RoomMetrics {
+        RoomMetrics {
+            timestamp,
+            temperature,
+            humidity: 40.0,
+            pressure: 1000.0,
+            noise_level: 10.0,
+            door_open,
+        }
+    }
+
+    fn rooms() -> Vec<(String, RoomMetrics)> {
+        vec![
+            ("a".to_string(), metrics(100, 20.0, false)),
+            ("a".to_string(), metrics(110, 24.0, true)),
+            ("b".to_string(), metrics(120, 18.0, false)),
+            ("a".to_string(), metrics(200, 22.0, true)),
+        ]
+    }
+
+    #[test]
+    fn test_range() {
+        let mut store = MetricsStore::in_memory().unwrap();
+        assert_eq!(store.insert_batch(&rooms()).unwrap(), 4);
+
+        let all = store.range(None, 100, 150).unwrap();
+        assert_eq!(all.len(), 3);
+        let a = store.range(Some("a"), 0, u64::MAX / 2).unwrap();
+        let stamps: Vec<u64> = a.iter().map(|(_, m)| m.timestamp).collect();
+        assert_eq!(stamps, [100, 110, 200]);
+        assert!(a[1].1.door_open);
+    }
+
+    #[test]
+    fn test_aggregates_survive_downsampling() {
+        let mut store = MetricsStore::in_memory().unwrap();
+        store.insert_batch(&rooms()).unwrap();
+        let before = store.aggregates(0, 1000).unwrap();
+        assert_eq!(
+            before[0],
+            RoomAggregate {
+                room: "a".to_string(),
+                samples: 3,
+                temperature_min: 20.0,
+                temperature_max: 24.0,
+                temperature_avg: 22.0,
+                door_open_count: 2,
+            }
+        );
+
+        // Два прохода в одну корзину складываются
+        assert_eq!(store.downsample(105, 60).unwrap(), 1);
+        assert_eq!(store.downsample(150, 60).unwrap(), 2);
+        assert_eq!(store.range(None, 0, 1000).unwrap().len(), 1);
+        assert_eq!(store.aggregates(0, 1000).unwrap(), before);
+
+        assert_eq!(store.purge(150).unwrap(), 2);
+        assert_eq!(store.aggregates(0, 1000).unwrap()[0].samples, 1);
+    }
+
+    #[test]
+    fn test_sink_batches() {
+        let (tx, rx) = mpsc::channel();
+        let sink = MetricsSink::new(MetricsStore::in_memory().unwrap())
+            .with_batch(2, Duration::from_secs(60));
+        let src: SocketAddr = "127.0.0.1:9999".parse().unwrap();
+        for (_, m) in rooms() {
+            tx.send((m, src)).unwrap();
+        }
+        tx.send((metrics(300, 1.0, false), src)).unwrap();
+        drop(tx);
+        // Неполная последняя пачка записывается при закрытии канала
+        assert_eq!(sink.run(rx).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_sink_survives_store_errors() {
+        let mut sink = MetricsSink::new(MetricsStore::in_memory().unwrap())
+            .with_batch(2, Duration::from_secs(60))
+            .with_max_batch(3);
+        let src: SocketAddr = "127.0.0.1:9999".parse().unwrap();
+        sink.store.conn.execute_batch("DROP TABLE metrics").unwrap();
+
+        // Пачка не пишется, но держит не больше max_batch метрик
+        let failed: Vec<_> = (0..5)
+            .map(|t| sink.push(metrics(t, 20.0, false), src).is_err())
+            .collect();
+        assert_eq!(failed, [false, true, true, true, true]);
+        assert_eq!((sink.batch.len(), sink.dropped(), sink.stored()), (3, 2, 0));
+
+        // Хранилище снова доступно: сохраняются последние метрики
+        sink.store.conn.execute_batch(SCHEMA).unwrap();
+        sink.tick().unwrap();
+        assert_eq!(sink.stored(), 3);
+        let stored: Vec<_> = sink
+            .store()
+            .range(None, 0, 1000)
+            .unwrap()
+            .iter()
+            .map(|(_, m)| m.timestamp)
+            .collect();
+        assert_eq!(stored, [2, 3, 4]);
+    }
+}
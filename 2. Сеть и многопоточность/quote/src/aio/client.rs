@@ -0,0 +1,240 @@
+use super::reciever::{Control, RecieverQuote};
+use crate::{
+    logging,
+    types::{
+        auth::{self, Credential},
+        candle::Feed,
+        codec::{self, Frame, PROTOCOL_VERSION, Protocol},
+        command::TcpCommand,
+        delivery::DeliveryPolicy,
+        error::QuoteError,
+        message::UdpMessage,
+        stock::Ticker,
+    },
+};
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpStream, UdpSocket, tcp::OwnedReadHalf},
+    sync::{Mutex, mpsc::UnboundedReceiver, watch},
+    task::JoinHandle,
+};
+
+/// Задачи ресиверов и их сигналы остановки
+type Recievers = HashMap<
+    u32,
+    (
+        JoinHandle<Result<RecieverQuote, String>>,
+        watch::Sender<bool>,
+    ),
+>;
+
+/// Асинхронный клиент для получения котировок
+pub struct ClientQuote {
+    recievers: Recievers,
+    /// Общий с ресиверами: они отправляют запросы переотправки
+    writer: Control,
+    reader: OwnedReadHalf,
+    protocol: Protocol,
+    /// Принятые, но еще не разобранные байты
+    input: Vec<u8>,
+    /// id следующего запроса, 0 - запросы без ответа
+    request_id: u32,
+
+    count: u32,
+}
+
+impl ClientQuote {
+    /// Подключиться по бинарному протоколу
+    pub async fn new(socket: SocketAddr) -> Result<Self, String> {
+        Self::with_protocol(socket, Protocol::default()).await
+    }
+
+    /// Подключиться по выбранному протоколу
+    pub async fn with_protocol(socket: SocketAddr, protocol: Protocol) -> Result<Self, String> {
+        let mut socket = TcpStream::connect(socket)
+            .await
+            .map_err(|e| e.to_string())?;
+        socket.set_nodelay(true).map_err(|e| e.to_string())?;
+
+        if protocol == Protocol::Framed {
+            socket
+                .write_all(&codec::hello(PROTOCOL_VERSION))
+                .await
+                .map_err(|e| e.to_string())?;
+            let mut hello = [0u8; codec::HELLO_LEN];
+            socket
+                .read_exact(&mut hello)
+                .await
+                .map_err(|e| e.to_string())?;
+            match codec::parse_hello(&hello) {
+                Some(version) if (1..=PROTOCOL_VERSION).contains(&version) => {
+                    logging!(info, ("Protocol v{}", version));
+                }
+                _ => return Err("Сервер не поддерживает бинарный протокол".to_string()),
+            }
+        }
+
+        let (reader, writer) = socket.into_split();
+        logging!(info, ("Connection succsessfully created"));
+
+        Ok(Self {
+            recievers: HashMap::new(),
+            writer: Arc::new(Mutex::new(writer)),
+            reader,
+            protocol,
+            input: Vec::new(),
+            request_id: 0,
+
+            count: 0,
+        })
+    }
+
+    /// Создать новый ресивер котировок или свечей.
+    ///
+    /// Порт 0 в `addr` - свободный порт, выбранный системой.
+    pub async fn create_reciever(
+        &mut self,
+        tickers: Vec<Ticker>,
+        addr: SocketAddr,
+        policy: DeliveryPolicy,
+        feed: Feed,
+    ) -> Result<UnboundedReceiver<UdpMessage>, QuoteError> {
+        let id = self.count;
+        self.count += 1;
+        let socket = UdpSocket::bind(addr).await.map_err(|e| {
+            logging!(error, ("Error create reciever: {}", e));
+            QuoteError::Other(e.to_string())
+        })?;
+        let (shutdown, shutdown_rx) = watch::channel(false);
+        let (reciever_quote, receiver) = RecieverQuote::new(
+            socket,
+            None,
+            Some((self.writer.clone(), self.protocol)),
+            shutdown_rx,
+        )
+        .map_err(QuoteError::Other)?;
+        let addr = reciever_quote.addr;
+        let reciever_join = tokio::spawn(reciever_quote.run());
+
+        if let Err(answ) = self
+            .send_socket(TcpCommand::Stream((addr, tickers, policy, feed)))
+            .await
+        {
+            logging!(debug, ("Error create reciever: {:?}", answ));
+            shutdown.send_replace(true);
+            if let Ok(Err(_e)) = reciever_join.await {
+                logging!(error, ("{:?}", _e));
+            }
+            return Err(answ);
+        };
+        self.recievers.insert(id, (reciever_join, shutdown));
+
+        Ok(receiver)
+    }
+
+    pub async fn stop_reciever(&mut self, id: u32) -> Result<(), String> {
+        let Some((reciever_join, shutdown)) = self.recievers.remove(&id) else {
+            return Err("Reciever not found".to_string());
+        };
+        shutdown.send_replace(true);
+        let receiver = reciever_join.await.map_err(|e| format!("{:?}", e))??;
+
+        self.send_socket(TcpCommand::Stop(receiver.addr))
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    /// Войти по HMAC от вызова сервера, ключ не передается
+    pub async fn auth(&mut self, name: &str, key: &str) -> Result<(), QuoteError> {
+        let challenge = self.send_socket(TcpCommand::Challenge).await?;
+        let signature = auth::sign_challenge(key, challenge.trim());
+        self.send_socket(TcpCommand::Auth((
+            name.to_string(),
+            Credential::Hmac(signature),
+        )))
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_tickers(&mut self) -> Result<Vec<Ticker>, QuoteError> {
+        let tickers = self.send_socket(TcpCommand::Tickers).await?;
+        Ok(tickers.trim().split('|').map(|s| s.to_string()).collect())
+    }
+
+    /// Выключить сервер
+    pub async fn shutdown(&mut self, secret_key: &str) -> Result<(), QuoteError> {
+        self.send_socket(TcpCommand::Shutdown(secret_key.to_string()))
+            .await?;
+        Ok(())
+    }
+
+    async fn send_socket(&mut self, command: TcpCommand) -> Result<String, QuoteError> {
+        logging!(info, ("Command {:?}", command.to_string()));
+
+        self.request_id = self.request_id.wrapping_add(1).max(1);
+        let id = self.request_id;
+        {
+            let mut writer = self.writer.lock().await;
+            writer
+                .write_all(&codec::encode_command(self.protocol, id, &command))
+                .await
+                .map_err(|_| QuoteError::NotConnection)?;
+        }
+
+        match self.protocol {
+            Protocol::Text => self.read_text().await,
+            Protocol::Framed => self.read_frame(id).await,
+        }
+    }
+
+    /// Дочитать в буфер
+    async fn read_more(&mut self) -> Result<(), QuoteError> {
+        let mut chunk = [0u8; 4096];
+        let n = self
+            .reader
+            .read(&mut chunk)
+            .await
+            .map_err(|_| QuoteError::NotConnection)?;
+        if n == 0 {
+            return Err(QuoteError::NotConnection);
+        }
+        self.input.extend_from_slice(&chunk[..n]);
+        Ok(())
+    }
+
+    async fn read_text(&mut self) -> Result<String, QuoteError> {
+        let end = loop {
+            match self.input.iter().position(|b| *b == b'\n') {
+                Some(end) => break end,
+                None => self.read_more().await?,
+            }
+        };
+        let line: Vec<u8> = self.input.drain(..=end).collect();
+        let buf = String::from_utf8_lossy(&line).to_string();
+
+        match QuoteError::from_string(buf.trim()) {
+            Ok(e) => Err(e),
+            Err(_) => Ok(buf),
+        }
+    }
+
+    /// Прочитать ответ на запрос `id`, ответы на другие запросы пропускаются
+    async fn read_frame(&mut self, id: u32) -> Result<String, QuoteError> {
+        loop {
+            match Frame::decode(&mut self.input) {
+                Ok(Some(Frame::Response {
+                    id: response_id,
+                    result,
+                })) if response_id == id || response_id == 0 => return result,
+                Ok(Some(_frame)) => {
+                    logging!(warn, ("Unexpected frame: {:?}", _frame));
+                }
+                Ok(None) => self.read_more().await?,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use super::tcp_worker::TcpWorker;
+use crate::{
+    logging,
+    master::{Connection, MasterConfig, StreamHandle},
+    session::{Runtime, Session},
+    types::{
+        state::{MasterState, MasterStateShell},
+        stock::StockQuote,
+    },
+};
+use std::{
+    sync::{
+        Arc,
+        mpsc::{Receiver, RecvTimeoutError},
+    },
+    time::Duration,
+};
+use tokio::{net::TcpListener, runtime::Handle, sync::watch};
+
+/// Как часто поток котировок проверяет выключение, если котировок нет
+const FEED_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// ## Асинхронный мастер сервера потоков
+///
+/// То же, что [crate::master::Master], но TCP соединения и UDP потоки - задачи tokio.
+/// Котировки от экстрактора ([crate::extractor::Extractor]) читает один блокирующий поток.
+pub struct Master {
+    rx_stock: Receiver<StockQuote>,
+    state: Arc<MasterState>,
+    config: MasterConfig,
+}
+
+impl Master {
+    pub fn new(rx_stock: Receiver<StockQuote>, config: Option<MasterConfig>) -> Self {
+        let config: MasterConfig = config.unwrap_or_default();
+        Self {
+            rx_stock,
+            state: Arc::new(config.state()),
+            config,
+        }
+    }
+
+    /// Запуск мастера на адресе из конфига
+    pub async fn run(self) -> Result<(), String> {
+        let listener = TcpListener::bind(self.config.tcp_addr())
+            .await
+            .map_err(|e| e.to_string())?;
+        self.serve(listener).await
+    }
+
+    /// Запуск мастера на уже открытом сокете
+    pub async fn serve(self, listener: TcpListener) -> Result<(), String> {
+        let Self {
+            rx_stock, state, ..
+        } = self;
+        let shutdown = Arc::new(watch::channel(false).0);
+        let mut shutdown_rx = shutdown.subscribe();
+
+        let feed_state = state.clone();
+        let feeder = tokio::task::spawn_blocking(move || Self::feed(rx_stock, feed_state));
+
+        let runtime = Runtime::Tokio(Handle::current());
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        match TcpWorker::new(stream, state.clone(), runtime.clone(), shutdown.clone()) {
+                            Ok(worker) => {
+                                tokio::spawn(worker.run());
+                            }
+                            Err(_e) => {
+                                logging!(warn, ("Connection failed: {}", _e));
+                            }
+                        }
+                    }
+                    Err(_e) => {
+                        logging!(warn, ("Connection failed: {}", _e.to_string()));
+                    }
+                },
+                _ = shutdown_rx.changed() => break,
+            }
+        }
+
+        for (_id, handle) in Self::stop_streams(&state) {
+            let StreamHandle::Task(task) = handle else {
+                continue;
+            };
+            match task.await {
+                Ok(Ok(())) => {
+                    logging!(warn, ("Поток {} успешно завершился", _id));
+                }
+                Ok(Err(_e)) => {
+                    logging!(warn, ("Поток {} завершился с ошибкой: {}", _id, _e));
+                }
+                Err(_e) => {
+                    logging!(warn, ("Поток {} запаниковал - {:#?}", _id, _e));
+                }
+            }
+        }
+        feeder.await.map_err(|e| e.to_string())
+    }
+
+    /// Передавать котировки экстрактора в рассылку до выключения
+    fn feed(rx_stock: Receiver<StockQuote>, state: Arc<MasterState>) {
+        let shell = MasterStateShell::new(state);
+        loop {
+            match rx_stock.recv_timeout(FEED_TIMEOUT) {
+                Ok(stock) => {
+                    let mut distributor_guard = shell.distributor();
+                    let distributor = distributor_guard.get_mut();
+                    distributor.send_all(stock);
+                    // Котировки, накопившиеся за время рассылки
+                    for stock in rx_stock.try_iter() {
+                        distributor.send_all(stock);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    logging!(info, ("Экстрактор завершился"));
+                    return;
+                }
+            }
+            if Session::is_shutdown(&shell) {
+                return;
+            }
+        }
+    }
+
+    /// Отписать все потоки, вернуть их задачи
+    fn stop_streams(state: &Arc<MasterState>) -> Vec<(u32, StreamHandle)> {
+        let shell = MasterStateShell::new(state.clone());
+        let mut all_connections_guard = shell.connections();
+        let mut distributor_guard = shell.distributor();
+
+        let distributor = distributor_guard.get_mut();
+        let mut handles = vec![];
+        for (_, connections) in all_connections_guard.get_mut().drain() {
+            for Connection(_, id, handle, _) in connections {
+                distributor.unsubscribe(id);
+                handles.push((id, handle));
+            }
+        }
+        handles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        aio::client::ClientQuote,
+        types::{candle::Feed, delivery::DeliveryPolicy, message::UdpMessage},
+    };
+    use std::net::SocketAddr;
+    use tokio::sync::mpsc::UnboundedReceiver;
+
+    async fn recv(receiver: &mut UnboundedReceiver<UdpMessage>) -> UdpMessage {
+        tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .expect("timeout")
+            .expect("reciever closed")
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_streams_and_shutdown() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let config = MasterConfig::new(Some("key".to_string()), Some(addr));
+        let server = tokio::spawn(Master::new(rx, Some(config)).serve(listener));
+
+        let mut client = ClientQuote::new(addr).await.unwrap();
+        let mut receivers = vec![];
+        for _ in 0..50 {
+            let receiver = client
+                .create_reciever(
+                    vec![StockQuote::one().ticker],
+                    SocketAddr::from(([127, 0, 0, 1], 0)),
+                    DeliveryPolicy::DropOldest,
+                    Feed::Ticks,
+                )
+                .await
+                .unwrap();
+            receivers.push(receiver);
+        }
+
+        tx.send(StockQuote::two()).unwrap();
+        tx.send(StockQuote::one()).unwrap();
+        for receiver in receivers.iter_mut() {
+            assert!(matches!(recv(receiver).await, UdpMessage::Init(0, _)));
+            match recv(receiver).await {
+                UdpMessage::Stock(1, stock) => assert_eq!(stock, StockQuote::one()),
+                message => panic!("{:?}", message),
+            }
+        }
+
+        client.shutdown("key").await.unwrap();
+        assert_eq!(server.await.unwrap(), Ok(()));
+    }
+}
@@ -0,0 +1,11 @@
+//! Асинхронный вариант сервера и клиента на tokio (фича `async`).
+//!
+//! Команды ([crate::types::command::TcpCommand]), кадры, сообщения и рассылка
+//! ([crate::distributor]) те же, что у синхронного варианта, но каждое TCP соединение
+//! и каждый UDP поток - задача tokio, а не поток ОС, и события приходят без опроса.
+
+pub mod client;
+pub mod master;
+mod reciever;
+mod tcp_worker;
+pub(crate) mod udp_worker;
@@ -0,0 +1,197 @@
+use crate::{
+    logging,
+    types::{
+        codec::{self, Protocol},
+        command::TcpCommand,
+        message::{UdpMessage, UdpMessageFormat},
+        sequence::{NACK_TIMEOUT, Recovery, Sequencer},
+    },
+};
+use std::{net::SocketAddr, sync::Arc};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{UdpSocket, tcp::OwnedWriteHalf},
+    sync::{Mutex, mpsc, watch},
+    time::{Duration, Instant, MissedTickBehavior},
+};
+
+/// Сколько ждать снимка после команды `STREAM`
+const INIT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Сервер отключает поток без ping дольше 5 секунд
+const PING_INTERVAL: Duration = Duration::from_millis(1000);
+/// Сервер молчит дольше - поток потерян
+const SILENCE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Снимок со свечами может не поместиться в 1 КБ
+const BUF_SIZE: usize = 64 * 1024;
+
+/// TCP соединение клиента для запросов переотправки
+pub(crate) type Control = Arc<Mutex<OwnedWriteHalf>>;
+
+/// Асинхронный ресивер потока котировок
+pub(crate) struct RecieverQuote {
+    pub(crate) addr: SocketAddr,
+
+    socket: UdpSocket,
+    format: UdpMessageFormat,
+    sender: mpsc::UnboundedSender<UdpMessage>,
+    server: Option<SocketAddr>,
+
+    /// Котировки или свечи потока
+    sequencer: Sequencer<UdpMessage>,
+    control: Option<(Control, Protocol)>,
+
+    shutdown: watch::Receiver<bool>,
+}
+
+impl RecieverQuote {
+    /// Ресивер на уже открытом сокете: датаграммы до запуска копятся в сокете
+    pub(crate) fn new(
+        socket: UdpSocket,
+        format: Option<UdpMessageFormat>,
+        control: Option<(Control, Protocol)>,
+        shutdown: watch::Receiver<bool>,
+    ) -> Result<(Self, mpsc::UnboundedReceiver<UdpMessage>), String> {
+        let addr = socket.local_addr().map_err(|e| e.to_string())?;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Ok((
+            Self {
+                addr,
+
+                socket,
+                format: format.unwrap_or(UdpMessageFormat::Json),
+                sender,
+                server: None,
+
+                sequencer: Sequencer::new(),
+                control,
+
+                shutdown,
+            },
+            receiver,
+        ))
+    }
+
+    pub(crate) async fn run(mut self) -> Result<Self, String> {
+        let mut buf = vec![0u8; BUF_SIZE];
+        let first = tokio::time::timeout(INIT_TIMEOUT, self.socket.recv_from(&mut buf)).await;
+        let (n, server) = match first {
+            Ok(Ok(received)) => received,
+            Ok(Err(e)) => {
+                logging!(warn, ("Не удалось получить данные: {}", e));
+                return Err(e.to_string());
+            }
+            Err(_) => {
+                logging!(warn, ("Не удалось получить снимок: {}", self.addr));
+                return Err("Нет снимка от сервера".to_string());
+            }
+        };
+        match UdpMessage::from_format(&buf[..n], &self.format) {
+            Ok(msg @ (UdpMessage::Init(..) | UdpMessage::CandleInit(..))) => {
+                self.server = Some(server);
+                self.message_handle(msg);
+            }
+            Ok(_) => {
+                logging!(warn, ("Не удалось получить данные"));
+                return Err("Не удалось получить данные".to_string());
+            }
+            Err(e) => {
+                logging!(warn, ("Не удалось получить данные: {}", e));
+                return Err(e.to_string());
+            }
+        }
+
+        let mut tick = tokio::time::interval(NACK_TIMEOUT);
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let (mut last_seen, mut last_ping) = (Instant::now(), Instant::now());
+        loop {
+            tokio::select! {
+                received = self.socket.recv(&mut buf) => match received {
+                    Ok(n) => match UdpMessage::from_format(&buf[..n], &self.format) {
+                        Ok(msg) => {
+                            last_seen = Instant::now();
+                            self.message_handle(msg);
+                        }
+                        Err(_e) => {
+                            logging!(warn, ("Ошибка преобразования: {}", _e));
+                        }
+                    },
+                    Err(_e) => {
+                        logging!(warn, ("{}", _e));
+                    }
+                },
+                _ = tick.tick() => {
+                    if last_seen.elapsed() > SILENCE_TIMEOUT {
+                        logging!(warn, ("Server not response. Client closed"));
+                        return Err("Server not response".to_string());
+                    }
+                    if last_ping.elapsed() > PING_INTERVAL {
+                        self.keepalive().await;
+                        last_ping = Instant::now();
+                    }
+                    self.recover().await;
+                },
+                // Ошибка - клиент удален, это тоже остановка
+                _ = self.shutdown.changed() => {
+                    self.message_handle(UdpMessage::Disconnect);
+                    return Ok(self);
+                }
+            }
+        }
+    }
+
+    fn message_handle(&mut self, message: UdpMessage) {
+        match message {
+            UdpMessage::Init(seq, _) | UdpMessage::CandleInit(seq, _) => {
+                self.deliver(message);
+                // Котировки, пришедшие раньше снимка
+                for (_, message) in self.sequencer.init(seq, std::time::Instant::now()) {
+                    self.deliver(message);
+                }
+            }
+            UdpMessage::Stock(seq, _) | UdpMessage::Candle(seq, _) => {
+                for (_, message) in self.sequencer.push(seq, message, std::time::Instant::now()) {
+                    self.deliver(message);
+                }
+            }
+            UdpMessage::Disconnect => self.deliver(UdpMessage::Disconnect),
+            UdpMessage::Pong | UdpMessage::Ping => {
+                logging!(info, ("Ping recv: {}", self.addr));
+            }
+            _ => (),
+        }
+    }
+
+    fn deliver(&self, message: UdpMessage) {
+        if let Err(e) = self.sender.send(message) {
+            logging!(warn, ("Send stock failed: {}", e));
+        };
+    }
+
+    /// Запросить по TCP пропущенные котировки или снимок
+    async fn recover(&mut self) {
+        let Some(recovery) = self.sequencer.recovery(std::time::Instant::now()) else {
+            return;
+        };
+        let command = match recovery {
+            Recovery::Resend(from, to) => TcpCommand::Resend((self.addr, from, to)),
+            Recovery::Snapshot => TcpCommand::Snapshot(self.addr),
+        };
+        logging!(info, ("Gap recovery: {:?}", command));
+        let Some((control, protocol)) = &self.control else {
+            return;
+        };
+        // id 0: сервер на эти запросы не отвечает
+        let request = codec::encode_command(*protocol, 0, &command);
+        if let Err(_e) = control.lock().await.write_all(&request).await {
+            logging!(warn, ("Ошибка запроса восстановления: {}", _e));
+        }
+    }
+
+    async fn keepalive(&mut self) {
+        if let (Some(server), Ok(ping)) = (self.server, UdpMessage::Ping.to_format(&self.format))
+            && let Err(_e) = self.socket.send_to(&ping, server).await
+        {
+            logging!(warn, ("Ошибка отправки ping: {}", _e));
+        };
+    }
+}
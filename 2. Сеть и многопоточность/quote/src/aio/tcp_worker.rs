@@ -0,0 +1,185 @@
+use crate::{
+    logging,
+    session::{Runtime, Session},
+    types::{
+        codec::{self, Frame, HELLO_LEN, PROTOCOL_VERSION, Protocol},
+        command::TcpCommand,
+        error::QuoteError,
+        state::{MasterState, MasterStateShell},
+    },
+};
+use std::sync::Arc;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    sync::watch,
+};
+
+/// Асинхронный worker TCP соединения
+pub(crate) struct TcpWorker {
+    stream: TcpStream,
+    session: Session,
+    /// Общий сигнал выключения сервера
+    shutdown: Arc<watch::Sender<bool>>,
+    /// Принятые, но еще не разобранные байты
+    input: Vec<u8>,
+}
+
+impl TcpWorker {
+    pub(crate) fn new(
+        stream: TcpStream,
+        state: Arc<MasterState>,
+        runtime: Runtime,
+        shutdown: Arc<watch::Sender<bool>>,
+    ) -> Result<Self, String> {
+        stream.set_nodelay(true).map_err(|e| e.to_string())?;
+        let domen = stream.peer_addr().map_err(|e| e.to_string())?;
+
+        logging!(info, ("Async tcp worker created: {}", domen));
+
+        Ok(Self {
+            stream,
+            session: Session::new(state, domen, runtime),
+            shutdown,
+            input: Vec::new(),
+        })
+    }
+
+    /// Запускает tcp worker
+    pub(crate) async fn run(mut self) -> Result<(), String> {
+        let mut shutdown = self.shutdown.subscribe();
+        let protocol = match self.negotiate(&mut shutdown).await? {
+            Some(protocol) => protocol,
+            None => return Ok(()),
+        };
+
+        loop {
+            match protocol {
+                Protocol::Text => self.handle_text().await?,
+                Protocol::Framed => self.handle_framed().await?,
+            }
+            if !self.read(&mut shutdown).await? {
+                if protocol == Protocol::Text && *shutdown.borrow() {
+                    let _ = self.stream.write_all("Finish\n".as_bytes()).await;
+                }
+                return Ok(());
+            }
+        }
+    }
+
+    /// Дочитать в буфер, `false` - соединение закрыто или сервер выключается
+    async fn read(&mut self, shutdown: &mut watch::Receiver<bool>) -> Result<bool, String> {
+        if *shutdown.borrow() {
+            return Ok(false);
+        }
+        let mut chunk = [0u8; 4096];
+        tokio::select! {
+            read = self.stream.read(&mut chunk) => match read {
+                Ok(0) => Ok(false),
+                Ok(n) => {
+                    self.input.extend_from_slice(&chunk[..n]);
+                    Ok(true)
+                }
+                Err(e) => Err(e.to_string()),
+            },
+            _ = shutdown.changed() => Ok(false),
+        }
+    }
+
+    /// Выбрать протокол по первым байтам, `None` - соединение закрыто раньше.
+    ///
+    /// Старые клиенты могут молчать до первой команды, поэтому ждем, пока не станет
+    /// ясно, приветствие это или текст.
+    async fn negotiate(
+        &mut self,
+        shutdown: &mut watch::Receiver<bool>,
+    ) -> Result<Option<Protocol>, String> {
+        while self.input.len() < HELLO_LEN && codec::is_hello_prefix(&self.input) {
+            if !self.read(shutdown).await? {
+                return Ok(None);
+            }
+        }
+        let Some(version) = codec::parse_hello(&self.input[..HELLO_LEN.min(self.input.len())])
+        else {
+            return Ok(Some(Protocol::Text));
+        };
+
+        self.input.drain(..HELLO_LEN);
+        let version = version.min(PROTOCOL_VERSION);
+        self.stream
+            .write_all(&codec::hello(version))
+            .await
+            .map_err(|e| e.to_string())?;
+        if version == 0 {
+            return Err("Неподдерживаемая версия протокола".to_string());
+        }
+
+        logging!(info, ("Protocol v{}: {}", version, self.session.domen()));
+        Ok(Some(Protocol::Framed))
+    }
+
+    /// Выполнить команду и разослать выключение, если она его включила
+    fn execute(&mut self, command: TcpCommand) -> Option<Result<String, QuoteError>> {
+        let shell = MasterStateShell::new(self.session.state());
+        let result = self.session.execute(command, &shell);
+        if Session::is_shutdown(&shell) {
+            self.shutdown.send_replace(true);
+        }
+        result
+    }
+
+    /// Обработка текстовых команд: команда и ответ - строки
+    async fn handle_text(&mut self) -> Result<(), String> {
+        while let Some(end) = self.input.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.input.drain(..=end).collect();
+            let res = match TcpCommand::parse(&String::from_utf8_lossy(&line)) {
+                Ok(command) => match self.execute(command) {
+                    Some(res) => res,
+                    None => continue,
+                },
+                Err(e) => Err(QuoteError::BadRequest(e.to_string())),
+            };
+
+            let answer = match res {
+                Ok(res) => res,
+                Err(e) => e.to_string(),
+            };
+            self.stream
+                .write_all(format!("{}\n", answer).as_bytes())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Обработка кадров, ответ с тем же id, см. [codec]
+    async fn handle_framed(&mut self) -> Result<(), String> {
+        loop {
+            match Frame::decode(&mut self.input) {
+                Ok(Some(Frame::Request { id, command })) => {
+                    let Some(result) = self.execute(command) else {
+                        continue;
+                    };
+                    let frame = Frame::Response { id, result };
+                    self.stream
+                        .write_all(&frame.encode())
+                        .await
+                        .map_err(|e| e.to_string())?;
+                }
+                Ok(Some(Frame::Response { id: _id, .. })) => {
+                    logging!(warn, ("Unexpected response frame: {}", _id));
+                }
+                Ok(None) => return Ok(()),
+                // Граница кадров потеряна, продолжать нельзя
+                Err(e) => {
+                    let frame = Frame::Response {
+                        id: 0,
+                        result: Err(e),
+                    };
+                    let _ = self.stream.write_all(&frame.encode()).await;
+                    return Err("Protocol error".to_string());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::{
+    distributor::Subscriber,
+    logging,
+    types::message::{UdpMessage, UdpMessageFormat},
+    udp_worker::StreamState,
+};
+use std::{net::SocketAddr, sync::Arc};
+use tokio::{
+    net::UdpSocket,
+    sync::Notify,
+    time::{Duration, Instant},
+};
+
+const PING_INTERVAL: Duration = Duration::from_millis(5000);
+const COUNT_TRY_SEND: u8 = 10;
+
+/// Что разбудило worker
+enum Wake {
+    /// В очереди подписчика появились события
+    Event,
+    /// Пришла датаграмма от клиента
+    Datagram(std::io::Result<usize>),
+    /// Клиент давно не присылал ping
+    PingTimeout,
+}
+
+/// Асинхронный worker по Udp подписки.
+///
+/// Ждет событий очереди через [Notify], а не опрашивает ее.
+pub(crate) struct UdpWorker {
+    subscriber: Subscriber,
+    socket: UdpSocket,
+    addr: SocketAddr,
+    format: UdpMessageFormat,
+    stream: StreamState,
+    notify: Arc<Notify>,
+
+    count: u8,
+}
+
+impl UdpWorker {
+    /// Создать worker, нужен контекст рантайма tokio
+    pub(crate) fn new(
+        subscriber: Subscriber,
+        addr: SocketAddr,
+        format: Option<UdpMessageFormat>,
+        stream: StreamState,
+    ) -> Result<Self, String> {
+        let socket = std::net::UdpSocket::bind("0.0.0.0:0")
+            .map_err(|_| "Not bind udp socket".to_string())?;
+        socket
+            .connect(addr)
+            .map_err(|_| "Not connect".to_string())?;
+        socket
+            .set_nonblocking(true)
+            .map_err(|_| "Not set nonblocking".to_string())?;
+        let socket = UdpSocket::from_std(socket).map_err(|e| e.to_string())?;
+
+        let notify = Arc::new(Notify::new());
+        let waker = notify.clone();
+        subscriber.set_waker(move || waker.notify_one());
+
+        logging!(info, ("Async SubscribeWorker running: {}", addr));
+        Ok(Self {
+            subscriber,
+            socket,
+            addr,
+            format: format.unwrap_or(UdpMessageFormat::Json),
+            stream,
+            notify,
+
+            count: 0,
+        })
+    }
+
+    /// Запустить worker
+    pub(crate) async fn run(mut self) -> Result<(), String> {
+        self.send(self.stream.snapshot()).await;
+        let mut last_ping = Instant::now();
+        let mut buf = [0u8; 1024];
+        loop {
+            if self.count >= COUNT_TRY_SEND {
+                logging!(warn, ("Client disconnected. Not response: {}", self.addr));
+                return Err("Client not response".to_string());
+            }
+
+            // Разбираем всю очередь, чтобы не отставать от рассылки
+            while let Some(event) = self.subscriber.get_event() {
+                let Some(messages) = self.stream.handle(event) else {
+                    self.send(UdpMessage::Disconnect).await;
+                    logging!(info, ("SubscribeWorker Disconnect: {}", self.addr));
+                    return Ok(());
+                };
+                for message in messages {
+                    self.send(message).await;
+                }
+            }
+
+            let wake = tokio::select! {
+                _ = self.notify.notified() => Wake::Event,
+                received = self.socket.recv(&mut buf) => Wake::Datagram(received),
+                _ = tokio::time::sleep_until(last_ping + PING_INTERVAL) => Wake::PingTimeout,
+            };
+            match wake {
+                Wake::Event => {}
+                Wake::Datagram(Ok(size)) => {
+                    match UdpMessage::from_format(&buf[..size], &self.format) {
+                        Ok(UdpMessage::Ping) => {
+                            last_ping = Instant::now();
+                            self.send(UdpMessage::Pong).await;
+                        }
+                        Ok(UdpMessage::Pong) => {
+                            last_ping = Instant::now();
+                            self.send(UdpMessage::Ping).await;
+                        }
+                        Ok(_) => {}
+                        Err(_e) => {
+                            logging!(warn, ("Error parse message: {}", _e));
+                        }
+                    }
+                }
+                Wake::Datagram(Err(_e)) => {
+                    logging!(warn, ("Error connection: {}", _e));
+                    self.count += 1;
+                }
+                Wake::PingTimeout => {
+                    logging!(
+                        warn,
+                        ("Client disconnected. Ping not response: {}", self.addr)
+                    );
+                    return Err("Client disconnected".to_string());
+                }
+            }
+        }
+    }
+
+    /// Отправить сообщение
+    async fn send(&mut self, message: UdpMessage) {
+        let Ok(mes) = message.to_format(&self.format) else {
+            logging!(error, ("Error format message"));
+            return;
+        };
+
+        if let Err(_e) = self.socket.send(&mes).await {
+            logging!(error, ("Error send message: {}", _e));
+            self.count += 1;
+        } else {
+            self.count = 0;
+        }
+    }
+}
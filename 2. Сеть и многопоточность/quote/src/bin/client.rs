@@ -1,7 +1,13 @@
 use clap::{Parser, command};
 use quote::{
     client::ClientQuote,
-    types::{message::UdpMessage, stock::Ticker},
+    types::{
+        candle::{Feed, Interval},
+        codec::Protocol,
+        delivery::DeliveryPolicy,
+        message::UdpMessage,
+        stock::Ticker,
+    },
 };
 use std::net::SocketAddr;
 
@@ -19,6 +25,26 @@ struct Cli {
     /// Тикеры
     #[arg(short, long)]
     tickers: Vec<Ticker>,
+
+    /// Что делать серверу, если клиент не успевает
+    #[arg(short, long, value_enum, default_value_t = DeliveryPolicy::default())]
+    policy: DeliveryPolicy,
+
+    /// Получать закрытые свечи интервала вместо котировок
+    #[arg(long, value_enum)]
+    candle: Option<Interval>,
+
+    /// Текстовый протокол вместо бинарного
+    #[arg(long)]
+    text: bool,
+
+    /// Имя клиента для входа
+    #[arg(long, requires = "key")]
+    name: Option<String>,
+
+    /// Ключ клиента для входа
+    #[arg(long, requires = "name")]
+    key: Option<String>,
 }
 
 fn main() {
@@ -33,14 +59,30 @@ fn main() {
         server,
         host,
         tickers,
+        policy,
+        candle,
+        text,
+        name,
+        key,
     } = Cli::parse();
 
     let host = host.unwrap_or("127.0.0.1:7878".parse().unwrap());
-    let Ok(mut client) = ClientQuote::new(server) else {
+    let protocol = match text {
+        true => Protocol::Text,
+        false => Protocol::Framed,
+    };
+    let Ok(mut client) = ClientQuote::with_protocol(server, protocol) else {
         println!("Init failed");
         return;
     };
 
+    if let (Some(name), Some(key)) = (name, key) {
+        if let Err(e) = client.auth(&name, &key) {
+            println!("Auth failed: {:?}", e);
+            return;
+        }
+    }
+
     match client.get_tickers() {
         Ok(tickers) => {
             println!("All tickers in server: {:#?}", tickers);
@@ -51,7 +93,8 @@ fn main() {
         }
     };
 
-    let reciever = match client.create_reciever(tickers, host) {
+    let feed = candle.map_or(Feed::Ticks, Feed::Candle);
+    let reciever = match client.create_reciever(tickers, host, policy, feed) {
         Ok(reciever) => reciever,
         Err(e) => {
             println!("Error create reciever: {:?}", e);
@@ -68,14 +111,22 @@ fn main() {
             }
         };
         match msg {
-            UdpMessage::Stock(stock) => {
+            UdpMessage::Stock(_, stock) => {
                 println!("{}", stock);
             }
-            UdpMessage::Init(stocks) => {
+            UdpMessage::Init(_, stocks) => {
                 for stock in stocks {
                     println!("{}", stock);
                 }
             }
+            UdpMessage::Candle(_, candle) => {
+                println!("{}", candle);
+            }
+            UdpMessage::CandleInit(_, candles) => {
+                for candle in candles {
+                    println!("{}", candle);
+                }
+            }
             UdpMessage::Disconnect => {
                 println!("Disconnect");
                 return;
@@ -0,0 +1,230 @@
+//! Нагрузочный тест асинхронного сервера: сервер, экстрактор и клиенты в одном процессе.
+//!
+//! `cargo run --release --features async --bin load -- --streams 5000`
+
+use clap::Parser;
+use quote::{
+    aio::{client::ClientQuote, master::Master},
+    extractor::Extractor,
+    master::MasterConfig,
+    types::{
+        candle::Feed,
+        delivery::DeliveryPolicy,
+        message::UdpMessage,
+        stock::{StockQuote, Ticker},
+    },
+};
+use std::{
+    net::SocketAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{Receiver, Sender},
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::net::TcpListener;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Число UDP потоков
+    #[arg(long, default_value_t = 2000)]
+    streams: usize,
+
+    /// Число TCP соединений, потоки делятся между ними поровну
+    #[arg(long, default_value_t = 20)]
+    connections: usize,
+
+    /// Длительность замера после открытия всех потоков, секунды
+    #[arg(long, default_value_t = 10)]
+    seconds: u64,
+
+    /// Число тикеров
+    #[arg(long, default_value_t = 10)]
+    tickers: usize,
+
+    /// Котировок в секунду от экстрактора
+    #[arg(long, default_value_t = 100)]
+    rate: u64,
+}
+
+/// Экстрактор с заданной частотой котировок
+struct SyntheticExtractor {
+    subscribers: Vec<Sender<StockQuote>>,
+    tickers: Vec<Ticker>,
+    rate: u64,
+    stop: Arc<AtomicBool>,
+}
+
+impl Extractor for SyntheticExtractor {
+    fn run(self: Box<Self>) -> Result<(), String> {
+        let period = Duration::from_secs(1) / self.rate.max(1) as u32;
+        let mut prices = vec![100.0; self.tickers.len()];
+        let start = Instant::now();
+        let mut sent: u32 = 0;
+        while !self.stop.load(Ordering::Relaxed) {
+            let indx = rand::random_range(0..self.tickers.len());
+            prices[indx] *= 1.0 + rand::random_range(-0.001..0.001);
+            let quote = StockQuote {
+                ticker: self.tickers[indx].clone(),
+                price: prices[indx],
+                volume: rand::random_range(1..1000),
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_err(|e| e.to_string())?
+                    .as_secs(),
+            };
+            // Сервер остановлен - котировки больше некому отправлять
+            if self
+                .subscribers
+                .iter()
+                .any(|tx| tx.send(quote.clone()).is_err())
+            {
+                return Ok(());
+            }
+
+            // Без накопления задержек
+            sent += 1;
+            let wait = (start + period * sent).saturating_duration_since(Instant::now());
+            thread::sleep(wait);
+        }
+        Ok(())
+    }
+
+    fn subscribe(&mut self) -> Receiver<StockQuote> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        self.subscribers.push(tx);
+        rx
+    }
+}
+
+/// Счетчики по всем потокам
+#[derive(Default)]
+struct Stats {
+    inits: AtomicU64,
+    messages: AtomicU64,
+    errors: AtomicU64,
+}
+
+fn main() {
+    #[cfg(feature = "logging")]
+    env_logger::init();
+
+    let cli = Cli::parse();
+    let runtime = match tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    if let Err(e) = runtime.block_on(run(cli)) {
+        println!("{}", e);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), String> {
+    let tickers: Vec<Ticker> = (0..cli.tickers.max(1)).map(|i| format!("T{}", i)).collect();
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut extractor = Box::new(SyntheticExtractor {
+        subscribers: Vec::new(),
+        tickers: tickers.clone(),
+        rate: cli.rate,
+        stop: stop.clone(),
+    });
+
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .map_err(|e| e.to_string())?;
+    let server_addr = listener.local_addr().map_err(|e| e.to_string())?;
+    let secret_key = MasterConfig::gen_secret_key();
+    let config = MasterConfig::new(Some(secret_key.clone()), Some(server_addr));
+    let master = Master::new(extractor.subscribe(), Some(config));
+
+    let th_extractor = thread::spawn(move || extractor.run());
+    let server = tokio::spawn(master.serve(listener));
+
+    let mut clients = Vec::new();
+    for _ in 0..cli.connections.max(1) {
+        clients.push(ClientQuote::new(server_addr).await?);
+    }
+
+    let stats = Arc::new(Stats::default());
+    let stream_addr = SocketAddr::from(([127, 0, 0, 1], 0));
+    let start = Instant::now();
+    let mut opened = 0;
+    for i in 0..cli.streams {
+        let client = &mut clients[i % cli.connections.max(1)];
+        // 1-3 тикера на поток
+        let stream_tickers: Vec<Ticker> = (0..=i % 3)
+            .map(|j| tickers[(i + j) % tickers.len()].clone())
+            .collect();
+        match client
+            .create_reciever(
+                stream_tickers,
+                stream_addr,
+                DeliveryPolicy::DropOldest,
+                Feed::Ticks,
+            )
+            .await
+        {
+            Ok(mut receiver) => {
+                opened += 1;
+                let stats = stats.clone();
+                tokio::spawn(async move {
+                    while let Some(message) = receiver.recv().await {
+                        let counter = match message {
+                            UdpMessage::Init(..) | UdpMessage::CandleInit(..) => &stats.inits,
+                            UdpMessage::Stock(..) | UdpMessage::Candle(..) => &stats.messages,
+                            _ => continue,
+                        };
+                        counter.fetch_add(1, Ordering::Relaxed);
+                    }
+                });
+            }
+            Err(e) => {
+                if stats.errors.fetch_add(1, Ordering::Relaxed) == 0 {
+                    println!("Ошибка открытия потока: {}", e);
+                }
+            }
+        }
+    }
+    let open_time = start.elapsed();
+
+    let before = stats.messages.load(Ordering::Relaxed);
+    tokio::time::sleep(Duration::from_secs(cli.seconds)).await;
+    let messages = stats.messages.load(Ordering::Relaxed) - before;
+
+    println!("Потоков открыто:    {} из {}", opened, cli.streams);
+    println!("Время открытия:     {:.2?}", open_time);
+    println!(
+        "Снимков получено:   {}",
+        stats.inits.load(Ordering::Relaxed)
+    );
+    println!("Котировок за замер: {}", messages);
+    println!(
+        "Котировок в секунду: {:.0}",
+        messages as f64 / cli.seconds.max(1) as f64
+    );
+    println!(
+        "Ошибок:             {}",
+        stats.errors.load(Ordering::Relaxed)
+    );
+
+    clients[0]
+        .shutdown(&secret_key)
+        .await
+        .map_err(|e| e.to_string())?;
+    server.await.map_err(|e| e.to_string())??;
+    stop.store(true, Ordering::Relaxed);
+
+    match th_extractor.join() {
+        Ok(res) => res,
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
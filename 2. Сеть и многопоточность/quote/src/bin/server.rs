@@ -1,9 +1,16 @@
 use clap::{Parser, command};
+#[cfg(feature = "async")]
+use quote::types::stock::StockQuote;
 use quote::{
-    extractor::{ConsoleExtractor, Extractor, ExtractorType, FileMockExtractor, RandomExtractor},
-    master::{Master, MasterConfig},
+    extractor::{
+        ConsoleExtractor, Extractor, ExtractorType, FileMockExtractor, RandomExtractor,
+        ReplayConfig, ReplayExtractor,
+    },
+    master::{Master, MasterConfig, MasterConfigFile},
 };
-use std::{fs::File, net::SocketAddr, thread};
+#[cfg(feature = "async")]
+use std::sync::mpsc::Receiver;
+use std::{fs::File, net::SocketAddr, path::PathBuf, thread};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -13,9 +20,21 @@ struct Cli {
     extractor: ExtractorType,
 
     /// Файл для экстратора
-    #[arg(short, long, required_if_eq("extractor", "file"))]
+    #[arg(short, long, required_if_eq_any([("extractor", "file"), ("extractor", "replay")]))]
     file: Option<String>,
 
+    /// Скорость воспроизведения (только для replay)
+    #[arg(long, default_value_t = 1.0)]
+    speed: f64,
+
+    /// Воспроизводить по кругу (только для replay)
+    #[arg(long = "loop")]
+    looped: bool,
+
+    /// Начать с этого времени, секунды unix (только для replay)
+    #[arg(long)]
+    seek: Option<u64>,
+
     /// Адрес сервера
     #[arg(long)]
     host: Option<SocketAddr>,
@@ -23,6 +42,15 @@ struct Cli {
     /// Секретный ключ
     #[arg(short, long)]
     secret_key: Option<String>,
+
+    /// Файл настроек (JSON): host, secret_key и ключи клиентов
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Асинхронный сервер на tokio
+    #[cfg(feature = "async")]
+    #[arg(long = "async")]
+    async_server: bool,
 }
 
 fn main() {
@@ -37,11 +65,34 @@ fn main() {
     let Cli {
         extractor,
         file,
+        speed,
+        looped,
+        seek,
         host,
         secret_key,
+        config,
+        #[cfg(feature = "async")]
+        async_server,
     } = Cli::parse();
 
-    let file: Option<File> = if extractor == ExtractorType::File {
+    // Аргументы командной строки важнее файла
+    let config_file = match config {
+        Some(path) => match MasterConfigFile::load(&path) {
+            Ok(config) => config,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        },
+        None => MasterConfigFile::default(),
+    };
+    let config = MasterConfig::new(
+        secret_key.or(config_file.secret_key),
+        host.or(config_file.host),
+    )
+    .with_clients(config_file.clients);
+
+    let file: Option<File> = if matches!(extractor, ExtractorType::File | ExtractorType::Replay) {
         let file = file.unwrap();
         if !std::path::Path::new(&file).exists() {
             println!("Файл не существует");
@@ -62,15 +113,34 @@ fn main() {
         ExtractorType::Console => Box::new(ConsoleExtractor::new()),
         ExtractorType::File => Box::new(FileMockExtractor::new(file.unwrap())),
         ExtractorType::Random => Box::new(RandomExtractor::new()),
+        ExtractorType::Replay => {
+            let config = ReplayConfig {
+                speed,
+                looped,
+                seek,
+            };
+            match ReplayExtractor::new(file.unwrap(), config) {
+                Ok(extractor) => Box::new(extractor),
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                }
+            }
+        }
         _ => Box::new(RandomExtractor::new()),
     };
 
     let rx_stock = extractor.subscribe();
-    let config = MasterConfig::new(secret_key, host);
-    let master = Master::new(rx_stock, Some(config));
-
     let th_extractor = thread::spawn(move || extractor.run());
-    match master.run() {
+
+    #[cfg(feature = "async")]
+    let master_result = match async_server {
+        true => run_async(rx_stock, config),
+        false => Master::new(rx_stock, Some(config)).run().map(|_| ()),
+    };
+    #[cfg(not(feature = "async"))]
+    let master_result = Master::new(rx_stock, Some(config)).run().map(|_| ());
+    match master_result {
         Ok(_) => println!("Master: Все прошло успешно!"),
         Err(e) => println!("{}", e),
     };
@@ -81,3 +151,13 @@ fn main() {
         Err(e) => println!("{:?}", e),
     };
 }
+
+/// Запуск асинхронного мастера в рантайме tokio
+#[cfg(feature = "async")]
+fn run_async(rx_stock: Receiver<StockQuote>, config: MasterConfig) -> Result<(), String> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string())?;
+    runtime.block_on(quote::aio::master::Master::new(rx_stock, Some(config)).run())
+}
@@ -1,15 +1,22 @@
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 
 use crate::{
     logging,
     types::{
-        command::TcpCommand, error::QuoteError, message::UdpMessage, reciever::RecieverQuote,
+        auth::{self, Credential},
+        candle::Feed,
+        codec::{self, Frame, PROTOCOL_VERSION, Protocol},
+        command::TcpCommand,
+        delivery::DeliveryPolicy,
+        error::QuoteError,
+        message::UdpMessage,
+        reciever::RecieverQuote,
         stock::Ticker,
     },
 };
 use std::{
     collections::HashMap,
-    io::{BufRead, BufReader, Write},
+    io::{BufRead, BufReader, Read, Write},
     net::{SocketAddr, TcpStream},
     sync::{Arc, mpsc::Receiver},
     thread::{self, JoinHandle},
@@ -18,47 +25,84 @@ use std::{
 /// Клиент для получения котировок
 pub struct ClientQuote {
     recievers: HashMap<u32, (JoinHandle<Result<RecieverQuote, String>>, Arc<RwLock<bool>>)>,
-    writer: TcpStream,
+    /// Общий с ресиверами: они отправляют запросы переотправки
+    writer: Arc<Mutex<TcpStream>>,
     reader: BufReader<TcpStream>,
+    protocol: Protocol,
+    /// Принятые, но еще не разобранные байты бинарного протокола
+    input: Vec<u8>,
+    /// id следующего запроса, 0 - запросы без ответа
+    request_id: u32,
 
     count: u32,
 }
 
 impl ClientQuote {
+    /// Подключиться по бинарному протоколу
     pub fn new(socket: SocketAddr) -> Result<Self, String> {
-        let socket = TcpStream::connect(socket).map_err(|e| e.to_string())?;
+        Self::with_protocol(socket, Protocol::default())
+    }
+
+    /// Подключиться по выбранному протоколу
+    pub fn with_protocol(socket: SocketAddr, protocol: Protocol) -> Result<Self, String> {
+        let mut socket = TcpStream::connect(socket).map_err(|e| e.to_string())?;
         socket.set_nodelay(true).map_err(|e| e.to_string())?;
 
+        if protocol == Protocol::Framed {
+            socket
+                .write_all(&codec::hello(PROTOCOL_VERSION))
+                .map_err(|e| e.to_string())?;
+            let mut hello = [0u8; codec::HELLO_LEN];
+            socket.read_exact(&mut hello).map_err(|e| e.to_string())?;
+            match codec::parse_hello(&hello) {
+                Some(version) if (1..=PROTOCOL_VERSION).contains(&version) => {
+                    logging!(info, ("Protocol v{}", version));
+                }
+                _ => return Err("Сервер не поддерживает бинарный протокол".to_string()),
+            }
+        }
+
         let reader = BufReader::new(socket.try_clone().map_err(|e| e.to_string())?);
         logging!(info, ("Connection succsessfully created"));
 
         Ok(Self {
             recievers: HashMap::new(),
-            writer: socket,
+            writer: Arc::new(Mutex::new(socket)),
             reader,
+            protocol,
+            input: Vec::new(),
+            request_id: 0,
 
             count: 0,
         })
     }
 
-    /// Создать новый ресивер
+    /// Создать новый ресивер котировок или свечей
     pub fn create_reciever(
         &mut self,
         tickers: Vec<Ticker>,
         addr: SocketAddr,
+        policy: DeliveryPolicy,
+        feed: Feed,
     ) -> Result<Receiver<UdpMessage>, QuoteError> {
         let id = self.count;
         self.count += 1;
         let shutdown = Arc::new(RwLock::new(false));
-        let (reciever_quote, receiver) =
-            RecieverQuote::new(tickers.clone(), addr, None, shutdown.clone()).map_err(|e| {
-                logging!(error, ("Error create reciever: {}", e));
-
-                QuoteError::Other(e.to_string())
-            })?;
+        let (reciever_quote, receiver) = RecieverQuote::new(
+            tickers.clone(),
+            addr,
+            None,
+            Some((self.writer.clone(), self.protocol)),
+            shutdown.clone(),
+        )
+        .map_err(|e| {
+            logging!(error, ("Error create reciever: {}", e));
+
+            QuoteError::Other(e.to_string())
+        })?;
         let reciever_join = thread::spawn(move || reciever_quote.run());
 
-        if let Err(answ) = self.send_socket(TcpCommand::Stream((addr, tickers))) {
+        if let Err(answ) = self.send_socket(TcpCommand::Stream((addr, tickers, policy, feed))) {
             logging!(debug, ("Error create reciever: {:?}", answ));
             let mut shutdown = shutdown.write();
             *shutdown = true;
@@ -94,6 +138,17 @@ impl ClientQuote {
         Ok(())
     }
 
+    /// Войти по HMAC от вызова сервера, ключ не передается
+    pub fn auth(&mut self, name: &str, key: &str) -> Result<(), QuoteError> {
+        let challenge = self.send_socket(TcpCommand::Challenge)?;
+        let signature = auth::sign_challenge(key, challenge.trim());
+        self.send_socket(TcpCommand::Auth((
+            name.to_string(),
+            Credential::Hmac(signature),
+        )))?;
+        Ok(())
+    }
+
     pub fn get_tickers(&mut self) -> Result<Vec<Ticker>, QuoteError> {
         match self.send_socket(TcpCommand::Tickers) {
             Ok(tickers) => Ok(tickers.trim().split('|').map(|s| s.to_string()).collect()),
@@ -104,11 +159,23 @@ impl ClientQuote {
     fn send_socket(&mut self, command: TcpCommand) -> Result<String, QuoteError> {
         logging!(info, ("Command {:?}", command.to_string()));
 
-        self.writer
-            .write_all(command.to_string().as_bytes())
-            .map_err(|_| QuoteError::NotConnection)?;
-        self.writer.flush().map_err(|_| QuoteError::NotConnection)?;
+        self.request_id = self.request_id.wrapping_add(1).max(1);
+        let id = self.request_id;
+        {
+            let mut writer = self.writer.lock();
+            writer
+                .write_all(&codec::encode_command(self.protocol, id, &command))
+                .map_err(|_| QuoteError::NotConnection)?;
+            writer.flush().map_err(|_| QuoteError::NotConnection)?;
+        }
 
+        match self.protocol {
+            Protocol::Text => self.read_text(),
+            Protocol::Framed => self.read_frame(id),
+        }
+    }
+
+    fn read_text(&mut self) -> Result<String, QuoteError> {
         let mut buf = String::new();
         self.reader
             .read_line(&mut buf)
@@ -119,4 +186,31 @@ impl ClientQuote {
             Err(_) => Ok(buf),
         }
     }
+
+    /// Прочитать ответ на запрос `id`, ответы на другие запросы пропускаются
+    fn read_frame(&mut self, id: u32) -> Result<String, QuoteError> {
+        let mut chunk = [0u8; 4096];
+        loop {
+            match Frame::decode(&mut self.input) {
+                Ok(Some(Frame::Response {
+                    id: response_id,
+                    result,
+                })) if response_id == id || response_id == 0 => return result,
+                Ok(Some(_frame)) => {
+                    logging!(warn, ("Unexpected frame: {:?}", _frame));
+                }
+                Ok(None) => {
+                    let n = self
+                        .reader
+                        .read(&mut chunk)
+                        .map_err(|_| QuoteError::NotConnection)?;
+                    if n == 0 {
+                        return Err(QuoteError::NotConnection);
+                    }
+                    self.input.extend_from_slice(&chunk[..n]);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
 }
@@ -1,50 +1,198 @@
 use crate::{
     logging,
-    types::stock::{StockQuote, Ticker},
+    types::{
+        candle::{Candle, CandleAggregator, Feed, Interval, SNAPSHOT_CANDLES},
+        delivery::{DeliveryPolicy, QUEUE_SIZE, SubscriberStats},
+        stock::{StockQuote, Ticker},
+    },
+};
+use parking_lot::Mutex;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::Instant,
 };
-use crossbeam::channel;
-use std::{collections::HashMap, sync::Arc};
 
 /// Событие для подписчиков
 pub(crate) enum Event {
     /// Обновить данные о акции
     Update(StockQuote),
 
+    /// Закрылась свеча
+    Candle(Candle),
+
     /// Отключить пользователя
     Disconnect,
+
+    /// Переотправить котировки с номерами `[from, to]`
+    Resend(u64, u64),
+
+    /// Отправить снимок последних котировок
+    Snapshot,
+}
+
+/// Котировка или свеча в очереди подписчика
+#[derive(Clone)]
+enum Quote {
+    Stock(StockQuote),
+    Candle(Candle),
+}
+
+impl Quote {
+    fn ticker(&self) -> &Ticker {
+        match self {
+            Quote::Stock(stock) => &stock.ticker,
+            Quote::Candle(candle) => &candle.ticker,
+        }
+    }
+
+    fn into_event(self) -> Event {
+        match self {
+            Quote::Stock(stock) => Event::Update(stock),
+            Quote::Candle(candle) => Event::Candle(candle),
+        }
+    }
+}
+
+/// Очередь котировок подписчика.
+///
+/// Запись в нее никогда не блокирует рассылку: при отставании подписчика
+/// срабатывает его [DeliveryPolicy].
+struct Queue {
+    policy: DeliveryPolicy,
+    capacity: usize,
+    /// Котировки (или свечи) и время их постановки в очередь
+    stocks: VecDeque<(Instant, Quote)>,
+    /// Запросы клиента по TCP, обрабатываются раньше котировок
+    requests: VecDeque<Event>,
+    closed: bool,
+    stats: SubscriberStats,
+    /// Будит асинхронного подписчика, когда в очереди появилось событие
+    waker: Option<Box<dyn Fn() + Send>>,
+}
+
+impl Queue {
+    fn new(policy: DeliveryPolicy, capacity: usize) -> Self {
+        Self {
+            policy,
+            capacity,
+            stocks: VecDeque::new(),
+            requests: VecDeque::new(),
+            closed: false,
+            stats: SubscriberStats::default(),
+            waker: None,
+        }
+    }
+
+    fn wake(&self) {
+        if let Some(waker) = &self.waker {
+            waker();
+        }
+    }
+
+    /// Поставить котировку в очередь, `false` - подписчик переполнен и отключен
+    fn push(&mut self, stock: Quote) -> bool {
+        if self.closed {
+            return true;
+        }
+        match self.policy {
+            DeliveryPolicy::Conflate => {
+                if let Some(pending) = self
+                    .stocks
+                    .iter_mut()
+                    .find(|(_, s)| s.ticker() == stock.ticker())
+                {
+                    // Место в очереди и время ожидания сохраняются
+                    pending.1 = stock;
+                    self.stats.conflated += 1;
+                    return true;
+                }
+            }
+            DeliveryPolicy::DropOldest => {
+                if self.stocks.len() >= self.capacity {
+                    self.stocks.pop_front();
+                    self.stats.dropped += 1;
+                }
+            }
+            DeliveryPolicy::Disconnect => {
+                if self.stocks.len() >= self.capacity {
+                    self.stats.dropped += self.stocks.len() as u64 + 1;
+                    self.stocks.clear();
+                    self.stats.overflowed = true;
+                    self.closed = true;
+                    self.wake();
+                    return false;
+                }
+            }
+        }
+        self.stocks.push_back((Instant::now(), stock));
+        self.stats.max_queued = self.stats.max_queued.max(self.stocks.len());
+        self.wake();
+        true
+    }
+
+    fn pop(&mut self) -> Option<Event> {
+        if self.closed {
+            return Some(Event::Disconnect);
+        }
+        if let Some(request) = self.requests.pop_front() {
+            return Some(request);
+        }
+        let (_, stock) = self.stocks.pop_front()?;
+        self.stats.delivered += 1;
+        Some(stock.into_event())
+    }
+
+    fn stats(&self) -> SubscriberStats {
+        SubscriberStats {
+            queued: self.stocks.len(),
+            lag_ms: self
+                .stocks
+                .front()
+                .map_or(0, |(at, _)| at.elapsed().as_millis() as u64),
+            ..self.stats.clone()
+        }
+    }
 }
 
 /// Udp Подписчик
 pub(crate) struct Subscriber {
     _id: u32,
-    _tickers: Vec<Ticker>,
-    receiver: channel::Receiver<Event>,
+    queue: Arc<Mutex<Queue>>,
 }
 
 impl Subscriber {
-    fn new(id: u32, tickers: Vec<String>, receiver: channel::Receiver<Event>) -> Self {
-        Self {
-            _id: id,
-            _tickers: tickers,
-            receiver,
-        }
+    /// Следующее событие, `None` - очередь пуста
+    pub fn get_event(&self) -> Option<Event> {
+        self.queue.lock().pop()
     }
 
-    pub fn get_event(&self) -> Result<Event, channel::TryRecvError> {
-        self.receiver.try_recv()
+    /// Вызывать `waker` при каждом новом событии вместо опроса очереди
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    pub fn set_waker(&self, waker: impl Fn() + Send + 'static) {
+        let mut queue = self.queue.lock();
+        queue.waker = Some(Box::new(waker));
+        // События, пришедшие до установки
+        if queue.closed || !queue.stocks.is_empty() || !queue.requests.is_empty() {
+            queue.wake();
+        }
     }
 }
 
-/// Данные о подписчике - какнал отправки, список акций
-struct SenderTicker(Arc<channel::Sender<Event>>, Vec<Ticker>);
+/// Очереди подписчиков по id
+type Queues = HashMap<u32, Arc<Mutex<Queue>>>;
+
+/// Данные о подписчике - очередь, список акций, что он получает
+struct SubscriberQueue(Arc<Mutex<Queue>>, Vec<Ticker>, Feed);
 
 /// Уведомляет подписчиков о новых ценах на акции
 pub(crate) struct Distributor {
     last_stocks: HashMap<Ticker, StockQuote>,
-    subscribers: HashMap<u32, SenderTicker>,
+    candles: CandleAggregator,
+    subscribers: HashMap<u32, SubscriberQueue>,
 
-    /// ticker: {id: sender}
-    ticker_senders: HashMap<Ticker, HashMap<u32, Arc<channel::Sender<Event>>>>,
+    /// (ticker, feed): {id: queue}
+    ticker_queues: HashMap<(Ticker, Feed), Queues>,
 
     /// Счетчик подписок (служит для генерации id)
     __count: u32,
@@ -54,67 +202,165 @@ impl Distributor {
     pub fn new() -> Self {
         Self {
             last_stocks: HashMap::new(),
+            candles: CandleAggregator::new(),
             subscribers: HashMap::new(),
 
-            ticker_senders: HashMap::new(),
+            ticker_queues: HashMap::new(),
             __count: 0,
         }
     }
 
-    /// Подписаться на отслеживание акции
-    pub fn subscribe(&mut self, tickers: Vec<Ticker>) -> (u32, Subscriber) {
+    /// Подписаться на котировки или свечи акций
+    pub fn subscribe(
+        &mut self,
+        tickers: Vec<Ticker>,
+        feed: Feed,
+        policy: DeliveryPolicy,
+    ) -> (u32, Subscriber) {
+        self.subscribe_with_capacity(tickers, feed, policy, QUEUE_SIZE)
+    }
+
+    fn subscribe_with_capacity(
+        &mut self,
+        tickers: Vec<Ticker>,
+        feed: Feed,
+        policy: DeliveryPolicy,
+        capacity: usize,
+    ) -> (u32, Subscriber) {
         let id = self.__count;
         self.__count += 1;
 
-        logging!(info, ("Подписка на акции {:?}. id: {}", tickers, id));
-
-        let (sender, receiver) = channel::bounded(1024);
-        let sender = Arc::new(sender);
+        logging!(
+            info,
+            (
+                "Подписка на акции {:?} ({}, {}). id: {}",
+                tickers,
+                feed,
+                policy,
+                id
+            )
+        );
 
+        let queue = Arc::new(Mutex::new(Queue::new(policy, capacity)));
         for ticker in &tickers {
-            self.ticker_senders
-                .entry(ticker.clone())
+            self.ticker_queues
+                .entry((ticker.clone(), feed))
                 .or_default()
-                .insert(id, sender.clone());
+                .insert(id, queue.clone());
         }
 
         self.subscribers
-            .insert(id, SenderTicker(sender, tickers.clone()));
+            .insert(id, SubscriberQueue(queue.clone(), tickers, feed));
 
-        return (id, Subscriber::new(id, tickers, receiver));
+        let subscriber = Subscriber { _id: id, queue };
+        (id, subscriber)
     }
 
     /// Отписаться от отслеживания акции
     pub fn unsubscribe(&mut self, id: u32) {
         logging!(info, ("Отпика от акций id: {}", id));
 
-        if let Some(SenderTicker(sender, tickers)) = self.subscribers.remove(&id) {
-            let _ = sender.send(Event::Disconnect);
-            for ticker in tickers {
-                // получаем всех senders, которые соответствуют этой акции и этому пользователю
-                if let Some(senders) = self.ticker_senders.get_mut(&ticker) {
-                    senders.remove(&id);
-                }
+        if let Some(SubscriberQueue(queue, tickers, feed)) = self.subscribers.remove(&id) {
+            let mut queue = queue.lock();
+            queue.closed = true;
+            queue.wake();
+            drop(queue);
+            self.remove_from_tickers(id, &tickers, feed);
+        }
+    }
+
+    /// Убрать подписчика из рассылки по тикерам
+    fn remove_from_tickers(&mut self, id: u32, tickers: &[Ticker], feed: Feed) {
+        for ticker in tickers {
+            let key = (ticker.clone(), feed);
+            // получаем все очереди, которые соответствуют этой акции и этому пользователю
+            if let Some(queues) = self.ticker_queues.get_mut(&key) {
+                queues.remove(&id);
                 // если на такой тикер никто не подписан, удаляем его
-                if let Some(senders) = self.ticker_senders.get(&ticker) {
-                    if senders.is_empty() {
-                        self.ticker_senders.remove(&ticker);
-                    }
+                if queues.is_empty() {
+                    self.ticker_queues.remove(&key);
                 }
             }
         }
     }
 
-    /// Отправить новые данные о акции
+    /// Отправить новые данные о акции и закрытые ими свечи.
+    ///
+    /// Не блокируется: отстающие подписчики обрабатываются своей политикой доставки,
+    /// переполненные с [DeliveryPolicy::Disconnect] убираются из рассылки.
     pub fn send_all(&mut self, stock: StockQuote) {
         logging!(info, ("Отправляем акцию: {:?}", &stock));
         self.last_stocks.insert(stock.ticker.clone(), stock.clone());
 
-        if let Some(senders) = self.ticker_senders.get(&stock.ticker) {
-            for (_, sender) in senders.iter() {
-                let _ = sender.send(Event::Update(stock.clone()));
+        let mut overflowed = vec![];
+        for candle in self.candles.push(&stock) {
+            let feed = Feed::Candle(candle.interval);
+            overflowed.extend(self.push(feed, Quote::Candle(candle)));
+        }
+        overflowed.extend(self.push(Feed::Ticks, Quote::Stock(stock)));
+
+        // Метрики остаются доступны до STOP/DISCONNECT
+        for id in overflowed {
+            if let Some(SubscriberQueue(_, tickers, feed)) = self.subscribers.get(&id) {
+                let (tickers, feed) = (tickers.clone(), *feed);
+                self.remove_from_tickers(id, &tickers, feed);
+            }
+        }
+    }
+
+    /// Поставить котировку в очереди подписчиков, вернуть переполненных
+    fn push(&self, feed: Feed, quote: Quote) -> Vec<u32> {
+        let mut overflowed = vec![];
+        let Some(queues) = self.ticker_queues.get(&(quote.ticker().clone(), feed)) else {
+            return overflowed;
+        };
+        for (id, queue) in queues.iter() {
+            if !queue.lock().push(quote.clone()) {
+                logging!(warn, ("Подписчик {} переполнен и отключен", id));
+                overflowed.push(*id);
             }
         }
+        overflowed
+    }
+
+    /// Передать подписчику запрос клиента, `false` - подписчик не найден
+    pub fn request(&self, id: u32, event: Event) -> bool {
+        let Some(SubscriberQueue(queue, _, _)) = self.subscribers.get(&id) else {
+            return false;
+        };
+        let mut queue = queue.lock();
+        queue.requests.push_back(event);
+        queue.wake();
+        true
+    }
+
+    /// Метрики отставания подписчика
+    pub fn stats(&self, id: u32) -> Option<SubscriberStats> {
+        self.subscribers
+            .get(&id)
+            .map(|SubscriberQueue(queue, _, _)| queue.lock().stats())
+    }
+
+    /// Политика доставки подписчика
+    pub fn policy(&self, id: u32) -> Option<DeliveryPolicy> {
+        self.subscribers
+            .get(&id)
+            .map(|SubscriberQueue(queue, _, _)| queue.lock().policy)
+    }
+
+    /// Что получает подписчик
+    pub fn feed(&self, id: u32) -> Option<Feed> {
+        self.subscribers
+            .get(&id)
+            .map(|SubscriberQueue(_, _, feed)| *feed)
+    }
+
+    /// Последние закрытые свечи акций для снимка, по [SNAPSHOT_CANDLES] на тикер
+    pub fn get_last_candles(&self, tickers: &[Ticker], interval: Interval) -> Vec<Candle> {
+        tickers
+            .iter()
+            .flat_map(|ticker| self.candles.last(ticker, interval, SNAPSHOT_CANDLES))
+            .collect()
     }
 
     /// Получить последние данные о акциях
@@ -132,3 +378,191 @@ impl Distributor {
         self.last_stocks.keys().cloned().collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stock(ticker: &str, price: f64) -> StockQuote {
+        StockQuote {
+            ticker: ticker.to_string(),
+            price,
+            volume: 1,
+            timestamp: 0,
+        }
+    }
+
+    fn prices(subscriber: &Subscriber) -> Vec<(Ticker, f64)> {
+        let mut res = vec![];
+        while let Some(Event::Update(s)) = subscriber.get_event() {
+            res.push((s.ticker, s.price));
+        }
+        res
+    }
+
+    #[test]
+    fn test_filter_by_tickers() {
+        let mut distributor = Distributor::new();
+        let (_, subscriber) = distributor.subscribe(
+            vec!["A".to_string()],
+            Feed::Ticks,
+            DeliveryPolicy::DropOldest,
+        );
+        distributor.send_all(stock("A", 1.0));
+        distributor.send_all(stock("B", 2.0));
+
+        assert_eq!(prices(&subscriber), [("A".to_string(), 1.0)]);
+    }
+
+    #[test]
+    fn test_conflate() {
+        let mut distributor = Distributor::new();
+        let (id, subscriber) = distributor.subscribe(
+            vec!["A".to_string(), "B".to_string()],
+            Feed::Ticks,
+            DeliveryPolicy::Conflate,
+        );
+        distributor.send_all(stock("A", 1.0));
+        distributor.send_all(stock("B", 2.0));
+        distributor.send_all(stock("A", 3.0));
+
+        let stats = distributor.stats(id).unwrap();
+        assert_eq!((stats.queued, stats.conflated), (2, 1));
+        // Последняя цена A на месте первой
+        assert_eq!(
+            prices(&subscriber),
+            [("A".to_string(), 3.0), ("B".to_string(), 2.0)]
+        );
+        assert_eq!(distributor.stats(id).unwrap().delivered, 2);
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        let mut distributor = Distributor::new();
+        let (id, subscriber) = distributor.subscribe_with_capacity(
+            vec!["A".to_string()],
+            Feed::Ticks,
+            DeliveryPolicy::DropOldest,
+            2,
+        );
+        for price in 0..5 {
+            distributor.send_all(stock("A", price as f64));
+        }
+
+        assert_eq!(distributor.stats(id).unwrap().dropped, 3);
+        assert_eq!(
+            prices(&subscriber),
+            [("A".to_string(), 3.0), ("A".to_string(), 4.0)]
+        );
+    }
+
+    #[test]
+    fn test_disconnect_on_overflow() {
+        let mut distributor = Distributor::new();
+        let (id, subscriber) = distributor.subscribe_with_capacity(
+            vec!["A".to_string()],
+            Feed::Ticks,
+            DeliveryPolicy::Disconnect,
+            2,
+        );
+        let (_, other) = distributor.subscribe_with_capacity(
+            vec!["A".to_string()],
+            Feed::Ticks,
+            DeliveryPolicy::DropOldest,
+            8,
+        );
+        for price in 0..3 {
+            distributor.send_all(stock("A", price as f64));
+        }
+
+        assert!(matches!(subscriber.get_event(), Some(Event::Disconnect)));
+        let stats = distributor.stats(id).unwrap();
+        assert!(stats.overflowed);
+        assert_eq!(stats.queued, 0);
+        // Остальные подписчики не затронуты
+        distributor.send_all(stock("A", 3.0));
+        assert_eq!(prices(&other).len(), 4);
+    }
+
+    #[test]
+    fn test_request_before_stocks() {
+        let mut distributor = Distributor::new();
+        let (id, subscriber) =
+            distributor.subscribe(vec!["A".to_string()], Feed::Ticks, DeliveryPolicy::Conflate);
+        distributor.send_all(stock("A", 1.0));
+        assert!(distributor.request(id, Event::Resend(1, 2)));
+        assert!(!distributor.request(id + 1, Event::Snapshot));
+
+        assert!(matches!(subscriber.get_event(), Some(Event::Resend(1, 2))));
+        assert!(matches!(subscriber.get_event(), Some(Event::Update(_))));
+    }
+
+    #[test]
+    fn test_candle_feed() {
+        let mut distributor = Distributor::new();
+        let (id, subscriber) = distributor.subscribe(
+            vec!["A".to_string()],
+            Feed::Candle(Interval::Min1),
+            DeliveryPolicy::DropOldest,
+        );
+        for (price, timestamp) in [(1.0, 0), (3.0, 30), (2.0, 60), (5.0, 90)] {
+            distributor.send_all(StockQuote {
+                timestamp,
+                ..stock("A", price)
+            });
+        }
+
+        // Котировки не приходят, только закрытая минута
+        let Some(Event::Candle(candle)) = subscriber.get_event() else {
+            panic!("Ожидалась свеча");
+        };
+        assert_eq!((candle.start, candle.high, candle.close), (0, 3.0, 3.0));
+        assert!(subscriber.get_event().is_none());
+        assert_eq!(distributor.feed(id), Some(Feed::Candle(Interval::Min1)));
+
+        let last = distributor.get_last_candles(&["A".to_string()], Interval::Sec1);
+        assert_eq!(last.len(), 3);
+        assert!(
+            distributor
+                .get_last_candles(&["A".to_string()], Interval::Min5)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_waker() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let mut distributor = Distributor::new();
+        let (id, subscriber) =
+            distributor.subscribe(vec!["A".to_string()], Feed::Ticks, DeliveryPolicy::Conflate);
+        distributor.send_all(stock("A", 1.0));
+
+        let wakes = Arc::new(AtomicUsize::new(0));
+        let counter = wakes.clone();
+        subscriber.set_waker(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        // Котировка уже в очереди
+        assert_eq!(wakes.load(Ordering::SeqCst), 1);
+
+        distributor.send_all(stock("B", 1.0));
+        distributor.send_all(stock("A", 2.0));
+        distributor.request(id, Event::Snapshot);
+        distributor.unsubscribe(id);
+        // B без подписчика, вторая A слита с первой
+        assert_eq!(wakes.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_unsubscribe() {
+        let mut distributor = Distributor::new();
+        let (id, subscriber) =
+            distributor.subscribe(vec!["A".to_string()], Feed::Ticks, DeliveryPolicy::Conflate);
+        distributor.send_all(stock("A", 1.0));
+        distributor.unsubscribe(id);
+
+        assert!(matches!(subscriber.get_event(), Some(Event::Disconnect)));
+        assert!(distributor.stats(id).is_none());
+    }
+}
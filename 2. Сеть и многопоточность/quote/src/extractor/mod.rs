@@ -2,11 +2,13 @@ mod api;
 mod console;
 mod file;
 mod random;
+mod replay;
 use crate::types::stock::StockQuote;
 use clap::ValueEnum;
 pub use console::ConsoleExtractor;
 pub use file::FileMockExtractor;
 pub use random::RandomExtractor;
+pub use replay::{ReplayConfig, ReplayExtractor};
 use std::sync::mpsc::Receiver;
 
 const SLEEP_TIME: std::time::Duration = std::time::Duration::from_millis(1_000);
@@ -24,4 +26,6 @@ pub enum ExtractorType {
     File,
     Api,
     Random,
+    /// Воспроизведение записанных котировок
+    Replay,
 }
@@ -0,0 +1,236 @@
+use super::Extractor;
+use crate::{logging, types::stock::StockQuote};
+use std::{
+    fs::File,
+    io::Read,
+    sync::mpsc::{Receiver, Sender},
+    time::{Duration, Instant},
+};
+
+/// ## Настройки воспроизведения
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayConfig {
+    /// Множитель скорости: 2.0 - в два раза быстрее записи
+    pub speed: f64,
+    /// Начинать заново после последней котировки
+    pub looped: bool,
+    /// Пропустить котировки раньше этого времени
+    pub seek: Option<u64>,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            looped: false,
+            seek: None,
+        }
+    }
+}
+
+/// Экстрактор записанных котировок.
+///
+/// Читает файл с котировками (CSV `ticker,price,volume,timestamp` или JSON lines)
+/// и отправляет их с исходными интервалами между ними.
+pub struct ReplayExtractor {
+    subscribers: Vec<Sender<StockQuote>>,
+    /// Котировки по возрастанию времени, начиная с `seek`
+    stocks: Vec<StockQuote>,
+    config: ReplayConfig,
+}
+
+impl ReplayExtractor {
+    pub fn new(mut file: File, config: ReplayConfig) -> Result<Self, String> {
+        let mut text = String::new();
+        file.read_to_string(&mut text)
+            .map_err(|e| format!("Невозможно прочитать файл: {}", e))?;
+
+        Self::from_text(&text, config)
+    }
+
+    fn from_text(text: &str, config: ReplayConfig) -> Result<Self, String> {
+        if !(config.speed.is_finite() && config.speed > 0.0) {
+            return Err(format!("Неправильная скорость: {}", config.speed));
+        }
+
+        let mut stocks = parse_ticks(text)?;
+        // Стабильно: котировки с одним временем остаются в порядке записи
+        stocks.sort_by_key(|s| s.timestamp);
+        if let Some(seek) = config.seek {
+            stocks.retain(|s| s.timestamp >= seek);
+        }
+        if stocks.is_empty() {
+            return Err("Нет котировок для воспроизведения".to_string());
+        }
+
+        Ok(Self {
+            subscribers: Vec::new(),
+            stocks,
+            config,
+        })
+    }
+
+    /// Через сколько после первой котировки отправить котировку
+    fn offset(&self, stock: &StockQuote) -> Duration {
+        let secs = (stock.timestamp - self.stocks[0].timestamp) as f64;
+        Duration::from_secs_f64(secs / self.config.speed)
+    }
+
+    fn send(&self, stock: &StockQuote) {
+        for tx in self.subscribers.iter() {
+            if let Err(_e) = tx.send(stock.clone()) {
+                logging!(warn, ("Extractor: Ошибка при отправке: {}", _e));
+            };
+        }
+    }
+}
+
+impl Extractor for ReplayExtractor {
+    fn run(self: Box<Self>) -> Result<(), String> {
+        logging!(
+            info,
+            (
+                "ReplayExtractor запущен: {} котировок, {:?}",
+                self.stocks.len(),
+                self.config
+            )
+        );
+
+        loop {
+            // Время считается от начала прохода, чтобы задержки не накапливались
+            let start = Instant::now();
+            for stock in self.stocks.iter() {
+                let wait = (start + self.offset(stock)).saturating_duration_since(Instant::now());
+                if !wait.is_zero() {
+                    std::thread::sleep(wait);
+                }
+                self.send(stock);
+            }
+
+            if !self.config.looped {
+                logging!(info, ("ReplayExtractor: запись закончилась"));
+                return Ok(());
+            }
+        }
+    }
+
+    fn subscribe(&mut self) -> Receiver<StockQuote> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        self.subscribers.push(tx);
+        rx
+    }
+}
+
+/// Разобрать котировки, формат определяется по каждой строке
+fn parse_ticks(text: &str) -> Result<Vec<StockQuote>, String> {
+    let mut stocks = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let stock = if line.starts_with('{') {
+            serde_json::from_str(line).map_err(|e| e.to_string())
+        } else {
+            parse_csv(line)
+        };
+        match stock {
+            Ok(stock) => stocks.push(stock),
+            // Заголовок CSV
+            Err(_) if i == 0 && line.starts_with("ticker") => continue,
+            Err(e) => return Err(format!("Строка {}: {}", i + 1, e)),
+        }
+    }
+    Ok(stocks)
+}
+
+fn parse_csv(line: &str) -> Result<StockQuote, String> {
+    let parts: Vec<&str> = line.split(',').map(str::trim).collect();
+    let [ticker, price, volume, timestamp] = parts[..] else {
+        return Err("ожидается ticker,price,volume,timestamp".to_string());
+    };
+    Ok(StockQuote {
+        ticker: ticker.to_string(),
+        price: price.parse().map_err(|_| format!("цена {}", price))?,
+        volume: volume.parse().map_err(|_| format!("объем {}", volume))?,
+        timestamp: timestamp
+            .parse()
+            .map_err(|_| format!("время {}", timestamp))?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICKS: &str = "ticker,price,volume,timestamp
+A,1.5,10,100
+{\"ticker\":\"B\",\"price\":2.0,\"volume\":20,\"timestamp\":102}
+
+# пропуск
+A,1.0,5,101
+";
+
+    fn replay(config: ReplayConfig) -> Result<ReplayExtractor, String> {
+        ReplayExtractor::from_text(TICKS, config)
+    }
+
+    #[test]
+    fn test_parse_sorted() {
+        let extractor = replay(ReplayConfig::default()).unwrap();
+        let stamps: Vec<u64> = extractor.stocks.iter().map(|s| s.timestamp).collect();
+        assert_eq!(stamps, [100, 101, 102]);
+        assert_eq!(extractor.stocks[2].ticker, "B");
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = ReplayExtractor::from_text("A,1.0,5,101\nA,x,5,102", ReplayConfig::default());
+        assert_eq!(err.err().unwrap(), "Строка 2: цена x");
+        assert!(
+            replay(ReplayConfig {
+                speed: 0.0,
+                ..Default::default()
+            })
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_seek_and_speed() {
+        let extractor = replay(ReplayConfig {
+            speed: 4.0,
+            seek: Some(101),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(extractor.stocks.len(), 2);
+        assert_eq!(
+            extractor.offset(&extractor.stocks[1]),
+            Duration::from_millis(250)
+        );
+        assert!(
+            replay(ReplayConfig {
+                seek: Some(200),
+                ..Default::default()
+            })
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_run_replays_all() {
+        let mut extractor = Box::new(
+            replay(ReplayConfig {
+                speed: 1000.0,
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        let rx = extractor.subscribe();
+        assert_eq!(extractor.run(), Ok(()));
+
+        let tickers: Vec<String> = rx.try_iter().map(|s| s.ticker).collect();
+        assert_eq!(tickers, ["A", "A", "B"]);
+    }
+}
@@ -1,7 +1,10 @@
+#[cfg(feature = "async")]
+pub mod aio;
 pub mod client;
 mod distributor;
 pub mod extractor;
 pub mod master;
+mod session;
 mod tcp_worker;
 pub mod types;
 mod udp_worker;
@@ -3,30 +3,86 @@ use crate::{
     logging,
     tcp_worker::TcpWorker,
     types::{
+        auth::ClientKey,
         state::{MasterState, MasterStateShell},
         stock::StockQuote,
     },
 };
+use serde::Deserialize;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     io,
     net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener},
+    path::Path,
     sync::{Arc, mpsc::Receiver},
     thread::{self, JoinHandle},
     time::Duration,
 };
 
-/// Тип соединения: адрес, id подписчика, поток
+/// Рассылка UDP потока: поток ОС или задача tokio
+pub(crate) enum StreamHandle {
+    Thread(thread::JoinHandle<Result<(), String>>),
+    #[cfg(feature = "async")]
+    Task(tokio::task::JoinHandle<Result<(), String>>),
+}
+
+impl StreamHandle {
+    pub(crate) fn is_finished(&self) -> bool {
+        match self {
+            StreamHandle::Thread(handle) => handle.is_finished(),
+            #[cfg(feature = "async")]
+            StreamHandle::Task(handle) => handle.is_finished(),
+        }
+    }
+
+    /// Поток ОС, задачи tokio запускает и дожидается только асинхронный мастер
+    fn into_thread(self) -> Option<thread::JoinHandle<Result<(), String>>> {
+        match self {
+            StreamHandle::Thread(handle) => Some(handle),
+            #[cfg(feature = "async")]
+            StreamHandle::Task(_) => None,
+        }
+    }
+}
+
+/// Тип соединения: адрес, id подписчика, рассылка, имя клиента (если включен вход)
 pub(crate) struct Connection(
     pub(crate) SocketAddr,
     pub(crate) u32,
-    pub(crate) thread::JoinHandle<Result<(), String>>,
+    pub(crate) StreamHandle,
+    pub(crate) Option<String>,
 );
 
 /// Конфиг мастера
 pub struct MasterConfig {
     secret_key: String,
     tcp_addr: SocketAddr,
+    /// Ключи клиентов, пустой список - вход не требуется
+    clients: Vec<ClientKey>,
+}
+
+/// ## Файл настроек сервера (JSON)
+#[derive(Debug, Default, Deserialize)]
+pub struct MasterConfigFile {
+    pub host: Option<SocketAddr>,
+    pub secret_key: Option<String>,
+    #[serde(default)]
+    pub clients: Vec<ClientKey>,
+}
+
+impl MasterConfigFile {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Невозможно прочитать настройки: {}", e))?;
+        let config: Self =
+            serde_json::from_str(&text).map_err(|e| format!("Неправильные настройки: {}", e))?;
+
+        let mut names = HashSet::new();
+        if let Some(client) = config.clients.iter().find(|c| !names.insert(&c.name)) {
+            return Err(format!("Клиент {} указан дважды", client.name));
+        }
+        Ok(config)
+    }
 }
 
 impl MasterConfig {
@@ -35,9 +91,32 @@ impl MasterConfig {
             secret_key: secret_key.unwrap_or_else(|| Self::gen_secret_key()),
             tcp_addr: tcp_addr
                 .unwrap_or_else(|| SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7878)),
+            clients: Vec::new(),
         }
     }
 
+    /// Требовать вход по ключам клиентов
+    pub fn with_clients(mut self, clients: Vec<ClientKey>) -> Self {
+        self.clients = clients;
+        self
+    }
+
+    /// Адрес управляющего TCP соединения
+    pub fn tcp_addr(&self) -> SocketAddr {
+        self.tcp_addr
+    }
+
+    /// Начальный стейт мастера
+    pub(crate) fn state(&self) -> MasterState {
+        MasterState::new(
+            HashMap::new(),
+            Distributor::new(),
+            false,
+            self.secret_key.clone(),
+            self.clients.clone(),
+        )
+    }
+
     /// Генерация секретного ключа
     pub fn gen_secret_key() -> String {
         let secret_key = rand::random_iter()
@@ -53,6 +132,7 @@ impl Default for MasterConfig {
         Self {
             secret_key: Self::gen_secret_key(),
             tcp_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7878),
+            clients: Vec::new(),
         }
     }
 }
@@ -69,12 +149,7 @@ pub struct Master {
 impl Master {
     pub fn new(rx_stock: Receiver<StockQuote>, config: Option<MasterConfig>) -> Self {
         let config: MasterConfig = config.unwrap_or_default();
-        let state = Arc::new(MasterState::new(
-            HashMap::new(),
-            Distributor::new(),
-            false,
-            config.secret_key.clone(),
-        ));
+        let state = Arc::new(config.state());
 
         Self {
             rx_stock,
@@ -114,9 +189,12 @@ impl Master {
                     logging!(warn, ("Connection failed: {}", _e.to_string()));
                 }
             }
-            if let Ok(stock) = self.rx_stock.try_recv() {
+            let mut stocks = self.rx_stock.try_iter().peekable();
+            if stocks.peek().is_some() {
                 let mut distributor_guard = shell.distributor();
-                distributor_guard.get_mut().send_all(stock);
+                for stock in stocks {
+                    distributor_guard.get_mut().send_all(stock);
+                }
             }
         }
 
@@ -133,13 +211,16 @@ impl Master {
             let Some(connections) = all_connections.remove(&domen) else {
                 continue;
             };
-            for Connection(_, id, thread) in connections {
+            for Connection(_, id, thread, _) in connections {
                 distributor.unsubscribe(id);
                 threads.push((id, thread));
             }
         }
 
-        for (_id, thread) in threads {
+        for (_id, handle) in threads {
+            let Some(thread) = handle.into_thread() else {
+                continue;
+            };
             match thread.join() {
                 Ok(Ok(())) => {
                     logging!(warn, ("Поток {} успешно завершился", _id));
@@ -0,0 +1,426 @@
+use crate::{
+    distributor::{Event, Subscriber},
+    logging,
+    master::{Connection, StreamHandle},
+    types::{
+        auth::{self, ClientKey, Credential, Role},
+        candle::Feed,
+        command::TcpCommand,
+        delivery::DeliveryPolicy,
+        error::QuoteError,
+        state::{MasterState, MasterStateShell},
+        stock::Ticker,
+    },
+    udp_worker::{StreamState, UdpWorker},
+};
+use std::{net::SocketAddr, sync::Arc};
+
+/// ## Где запускать рассылку UDP потоков
+#[derive(Clone)]
+pub(crate) enum Runtime {
+    /// Поток ОС на каждый UDP поток
+    Threads,
+    /// Задача tokio на каждый UDP поток
+    #[cfg(feature = "async")]
+    Tokio(tokio::runtime::Handle),
+}
+
+impl Runtime {
+    /// Запустить рассылку потока
+    fn spawn_stream(
+        &self,
+        subscriber: Subscriber,
+        socket: SocketAddr,
+        stream: StreamState,
+    ) -> Result<StreamHandle, String> {
+        match self {
+            Runtime::Threads => {
+                // пока формат захардкожен для соблюдения ТЗ, но если надо будет, то можно будет передавать через команду)
+                let worker = UdpWorker::new(subscriber, socket, None, stream)?;
+                Ok(StreamHandle::Thread(std::thread::spawn(move || {
+                    worker.run()
+                })))
+            }
+            #[cfg(feature = "async")]
+            Runtime::Tokio(handle) => {
+                let _guard = handle.enter();
+                let worker =
+                    crate::aio::udp_worker::UdpWorker::new(subscriber, socket, None, stream)?;
+                Ok(StreamHandle::Task(handle.spawn(worker.run())))
+            }
+        }
+    }
+}
+
+/// ## Сессия управляющего соединения
+///
+/// Выполняет команды клиента независимо от транспорта: ее используют и
+/// [crate::tcp_worker::TcpWorker], и асинхронный worker.
+pub(crate) struct Session {
+    state: Arc<MasterState>,
+    /// Адрес клиента, по нему хранятся его потоки
+    domen: SocketAddr,
+    runtime: Runtime,
+
+    /// Клиент, выполнивший вход
+    client: Option<ClientKey>,
+    /// Последний выданный вызов, одноразовый
+    challenge: Option<String>,
+}
+
+impl Session {
+    pub(crate) fn new(state: Arc<MasterState>, domen: SocketAddr, runtime: Runtime) -> Self {
+        Self {
+            state,
+            domen,
+            runtime,
+
+            client: None,
+            challenge: None,
+        }
+    }
+
+    pub(crate) fn domen(&self) -> SocketAddr {
+        self.domen
+    }
+
+    pub(crate) fn state(&self) -> Arc<MasterState> {
+        self.state.clone()
+    }
+
+    pub(crate) fn is_shutdown(shell: &MasterStateShell) -> bool {
+        let shutdown_guard = shell.shutdown();
+        **shutdown_guard.get()
+    }
+
+    /// Выполнить команду, `None` - команда без ответа
+    pub(crate) fn execute(
+        &mut self,
+        command: TcpCommand,
+        shell: &MasterStateShell,
+    ) -> Option<Result<String, QuoteError>> {
+        logging!(info, ("Command tcp: {:?}", command));
+        // Запросы восстановления потока идут без ответа, чтобы не мешать ответам на команды
+        let silent = matches!(command, TcpCommand::Resend(_) | TcpCommand::Snapshot(_));
+        if let Err(e) = self.authorize(&command) {
+            if silent {
+                return None;
+            }
+            return Some(Err(e));
+        }
+        let res = match command {
+            TcpCommand::Challenge => Ok(self.command_challenge()),
+            TcpCommand::Auth((name, credential)) => self.command_auth(name, credential),
+            TcpCommand::Stream((socket, tickers, policy, feed)) => {
+                self.command_stream(socket, tickers, policy, feed, shell)
+            }
+            TcpCommand::Stop(socket) => self.command_stop(socket, shell),
+            TcpCommand::Resend((socket, from, to)) => {
+                self.command_request(socket, Event::Resend(from, to), shell)
+            }
+            TcpCommand::Snapshot(socket) => self.command_request(socket, Event::Snapshot, shell),
+            TcpCommand::Disconnect => self.command_disconnect(shell),
+            TcpCommand::List => self.command_list(shell),
+            TcpCommand::Tickers => self.command_tickers(shell),
+            TcpCommand::Help => Ok(Self::command_help()),
+            TcpCommand::Shutdown(key) => self.command_shutdown(key, shell),
+        };
+
+        if silent {
+            if let Err(_e) = &res {
+                logging!(warn, ("Stream request failed: {}", _e.to_string()));
+            }
+            return None;
+        }
+        Some(res)
+    }
+
+    /// Проверить права на команду, если сервер требует вход
+    fn authorize(&self, command: &TcpCommand) -> Result<(), QuoteError> {
+        if !self.state.auth_required() {
+            return Ok(());
+        }
+        if matches!(
+            command,
+            TcpCommand::Help | TcpCommand::Challenge | TcpCommand::Auth(_)
+        ) {
+            return Ok(());
+        }
+        let Some(client) = &self.client else {
+            return Err(QuoteError::Unauthorized);
+        };
+
+        match command {
+            TcpCommand::List | TcpCommand::Shutdown(_) if client.role != Role::Admin => Err(
+                QuoteError::Forbidden("команда только для администратора".to_string()),
+            ),
+            TcpCommand::Stream((_, tickers, _, _)) => {
+                let forbidden = client.forbidden_tickers(tickers);
+                if forbidden.is_empty() {
+                    Ok(())
+                } else {
+                    Err(QuoteError::Forbidden(format!(
+                        "тикеры {}",
+                        forbidden.join(",")
+                    )))
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // --- обработка команд с tcp ---
+
+    fn command_challenge(&mut self) -> String {
+        let challenge = auth::gen_challenge();
+        self.challenge = Some(challenge.clone());
+        challenge
+    }
+
+    fn command_auth(&mut self, name: String, credential: Credential) -> Result<String, QuoteError> {
+        if !self.state.auth_required() {
+            return Err(QuoteError::BadRequest("Вход не требуется".to_string()));
+        }
+        // Вызов действует на одну попытку
+        let challenge = self.challenge.take();
+        let Some(client) = self.state.client(&name) else {
+            logging!(warn, ("Unknown client {}: {}", name, self.domen));
+            return Err(QuoteError::Unauthorized);
+        };
+        if !auth::verify(client, &credential, challenge.as_deref()) {
+            logging!(warn, ("Wrong key {}: {}", name, self.domen));
+            return Err(QuoteError::Unauthorized);
+        }
+
+        logging!(
+            info,
+            ("Authorized {} ({:?}): {}", name, client.role, self.domen)
+        );
+        self.client = Some(client.clone());
+        Ok("Authorized".to_string())
+    }
+
+    fn command_stream(
+        &mut self,
+        socket: SocketAddr,
+        tickers: Vec<Ticker>,
+        policy: DeliveryPolicy,
+        feed: Feed,
+        shell: &MasterStateShell,
+    ) -> Result<String, QuoteError> {
+        let mut all_connections_guard = shell.connections();
+        let mut distributor_guard = shell.distributor();
+
+        let all_connections = all_connections_guard.get_mut();
+        let distributor = distributor_guard.get_mut();
+
+        if let Some(connections) = all_connections.get_mut(&self.domen) {
+            if let Some(indx) = connections.iter().position(|c| c.0 == socket) {
+                if connections[indx].2.is_finished() {
+                    connections.remove(indx);
+                    return Err(QuoteError::BadRequest("Поток уже запущен".to_string()));
+                }
+                return Err(QuoteError::AlreadyExists);
+            }
+        }
+        let owner = self.client.as_ref().map(|s| s.name.clone());
+        if let Some(max_streams) = self.client.as_ref().and_then(|s| s.max_streams) {
+            // Потоки клиента по всем его подключениям
+            let streams = all_connections
+                .values()
+                .flatten()
+                .filter(|c| c.3 == owner && !c.2.is_finished())
+                .count();
+            if streams >= max_streams {
+                return Err(QuoteError::Forbidden(format!(
+                    "не больше {} потоков",
+                    max_streams
+                )));
+            }
+        }
+        let (last_stocks, last_candles) = match feed {
+            Feed::Ticks => (distributor.get_last_stocks(&tickers), vec![]),
+            Feed::Candle(interval) => (vec![], distributor.get_last_candles(&tickers, interval)),
+        };
+
+        let (id, subscriber) = distributor.subscribe(tickers, feed, policy);
+
+        let stream = StreamState::new(feed, last_stocks, last_candles);
+        let handle = match self.runtime.spawn_stream(subscriber, socket, stream) {
+            Ok(handle) => handle,
+            Err(_e) => {
+                logging!(info, ("{}: Connection failed: {}", socket, _e.to_string()));
+                distributor.unsubscribe(id);
+                return Err(QuoteError::NotConnection);
+            }
+        };
+
+        all_connections
+            .entry(self.domen)
+            .or_default()
+            .push(Connection(socket, id, handle, owner));
+        Ok("Running".to_string())
+    }
+
+    fn command_stop(
+        &mut self,
+        socket: SocketAddr,
+        shell: &MasterStateShell,
+    ) -> Result<String, QuoteError> {
+        let mut all_connections_guard = shell.connections();
+        let mut distributor_guard = shell.distributor();
+
+        let all_connections = all_connections_guard.get_mut();
+        let distributor = distributor_guard.get_mut();
+
+        let Some(connections) = all_connections.get_mut(&self.domen) else {
+            return Err(QuoteError::NotFound);
+        };
+        let Some(indx) = connections.iter().position(|c| c.0 == socket) else {
+            return Err(QuoteError::NotFound);
+        };
+
+        let Connection(_, id, _, _) = connections.remove(indx);
+        distributor.unsubscribe(id);
+        // Вопрос такой, правильно ли так делать?)
+        // по факту это может затормозить tcp-поток...
+        // handle.join();
+        Ok("Stopped".to_string())
+    }
+
+    fn command_request(
+        &self,
+        socket: SocketAddr,
+        event: Event,
+        shell: &MasterStateShell,
+    ) -> Result<String, QuoteError> {
+        let all_connections_guard = shell.connections();
+        let distributor_guard = shell.distributor();
+
+        let Some(Connection(_, id, _, _)) = all_connections_guard
+            .get()
+            .get(&self.domen)
+            .and_then(|connections| connections.iter().find(|c| c.0 == socket))
+        else {
+            return Err(QuoteError::NotFound);
+        };
+        if !distributor_guard.get().request(*id, event) {
+            return Err(QuoteError::NotFound);
+        }
+        Ok("Requested".to_string())
+    }
+
+    fn command_list(&self, shell: &MasterStateShell) -> Result<String, QuoteError> {
+        let all_connections_guard = shell.connections();
+        let distributor_guard = shell.distributor();
+
+        let all_connections = all_connections_guard.get();
+        // Администратор видит подключения всех клиентов
+        let is_admin = self.client.as_ref().is_some_and(|s| s.role == Role::Admin);
+        let domens: Vec<&SocketAddr> = match is_admin {
+            true => all_connections.keys().collect(),
+            false => vec![&self.domen],
+        };
+
+        // Ответ читается одной строкой, поэтому потоки разделены `|`
+        let mut res = vec![];
+        for domen in domens {
+            let Some(connections) = all_connections.get(domen) else {
+                return Err(QuoteError::NotFound);
+            };
+            for (i, Connection(socket, id, handle, owner)) in connections.iter().enumerate() {
+                let distributor = distributor_guard.get();
+                let (policy, stats) = (distributor.policy(*id), distributor.stats(*id));
+                let line = format!(
+                    "{}:{}:{}:{}:{} {}",
+                    i,
+                    socket,
+                    !handle.is_finished(),
+                    policy.unwrap_or_default(),
+                    distributor.feed(*id).unwrap_or_default(),
+                    stats.unwrap_or_default()
+                );
+                res.push(match is_admin {
+                    true => format!("{} {} {}", domen, owner.as_deref().unwrap_or("-"), line),
+                    false => line,
+                });
+            }
+        }
+        if res.is_empty() {
+            return Err(QuoteError::NotFound);
+        }
+        Ok(res.join("|"))
+    }
+
+    fn command_disconnect(&mut self, shell: &MasterStateShell) -> Result<String, QuoteError> {
+        let mut all_connections_guard = shell.connections();
+        let mut distributor_guard = shell.distributor();
+
+        let Some(connections) = all_connections_guard.get_mut().remove(&self.domen) else {
+            return Err(QuoteError::NotFound);
+        };
+        for Connection(_, id, _, _) in connections {
+            distributor_guard.get_mut().unsubscribe(id);
+        }
+
+        return Ok("Disconnected".to_string());
+    }
+
+    fn command_tickers(&self, shell: &MasterStateShell) -> Result<String, QuoteError> {
+        let distributor_guard = shell.distributor();
+        let distributor = distributor_guard.get();
+        let mut tickers = distributor.get_tickers();
+        if let Some(client) = &self.client {
+            let forbidden = client.forbidden_tickers(&tickers);
+            tickers.retain(|t| !forbidden.contains(t));
+        }
+        if tickers.is_empty() {
+            return Err(QuoteError::NotFound);
+        }
+        Ok(tickers.join("|"))
+    }
+
+    fn command_help() -> String {
+        "
+Комманды:
+challenge - получить вызов для входа по HMAC
+auth <name> <key> | auth <name> hmac <hex> - войти (если сервер требует вход)
+stream <ip>:<port> <ticker,ticker...> [conflate|drop_oldest|disconnect] [candle <1s|1m|5m>] - создать поток
+  conflate - только последняя котировка тикера, drop_oldest - выбросить самую старую,
+  disconnect - отключить поток при переполнении очереди
+  candle - вместо котировок закрытые свечи OHLCV с VWAP и скользящей средней
+stop <ip>:<port> - остановить поток
+resend <ip>:<port> <from> <to> - переотправить котировки потока (без ответа)
+snapshot <ip>:<port> - прислать в поток снимок котировок (без ответа)
+list - список подключений и их отставание (при входе - только администратор, видит всех)
+disconnect - отключиться (завершив все потоки)
+tickers - список тикеров
+help - список комманд
+shutdown [key] - выключить сервер (при входе - только администратор, без ключа)"
+            .to_string()
+    }
+
+    fn command_shutdown(
+        &mut self,
+        key: String,
+        shell: &MasterStateShell,
+    ) -> Result<String, QuoteError> {
+        // Права администратора уже проверены в authorize
+        if self.state.auth_required() {
+            let mut shutdown_guard = shell.shutdown_mut();
+            **shutdown_guard.get_mut() = true;
+            return Ok("Shutdown".to_string());
+        }
+
+        let secret_key_guard = shell.secret_key();
+        if key == **secret_key_guard.get() {
+            drop(secret_key_guard);
+
+            let mut shutdown_guard = shell.shutdown_mut();
+            let shutdown = shutdown_guard.get_mut();
+            **shutdown = true;
+            return Ok("Shutdown".to_string());
+        }
+        Err(QuoteError::KeyNotEqual)
+    }
+}
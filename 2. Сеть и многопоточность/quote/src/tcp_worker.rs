@@ -1,19 +1,19 @@
 use crate::{
     logging,
-    master::Connection,
+    session::{Runtime, Session},
     types::{
+        codec::{self, Frame, HELLO_LEN, PROTOCOL_VERSION, Protocol},
         command::TcpCommand,
         error::QuoteError,
         state::{MasterState, MasterStateShell},
-        stock::Ticker,
     },
-    udp_worker::UdpWorker,
 };
+use std::sync::Arc;
 use std::{
-    io::{BufRead, BufReader, Write},
+    io::{BufRead, BufReader, Read, Write},
     net::TcpStream,
+    time::Instant,
 };
-use std::{net::SocketAddr, sync::Arc};
 
 const DURATION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
 const DURATION_SLEEP: std::time::Duration = std::time::Duration::from_millis(100);
@@ -22,8 +22,7 @@ const COUNT_TRY_SEND: u8 = 10;
 /// Worker TCP соединения
 pub(crate) struct TcpWorker {
     stream: TcpStream,
-    state: Arc<MasterState>,
-    domen: SocketAddr,
+    session: Session,
 
     count: u8,
 }
@@ -45,8 +44,7 @@ impl TcpWorker {
 
         Ok(Self {
             stream,
-            state,
-            domen,
+            session: Session::new(state, domen, Runtime::Threads),
 
             count: 0,
         })
@@ -54,21 +52,62 @@ impl TcpWorker {
 
     /// Запускает tcp worker
     pub(crate) fn run(mut self) -> Result<Self, String> {
-        logging!(info, ("TcpWorker running: {}", self.domen));
+        logging!(info, ("TcpWorker running: {}", self.session.domen()));
 
-        let mut reader = BufReader::new(self.stream.try_clone().map_err(|e| e.to_string())?);
-        let mut shutdown = false;
-        let shell = MasterStateShell::new(self.state.clone());
+        let shell = MasterStateShell::new(self.session.state());
+        match self.negotiate()? {
+            Protocol::Text => self.run_text(&shell),
+            Protocol::Framed => self.run_framed(&shell),
+        }
+    }
+
+    /// Выбрать протокол: бинарный, если соединение начинается с приветствия
+    fn negotiate(&mut self) -> Result<Protocol, String> {
+        let deadline = Instant::now() + DURATION_TIMEOUT;
+        let mut hello = [0u8; HELLO_LEN];
         loop {
-            {
-                let shutdown_guard = shell.shutdown();
-                if **shutdown_guard.get() {
-                    shutdown = true;
-                }
-                if shutdown {
-                    let _ = self.stream.write_all("Finish\n".as_bytes());
-                    break Ok(self);
+            match self.stream.peek(&mut hello) {
+                Ok(0) => return Ok(Protocol::Text),
+                Ok(n) if !codec::is_hello_prefix(&hello[..n]) => return Ok(Protocol::Text),
+                Ok(HELLO_LEN) => break,
+                // Приветствие пришло не целиком
+                Ok(_) if Instant::now() < deadline => std::thread::sleep(DURATION_SLEEP),
+                Ok(_) => return Err("Неполное приветствие".to_string()),
+                // Старые клиенты могут молчать до первой команды
+                Err(e)
+                    if e.kind() == std::io::ErrorKind::WouldBlock
+                        || e.kind() == std::io::ErrorKind::TimedOut =>
+                {
+                    return Ok(Protocol::Text);
                 }
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+
+        self.stream
+            .read_exact(&mut hello)
+            .map_err(|e| e.to_string())?;
+        let version = codec::parse_hello(&hello)
+            .unwrap_or_default()
+            .min(PROTOCOL_VERSION);
+        self.stream
+            .write_all(&codec::hello(version))
+            .map_err(|e| e.to_string())?;
+        if version == 0 {
+            return Err("Неподдерживаемая версия протокола".to_string());
+        }
+
+        logging!(info, ("Protocol v{}: {}", version, self.session.domen()));
+        Ok(Protocol::Framed)
+    }
+
+    /// Текстовый протокол: команда и ответ - строки
+    fn run_text(mut self, shell: &MasterStateShell) -> Result<Self, String> {
+        let mut reader = BufReader::new(self.stream.try_clone().map_err(|e| e.to_string())?);
+        loop {
+            if Session::is_shutdown(shell) {
+                let _ = self.stream.write_all("Finish\n".as_bytes());
+                break Ok(self);
             }
 
             if self.count >= COUNT_TRY_SEND {
@@ -82,7 +121,7 @@ impl TcpWorker {
                 }
                 Ok(_) => {
                     self.count = 0;
-                    let _ = self.tcp_handle(&mut buf, &shell);
+                    let _ = self.handle_text(&buf, shell);
                 }
                 Err(e)
                     if e.kind() == std::io::ErrorKind::WouldBlock
@@ -103,24 +142,69 @@ impl TcpWorker {
         }
     }
 
-    /// Обработка команд
-    fn tcp_handle(
-        &mut self,
-        buffer: &mut String,
-        shell: &MasterStateShell,
-    ) -> Result<(), QuoteError> {
-        logging!(info, ("Command tcp: {:?}", buffer));
-        let res: Result<String, QuoteError> = match TcpCommand::parse(buffer) {
-            Ok(command) => match command {
-                TcpCommand::Stream((socket, tickers)) => {
-                    self.command_stream(socket, tickers, &shell)
+    /// Бинарный протокол: кадры с id запроса, см. [codec]
+    fn run_framed(mut self, shell: &MasterStateShell) -> Result<Self, String> {
+        let mut input = vec![];
+        let mut chunk = [0u8; 4096];
+        loop {
+            if Session::is_shutdown(shell) {
+                break Ok(self);
+            }
+
+            if self.count >= COUNT_TRY_SEND {
+                break Err("Client not response".to_string());
+            }
+
+            match self.stream.read(&mut chunk) {
+                Ok(0) => {
+                    break Ok(self);
+                }
+                Ok(n) => {
+                    self.count = 0;
+                    input.extend_from_slice(&chunk[..n]);
+                    loop {
+                        match Frame::decode(&mut input) {
+                            Ok(Some(Frame::Request { id, command })) => {
+                                let _ = self.handle_framed(id, command, shell);
+                            }
+                            Ok(Some(Frame::Response { id: _id, .. })) => {
+                                logging!(warn, ("Unexpected response frame: {}", _id));
+                            }
+                            Ok(None) => break,
+                            // Граница кадров потеряна, продолжать нельзя
+                            Err(e) => {
+                                let frame = Frame::Response {
+                                    id: 0,
+                                    result: Err(e),
+                                };
+                                let _ = self.stream.write_all(&frame.encode());
+                                return Err("Protocol error".to_string());
+                            }
+                        }
+                    }
                 }
-                TcpCommand::Stop(socket) => self.command_stop(socket, &shell),
-                TcpCommand::Disconnect => self.command_disconnect(&shell),
-                TcpCommand::List => self.command_list(&shell),
-                TcpCommand::Tickers => self.command_tickers(&shell),
-                TcpCommand::Help => Ok(Self::command_help()),
-                TcpCommand::Shutdown(key) => self.command_shutdown(key, &shell),
+                Err(e)
+                    if e.kind() == std::io::ErrorKind::WouldBlock
+                        || e.kind() == std::io::ErrorKind::TimedOut =>
+                {
+                    std::thread::sleep(DURATION_SLEEP);
+                    continue;
+                }
+                Err(_e) => {
+                    std::thread::sleep(DURATION_SLEEP);
+                    logging!(warn, ("Error read: {:?}", _e));
+                    self.count += 1;
+                }
+            };
+        }
+    }
+
+    /// Обработка текстовой команды
+    fn handle_text(&mut self, buffer: &str, shell: &MasterStateShell) -> Result<(), QuoteError> {
+        let res = match TcpCommand::parse(buffer) {
+            Ok(command) => match self.session.execute(command, shell) {
+                Some(res) => res,
+                None => return Ok(()),
             },
             Err(e) => Err(QuoteError::BadRequest(e.to_string())),
         };
@@ -139,138 +223,26 @@ impl TcpWorker {
         res.map(|_| ())
     }
 
-    // --- обработка команд с tcp ---
-
-    fn command_stream(
+    /// Обработка команды из кадра, ответ с тем же id
+    fn handle_framed(
         &mut self,
-        socket: SocketAddr,
-        tickers: Vec<Ticker>,
+        id: u32,
+        command: TcpCommand,
         shell: &MasterStateShell,
-    ) -> Result<String, QuoteError> {
-        let mut all_connections_guard = shell.connections();
-        let mut distributor_guard = shell.distributor();
-
-        let all_connections = all_connections_guard.get_mut();
-        let distributor = distributor_guard.get_mut();
-
-        if let Some(connections) = all_connections.get_mut(&self.domen) {
-            if let Some(indx) = connections.iter().position(|c| c.0 == socket) {
-                if connections[indx].2.is_finished() {
-                    connections.remove(indx);
-                    return Err(QuoteError::BadRequest("Поток уже запущен".to_string()));
-                }
-                return Err(QuoteError::AlreadyExists);
-            }
-        }
-        let last_stocks = distributor.get_last_stocks(&tickers);
-
-        let (id, subscriber) = distributor.subscribe(tickers);
-
-        // пока формат захардкожен для соблюдения ТЗ, но если надо будет, то можно будет передавать через команду)
-        let worker = match UdpWorker::new(subscriber, socket, None) {
-            Ok(worker) => worker,
-            Err(_e) => {
-                logging!(info, ("{}: Connection failed: {}", socket, _e.to_string()));
-                return Err(QuoteError::NotConnection);
-            }
-        };
-
-        let handle = std::thread::spawn(move || worker.run(last_stocks));
-        all_connections
-            .entry(socket)
-            .or_default()
-            .push(Connection(socket, id, handle));
-        Ok("Running".to_string())
-    }
-
-    fn command_stop(
-        &mut self,
-        socket: SocketAddr,
-        shell: &MasterStateShell,
-    ) -> Result<String, QuoteError> {
-        let mut all_connections_guard = shell.connections();
-        let mut distributor_guard = shell.distributor();
-
-        let all_connections = all_connections_guard.get_mut();
-        let distributor = distributor_guard.get_mut();
-
-        let Some(connections) = all_connections.get_mut(&self.domen) else {
-            return Err(QuoteError::NotFound);
-        };
-        let Some(indx) = connections.iter().position(|c| c.0 == socket) else {
-            return Err(QuoteError::NotFound);
+    ) -> Result<(), QuoteError> {
+        let Some(result) = self.session.execute(command, shell) else {
+            return Ok(());
         };
 
-        let Connection(_, id, _) = connections.remove(indx);
-        distributor.unsubscribe(id);
-        // Вопрос такой, правильно ли так делать?)
-        // по факту это может затормозить tcp-поток...
-        // handle.join();
-        Ok("Stopped".to_string())
-    }
-
-    fn command_list(&self, shell: &MasterStateShell) -> Result<String, QuoteError> {
-        let all_connections_guard = shell.connections();
-
-        let Some(connections) = all_connections_guard.get().get(&self.domen) else {
-            return Err(QuoteError::NotFound);
+        let frame = Frame::Response {
+            id,
+            result: result.clone(),
         };
-        let mut res = String::new();
-        for (i, Connection(socket, _, handle)) in connections.iter().enumerate() {
-            res.push_str(&format!("{}:{}:{}\n", i, socket, !handle.is_finished()));
-        }
-        Ok(res)
-    }
-
-    fn command_disconnect(&mut self, shell: &MasterStateShell) -> Result<String, QuoteError> {
-        let mut all_connections_guard = shell.connections();
-        let mut distributor_guard = shell.distributor();
-
-        let Some(connections) = all_connections_guard.get_mut().remove(&self.domen) else {
-            return Err(QuoteError::NotFound);
+        if let Err(_e) = self.stream.write_all(&frame.encode()) {
+            logging!(warn, ("Error write: {}", _e));
+            return Err(QuoteError::NotConnection);
         };
-        for Connection(_, id, _) in connections {
-            distributor_guard.get_mut().unsubscribe(id);
-        }
 
-        return Ok("Disconnected".to_string());
-    }
-
-    fn command_tickers(&self, shell: &MasterStateShell) -> Result<String, QuoteError> {
-        let distributor_guard = shell.distributor();
-        let distributor = distributor_guard.get();
-        if distributor.get_tickers().is_empty() {
-            return Err(QuoteError::NotFound);
-        }
-        Ok(distributor.get_tickers().join("|"))
-    }
-
-    fn command_help() -> String {
-        "
-Комманды:
-stream <ip>:<port> <ticker,ticker...> - создать поток
-stop <ip>:<port> - остановить поток
-list - список подключений
-disconnect - отключиться (завершив все потоки)
-tickers - список тикеров
-help - список комманд"
-            .to_string()
-    }
-
-    fn command_shutdown(
-        &mut self,
-        key: String,
-        shell: &MasterStateShell,
-    ) -> Result<String, QuoteError> {
-        let secret_key_guard = shell.secret_key();
-        if key == **secret_key_guard.get() {
-            drop(secret_key_guard);
-
-            let mut shutdown_guard = shell.shutdown_mut();
-            let shutdown = shutdown_guard.get_mut();
-            **shutdown = true;
-            return Ok("Shutdown".to_string());
-        }
-        Err(QuoteError::KeyNotEqual)
+        result.map(|_| ())
     }
 }
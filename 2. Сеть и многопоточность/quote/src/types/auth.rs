@@ -0,0 +1,175 @@
+use super::stock::Ticker;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// Длина случайного вызова для challenge-response, байт
+pub const CHALLENGE_LEN: usize = 32;
+
+/// ## Роль клиента
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Только свои потоки
+    #[default]
+    Client,
+    /// Плюс `LIST` по всем подключениям и `SHUTDOWN`
+    Admin,
+}
+
+/// ## Ключ клиента и его права
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClientKey {
+    pub name: String,
+    pub key: String,
+    #[serde(default)]
+    pub role: Role,
+    /// Разрешенные тикеры, `None` - все
+    #[serde(default)]
+    pub tickers: Option<Vec<Ticker>>,
+    /// Наибольшее число одновременных потоков по всем подключениям, `None` - без ограничений
+    #[serde(default)]
+    pub max_streams: Option<usize>,
+}
+
+impl ClientKey {
+    /// Тикеры, на которые подписка не разрешена
+    pub fn forbidden_tickers(&self, tickers: &[Ticker]) -> Vec<Ticker> {
+        let Some(allowed) = &self.tickers else {
+            return vec![];
+        };
+        tickers
+            .iter()
+            .filter(|t| !allowed.contains(t))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Способ подтвердить ключ
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Credential {
+    /// Ключ открытым текстом
+    Key(String),
+    /// HMAC-SHA256 от последнего вызова сервера, hex
+    Hmac(String),
+}
+
+/// Случайный вызов для challenge-response, hex
+pub fn gen_challenge() -> String {
+    let challenge: [u8; CHALLENGE_LEN] = rand::random();
+    hex::encode(challenge)
+}
+
+/// Ответ на вызов: HMAC-SHA256 от вызова на ключе клиента, hex
+pub fn sign_challenge(key: &str, challenge: &str) -> String {
+    hex::encode(mac(key, challenge).finalize().into_bytes())
+}
+
+/// Проверить ключ клиента, `challenge` - выданный ему вызов
+pub fn verify(client: &ClientKey, credential: &Credential, challenge: Option<&str>) -> bool {
+    match credential {
+        Credential::Key(key) => {
+            // Сравнение через HMAC не зависит по времени от совпавшего префикса
+            let expected = mac(&client.key, "");
+            mac(key, "")
+                .verify(&expected.finalize().into_bytes())
+                .is_ok()
+        }
+        Credential::Hmac(signature) => {
+            let (Some(challenge), Ok(signature)) = (challenge, hex::decode(signature)) else {
+                return false;
+            };
+            mac(&client.key, challenge).verify_slice(&signature).is_ok()
+        }
+    }
+}
+
+fn mac(key: &str, data: &str) -> Hmac<Sha256> {
+    // HMAC принимает ключ любой длины
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC key");
+    mac.update(data.as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client() -> ClientKey {
+        ClientKey {
+            name: "alice".to_string(),
+            key: "secret".to_string(),
+            role: Role::Client,
+            tickers: Some(vec!["A".to_string()]),
+            max_streams: Some(1),
+        }
+    }
+
+    #[test]
+    fn test_verify_key() {
+        let client = client();
+        assert!(verify(
+            &client,
+            &Credential::Key("secret".to_string()),
+            None
+        ));
+        assert!(!verify(
+            &client,
+            &Credential::Key("secret2".to_string()),
+            None
+        ));
+    }
+
+    #[test]
+    fn test_verify_hmac() {
+        let client = client();
+        let challenge = gen_challenge();
+        let signature = sign_challenge("secret", &challenge);
+
+        assert!(verify(
+            &client,
+            &Credential::Hmac(signature.clone()),
+            Some(&challenge)
+        ));
+        // Без вызова, с чужим вызовом или чужим ключом
+        assert!(!verify(&client, &Credential::Hmac(signature.clone()), None));
+        assert!(!verify(
+            &client,
+            &Credential::Hmac(signature),
+            Some(&gen_challenge())
+        ));
+        assert!(!verify(
+            &client,
+            &Credential::Hmac(sign_challenge("other", &challenge)),
+            Some(&challenge)
+        ));
+        assert!(!verify(
+            &client,
+            &Credential::Hmac("zz".to_string()),
+            Some(&challenge)
+        ));
+    }
+
+    #[test]
+    fn test_forbidden_tickers() {
+        let client = client();
+        assert_eq!(
+            client.forbidden_tickers(&["A".to_string(), "B".to_string()]),
+            ["B".to_string()]
+        );
+        let all = ClientKey {
+            tickers: None,
+            ..client
+        };
+        assert!(all.forbidden_tickers(&["B".to_string()]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let json = r#"[{"name": "root", "key": "k", "role": "admin"}]"#;
+        let keys: Vec<ClientKey> = serde_json::from_str(json).unwrap();
+        assert_eq!(keys[0].role, Role::Admin);
+        assert_eq!((keys[0].tickers.clone(), keys[0].max_streams), (None, None));
+    }
+}
@@ -0,0 +1,432 @@
+use super::stock::{StockQuote, Ticker};
+use bincode::{Decode, Encode};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    str::FromStr,
+};
+
+/// Сколько закрытых свечей хранится по тикеру и интервалу
+pub const HISTORY_SIZE: usize = 64;
+/// Сколько последних свечей тикера уходит в снимок потока
+pub const SNAPSHOT_CANDLES: usize = 10;
+/// Период скользящей средней, свечей
+pub const MA_PERIOD: usize = 20;
+
+/// ## Интервал свечи
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Encode, Decode, ValueEnum,
+)]
+pub enum Interval {
+    #[value(name = "1s")]
+    Sec1,
+    #[value(name = "1m")]
+    Min1,
+    #[value(name = "5m")]
+    Min5,
+}
+
+impl Interval {
+    pub const ALL: [Interval; 3] = [Interval::Sec1, Interval::Min1, Interval::Min5];
+
+    /// Длина интервала, секунды
+    pub fn secs(self) -> u64 {
+        match self {
+            Interval::Sec1 => 1,
+            Interval::Min1 => 60,
+            Interval::Min5 => 300,
+        }
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Interval::Sec1 => "1s",
+            Interval::Min1 => "1m",
+            Interval::Min5 => "5m",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Interval {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "1s" => Ok(Interval::Sec1),
+            "1m" => Ok(Interval::Min1),
+            "5m" => Ok(Interval::Min5),
+            _ => Err(()),
+        }
+    }
+}
+
+/// ## Что получает поток
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Feed {
+    /// Котировки как есть
+    #[default]
+    Ticks,
+    /// Закрытые свечи интервала
+    Candle(Interval),
+}
+
+impl Display for Feed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Feed::Ticks => write!(f, "TICKS"),
+            Feed::Candle(interval) => write!(f, "CANDLE {}", interval),
+        }
+    }
+}
+
+/// ## Свеча OHLCV
+#[derive(Serialize, Deserialize, Encode, Decode, PartialEq, Debug, Clone)]
+pub struct Candle {
+    pub ticker: Ticker,
+    pub interval: Interval,
+    /// Начало интервала, секунды
+    pub start: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: u64,
+    /// Средняя цена, взвешенная по объему
+    pub vwap: f64,
+    /// Простая скользящая средняя цен закрытия за [MA_PERIOD] свечей
+    pub sma: f64,
+}
+
+impl Display for Candle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} ({}): O {} H {} L {} C {} - {} ед., VWAP {:.2}, SMA{} {:.2}",
+            self.ticker,
+            self.interval,
+            self.start,
+            self.open,
+            self.high,
+            self.low,
+            self.close,
+            self.volume,
+            self.vwap,
+            MA_PERIOD,
+            self.sma
+        )
+    }
+}
+
+// Методы для текстового формата сообщений
+impl Candle {
+    pub fn to_text(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{}|{}|{}|{}|{}|{}",
+            self.ticker,
+            self.interval,
+            self.start,
+            self.open,
+            self.high,
+            self.low,
+            self.close,
+            self.volume,
+            self.vwap,
+            self.sma
+        )
+    }
+
+    pub fn from_text(s: &str) -> Option<Self> {
+        let parts: Vec<&str> = s.split('|').collect();
+        let [
+            ticker,
+            interval,
+            start,
+            open,
+            high,
+            low,
+            close,
+            volume,
+            vwap,
+            sma,
+        ] = parts[..]
+        else {
+            return None;
+        };
+        Some(Candle {
+            ticker: ticker.to_string(),
+            interval: interval.parse().ok()?,
+            start: start.parse().ok()?,
+            open: open.parse().ok()?,
+            high: high.parse().ok()?,
+            low: low.parse().ok()?,
+            close: close.parse().ok()?,
+            volume: volume.parse().ok()?,
+            vwap: vwap.parse().ok()?,
+            sma: sma.parse().ok()?,
+        })
+    }
+}
+
+/// Незакрытая свеча и суммы для VWAP
+struct OpenCandle {
+    candle: Candle,
+    /// Сумма цена * объем
+    turnover: f64,
+    /// Для VWAP, если объема не было
+    price_sum: f64,
+    ticks: u64,
+}
+
+impl OpenCandle {
+    fn new(stock: &StockQuote, interval: Interval, start: u64) -> Self {
+        let mut open = Self {
+            candle: Candle {
+                ticker: stock.ticker.clone(),
+                interval,
+                start,
+                open: stock.price,
+                high: stock.price,
+                low: stock.price,
+                close: stock.price,
+                volume: 0,
+                vwap: stock.price,
+                sma: stock.price,
+            },
+            turnover: 0.0,
+            price_sum: 0.0,
+            ticks: 0,
+        };
+        open.add(stock);
+        open
+    }
+
+    fn add(&mut self, stock: &StockQuote) {
+        let candle = &mut self.candle;
+        candle.high = candle.high.max(stock.price);
+        candle.low = candle.low.min(stock.price);
+        candle.close = stock.price;
+        candle.volume += stock.volume as u64;
+        self.turnover += stock.price * stock.volume as f64;
+        self.price_sum += stock.price;
+        self.ticks += 1;
+    }
+
+    fn vwap(&self) -> f64 {
+        match self.candle.volume {
+            0 => self.price_sum / self.ticks as f64,
+            volume => self.turnover / volume as f64,
+        }
+    }
+}
+
+/// Свечи одного тикера и интервала
+#[derive(Default)]
+struct Series {
+    open: Option<OpenCandle>,
+    /// Закрытые свечи, от старых к новым
+    closed: VecDeque<Candle>,
+}
+
+impl Series {
+    /// Учесть котировку, вернуть свечу, если котировка ее закрыла
+    fn update(&mut self, stock: &StockQuote, interval: Interval) -> Option<Candle> {
+        let start = stock.timestamp - stock.timestamp % interval.secs();
+        if let Some(open) = &mut self.open
+            && open.candle.start == start
+        {
+            open.add(stock);
+            return None;
+        }
+        // Котировка другого интервала (или запись пошла заново)
+        let closed = self.close();
+        self.open = Some(OpenCandle::new(stock, interval, start));
+        closed
+    }
+
+    fn close(&mut self) -> Option<Candle> {
+        let open = self.open.take()?;
+        let vwap = open.vwap();
+        let mut candle = open.candle;
+        candle.vwap = vwap;
+
+        let closes: Vec<f64> = self
+            .closed
+            .iter()
+            .rev()
+            .take(MA_PERIOD - 1)
+            .map(|c| c.close)
+            .chain([candle.close])
+            .collect();
+        candle.sma = closes.iter().sum::<f64>() / closes.len() as f64;
+
+        if self.closed.len() >= HISTORY_SIZE {
+            self.closed.pop_front();
+        }
+        self.closed.push_back(candle.clone());
+        Some(candle)
+    }
+}
+
+/// ## Свечи по котировкам
+///
+/// Свеча закрывается первой котировкой следующего интервала. Время берется из котировок,
+/// а не из часов сервера, поэтому запись воспроизводится с теми же свечами.
+#[derive(Default)]
+pub(crate) struct CandleAggregator {
+    series: HashMap<(Ticker, Interval), Series>,
+}
+
+impl CandleAggregator {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Учесть котировку во всех интервалах, вернуть закрытые ею свечи
+    pub(crate) fn push(&mut self, stock: &StockQuote) -> Vec<Candle> {
+        Interval::ALL
+            .into_iter()
+            .filter_map(|interval| {
+                self.series
+                    .entry((stock.ticker.clone(), interval))
+                    .or_default()
+                    .update(stock, interval)
+            })
+            .collect()
+    }
+
+    /// Последние `n` закрытых свечей тикера, от старых к новым
+    pub(crate) fn last(&self, ticker: &Ticker, interval: Interval, n: usize) -> Vec<Candle> {
+        let Some(series) = self.series.get(&(ticker.clone(), interval)) else {
+            return vec![];
+        };
+        let skip = series.closed.len().saturating_sub(n);
+        series.closed.iter().skip(skip).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+impl Candle {
+    pub fn one() -> Self {
+        Self {
+            ticker: "BCD".to_string(),
+            interval: Interval::Min1,
+            start: 123120,
+            open: 100.0,
+            high: 101.5,
+            low: 99.25,
+            close: 100.5,
+            volume: 42,
+            vwap: 100.125,
+            sma: 100.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stock(price: f64, volume: u32, timestamp: u64) -> StockQuote {
+        StockQuote {
+            ticker: "A".to_string(),
+            price,
+            volume,
+            timestamp,
+        }
+    }
+
+    fn minute_candles(aggregator: &mut CandleAggregator, stocks: &[StockQuote]) -> Vec<Candle> {
+        stocks
+            .iter()
+            .flat_map(|s| aggregator.push(s))
+            .filter(|c| c.interval == Interval::Min1)
+            .collect()
+    }
+
+    #[test]
+    fn test_ohlcv_vwap() {
+        let mut aggregator = CandleAggregator::new();
+        let candles = minute_candles(
+            &mut aggregator,
+            &[
+                stock(10.0, 1, 60),
+                stock(12.0, 3, 70),
+                stock(9.0, 0, 80),
+                stock(11.0, 1, 119),
+                // Закрывает первую минуту
+                stock(20.0, 1, 120),
+            ],
+        );
+
+        assert_eq!(candles.len(), 1);
+        let candle = &candles[0];
+        assert_eq!(candle.start, 60);
+        assert_eq!(
+            (candle.open, candle.high, candle.low, candle.close),
+            (10.0, 12.0, 9.0, 11.0)
+        );
+        assert_eq!(candle.volume, 5);
+        assert_eq!(candle.vwap, (10.0 + 36.0 + 11.0) / 5.0);
+    }
+
+    #[test]
+    fn test_vwap_without_volume() {
+        let mut aggregator = CandleAggregator::new();
+        let candles = minute_candles(
+            &mut aggregator,
+            &[stock(10.0, 0, 0), stock(20.0, 0, 1), stock(1.0, 0, 60)],
+        );
+        assert_eq!(candles[0].vwap, 15.0);
+    }
+
+    #[test]
+    fn test_sma_and_history() {
+        let mut aggregator = CandleAggregator::new();
+        // Одна котировка на минуту, цена - номер минуты
+        let stocks: Vec<StockQuote> = (0..=HISTORY_SIZE as u64 + 1)
+            .map(|m| stock(m as f64, 1, m * 60))
+            .collect();
+        let candles = minute_candles(&mut aggregator, &stocks);
+
+        assert_eq!(candles[0].sma, 0.0);
+        assert_eq!(candles[3].sma, 1.5);
+        // Среднее последних MA_PERIOD закрытий: 31..=50
+        assert_eq!(candles[50].sma, 40.5);
+
+        let last = aggregator.last(&"A".to_string(), Interval::Min1, 3);
+        let starts: Vec<u64> = last.iter().map(|c| c.start / 60).collect();
+        assert_eq!(starts, [62, 63, 64]);
+        assert!(
+            aggregator
+                .last(&"B".to_string(), Interval::Min1, 3)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_intervals() {
+        let mut aggregator = CandleAggregator::new();
+        let closed = aggregator.push(&stock(1.0, 1, 299));
+        assert!(closed.is_empty());
+        // Новая секунда, минута и пять минут
+        let closed = aggregator.push(&stock(2.0, 1, 300));
+        let intervals: Vec<Interval> = closed.iter().map(|c| c.interval).collect();
+        assert_eq!(intervals, Interval::ALL);
+        assert_eq!(closed[2].start, 0);
+    }
+
+    #[test]
+    fn test_text_roundtrip() {
+        let candle = Candle::one();
+        assert_eq!(Candle::from_text(&candle.to_text()), Some(candle));
+        assert_eq!(Candle::from_text("A|1m|0"), None);
+        for interval in Interval::ALL {
+            assert_eq!(interval.to_string().parse(), Ok(interval));
+        }
+    }
+}
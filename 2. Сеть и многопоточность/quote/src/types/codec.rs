@@ -0,0 +1,294 @@
+//! Бинарный протокол управляющего TCP соединения.
+//!
+//! Клиент начинает соединение с приветствия `QBIN <версия>` (5 байт), сервер отвечает
+//! так же выбранной версией. Если приветствия нет, соединение работает в текстовом режиме.
+//!
+//! Кадр версии 1: `[длина u32][тип u8][id запроса u32][данные]`, числа big endian,
+//! длина считается без собственных 4 байт.
+//! - запрос: команда [TcpCommand] в bincode
+//! - ответ: текст ответа в utf8
+//! - ошибка: код [QuoteError::code] u16 и текст ошибки в utf8
+
+use super::{command::TcpCommand, error::QuoteError};
+
+/// Начало приветствия бинарного протокола
+pub const MAGIC: &[u8; 4] = b"QBIN";
+/// Длина приветствия: [MAGIC] и версия
+pub const HELLO_LEN: usize = 5;
+/// Последняя поддерживаемая версия протокола
+pub const PROTOCOL_VERSION: u8 = 1;
+/// Наибольшая длина кадра, длиннее - ошибка протокола
+pub const MAX_FRAME: usize = 64 * 1024;
+
+const KIND_REQUEST: u8 = 1;
+const KIND_OK: u8 = 2;
+const KIND_ERROR: u8 = 3;
+/// Тип и id запроса
+const HEADER_LEN: usize = 5;
+
+/// ## Протокол управляющего соединения
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Protocol {
+    /// Команды и ответы строками (по умолчанию для старых клиентов)
+    Text,
+    /// Кадры с длиной, id запросов и кодами ошибок
+    #[default]
+    Framed,
+}
+
+/// ## Кадр бинарного протокола
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frame {
+    /// Команда клиента
+    Request { id: u32, command: TcpCommand },
+    /// Ответ сервера на запрос с тем же id
+    Response {
+        id: u32,
+        result: Result<String, QuoteError>,
+    },
+}
+
+/// Приветствие с версией протокола
+pub fn hello(version: u8) -> [u8; HELLO_LEN] {
+    let mut hello = [0u8; HELLO_LEN];
+    hello[..4].copy_from_slice(MAGIC);
+    hello[4] = version;
+    hello
+}
+
+/// Версия из приветствия, `None` - это не приветствие
+pub fn parse_hello(data: &[u8]) -> Option<u8> {
+    match data {
+        [m0, m1, m2, m3, version] if [*m0, *m1, *m2, *m3] == *MAGIC => Some(*version),
+        _ => None,
+    }
+}
+
+/// Может ли начало соединения оказаться приветствием
+pub fn is_hello_prefix(data: &[u8]) -> bool {
+    let n = data.len().min(MAGIC.len());
+    data[..n] == MAGIC[..n]
+}
+
+/// Закодировать команду для отправки в выбранном протоколе
+pub fn encode_command(protocol: Protocol, id: u32, command: &TcpCommand) -> Vec<u8> {
+    match protocol {
+        Protocol::Text => command.to_string().into_bytes(),
+        Protocol::Framed => Frame::Request {
+            id,
+            command: command.clone(),
+        }
+        .encode(),
+    }
+}
+
+fn bincode_config() -> impl bincode::config::Config {
+    bincode::config::standard().with_limit::<MAX_FRAME>()
+}
+
+impl Frame {
+    pub fn encode(&self) -> Vec<u8> {
+        let (kind, id, payload) = match self {
+            Frame::Request { id, command } => (
+                KIND_REQUEST,
+                *id,
+                // Команда всегда кодируется: в ней нет несериализуемых данных
+                bincode::serde::encode_to_vec(command, bincode_config()).unwrap_or_default(),
+            ),
+            Frame::Response { id, result: Ok(s) } => (KIND_OK, *id, s.as_bytes().to_vec()),
+            Frame::Response { id, result: Err(e) } => {
+                let mut payload = e.code().to_be_bytes().to_vec();
+                payload.extend_from_slice(e.message().as_bytes());
+                (KIND_ERROR, *id, payload)
+            }
+        };
+
+        let mut frame = ((HEADER_LEN + payload.len()) as u32).to_be_bytes().to_vec();
+        frame.push(kind);
+        frame.extend_from_slice(&id.to_be_bytes());
+        frame.extend_from_slice(&payload);
+        frame
+    }
+
+    /// Достать из начала буфера один кадр.
+    ///
+    /// `Ok(None)` - кадр еще не пришел целиком. После ошибки граница кадров потеряна,
+    /// и соединение нужно закрывать.
+    pub fn decode(buf: &mut Vec<u8>) -> Result<Option<Frame>, QuoteError> {
+        let Some(len) = buf.get(..4) else {
+            return Ok(None);
+        };
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        if !(HEADER_LEN..=MAX_FRAME).contains(&len) {
+            return Err(QuoteError::BadRequest(format!(
+                "Неправильная длина кадра: {}",
+                len
+            )));
+        }
+        if buf.len() < 4 + len {
+            return Ok(None);
+        }
+
+        let body: Vec<u8> = buf.drain(..4 + len).skip(4).collect();
+        let (kind, id, payload) = (
+            body[0],
+            u32::from_be_bytes([body[1], body[2], body[3], body[4]]),
+            &body[HEADER_LEN..],
+        );
+        let frame = match kind {
+            KIND_REQUEST => {
+                let (command, read) = bincode::serde::decode_from_slice(payload, bincode_config())
+                    .map_err(|_| QuoteError::BadRequest("Неправильная команда".to_string()))?;
+                if read != payload.len() {
+                    return Err(QuoteError::BadRequest("Лишние данные в кадре".to_string()));
+                }
+                Frame::Request { id, command }
+            }
+            KIND_OK => Frame::Response {
+                id,
+                result: Ok(utf8(payload)?),
+            },
+            KIND_ERROR => {
+                let [c0, c1, message @ ..] = payload else {
+                    return Err(QuoteError::BadRequest("Нет кода ошибки".to_string()));
+                };
+                let code = u16::from_be_bytes([*c0, *c1]);
+                let error = QuoteError::from_code(code, utf8(message)?).ok_or_else(|| {
+                    QuoteError::BadRequest(format!("Неизвестный код ошибки: {}", code))
+                })?;
+                Frame::Response {
+                    id,
+                    result: Err(error),
+                }
+            }
+            _ => {
+                return Err(QuoteError::BadRequest(format!(
+                    "Неизвестный тип кадра: {}",
+                    kind
+                )));
+            }
+        };
+        Ok(Some(frame))
+    }
+}
+
+fn utf8(data: &[u8]) -> Result<String, QuoteError> {
+    String::from_utf8(data.to_vec()).map_err(|_| QuoteError::BadRequest("Не utf8".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        candle::{Feed, Interval},
+        delivery::DeliveryPolicy,
+    };
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    fn frames() -> Vec<Frame> {
+        vec![
+            Frame::Request {
+                id: 1,
+                command: TcpCommand::Stream((
+                    "127.0.0.1:7879".parse().unwrap(),
+                    vec!["A".to_string(), "B".to_string()],
+                    DeliveryPolicy::DropOldest,
+                    Feed::Candle(Interval::Min5),
+                )),
+            },
+            Frame::Request {
+                id: 2,
+                command: TcpCommand::Shutdown("key with spaces".to_string()),
+            },
+            Frame::Response {
+                id: 1,
+                result: Ok("Running".to_string()),
+            },
+            Frame::Response {
+                id: u32::MAX,
+                result: Err(QuoteError::BadRequest("нет".to_string())),
+            },
+            Frame::Response {
+                id: 3,
+                result: Err(QuoteError::NotFound),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_roundtrip_stream() {
+        let mut buf: Vec<u8> = frames().iter().flat_map(Frame::encode).collect();
+        // Кадры приходят по одному байту
+        let mut input = vec![];
+        let mut decoded = vec![];
+        for byte in buf.drain(..) {
+            input.push(byte);
+            while let Some(frame) = Frame::decode(&mut input).unwrap() {
+                decoded.push(frame);
+            }
+        }
+        assert_eq!(decoded, frames());
+        assert!(input.is_empty());
+    }
+
+    #[test]
+    fn test_hello() {
+        assert_eq!(
+            parse_hello(&hello(PROTOCOL_VERSION)),
+            Some(PROTOCOL_VERSION)
+        );
+        assert_eq!(parse_hello(b"STREA"), None);
+        assert!(is_hello_prefix(b"QB"));
+        assert!(!is_hello_prefix(b"STOP 127.0.0.1:1"));
+    }
+
+    #[test]
+    fn test_bad_length() {
+        let mut buf = ((MAX_FRAME + 1) as u32).to_be_bytes().to_vec();
+        assert!(Frame::decode(&mut buf).is_err());
+        let mut buf = 0u32.to_be_bytes().to_vec();
+        assert!(Frame::decode(&mut buf).is_err());
+    }
+
+    /// Произвольные байты не должны ронять разбор
+    #[test]
+    fn test_fuzz_arbitrary_bytes() {
+        let mut rng = StdRng::seed_from_u64(14);
+        for _ in 0..5_000 {
+            let len = rng.random_range(0..64);
+            let data: Vec<u8> = (0..len).map(|_| rng.random()).collect();
+
+            let mut buf = data.clone();
+            while let Ok(Some(_)) = Frame::decode(&mut buf) {}
+
+            let text = String::from_utf8_lossy(&data);
+            let _ = TcpCommand::parse(&text);
+            let _ = QuoteError::from_string(&text);
+            let _ = parse_hello(&data);
+        }
+    }
+
+    /// Испорченные валидные кадры: ошибка или кадр, но не паника
+    #[test]
+    fn test_fuzz_mutated_frames() {
+        let mut rng = StdRng::seed_from_u64(41);
+        let encoded: Vec<Vec<u8>> = frames().iter().map(Frame::encode).collect();
+        for _ in 0..5_000 {
+            let mut data = encoded[rng.random_range(0..encoded.len())].clone();
+            // Длину не трогаем, чтобы дойти до разбора содержимого
+            let i = rng.random_range(4..data.len());
+            data[i] = rng.random();
+            if rng.random_bool(0.3) {
+                data.truncate(rng.random_range(0..data.len()));
+            }
+            let _ = Frame::decode(&mut data);
+        }
+    }
+
+    #[test]
+    fn test_text_commands_short_input() {
+        for s in ["", "L", "LI", "ЛИ", "ЛИСТ", "\n"] {
+            assert!(TcpCommand::parse(s).is_err());
+        }
+    }
+}
@@ -1,17 +1,39 @@
-use super::stock::Ticker;
+use super::{
+    auth::Credential,
+    candle::{Feed, Interval},
+    delivery::DeliveryPolicy,
+    stock::Ticker,
+};
+use serde::{Deserialize, Serialize};
 use std::{net::SocketAddr, str::FromStr};
 
 /// Команды для общения с tcp-мастером
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum TcpCommand {
-    /// STREAM <ip>:<port> <ticker,ticker...>
-    /// создать поток
-    Stream((SocketAddr, Vec<Ticker>)),
+    /// CHALLENGE
+    /// получить случайный вызов для входа по HMAC
+    Challenge,
+
+    /// AUTH <name> <key> | AUTH <name> HMAC <hex>
+    /// войти по ключу или по HMAC-SHA256 от вызова
+    Auth((String, Credential)),
+
+    /// STREAM <ip>:<port> <ticker,ticker...> [CONFLATE|DROP_OLDEST|DISCONNECT] [CANDLE <1s|1m|5m>]
+    /// создать поток котировок или свечей с политикой доставки (по умолчанию CONFLATE)
+    Stream((SocketAddr, Vec<Ticker>, DeliveryPolicy, Feed)),
 
     /// STOP <ip>:<port>
     /// остановить поток
     Stop(SocketAddr),
 
+    /// RESEND <ip>:<port> <from> <to>
+    /// переотправить котировки потока с номерами от from до to, без ответа
+    Resend((SocketAddr, u64, u64)),
+
+    /// SNAPSHOT <ip>:<port>
+    /// прислать в поток снимок последних котировок, без ответа
+    Snapshot(SocketAddr),
+
     /// LIST
     /// список подключений
     List,
@@ -28,20 +50,41 @@ pub enum TcpCommand {
     /// выводит список команд
     Help,
 
-    /// SHUTDOWN
-    /// выключить сервер
+    /// SHUTDOWN [key]
+    /// выключить сервер (ключ не нужен администратору)
     Shutdown(String),
 }
 
 impl TcpCommand {
     pub fn parse(s: &str) -> Result<Self, &str> {
         let s = s.trim();
-        match s[0..4].to_uppercase().as_str() {
+        // Первые 4 символа, а не байта: строка может быть не ASCII
+        let head: String = s.chars().take(4).collect();
+        match head.to_uppercase().as_str() {
+            "CHAL" => Ok(TcpCommand::Challenge),
+            "AUTH" => {
+                let parts: Vec<&str> = s.split(' ').collect();
+                match parts[..] {
+                    [_, name, key] => Ok(TcpCommand::Auth((
+                        name.to_string(),
+                        Credential::Key(key.to_string()),
+                    ))),
+                    [_, name, mode, signature] if mode.eq_ignore_ascii_case("HMAC") => {
+                        Ok(TcpCommand::Auth((
+                            name.to_string(),
+                            Credential::Hmac(signature.to_string()),
+                        )))
+                    }
+                    _ => Err(
+                        "Неправильная команда входа\nAUTH <name> <key> | AUTH <name> HMAC <hex>",
+                    ),
+                }
+            }
             "STRE" => {
                 let parts: Vec<&str> = s.split(' ').collect();
-                if parts.len() != 3 {
+                if !(3..=6).contains(&parts.len()) {
                     return Err(
-                        "Неправильная команда стрима\nSTREAM <ip>:<port> <ticker,ticker...>",
+                        "Неправильная команда стрима\nSTREAM <ip>:<port> <ticker,ticker...> [policy] [CANDLE <interval>]",
                     );
                 }
                 let tickers: Vec<Ticker> = parts[2]
@@ -52,11 +95,33 @@ impl TcpCommand {
                     .collect();
                 let Ok(addr) = SocketAddr::from_str(parts[1]) else {
                     return Err(
-                        "Неправильная команда стрима\nSTREAM <ip>:<port> <ticker,ticker...>",
+                        "Неправильная команда стрима\nSTREAM <ip>:<port> <ticker,ticker...> [policy] [CANDLE <interval>]",
                     );
                 };
+                // Политика и свечи необязательны, свечи - всегда в конце
+                let mut options = &parts[3..];
+                let feed = match options {
+                    [rest @ .., candle, interval] if candle.eq_ignore_ascii_case("CANDLE") => {
+                        options = rest;
+                        let interval = Interval::from_str(interval)
+                            .map_err(|_| "Неправильный интервал свечей\n1s, 1m или 5m")?;
+                        Feed::Candle(interval)
+                    }
+                    _ => Feed::Ticks,
+                };
+                let policy = match options {
+                    [] => DeliveryPolicy::default(),
+                    [policy] => DeliveryPolicy::from_str(policy).map_err(
+                        |_| "Неправильная политика доставки\nCONFLATE, DROP_OLDEST или DISCONNECT",
+                    )?,
+                    _ => {
+                        return Err(
+                            "Неправильная команда стрима\nSTREAM <ip>:<port> <ticker,ticker...> [policy] [CANDLE <interval>]",
+                        );
+                    }
+                };
 
-                Ok(TcpCommand::Stream((addr, tickers)))
+                Ok(TcpCommand::Stream((addr, tickers, policy, feed)))
             }
             "STOP" => {
                 let parts: Vec<&str> = s.split(' ').collect();
@@ -68,15 +133,38 @@ impl TcpCommand {
                 };
                 Ok(TcpCommand::Stop(addr))
             }
-            "LIST" => Ok(TcpCommand::Disconnect),
+            "RESE" => {
+                let parts: Vec<&str> = s.split(' ').collect();
+                let err = "Неправильная команда переотправки\nRESEND <ip>:<port> <from> <to>";
+                if parts.len() != 4 {
+                    return Err(err);
+                }
+                let (Ok(addr), Ok(from), Ok(to)) = (
+                    SocketAddr::from_str(parts[1]),
+                    parts[2].parse(),
+                    parts[3].parse(),
+                ) else {
+                    return Err(err);
+                };
+                Ok(TcpCommand::Resend((addr, from, to)))
+            }
+            "SNAP" => {
+                let parts: Vec<&str> = s.split(' ').collect();
+                if parts.len() != 2 {
+                    return Err("Неправильная команда снимка\nSNAPSHOT <ip>:<port>");
+                }
+                let Ok(addr) = SocketAddr::from_str(parts[1]) else {
+                    return Err("Неправильная команда снимка\nSNAPSHOT <ip>:<port>");
+                };
+                Ok(TcpCommand::Snapshot(addr))
+            }
+            "LIST" => Ok(TcpCommand::List),
             "DISC" => Ok(TcpCommand::Disconnect),
             "TICK" => Ok(TcpCommand::Tickers),
             "HELP" => Ok(TcpCommand::Help),
             "SHUT" => {
-                let Some((_, r)) = s.split_once(' ') else {
-                    return Err("Неправильная команда SHUTDOWN\nSHUTDOWN <key>");
-                };
-                Ok(TcpCommand::Shutdown(r.to_string()))
+                let key = s.split_once(' ').map(|(_, r)| r).unwrap_or_default();
+                Ok(TcpCommand::Shutdown(key.to_string()))
             }
             _ => Err("Неизсветная команда. Отправьте HELP"),
         }
@@ -84,14 +172,29 @@ impl TcpCommand {
 
     pub fn to_string(&self) -> String {
         match self {
-            TcpCommand::Stream((addr, tickers)) => {
-                format!("STREAM {} {}\n", addr, tickers.join(","))
+            TcpCommand::Challenge => "CHALLENGE\n".to_string(),
+            TcpCommand::Auth((name, Credential::Key(key))) => format!("AUTH {} {}\n", name, key),
+            TcpCommand::Auth((name, Credential::Hmac(signature))) => {
+                format!("AUTH {} HMAC {}\n", name, signature)
+            }
+            TcpCommand::Stream((addr, tickers, policy, feed)) => {
+                let mut command = format!("STREAM {} {}", addr, tickers.join(","));
+                if *policy != DeliveryPolicy::default() {
+                    command.push_str(&format!(" {}", policy));
+                }
+                if let Feed::Candle(_) = feed {
+                    command.push_str(&format!(" {}", feed));
+                }
+                command + "\n"
             }
             TcpCommand::Stop(addr) => format!("STOP {}\n", addr),
+            TcpCommand::Resend((addr, from, to)) => format!("RESEND {} {} {}\n", addr, from, to),
+            TcpCommand::Snapshot(addr) => format!("SNAPSHOT {}\n", addr),
             TcpCommand::Disconnect => "DISCONNECT\n".to_string(),
             TcpCommand::List => "LIST\n".to_string(),
             TcpCommand::Tickers => "TICKERS\n".to_string(),
             TcpCommand::Help => "HELP\n".to_string(),
+            TcpCommand::Shutdown(key) if key.is_empty() => "SHUTDOWN\n".to_string(),
             TcpCommand::Shutdown(key) => format!("SHUTDOWN {}\n", key),
         }
     }
@@ -109,6 +212,8 @@ mod test {
         let parsed_command = TcpCommand::Stream((
             "127.0.0.1:7879".parse().unwrap(),
             vec!["T1".to_string(), "T9".to_string()],
+            DeliveryPolicy::Conflate,
+            Feed::Ticks,
         ));
         assert_eq!(parsed_command, command_parsed.unwrap());
     }
@@ -122,6 +227,72 @@ mod test {
         assert_eq!(str_command, command_str);
     }
 
+    #[test]
+    fn test_command_stream_policy() {
+        let str_command = "STREAM 127.0.0.1:8080 BTC DROP_OLDEST\n";
+        let command_parsed = TcpCommand::parse(str_command).unwrap();
+        assert!(matches!(
+            command_parsed,
+            TcpCommand::Stream((_, _, DeliveryPolicy::DropOldest, Feed::Ticks))
+        ));
+        assert_eq!(str_command, command_parsed.to_string());
+        assert!(TcpCommand::parse("STREAM 127.0.0.1:8080 BTC FAST").is_err());
+    }
+
+    #[test]
+    fn test_command_stream_candle() {
+        for str_command in [
+            "STREAM 127.0.0.1:8080 BTC CANDLE 1m\n",
+            "STREAM 127.0.0.1:8080 BTC,ETH DROP_OLDEST CANDLE 5m\n",
+        ] {
+            let command = TcpCommand::parse(str_command).unwrap();
+            assert_eq!(str_command, command.to_string());
+        }
+        assert_eq!(
+            TcpCommand::parse("stream 127.0.0.1:8080 BTC disconnect candle 1S"),
+            Ok(TcpCommand::Stream((
+                "127.0.0.1:8080".parse().unwrap(),
+                vec!["BTC".to_string()],
+                DeliveryPolicy::Disconnect,
+                Feed::Candle(Interval::Sec1),
+            )))
+        );
+        assert!(TcpCommand::parse("STREAM 127.0.0.1:8080 BTC CANDLE").is_err());
+        assert!(TcpCommand::parse("STREAM 127.0.0.1:8080 BTC CANDLE 5s").is_err());
+        assert!(TcpCommand::parse("STREAM 127.0.0.1:8080 BTC CANDLE 1m CONFLATE").is_err());
+    }
+
+    #[test]
+    fn test_command_resend() {
+        for command in [
+            TcpCommand::Resend(("127.0.0.1:8080".parse().unwrap(), 3, 10)),
+            TcpCommand::Snapshot("127.0.0.1:8080".parse().unwrap()),
+        ] {
+            assert_eq!(TcpCommand::parse(&command.to_string()), Ok(command));
+        }
+        assert!(TcpCommand::parse("RESEND 127.0.0.1:8080 3").is_err());
+        assert!(TcpCommand::parse("RESEND 127.0.0.1:8080 a 10").is_err());
+    }
+
+    #[test]
+    fn test_command_auth() {
+        for command in [
+            TcpCommand::Challenge,
+            TcpCommand::Auth(("alice".to_string(), Credential::Key("k".to_string()))),
+            TcpCommand::Auth(("alice".to_string(), Credential::Hmac("ab01".to_string()))),
+            TcpCommand::Shutdown(String::new()),
+        ] {
+            assert_eq!(TcpCommand::parse(&command.to_string()), Ok(command));
+        }
+        assert!(TcpCommand::parse("AUTH alice").is_err());
+        assert!(TcpCommand::parse("AUTH alice SHA ab01").is_err());
+    }
+
+    #[test]
+    fn test_command_list() {
+        assert_eq!(TcpCommand::parse("LIST\n"), Ok(TcpCommand::List));
+    }
+
     #[test]
     fn test_command_stop() {
         let str_command = "STOP 127.0.0.1:8080\n";
@@ -0,0 +1,95 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
+
+/// Размер очереди подписчика для [DeliveryPolicy::DropOldest] и [DeliveryPolicy::Disconnect]
+pub const QUEUE_SIZE: usize = 1024;
+
+/// ## Что делать, когда подписчик не успевает
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+pub enum DeliveryPolicy {
+    /// Хранить только последнюю котировку по каждому тикеру
+    #[default]
+    Conflate,
+    /// При переполнении очереди выбрасывать самую старую котировку
+    DropOldest,
+    /// При переполнении очереди отключать подписчика
+    Disconnect,
+}
+
+impl Display for DeliveryPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            DeliveryPolicy::Conflate => "CONFLATE",
+            DeliveryPolicy::DropOldest => "DROP_OLDEST",
+            DeliveryPolicy::Disconnect => "DISCONNECT",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for DeliveryPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "CONFLATE" => Ok(DeliveryPolicy::Conflate),
+            "DROP_OLDEST" => Ok(DeliveryPolicy::DropOldest),
+            "DISCONNECT" => Ok(DeliveryPolicy::Disconnect),
+            _ => Err(()),
+        }
+    }
+}
+
+/// ## Метрики отставания подписчика
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SubscriberStats {
+    /// Котировок в очереди сейчас
+    pub queued: usize,
+    /// Наибольшая длина очереди
+    pub max_queued: usize,
+    /// Возраст самой старой котировки в очереди, мс
+    pub lag_ms: u64,
+    /// Отправлено подписчику
+    pub delivered: u64,
+    /// Выброшено из переполненной очереди
+    pub dropped: u64,
+    /// Заменено более свежей котировкой того же тикера
+    pub conflated: u64,
+    /// Подписчик отключен из-за переполнения
+    pub overflowed: bool,
+}
+
+impl Display for SubscriberStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "queued={} max_queued={} lag_ms={} delivered={} dropped={} conflated={} overflowed={}",
+            self.queued,
+            self.max_queued,
+            self.lag_ms,
+            self.delivered,
+            self.dropped,
+            self.conflated,
+            self.overflowed
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_parse() {
+        for policy in [
+            DeliveryPolicy::Conflate,
+            DeliveryPolicy::DropOldest,
+            DeliveryPolicy::Disconnect,
+        ] {
+            assert_eq!(policy.to_string().parse(), Ok(policy));
+        }
+        assert_eq!("drop_oldest".parse(), Ok(DeliveryPolicy::DropOldest));
+        assert!("other".parse::<DeliveryPolicy>().is_err());
+    }
+}
@@ -15,6 +15,10 @@ pub enum QuoteError {
     AlreadyExists,
     #[error("Ключи не совпадают.")]
     KeyNotEqual,
+    #[error("Требуется вход: AUTH.")]
+    Unauthorized,
+    #[error("Нет прав: {0}")]
+    Forbidden(String),
     #[error("Другая ошибка: {0}")]
     Other(String),
 }
@@ -28,25 +32,102 @@ impl QuoteError {
             QuoteError::NotConnection => "NotConnection".to_string(),
             QuoteError::AlreadyExists => "AlreadyExists".to_string(),
             QuoteError::KeyNotEqual => "KeyNotEqual".to_string(),
+            QuoteError::Unauthorized => "Unauthorized".to_string(),
+            QuoteError::Forbidden(e) => format!("Forbidden {}", e),
             QuoteError::Other(e) => format!("Other {}", e),
         }
     }
 
     pub fn from_string(s: &str) -> Result<Self, ()> {
-        if s.len() < 5 {
+        let Some(head) = s.get(..5) else {
             return Err(());
-        }
+        };
 
-        println!("{}", s);
-        match &s[..5] {
+        match head {
             "NotFo" => Ok(QuoteError::NotFound),
-            "BadRe" => Ok(QuoteError::BadRequest(s[11..].to_string())),
+            "BadRe" => Ok(QuoteError::BadRequest(
+                s.get(11..).unwrap_or_default().to_string(),
+            )),
             "Inter" => Ok(QuoteError::InternalError),
             "NotCo" => Ok(QuoteError::NotConnection),
             "Alrea" => Ok(QuoteError::AlreadyExists),
             "KeyNo" => Ok(QuoteError::KeyNotEqual),
-            "Other" => Ok(QuoteError::Other(s[6..].to_string())),
+            "Unaut" => Ok(QuoteError::Unauthorized),
+            "Forbi" => Ok(QuoteError::Forbidden(
+                s.get(10..).unwrap_or_default().to_string(),
+            )),
+            "Other" => Ok(QuoteError::Other(
+                s.get(6..).unwrap_or_default().to_string(),
+            )),
             _ => Err(()),
         }
     }
+
+    /// Код ошибки для бинарного протокола
+    pub fn code(&self) -> u16 {
+        match self {
+            QuoteError::NotFound => 1,
+            QuoteError::BadRequest(_) => 2,
+            QuoteError::InternalError => 3,
+            QuoteError::NotConnection => 4,
+            QuoteError::AlreadyExists => 5,
+            QuoteError::KeyNotEqual => 6,
+            QuoteError::Other(_) => 7,
+            QuoteError::Unauthorized => 8,
+            QuoteError::Forbidden(_) => 9,
+        }
+    }
+
+    /// Текст ошибки без кода, есть только у [QuoteError::BadRequest], [QuoteError::Forbidden]
+    /// и [QuoteError::Other]
+    pub fn message(&self) -> &str {
+        match self {
+            QuoteError::BadRequest(e) | QuoteError::Forbidden(e) | QuoteError::Other(e) => e,
+            _ => "",
+        }
+    }
+
+    /// Ошибка по коду и тексту, `None` - неизвестный код
+    pub fn from_code(code: u16, message: String) -> Option<Self> {
+        match code {
+            1 => Some(QuoteError::NotFound),
+            2 => Some(QuoteError::BadRequest(message)),
+            3 => Some(QuoteError::InternalError),
+            4 => Some(QuoteError::NotConnection),
+            5 => Some(QuoteError::AlreadyExists),
+            6 => Some(QuoteError::KeyNotEqual),
+            7 => Some(QuoteError::Other(message)),
+            8 => Some(QuoteError::Unauthorized),
+            9 => Some(QuoteError::Forbidden(message)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes() {
+        for e in [
+            QuoteError::NotFound,
+            QuoteError::BadRequest("x".to_string()),
+            QuoteError::InternalError,
+            QuoteError::NotConnection,
+            QuoteError::AlreadyExists,
+            QuoteError::KeyNotEqual,
+            QuoteError::Other("y".to_string()),
+            QuoteError::Unauthorized,
+            QuoteError::Forbidden("z".to_string()),
+        ] {
+            assert_eq!(
+                QuoteError::from_code(e.code(), e.message().to_string()),
+                Some(e.clone())
+            );
+            assert_eq!(QuoteError::from_string(&e.clone().to_string()), Ok(e));
+        }
+        assert_eq!(QuoteError::from_code(0, String::new()), None);
+        assert!(QuoteError::from_string("BadRe").is_ok());
+    }
 }
@@ -1,12 +1,24 @@
-use super::stock::{StockQuote, Ticker};
+use super::{
+    candle::Candle,
+    stock::{StockQuote, Ticker},
+};
 use bincode::error::EncodeError;
 use serde::{Deserialize, Serialize};
 
-/// Сообщение сервера
+/// Сообщение сервера.
+///
+/// Котировки и свечи потока пронумерованы по порядку начиная с 1, чтобы клиент видел
+/// потери и перестановки датаграмм.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub enum UdpMessage {
-    Init(Vec<StockQuote>),
-    Stock(StockQuote),
+    /// Снимок последних котировок и номер последней отправленной котировки потока
+    Init(u64, Vec<StockQuote>),
+    /// Номер котировки в потоке и сама котировка
+    Stock(u64, StockQuote),
+    /// Снимок последних свечей потока свечей и номер последней отправленной свечи
+    CandleInit(u64, Vec<Candle>),
+    /// Номер свечи в потоке и сама свеча
+    Candle(u64, Candle),
     Close(Ticker),
     Pong,
     Ping,
@@ -32,23 +44,39 @@ impl UdpMessage {
     pub fn to_bin(&self) -> Result<Vec<u8>, EncodeError> {
         let config = bincode::config::standard();
         match self {
-            UdpMessage::Init(stocks) => {
+            UdpMessage::Init(seq, stocks) => {
                 let mut mess = "INIT".as_bytes().to_vec();
 
-                let Ok(mut stocks) = bincode::encode_to_vec(stocks, config) else {
+                let Ok(mut stocks) = bincode::encode_to_vec((seq, stocks), config) else {
                     return Err(EncodeError::Other("Ошибка кодирования"));
                 };
                 mess.append(&mut stocks);
                 Ok(mess)
             }
-            UdpMessage::Stock(stock) => {
+            UdpMessage::Stock(seq, stock) => {
                 let mut mess = "STOC".as_bytes().to_vec();
-                let Ok(mut stock) = bincode::encode_to_vec(stock, config) else {
+                let Ok(mut stock) = bincode::encode_to_vec((seq, stock), config) else {
                     return Err(EncodeError::Other("Ошибка кодирования"));
                 };
                 mess.append(&mut stock);
                 Ok(mess)
             }
+            UdpMessage::CandleInit(seq, candles) => {
+                let mut mess = "CINI".as_bytes().to_vec();
+                let Ok(mut candles) = bincode::encode_to_vec((seq, candles), config) else {
+                    return Err(EncodeError::Other("Ошибка кодирования"));
+                };
+                mess.append(&mut candles);
+                Ok(mess)
+            }
+            UdpMessage::Candle(seq, candle) => {
+                let mut mess = "CNDL".as_bytes().to_vec();
+                let Ok(mut candle) = bincode::encode_to_vec((seq, candle), config) else {
+                    return Err(EncodeError::Other("Ошибка кодирования"));
+                };
+                mess.append(&mut candle);
+                Ok(mess)
+            }
             UdpMessage::Close(ticker) => {
                 let mut mess = "CLOS".as_bytes().to_vec();
                 mess.append(&mut ticker.as_bytes().to_vec());
@@ -71,14 +99,28 @@ impl UdpMessage {
 
     pub fn to_string(&self) -> String {
         match self {
-            UdpMessage::Init(stocks) => {
-                let mut mess = "Welcome! Last stocks (ticker|price|volume|timestamp):".to_string();
+            UdpMessage::Init(seq, stocks) => {
+                let mut mess = format!(
+                    "Welcome! Seq {}. Last stocks (ticker|price|volume|timestamp):",
+                    seq
+                );
                 for stock in stocks {
                     mess.push_str(format!("\n{}", stock.to_string()).as_str());
                 }
                 mess
             }
-            UdpMessage::Stock(stock) => stock.to_string(),
+            UdpMessage::Stock(seq, stock) => format!("{}|{}", seq, stock.to_string()),
+            UdpMessage::CandleInit(seq, candles) => {
+                let mut mess = format!(
+                    "Candles! Seq {}. Last candles (ticker|interval|start|open|high|low|close|volume|vwap|sma):",
+                    seq
+                );
+                for candle in candles {
+                    mess.push_str(format!("\n{}", candle.to_text()).as_str());
+                }
+                mess
+            }
+            UdpMessage::Candle(seq, candle) => format!("CNDL {}|{}", seq, candle.to_text()),
             UdpMessage::Close(ticker) => format!("CLOSE STOCK: {}", ticker),
             UdpMessage::Pong => "PONG".to_string(),
             UdpMessage::Ping => "Ping".to_string(),
@@ -114,6 +156,11 @@ impl UdpMessage {
 
         match mes {
             "Welc" => {
+                let seq = data
+                    .split_once("Seq ")
+                    .and_then(|(_, r)| r.split_once('.'))
+                    .and_then(|(seq, _)| seq.parse().ok())
+                    .ok_or(EncodeError::Other("Ошибка кодирования"))?;
                 let mut stocks = vec![];
                 for line in data.lines().skip(1) {
                     let Some(stock) = StockQuote::from_string(line) else {
@@ -122,15 +169,39 @@ impl UdpMessage {
                     stocks.push(stock);
                 }
 
-                Ok(UdpMessage::Init(stocks))
+                Ok(UdpMessage::Init(seq, stocks))
             }
+            "Cand" => {
+                let seq = data
+                    .split_once("Seq ")
+                    .and_then(|(_, r)| r.split_once('.'))
+                    .and_then(|(seq, _)| seq.parse().ok())
+                    .ok_or(EncodeError::Other("Ошибка кодирования"))?;
+                let mut candles = vec![];
+                for line in data.lines().skip(1) {
+                    let Some(candle) = Candle::from_text(line) else {
+                        return Err(EncodeError::Other("Ошибка кодирования"));
+                    };
+                    candles.push(candle);
+                }
+
+                Ok(UdpMessage::CandleInit(seq, candles))
+            }
+            "CNDL" => data[4..]
+                .trim_start()
+                .split_once('|')
+                .and_then(|(seq, candle)| Some((seq.parse().ok()?, Candle::from_text(candle)?)))
+                .ok_or(EncodeError::Other("Ошибка кодирования"))
+                .map(|(seq, candle)| UdpMessage::Candle(seq, candle)),
             "CLOS" => Ok(UdpMessage::Close(data[13..].to_string())),
             "PONG" => Ok(UdpMessage::Pong),
             "PING" => Ok(UdpMessage::Ping),
             "DISC" => Ok(UdpMessage::Disconnect),
-            _ => StockQuote::from_string(&data)
+            _ => data
+                .split_once('|')
+                .and_then(|(seq, stock)| Some((seq.parse().ok()?, StockQuote::from_string(stock)?)))
                 .ok_or(EncodeError::Other("Ошибка кодирования"))
-                .map(UdpMessage::Stock),
+                .map(|(seq, stock)| UdpMessage::Stock(seq, stock)),
         }
     }
 
@@ -143,16 +214,28 @@ impl UdpMessage {
 
         match mes_type.as_str() {
             "INIT" => {
-                let Ok((stocks, _)) = bincode::decode_from_slice(&data[4..], config) else {
+                let Ok(((seq, stocks), _)) = bincode::decode_from_slice(&data[4..], config) else {
                     return Err(EncodeError::Other("Ошибка кодирования"));
                 };
-                Ok(UdpMessage::Init(stocks))
+                Ok(UdpMessage::Init(seq, stocks))
             }
             "STOC" => {
-                let Ok((stock, _)) = bincode::decode_from_slice(&data[4..], config) else {
+                let Ok(((seq, stock), _)) = bincode::decode_from_slice(&data[4..], config) else {
+                    return Err(EncodeError::Other("Ошибка кодирования"));
+                };
+                Ok(UdpMessage::Stock(seq, stock))
+            }
+            "CINI" => {
+                let Ok(((seq, candles), _)) = bincode::decode_from_slice(&data[4..], config) else {
                     return Err(EncodeError::Other("Ошибка кодирования"));
                 };
-                Ok(UdpMessage::Stock(stock))
+                Ok(UdpMessage::CandleInit(seq, candles))
+            }
+            "CNDL" => {
+                let Ok(((seq, candle), _)) = bincode::decode_from_slice(&data[4..], config) else {
+                    return Err(EncodeError::Other("Ошибка кодирования"));
+                };
+                Ok(UdpMessage::Candle(seq, candle))
             }
             "CLOS" => Ok(UdpMessage::Close(
                 String::from_utf8(data[4..].to_vec()).unwrap(),
@@ -185,26 +268,35 @@ mod tests {
 
     #[test]
     fn test_init_empty() {
-        let init = UdpMessage::Init(vec![]);
+        let init = UdpMessage::Init(0, vec![]);
         check_formats(init);
     }
 
     #[test]
     fn test_init_full() {
-        let init = UdpMessage::Init(vec![
-            StockQuote::one(),
-            StockQuote::two(),
-            StockQuote::one(),
-        ]);
+        let init = UdpMessage::Init(
+            42,
+            vec![StockQuote::one(), StockQuote::two(), StockQuote::one()],
+        );
         check_formats(init);
     }
 
     #[test]
     fn test_stock() {
-        let stock = UdpMessage::Stock(StockQuote::one());
+        let stock = UdpMessage::Stock(7, StockQuote::one());
         check_formats(stock);
     }
 
+    #[test]
+    fn test_candles() {
+        check_formats(UdpMessage::CandleInit(0, vec![]));
+        check_formats(UdpMessage::CandleInit(
+            3,
+            vec![Candle::one(), Candle::one()],
+        ));
+        check_formats(UdpMessage::Candle(9, Candle::one()));
+    }
+
     #[test]
     fn test_close() {
         let close = UdpMessage::Close("BCD".to_string());
@@ -1,7 +1,12 @@
+pub mod auth;
+pub mod candle;
+pub mod codec;
 pub mod command;
+pub mod delivery;
 pub mod error;
 pub(crate) mod macros;
 pub mod message;
 pub(crate) mod reciever;
+pub(crate) mod sequence;
 pub(crate) mod state;
 pub mod stock;
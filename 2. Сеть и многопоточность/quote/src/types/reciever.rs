@@ -1,20 +1,24 @@
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 
 use crate::{
     logging,
     types::{
+        codec::{self, Protocol},
+        command::TcpCommand,
         message::{UdpMessage, UdpMessageFormat},
+        sequence::{Recovery, Sequencer},
         stock::Ticker,
     },
 };
 use std::{
-    io::{self},
-    net::{SocketAddr, UdpSocket},
+    io::{self, Write},
+    net::{SocketAddr, TcpStream, UdpSocket},
     sync::{
         Arc,
         mpsc::{Receiver, Sender},
     },
     thread,
+    time::Instant,
 };
 
 const DURATION_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(200);
@@ -22,6 +26,8 @@ const TIME_SLEEP: std::time::Duration = std::time::Duration::from_millis(200);
 const PING_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
 const COUNT_RECONNECT: u8 = 5;
 const COUNT_TIMEOUT: u8 = 10;
+/// Снимок со свечами может не поместиться в 1 КБ
+const BUF_SIZE: usize = 64 * 1024;
 
 pub(crate) struct RecieverQuote {
     pub(crate) addr: SocketAddr,
@@ -32,6 +38,11 @@ pub(crate) struct RecieverQuote {
     sender: Sender<UdpMessage>,
     server: Option<SocketAddr>,
 
+    /// Котировки или свечи потока
+    sequencer: Sequencer<UdpMessage>,
+    /// TCP соединение клиента и его протокол для запросов переотправки
+    control: Option<(Arc<Mutex<TcpStream>>, Protocol)>,
+
     count_reconnect: u8,
     count_timeout: u8,
     ping_interval: std::time::Instant,
@@ -44,6 +55,7 @@ impl RecieverQuote {
         tickers: Vec<Ticker>,
         addr: SocketAddr,
         format: Option<UdpMessageFormat>,
+        control: Option<(Arc<Mutex<TcpStream>>, Protocol)>,
         shutdown: Arc<RwLock<bool>>,
     ) -> Result<(Self, Receiver<UdpMessage>), String> {
         let Ok(socket) = UdpSocket::bind(addr) else {
@@ -70,6 +82,9 @@ impl RecieverQuote {
                 sender,
                 server: None,
 
+                sequencer: Sequencer::new(),
+                control,
+
                 count_reconnect: 0,
                 count_timeout: 0,
                 ping_interval: std::time::Instant::now(),
@@ -81,12 +96,13 @@ impl RecieverQuote {
     }
 
     pub fn run(mut self) -> Result<Self, String> {
-        let mut buf = [0u8; 1024];
+        let mut buf = vec![0u8; BUF_SIZE];
         self.server = match self.socket.recv_from(&mut buf) {
             Ok((n, server)) => match UdpMessage::from_format(&buf[..n], &self.format) {
                 Ok(msg) => {
-                    if let UdpMessage::Init(last_stocks) = msg {
-                        let _ = self.sender.send(UdpMessage::Init(last_stocks));
+                    if let UdpMessage::Init(seq, _) | UdpMessage::CandleInit(seq, _) = msg {
+                        self.sequencer.init(seq, Instant::now());
+                        let _ = self.sender.send(msg);
                         Some(server)
                     } else {
                         logging!(warn, ("Не удалось получить данные"));
@@ -105,7 +121,6 @@ impl RecieverQuote {
         };
 
         loop {
-            let mut buf = [0u8; 1024];
             if *self.shutdown.read() {
                 self.message_handle(UdpMessage::Disconnect);
                 break Ok(self);
@@ -139,6 +154,7 @@ impl RecieverQuote {
                     logging!(warn, ("{}", _e));
                 }
             }
+            self.recover();
 
             if self.count_reconnect >= COUNT_RECONNECT {
                 logging!(warn, ("Server not response. Client closed"));
@@ -169,15 +185,19 @@ impl RecieverQuote {
         self.count_reconnect = 0;
         self.count_timeout = 0;
         match message {
-            UdpMessage::Init(stocks) => {
-                if let Err(e) = self.sender.send(UdpMessage::Init(stocks)) {
+            UdpMessage::Init(seq, _) | UdpMessage::CandleInit(seq, _) => {
+                if let Err(e) = self.sender.send(message) {
                     logging!(warn, ("Send init failed: {}", e));
                 };
+                // Котировки, пришедшие раньше снимка
+                for (_, message) in self.sequencer.init(seq, Instant::now()) {
+                    self.deliver(message);
+                }
             }
-            UdpMessage::Stock(stock) => {
-                if let Err(e) = self.sender.send(UdpMessage::Stock(stock)) {
-                    logging!(warn, ("Send stock failed: {}", e));
-                };
+            UdpMessage::Stock(seq, _) | UdpMessage::Candle(seq, _) => {
+                for (_, message) in self.sequencer.push(seq, message, Instant::now()) {
+                    self.deliver(message);
+                }
             }
             UdpMessage::Disconnect => {
                 if let Err(e) = self.sender.send(UdpMessage::Disconnect) {
@@ -196,6 +216,32 @@ impl RecieverQuote {
         }
     }
 
+    fn deliver(&self, message: UdpMessage) {
+        if let Err(e) = self.sender.send(message) {
+            logging!(warn, ("Send stock failed: {}", e));
+        };
+    }
+
+    /// Запросить по TCP пропущенные котировки или снимок
+    fn recover(&mut self) {
+        let Some(recovery) = self.sequencer.recovery(Instant::now()) else {
+            return;
+        };
+        let command = match recovery {
+            Recovery::Resend(from, to) => TcpCommand::Resend((self.addr, from, to)),
+            Recovery::Snapshot => TcpCommand::Snapshot(self.addr),
+        };
+        logging!(info, ("Gap recovery: {:?}", command));
+        let Some((control, protocol)) = &self.control else {
+            return;
+        };
+        // id 0: сервер на эти запросы не отвечает
+        let request = codec::encode_command(*protocol, 0, &command);
+        if let Err(_e) = control.lock().write_all(&request) {
+            logging!(warn, ("Ошибка запроса восстановления: {}", _e));
+        }
+    }
+
     fn keepalive(&mut self) {
         if let (Some(server), Ok(ping)) = (self.server, UdpMessage::Ping.to_format(&self.format)) {
             logging!(info, ("Ping send: {}", self.addr));
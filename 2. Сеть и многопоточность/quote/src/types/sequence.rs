@@ -0,0 +1,168 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+/// Пропуск больше этого восстанавливается снимком, а не переотправкой
+pub(crate) const MAX_GAP: u64 = 256;
+/// Сколько ждать заполнения пропуска перед повторным запросом
+pub(crate) const NACK_TIMEOUT: Duration = Duration::from_millis(100);
+/// После стольких безуспешных запросов переотправки запрашивается снимок
+pub(crate) const MAX_NACKS: u8 = 3;
+
+/// Как восстановить пропущенные котировки
+#[derive(Debug, PartialEq)]
+pub(crate) enum Recovery {
+    /// Переотправить котировки с номерами `[from, to]`
+    Resend(u64, u64),
+    /// Прислать снимок последних котировок
+    Snapshot,
+}
+
+/// ## Буфер упорядочивания сообщений потока
+///
+/// Отдает котировки (или свечи) строго по порядку номеров, пришедшие раньше времени
+/// придерживает до заполнения пропуска.
+pub(crate) struct Sequencer<T> {
+    /// Следующий ожидаемый номер
+    next: u64,
+    /// Котировки после пропуска
+    pending: BTreeMap<u64, T>,
+    /// Когда пропуск обнаружен или запрошен последний раз
+    gap_since: Option<Instant>,
+    nacks: u8,
+}
+
+impl<T> Sequencer<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            next: 1,
+            pending: BTreeMap::new(),
+            gap_since: None,
+            nacks: 0,
+        }
+    }
+
+    /// Снимок с номером `seq`: все котировки до него включительно уже не нужны
+    pub(crate) fn init(&mut self, seq: u64, now: Instant) -> Vec<(u64, T)> {
+        self.next = seq + 1;
+        self.pending = self.pending.split_off(&self.next);
+        self.nacks = 0;
+        self.gap_since = None;
+        self.drain(now)
+    }
+
+    /// Принять сообщение, вернуть готовые к выдаче по порядку
+    pub(crate) fn push(&mut self, seq: u64, item: T, now: Instant) -> Vec<(u64, T)> {
+        // Повтор уже выданного сообщения
+        if seq < self.next {
+            return vec![];
+        }
+        self.pending.insert(seq, item);
+        self.drain(now)
+    }
+
+    fn drain(&mut self, now: Instant) -> Vec<(u64, T)> {
+        let mut ready = vec![];
+        while let Some(item) = self.pending.remove(&self.next) {
+            ready.push((self.next, item));
+            self.next += 1;
+        }
+        if !ready.is_empty() {
+            self.nacks = 0;
+        }
+        self.gap_since = match self.pending.is_empty() {
+            true => None,
+            false => self.gap_since.or(Some(now)),
+        };
+        ready
+    }
+
+    /// Что запросить у сервера, `None` - пропуска нет или ждем его заполнения
+    pub(crate) fn recovery(&mut self, now: Instant) -> Option<Recovery> {
+        let since = self.gap_since?;
+        let (&first, _) = self.pending.first_key_value()?;
+        if now.duration_since(since) < NACK_TIMEOUT {
+            return None;
+        }
+        self.gap_since = Some(now);
+
+        let (from, to) = (self.next, first - 1);
+        if to - from + 1 > MAX_GAP || self.nacks >= MAX_NACKS {
+            self.nacks = 0;
+            return Some(Recovery::Snapshot);
+        }
+        self.nacks += 1;
+        Some(Recovery::Resend(from, to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::stock::StockQuote;
+
+    fn seqs(ready: Vec<(u64, StockQuote)>) -> Vec<u64> {
+        ready.into_iter().map(|(seq, _)| seq).collect()
+    }
+
+    #[test]
+    fn test_in_order_and_duplicates() {
+        let now = Instant::now();
+        let mut sequencer = Sequencer::new();
+        assert_eq!(seqs(sequencer.init(0, now)), Vec::<u64>::new());
+        assert_eq!(seqs(sequencer.push(1, StockQuote::one(), now)), [1]);
+        assert_eq!(
+            seqs(sequencer.push(1, StockQuote::one(), now)),
+            Vec::<u64>::new()
+        );
+        assert_eq!(sequencer.recovery(now + NACK_TIMEOUT), None);
+    }
+
+    #[test]
+    fn test_gap_resend() {
+        let now = Instant::now();
+        let mut sequencer = Sequencer::new();
+        sequencer.push(1, StockQuote::one(), now);
+        assert!(sequencer.push(4, StockQuote::two(), now).is_empty());
+
+        // Перестановка датаграмм - ждем, прежде чем запрашивать
+        assert_eq!(sequencer.recovery(now), None);
+        assert_eq!(
+            sequencer.recovery(now + NACK_TIMEOUT),
+            Some(Recovery::Resend(2, 3))
+        );
+
+        sequencer.push(3, StockQuote::one(), now);
+        assert_eq!(seqs(sequencer.push(2, StockQuote::one(), now)), [2, 3, 4]);
+        assert_eq!(sequencer.recovery(now + NACK_TIMEOUT * 2), None);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let now = Instant::now();
+        let mut sequencer = Sequencer::new();
+        sequencer.push(MAX_GAP + 2, StockQuote::one(), now);
+        assert_eq!(
+            sequencer.recovery(now + NACK_TIMEOUT),
+            Some(Recovery::Snapshot)
+        );
+
+        // Снимок покрывает пропуск, буфер выдается с места снимка
+        sequencer.push(MAX_GAP + 3, StockQuote::two(), now);
+        assert_eq!(seqs(sequencer.init(MAX_GAP + 2, now)), [MAX_GAP + 3]);
+    }
+
+    #[test]
+    fn test_snapshot_after_nacks() {
+        let mut now = Instant::now();
+        let mut sequencer = Sequencer::new();
+        sequencer.push(3, StockQuote::one(), now);
+        for _ in 0..MAX_NACKS {
+            now += NACK_TIMEOUT;
+            assert_eq!(sequencer.recovery(now), Some(Recovery::Resend(1, 2)));
+        }
+        now += NACK_TIMEOUT;
+        assert_eq!(sequencer.recovery(now), Some(Recovery::Snapshot));
+    }
+}
@@ -1,5 +1,5 @@
 use super::guard::ValueGuard;
-use crate::{distributor::Distributor, master::Connection, state_accessor};
+use crate::{distributor::Distributor, master::Connection, state_accessor, types::auth::ClientKey};
 use parking_lot::{Mutex, RwLock};
 use std::{cell::RefCell, collections::HashMap, net::SocketAddr, rc::Rc, sync::Arc};
 
@@ -14,6 +14,9 @@ pub(crate) struct MasterState {
     shutdown: RwLock<bool>,
     // последовательность: 4
     secret_key: RwLock<String>,
+
+    /// Ключи клиентов, не меняются после запуска
+    clients: Vec<ClientKey>,
 }
 
 impl MasterState {
@@ -22,14 +25,26 @@ impl MasterState {
         distributor: Distributor,
         shutdown: bool,
         secret_key: String,
+        clients: Vec<ClientKey>,
     ) -> Self {
         Self {
             connections: Mutex::new(connections),
             distributor: Mutex::new(distributor),
             shutdown: RwLock::new(shutdown),
             secret_key: RwLock::new(secret_key),
+            clients,
         }
     }
+
+    /// Нужен ли вход
+    pub(crate) fn auth_required(&self) -> bool {
+        !self.clients.is_empty()
+    }
+
+    /// Ключ клиента по имени
+    pub(crate) fn client(&self, name: &str) -> Option<&ClientKey> {
+        self.clients.iter().find(|c| c.name == name)
+    }
 }
 
 fn gen_callback(numb: u8) -> impl FnOnce(Rc<RefCell<Vec<u8>>>) {
@@ -112,6 +127,7 @@ mod tests {
             Distributor::new(),
             false,
             "test".to_string(),
+            Vec::new(),
         ))
     }
 
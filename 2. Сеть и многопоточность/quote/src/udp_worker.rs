@@ -2,16 +2,160 @@ use crate::{
     distributor::{Event, Subscriber},
     logging,
     types::{
+        candle::{Candle, Feed, SNAPSHOT_CANDLES},
         message::{UdpMessage, UdpMessageFormat},
-        stock::StockQuote,
+        stock::{StockQuote, Ticker},
     },
 };
-use std::net::{SocketAddr, UdpSocket};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    net::{SocketAddr, UdpSocket},
+};
 
 const DURATION_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(100);
 const DURATION_SLEEP: std::time::Duration = std::time::Duration::from_millis(100);
 const PING_INTERVAL: std::time::Duration = std::time::Duration::from_millis(5000);
 const COUNT_TRY_SEND: u8 = 10;
+/// Сколько последних сообщений хранится для переотправки
+const RING_SIZE: usize = 1024;
+
+/// Последние отправленные котировки (или свечи) потока для переотправки по запросу
+struct RetransmitRing {
+    capacity: usize,
+    stocks: VecDeque<(u64, UdpMessage)>,
+}
+
+impl RetransmitRing {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            stocks: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    fn push(&mut self, seq: u64, message: UdpMessage) {
+        if self.stocks.len() >= self.capacity {
+            self.stocks.pop_front();
+        }
+        self.stocks.push_back((seq, message));
+    }
+
+    /// Сообщения `[from, to]`, `None` - часть из них уже вытеснена
+    fn range(&self, from: u64, to: u64) -> Option<Vec<(u64, UdpMessage)>> {
+        let (first, _) = self.stocks.front()?;
+        let (last, _) = self.stocks.back()?;
+        if from > to || from < *first || to > *last {
+            return None;
+        }
+        // Номера в кольце идут подряд
+        let start = (from - first) as usize;
+        Some(
+            self.stocks
+                .range(start..=start + (to - from) as usize)
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+/// ## Нумерация, снимок и переотправка сообщений потока
+///
+/// Не зависит от транспорта: общий для потоков ОС и задач tokio.
+pub(crate) struct StreamState {
+    feed: Feed,
+
+    /// Номер последнего отправленного сообщения
+    seq: u64,
+    ring: RetransmitRing,
+    /// Последние котировки по тикерам для снимка
+    last_stocks: BTreeMap<Ticker, StockQuote>,
+    /// Последние свечи по тикерам для снимка потока свечей
+    last_candles: BTreeMap<Ticker, VecDeque<Candle>>,
+}
+
+impl StreamState {
+    /// Снимок - последние котировки или свечи (по потоку)
+    pub(crate) fn new(feed: Feed, last_stocks: Vec<StockQuote>, last_candles: Vec<Candle>) -> Self {
+        let mut state = Self {
+            feed,
+
+            seq: 0,
+            ring: RetransmitRing::new(RING_SIZE),
+            last_stocks: last_stocks
+                .into_iter()
+                .map(|s| (s.ticker.clone(), s))
+                .collect(),
+            last_candles: BTreeMap::new(),
+        };
+        for candle in last_candles {
+            state.remember_candle(candle);
+        }
+        state
+    }
+
+    /// Сообщения в ответ на событие подписчика, `None` - поток нужно закрыть
+    pub(crate) fn handle(&mut self, event: Event) -> Option<Vec<UdpMessage>> {
+        match event {
+            Event::Update(stock) => Some(vec![self.stock(stock)]),
+            Event::Candle(candle) => Some(vec![self.candle(candle)]),
+            Event::Resend(from, to) => Some(self.resend(from, to)),
+            Event::Snapshot => Some(vec![self.snapshot()]),
+            Event::Disconnect => None,
+        }
+    }
+
+    /// Пронумеровать котировку
+    fn stock(&mut self, stock: StockQuote) -> UdpMessage {
+        self.seq += 1;
+        self.last_stocks.insert(stock.ticker.clone(), stock.clone());
+        self.numbered(UdpMessage::Stock(self.seq, stock))
+    }
+
+    /// Пронумеровать свечу
+    fn candle(&mut self, candle: Candle) -> UdpMessage {
+        self.seq += 1;
+        self.remember_candle(candle.clone());
+        self.numbered(UdpMessage::Candle(self.seq, candle))
+    }
+
+    fn numbered(&mut self, message: UdpMessage) -> UdpMessage {
+        self.ring.push(self.seq, message.clone());
+        message
+    }
+
+    fn remember_candle(&mut self, candle: Candle) {
+        let candles = self.last_candles.entry(candle.ticker.clone()).or_default();
+        if candles.len() >= SNAPSHOT_CANDLES {
+            candles.pop_front();
+        }
+        candles.push_back(candle);
+    }
+
+    /// Снимок последних котировок (свечей), клиент продолжит с `seq + 1`
+    pub(crate) fn snapshot(&self) -> UdpMessage {
+        match self.feed {
+            Feed::Ticks => UdpMessage::Init(self.seq, self.last_stocks.values().cloned().collect()),
+            Feed::Candle(_) => UdpMessage::CandleInit(
+                self.seq,
+                self.last_candles.values().flatten().cloned().collect(),
+            ),
+        }
+    }
+
+    /// Переотправить сообщения, если их уже нет - снимок
+    fn resend(&mut self, from: u64, to: u64) -> Vec<UdpMessage> {
+        match self.ring.range(from, to) {
+            Some(messages) => {
+                logging!(info, ("Resend {}..={}", from, to));
+                messages.into_iter().map(|(_, message)| message).collect()
+            }
+            None => {
+                logging!(info, ("Resend {}..={} unavailable, snapshot", from, to));
+                vec![self.snapshot()]
+            }
+        }
+    }
+}
 
 /// Worker по Udp подписки
 pub(crate) struct UdpWorker {
@@ -19,6 +163,7 @@ pub(crate) struct UdpWorker {
     socket: UdpSocket,
     addr: SocketAddr,
     format: UdpMessageFormat,
+    stream: StreamState,
 
     count: u8,
     ping_interval: std::time::Instant,
@@ -29,6 +174,7 @@ impl UdpWorker {
         subscriber: Subscriber,
         addr: SocketAddr,
         format: Option<UdpMessageFormat>,
+        stream: StreamState,
     ) -> Result<Self, String> {
         let Ok(socket) = UdpSocket::bind("0.0.0.0:0") else {
             return Err("Not bind udp socket".to_string());
@@ -48,6 +194,7 @@ impl UdpWorker {
             socket,
             addr,
             format,
+            stream,
 
             count: 0,
             ping_interval: std::time::Instant::now(),
@@ -55,26 +202,32 @@ impl UdpWorker {
     }
 
     /// Запустить worker
-    pub(crate) fn run(mut self, last_stocks: Vec<StockQuote>) -> Result<(), String> {
-        self.send(UdpMessage::Init(last_stocks));
+    pub(crate) fn run(mut self) -> Result<(), String> {
+        self.send(self.stream.snapshot());
         loop {
             if self.count >= COUNT_TRY_SEND {
                 logging!(warn, ("Client disconnected. Not response: {}", self.addr));
                 break Err("Client not response".to_string());
             }
 
-            if let Ok(event) = self.subscriber.get_event() {
-                match event {
-                    Event::Update(stock) => self.send(UdpMessage::Stock(stock)),
-                    Event::Disconnect => {
-                        self.send(UdpMessage::Disconnect);
+            // Разбираем всю очередь, чтобы не отставать от рассылки
+            let mut disconnect = false;
+            while let Some(event) = self.subscriber.get_event() {
+                let Some(messages) = self.stream.handle(event) else {
+                    disconnect = true;
+                    break;
+                };
+                for message in messages {
+                    self.send(message);
+                }
+            }
+            if disconnect {
+                self.send(UdpMessage::Disconnect);
 
-                        logging!(info, ("SubscribeWorker Disconnect: {}", self.addr));
+                logging!(info, ("SubscribeWorker Disconnect: {}", self.addr));
 
-                        break Ok(());
-                    }
-                }
-            };
+                break Ok(());
+            }
 
             let mut buf = [0u8; 1024];
             if (std::time::Instant::now() - self.ping_interval) > PING_INTERVAL {
@@ -144,3 +297,23 @@ impl UdpWorker {
         };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retransmit_ring() {
+        let mut ring = RetransmitRing::new(3);
+        for seq in 1..=5 {
+            ring.push(seq, UdpMessage::Stock(seq, StockQuote::one()));
+        }
+
+        let seqs: Vec<u64> = ring.range(4, 5).unwrap().into_iter().map(|s| s.0).collect();
+        assert_eq!(seqs, [4, 5]);
+        // Вытеснены или еще не отправлены
+        assert!(ring.range(2, 4).is_none());
+        assert!(ring.range(5, 6).is_none());
+        assert!(ring.range(5, 4).is_none());
+    }
+}
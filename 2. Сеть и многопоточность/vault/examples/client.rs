@@ -1,48 +1,23 @@
-use socket2::{Domain, Protocol, Socket, Type};
-use std::io::{self, BufRead, BufReader, Write};
-use std::net::{SocketAddr, TcpStream};
+use std::io::{self, Write};
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Duration;
+
+use vault::client::{ClientError, VaultClient};
+use vault::protocol::Request;
 
 const URL: &str = "127.0.0.1:7878";
 const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(10);
 
-// Подключение к серверу
-fn connect() -> io::Result<(TcpStream, BufReader<TcpStream>)> {
-    let socket = Socket::new(Domain::IPV4, Type::STREAM, Some(Protocol::TCP))?;
-
-    socket.set_keepalive(true)?;
-    #[cfg(any(target_os = "linux", target_os = "macos"))]
-    {
-        socket.set_tcp_keepalive(
-            &socket2::TcpKeepalive::new()
-                .with_time(Duration::from_secs(10))
-                .with_interval(Duration::from_secs(5)),
-        )?;
-    }
-
-    let addr: SocketAddr = URL.parse().unwrap();
-    socket.connect(&addr.into())?;
-
-    let stream: TcpStream = socket.into();
-    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
-    let mut reader = BufReader::new(stream.try_clone()?);
-
-    // Читаем welcome message один раз
-    let mut line = String::new();
-    reader.read_line(&mut line)?;
-    print!("{}", line);
-
-    println!("Connected to server!");
-    Ok((stream, reader))
-}
-
 // Реконнект
-fn reconnect() -> (TcpStream, BufReader<TcpStream>) {
+fn reconnect(client: &mut VaultClient) {
     loop {
-        match connect() {
-            Ok(pair) => return pair,
+        match client.reconnect() {
+            Ok(()) => {
+                println!("Connected to server!");
+                return;
+            }
             Err(e) => {
                 eprintln!("Reconnect failed: {}. Retrying in 2s...", e);
                 thread::sleep(Duration::from_secs(2));
@@ -51,63 +26,18 @@ fn reconnect() -> (TcpStream, BufReader<TcpStream>) {
     }
 }
 
-// Отправка команды
-fn send_command(
-    stream: &mut TcpStream,
-    reader: &mut BufReader<TcpStream>,
-    command: &str,
-) -> io::Result<String> {
-    stream.write_all(command.as_bytes())?;
-    stream.write_all(b"\n")?;
-    stream.flush()?;
-
-    let mut buffer = String::new();
-    let bytes = reader.read_line(&mut buffer)?;
-    if bytes == 0 {
-        return Err(io::Error::new(
-            io::ErrorKind::UnexpectedEof,
-            "Server closed connection",
-        ));
-    }
-    Ok(buffer)
-}
-
-// Отправка PING
-fn send_ping(stream: &mut TcpStream, reader: &mut BufReader<TcpStream>) -> io::Result<u64> {
-    let start = Instant::now();
-    stream.write_all(b"PING\n")?;
-    stream.flush()?;
-
-    let mut buffer = String::new();
-    let bytes = reader.read_line(&mut buffer)?;
-    if bytes == 0 {
-        return Err(io::Error::new(
-            io::ErrorKind::UnexpectedEof,
-            "Server closed connection",
-        ));
-    }
-
-    if buffer.trim() == "PONG" {
-        Ok(start.elapsed().as_millis() as u64)
-    } else {
-        Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!("Invalid response: {}", buffer),
-        ))
-    }
-}
-
 fn main() -> io::Result<()> {
-    let (stream, reader) = connect()?;
-    let latency = Arc::new(Mutex::new(1000u64));
+    let addr: SocketAddr = URL.parse().unwrap();
+    let client = VaultClient::connect(addr)?;
+    println!("Connected to server!");
+
+    let latency = Arc::new(Mutex::new(Duration::from_secs(1)));
     let keepalive_flag_end = Arc::new(Mutex::new(false));
-    let stream = Arc::new(Mutex::new(stream));
-    let reader = Arc::new(Mutex::new(reader));
+    let client = Arc::new(Mutex::new(client));
 
     // Keepalive-поток
     let thread = {
-        let stream_clone = Arc::clone(&stream);
-        let reader_clone = Arc::clone(&reader);
+        let client = Arc::clone(&client);
         let latency_clone = Arc::clone(&latency);
         let keepalive_flag_end = keepalive_flag_end.clone();
 
@@ -119,27 +49,22 @@ fn main() -> io::Result<()> {
                     break;
                 }
 
-                let mut stream = stream_clone.lock().unwrap();
-                let mut reader = reader_clone.lock().unwrap();
-                match send_ping(&mut stream, &mut reader) {
+                let mut client = client.lock().unwrap();
+                match client.ping() {
                     Ok(latency) => {
                         *latency_clone.lock().unwrap() = latency;
                     }
                     Err(err) => {
                         println!("Keepalive failed. Reconnecting...");
                         println!("{}", err);
-                        if let Ok((stream_new, reader_new)) = connect() {
-                            *stream = stream_new;
-                            *reader = reader_new;
-                        } else {
-                        }
+                        let _ = client.reconnect();
                     }
                 };
             }
         })
     };
 
-    // Основной интерактивный цикл
+    // Основной интерактивный цикл: текстовые команды, как для nc
     let stdin = io::stdin();
     loop {
         print!("vault> ");
@@ -152,27 +77,32 @@ fn main() -> io::Result<()> {
         if command.is_empty() {
             continue;
         }
-        if command.eq_ignore_ascii_case("EXIT") {
-            println!("Bye!");
-            break;
-        }
-
-        let mut s = stream.lock().unwrap();
-        let mut r = reader.lock().unwrap();
-
-        match send_command(&mut *s, &mut *r, command) {
-            Ok(resp) => print!("{}", resp),
+        let request = match Request::parse_text(command) {
+            Ok(Request::Exit) => {
+                println!("Bye!");
+                break;
+            }
+            Ok(request) => request,
             Err(e) => {
+                println!("ERROR: {}", e);
+                continue;
+            }
+        };
+
+        let mut client = client.lock().unwrap();
+        match client.request(&request) {
+            Ok(resp) => print!("{}", resp.to_text()),
+            Err(ClientError::Io(e)) => {
                 eprintln!("Command failed: {}. Reconnecting...", e);
-                let (new_s, new_r) = reconnect();
-                *s = new_s;
-                *r = new_r;
+                reconnect(&mut client);
             }
+            Err(e) => println!("ERROR: {}", e),
         }
     }
 
     let mut keepalive_flag_end = keepalive_flag_end.lock().unwrap();
     *keepalive_flag_end = true;
+    drop(keepalive_flag_end);
 
     thread.join().unwrap();
 
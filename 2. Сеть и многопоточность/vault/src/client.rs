@@ -0,0 +1,217 @@
+use socket2::{Domain, Protocol, Socket, Type};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+use crate::protocol::{CellInfo, Request, Response};
+use crate::vault::{Item, Keys, Stats, VaultError};
+
+/// Ответ дольше - соединение считается потерянным (PING отвечает до 4 секунд)
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub enum ClientError {
+    Io(io::Error),
+    /// Сервер выполнил запрос с ошибкой
+    Vault(VaultError),
+    /// Сервер не разобрал запрос
+    BadRequest(String),
+    /// Ответ не подходит к запросу
+    Unexpected(Response),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Io(e) => write!(f, "{}", e),
+            ClientError::Vault(e) => write!(f, "{}", e),
+            ClientError::BadRequest(e) => write!(f, "{}", e),
+            ClientError::Unexpected(response) => write!(f, "unexpected response: {:?}", response),
+        }
+    }
+}
+
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> Self {
+        ClientError::Io(e)
+    }
+}
+
+/// ## Клиент хранилища
+///
+/// Запросы и ответы в JSON ([crate::protocol]). Помнит токены открытых ячеек
+/// и открывает их заново после переподключения.
+pub struct VaultClient {
+    addr: SocketAddr,
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+    keys: Keys,
+}
+
+// Подключение к серверу
+fn connect(addr: SocketAddr) -> io::Result<(TcpStream, BufReader<TcpStream>)> {
+    let socket = Socket::new(Domain::IPV4, Type::STREAM, Some(Protocol::TCP))?;
+
+    socket.set_keepalive(true)?;
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    {
+        socket.set_tcp_keepalive(
+            &socket2::TcpKeepalive::new()
+                .with_time(Duration::from_secs(10))
+                .with_interval(Duration::from_secs(5)),
+        )?;
+    }
+    socket.connect(&addr.into())?;
+
+    let stream: TcpStream = socket.into();
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    // Приветствие сервера
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    Ok((stream, reader))
+}
+
+impl VaultClient {
+    pub fn connect(addr: SocketAddr) -> io::Result<Self> {
+        let (stream, reader) = connect(addr)?;
+        Ok(Self {
+            addr,
+            stream,
+            reader,
+            keys: Keys::new(),
+        })
+    }
+
+    /// Подключиться заново и открыть ячейки, открытые раньше
+    pub fn reconnect(&mut self) -> io::Result<()> {
+        (self.stream, self.reader) = connect(self.addr)?;
+        for (cell, token) in self.keys.clone() {
+            if let Err(ClientError::Io(e)) = self.unlock(cell, &token) {
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Отправить запрос, ошибка сервера - [ClientError::Vault]
+    pub fn request(&mut self, request: &Request) -> Result<Response, ClientError> {
+        let mut line = serde_json::to_vec(request).map_err(io::Error::other)?;
+        line.push(b'\n');
+        self.stream.write_all(&line)?;
+        self.stream.flush()?;
+
+        let mut buffer = String::new();
+        if self.reader.read_line(&mut buffer)? == 0 {
+            return Err(ClientError::Io(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Server closed connection",
+            )));
+        }
+        match serde_json::from_str(&buffer).map_err(io::Error::other)? {
+            Response::Error(e) => Err(ClientError::Vault(e)),
+            Response::BadRequest(e) => Err(ClientError::BadRequest(e)),
+            response => Ok(response),
+        }
+    }
+
+    fn expect_done(&mut self, request: &Request) -> Result<(), ClientError> {
+        match self.request(request)? {
+            Response::Done | Response::Stored => Ok(()),
+            response => Err(ClientError::Unexpected(response)),
+        }
+    }
+
+    pub fn put(&mut self, cell: u32, item: Item) -> Result<(), ClientError> {
+        self.expect_done(&Request::Put { cell, item })
+    }
+
+    pub fn get(&mut self, cell: u32) -> Result<CellInfo, ClientError> {
+        match self.request(&Request::Get { cell })? {
+            Response::Cell(info) => Ok(info),
+            response => Err(ClientError::Unexpected(response)),
+        }
+    }
+
+    pub fn take(&mut self, cell: u32, name: &str) -> Result<Item, ClientError> {
+        let name = name.to_string();
+        match self.request(&Request::Take { cell, name })? {
+            Response::Item(item) => Ok(item),
+            response => Err(ClientError::Unexpected(response)),
+        }
+    }
+
+    pub fn list(&mut self) -> Result<Vec<u32>, ClientError> {
+        match self.request(&Request::List)? {
+            Response::Cells(cells) => Ok(cells),
+            response => Err(ClientError::Unexpected(response)),
+        }
+    }
+
+    /// Создать свою ячейку, токен запоминается
+    pub fn open(
+        &mut self,
+        cell: u32,
+        owner: &str,
+        capacity: Option<u32>,
+    ) -> Result<String, ClientError> {
+        let owner = owner.to_string();
+        match self.request(&Request::Open {
+            cell,
+            owner,
+            capacity,
+        })? {
+            Response::Token(token) => {
+                self.keys.insert(cell, token.clone());
+                Ok(token)
+            }
+            response => Err(ClientError::Unexpected(response)),
+        }
+    }
+
+    /// Открыть чужую или свою ячейку по токену, токен запоминается
+    pub fn unlock(&mut self, cell: u32, token: &str) -> Result<(), ClientError> {
+        let token = token.to_string();
+        self.expect_done(&Request::Unlock {
+            cell,
+            token: token.clone(),
+        })?;
+        self.keys.insert(cell, token);
+        Ok(())
+    }
+
+    pub fn move_item(&mut self, from: u32, to: u32, name: &str) -> Result<(), ClientError> {
+        let name = name.to_string();
+        self.expect_done(&Request::Move { from, to, name })
+    }
+
+    pub fn resize(&mut self, cell: u32, capacity: u32) -> Result<(), ClientError> {
+        self.expect_done(&Request::Resize { cell, capacity })
+    }
+
+    pub fn stats(&mut self) -> Result<Stats, ClientError> {
+        match self.request(&Request::Stats)? {
+            Response::Stats(stats) => Ok(stats),
+            response => Err(ClientError::Unexpected(response)),
+        }
+    }
+
+    /// Задержка ответа сервера
+    pub fn ping(&mut self) -> Result<Duration, ClientError> {
+        let start = Instant::now();
+        match self.request(&Request::Ping)? {
+            Response::Pong => Ok(start.elapsed()),
+            response => Err(ClientError::Unexpected(response)),
+        }
+    }
+
+    /// Закрыть соединение
+    pub fn exit(mut self) -> Result<(), ClientError> {
+        match self.request(&Request::Exit)? {
+            Response::Bye => Ok(()),
+            response => Err(ClientError::Unexpected(response)),
+        }
+    }
+}
@@ -1,19 +1,38 @@
-use std::net::TcpListener;
+use clap::Parser;
+use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::sync::Mutex;
 use std::thread;
 
-mod server;
-mod vault;
+use vault::server::handle_client;
+use vault::storage::Storage;
+use vault::vault::Vault;
 
-use crate::server::handle_client;
-use crate::vault::Vault;
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Адрес сервера
+    #[arg(long, default_value = "127.0.0.1:7878")]
+    host: SocketAddr,
+
+    /// Лимит ячеек
+    #[arg(long, default_value_t = 10)]
+    cells: usize,
+
+    /// Папка для ячеек на диске
+    #[arg(long, default_value = "vault-data")]
+    data: PathBuf,
+}
 
 fn main() -> std::io::Result<()> {
-    let listener = TcpListener::bind("127.0.0.1:7878")?;
-    println!("Server listening on port 7878");
+    let cli = Cli::parse();
+
+    let (storage, cells) = Storage::open(&cli.data)?;
+    println!("Restored {} cells from {}", cells.len(), cli.data.display());
+    let vault = Arc::new(Vault::with_storage(cli.cells, storage, cells));
 
-    let vault = Arc::new(Mutex::new(Vault::new(10))); // лимит 10 ячеек
+    let listener = TcpListener::bind(cli.host)?;
+    println!("Server listening on {}", cli.host);
 
     for stream in listener.incoming() {
         match stream {
@@ -0,0 +1,278 @@
+//! Протокол хранилища: запросы и ответы.
+//!
+//! Одна строка - один запрос. Строка, начинающаяся с `{`, - запрос [Request] в JSON,
+//! ответ на нее - [Response] в JSON одной строкой. Иначе это текстовая команда
+//! (`PUT 1 gold 10`, удобно для nc) и ответ текстом.
+
+use crate::vault::{Cell, Item, Stats, VaultError};
+use serde::{Deserialize, Serialize};
+
+/// ## Запрос клиента
+///
+/// В JSON всегда объект: `{"command": "Get", "cell": 1}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command")]
+pub enum Request {
+    /// Положить предмет, ячейки нет - создается общая
+    Put {
+        cell: u32,
+        item: Item,
+    },
+    Get {
+        cell: u32,
+    },
+    Take {
+        cell: u32,
+        name: String,
+    },
+    List,
+    /// Создать ячейку владельца, в ответ токен доступа
+    Open {
+        cell: u32,
+        owner: String,
+        capacity: Option<u32>,
+    },
+    /// Открыть ячейку владельца в этой сессии
+    Unlock {
+        cell: u32,
+        token: String,
+    },
+    Move {
+        from: u32,
+        to: u32,
+        name: String,
+    },
+    Resize {
+        cell: u32,
+        capacity: u32,
+    },
+    Stats,
+    Ping,
+    Exit,
+}
+
+/// Содержимое ячейки без токена
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CellInfo {
+    pub items: Vec<Item>,
+    pub capacity: u32,
+    pub used_space: u32,
+    pub owner: Option<String>,
+}
+
+impl From<Cell> for CellInfo {
+    fn from(cell: Cell) -> Self {
+        Self {
+            items: cell.items,
+            capacity: cell.capacity,
+            used_space: cell.used_space,
+            owner: cell.owner,
+        }
+    }
+}
+
+/// ## Ответ сервера
+///
+/// В JSON всегда объект: `{"status": "Token", "data": "..."}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", content = "data")]
+pub enum Response {
+    Stored,
+    Done,
+    Token(String),
+    Cell(CellInfo),
+    Item(Item),
+    Cells(Vec<u32>),
+    Stats(Stats),
+    Pong,
+    Bye,
+    Error(VaultError),
+    /// Запрос не разобран
+    BadRequest(String),
+}
+
+fn id(s: Option<&str>) -> Option<u32> {
+    s.and_then(|s| s.parse::<u32>().ok())
+}
+
+impl Request {
+    /// Разобрать текстовую команду, ошибка - текст для клиента
+    pub fn parse_text(input: &str) -> Result<Self, String> {
+        let mut parts = input.split_whitespace();
+        let command = parts.next().unwrap_or_default().to_uppercase();
+        let args: Vec<&str> = parts.collect();
+        let arg = |i: usize| args.get(i).copied();
+
+        let request = match command.as_str() {
+            "PUT" => match (id(arg(0)), arg(1), id(arg(2))) {
+                (Some(cell), Some(name), Some(size)) => Request::Put {
+                    cell,
+                    item: Item {
+                        name: name.to_string(),
+                        size,
+                    },
+                },
+                _ => return Err("usage PUT <id> <name> <size>".to_string()),
+            },
+            "GET" => match arg(0) {
+                Some(cell) => Request::Get {
+                    cell: id(Some(cell)).ok_or("invalid id")?,
+                },
+                None => return Err("usage GET <id>".to_string()),
+            },
+            "TAKE" => match (id(arg(0)), arg(1)) {
+                (Some(cell), Some(name)) => Request::Take {
+                    cell,
+                    name: name.to_string(),
+                },
+                _ => return Err("usage TAKE <id> <name>".to_string()),
+            },
+            "LIST" => Request::List,
+            "OPEN" => match (id(arg(0)), arg(1), arg(2)) {
+                (Some(cell), Some(owner), capacity) => Request::Open {
+                    cell,
+                    owner: owner.to_string(),
+                    capacity: match capacity {
+                        Some(capacity) => Some(id(Some(capacity)).ok_or("invalid capacity")?),
+                        None => None,
+                    },
+                },
+                _ => return Err("usage OPEN <id> <owner> [capacity]".to_string()),
+            },
+            "UNLOCK" => match (id(arg(0)), arg(1)) {
+                (Some(cell), Some(token)) => Request::Unlock {
+                    cell,
+                    token: token.to_string(),
+                },
+                _ => return Err("usage UNLOCK <id> <token>".to_string()),
+            },
+            "MOVE" => match (id(arg(0)), id(arg(1)), arg(2)) {
+                (Some(from), Some(to), Some(name)) => Request::Move {
+                    from,
+                    to,
+                    name: name.to_string(),
+                },
+                _ => return Err("usage MOVE <from> <to> <name>".to_string()),
+            },
+            "RESIZE" => match (id(arg(0)), id(arg(1))) {
+                (Some(cell), Some(capacity)) => Request::Resize { cell, capacity },
+                _ => return Err("usage RESIZE <id> <capacity>".to_string()),
+            },
+            "STATS" => Request::Stats,
+            "PING" => Request::Ping,
+            "EXIT" => Request::Exit,
+            _ => return Err("unknown command".to_string()),
+        };
+        Ok(request)
+    }
+}
+
+impl Response {
+    /// Ответ для текстового режима, с переводом строки
+    pub fn to_text(&self) -> String {
+        match self {
+            Response::Stored => "OK: item stored\n".to_string(),
+            Response::Done => "OK\n".to_string(),
+            Response::Token(token) => format!("OK: token {}\n", token),
+            Response::Cell(cell) if cell.items.is_empty() => "Cell is empty\n".to_string(),
+            Response::Cell(cell) => {
+                let descriptions: Vec<String> = cell
+                    .items
+                    .iter()
+                    .map(|i| format!("{}({})", i.name, i.size))
+                    .collect();
+                let owner = match &cell.owner {
+                    Some(owner) => format!(" | Owner: {}", owner),
+                    None => String::new(),
+                };
+                format!(
+                    "Items: {} | Used: {}/{}{}\n",
+                    descriptions.join(", "),
+                    cell.used_space,
+                    cell.capacity,
+                    owner
+                )
+            }
+            Response::Item(item) => format!("Item name: {}, size: {}\n", item.name, item.size),
+            Response::Cells(cells) if cells.is_empty() => "Vault is empty\n".to_string(),
+            Response::Cells(cells) => {
+                let keys: Vec<String> = cells.iter().map(|id| id.to_string()).collect();
+                format!("Occupied cells: {}\n", keys.join(", "))
+            }
+            Response::Stats(stats) => format!(
+                "Cells: {}/{} | Items: {} | Used: {}/{}\n",
+                stats.cells, stats.max_cells, stats.items, stats.used_space, stats.capacity
+            ),
+            Response::Pong => "PONG\n".to_string(),
+            Response::Bye => "BYE\n".to_string(),
+            Response::Error(e) => format!("ERROR: {}\n", e),
+            Response::BadRequest(e) => format!("ERROR: {}\n", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_text() {
+        assert_eq!(
+            Request::parse_text("PUT 1 gold 10"),
+            Ok(Request::Put {
+                cell: 1,
+                item: Item {
+                    name: "gold".to_string(),
+                    size: 10
+                }
+            })
+        );
+        // Старое написание команды
+        assert_eq!(
+            Request::parse_text("Take 1 gold"),
+            Ok(Request::Take {
+                cell: 1,
+                name: "gold".to_string()
+            })
+        );
+        assert_eq!(
+            Request::parse_text("OPEN 2 alice"),
+            Ok(Request::Open {
+                cell: 2,
+                owner: "alice".to_string(),
+                capacity: None
+            })
+        );
+        assert_eq!(
+            Request::parse_text("MOVE 1 2"),
+            Err("usage MOVE <from> <to> <name>".to_string())
+        );
+        assert_eq!(Request::parse_text("GET x"), Err("invalid id".to_string()));
+        assert_eq!(Request::parse_text(""), Err("unknown command".to_string()));
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        for request in [
+            Request::Resize {
+                cell: 3,
+                capacity: 50,
+            },
+            Request::List,
+        ] {
+            let json = serde_json::to_string(&request).unwrap();
+            assert!(json.starts_with('{'));
+            assert_eq!(serde_json::from_str::<Request>(&json).unwrap(), request);
+        }
+
+        for response in [Response::Pong, Response::Error(VaultError::AccessDenied)] {
+            let json = serde_json::to_string(&response).unwrap();
+            assert!(json.starts_with('{'));
+            assert_eq!(serde_json::from_str::<Response>(&json).unwrap(), response);
+        }
+        assert_eq!(
+            Response::Error(VaultError::AccessDenied).to_text(),
+            "ERROR: access denied\n"
+        );
+    }
+}
@@ -3,15 +3,18 @@ use std::io::BufRead;
 use std::io::BufReader;
 use std::io::Write;
 use std::net::TcpStream;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use std::time::Duration;
 
-use crate::vault::{Item, Vault, VaultError};
+use crate::protocol::{Request, Response};
+use crate::vault::{DEFAULT_CELL_CAPACITY, Keys, Vault};
 
-pub fn handle_client(stream: TcpStream, vault: Arc<Mutex<Vault>>) {
+pub fn handle_client(stream: TcpStream, vault: Arc<Vault>) {
     // клонируем stream: один экземпляр для чтения (обёрнут в BufReader), другой — для записи
     let mut writer = stream.try_clone().expect("failed to clone stream");
     let mut reader = BufReader::new(stream);
+    // токены ячеек, открытых в этой сессии
+    let mut keys = Keys::new();
 
     // send initial prompt
     let _ = writer.write_all(b"Welcome to the Vault!\n");
@@ -35,99 +38,29 @@ pub fn handle_client(stream: TcpStream, vault: Arc<Mutex<Vault>>) {
                     continue;
                 }
 
-                let mut parts = input.split_whitespace();
-                let response = match parts.next() {
-                    Some("PUT") => {
-                        let id = parts.next().and_then(|s| s.parse::<u32>().ok());
-                        let name = parts.next();
-                        let size = parts.next().and_then(|s| s.parse::<u32>().ok());
-
-                        if let (Some(id), Some(name), Some(size)) = (id, name, size) {
-                            let item = Item {
-                                name: name.to_string(),
-                                size,
-                            };
-                            let mut v = vault.lock().unwrap();
-                            match v.put(id, item, 100) {
-                                Ok(_) => "OK: item stored\n".to_string(),
-                                Err(VaultError::VaultFull) => "ERROR: vault full\n".to_string(),
-                                Err(VaultError::CellFull) => "ERROR: cell full\n".to_string(),
-                                _ => "ERROR: unknown\n".to_string(),
-                            }
-                        } else {
-                            "ERROR: usage PUT <id> <name> <size>\n".to_string()
-                        }
-                    }
-
-                    Some("GET") => {
-                        if let Some(id_str) = parts.next() {
-                            if let Ok(id) = id_str.parse::<u32>() {
-                                let v = vault.lock().unwrap();
-                                match v.get(id) {
-                                    Ok(Some(items)) => items,
-                                    Ok(None) => "Cell is empty\n".to_string(),
-                                    Err(VaultError::CellNotFound) => {
-                                        "ERROR: cell not found\n".to_string()
-                                    }
-                                    _ => "ERROR: unknown\n".to_string(),
-                                }
-                            } else {
-                                "ERROR: invalid id\n".to_string()
-                            }
-                        } else {
-                            "ERROR: usage GET <id>\n".to_string()
-                        }
-                    }
-
-                    Some("Take") => {
-                        let id = parts.next().and_then(|s| s.parse::<u32>().ok());
-                        let name = parts.next();
-
-                        if let (Some(id), Some(name)) = (id, name) {
-                            let mut v = vault.lock().unwrap();
-                            match v.take(id, name) {
-                                Ok(item) => {
-                                    format!("Item name: {}, size: {}", item.name, item.size)
-                                }
-                                Err(VaultError::CellNotFound) => {
-                                    "ERROR: cell not found\n".to_string()
-                                }
-                                Err(VaultError::ItemNotFound) => {
-                                    "ERROR: item in cell not found\n".to_string()
-                                }
-                                _ => "Error: unknow\n".to_string(),
-                            }
-                        } else {
-                            "ERROR: usage TAKE <id> <name>\n".to_string()
-                        }
-                    }
-
-                    Some("LIST") => {
-                        let v = vault.lock().unwrap();
-                        v.list().unwrap_or_else(|| "Vault is empty\n".to_string())
-                    }
-
-                    Some("PING") => {
-                        let mut rng = rand::rng();
-
-                        // Случайная задержка от 1 до 4 секунд
-                        let delay_ms = rng.random_range(50..=4000);
-                        std::thread::sleep(Duration::from_millis(delay_ms));
-                        "PONG\n".to_string()
-                    }
-
-                    Some("EXIT") => {
-                        let _ = writer.write_all(b"BYE\n");
-                        let _ = writer.flush();
-                        return;
-                    }
-
-                    _ => "ERROR: unknown command\n".to_string(),
+                // JSON запрос - JSON ответ, текстовая команда - текстовый ответ
+                let json = input.starts_with('{');
+                let request = match json {
+                    true => serde_json::from_str::<Request>(input)
+                        .map_err(|e| format!("invalid request: {}", e)),
+                    false => Request::parse_text(input),
+                };
+                let response = match request {
+                    Ok(request) => execute(&vault, &mut keys, request),
+                    Err(e) => Response::BadRequest(e),
                 };
 
-                // отправляем ответ и снова показываем prompt
-                let _ = writer.write_all(response.as_bytes());
+                let answer = match json {
+                    true => serde_json::to_string(&response)
+                        .map(|s| s + "\n")
+                        .unwrap_or_else(|e| format!("{}\n", e)),
+                    false => response.to_text(),
+                };
+                let _ = writer.write_all(answer.as_bytes());
                 let _ = writer.flush();
+                if response == Response::Bye {
+                    return;
+                }
             }
             Err(_) => {
                 // ошибка чтения — закрываем
@@ -136,3 +69,44 @@ pub fn handle_client(stream: TcpStream, vault: Arc<Mutex<Vault>>) {
         }
     }
 }
+
+/// Выполнить запрос от имени сессии с токенами `keys`
+fn execute(vault: &Vault, keys: &mut Keys, request: Request) -> Response {
+    let result = match request {
+        Request::Put { cell, item } => vault.put(cell, item, keys).map(|_| Response::Stored),
+        Request::Get { cell } => vault.get(cell, keys).map(|c| Response::Cell(c.into())),
+        Request::Take { cell, name } => vault.take(cell, &name, keys).map(Response::Item),
+        Request::List => Ok(Response::Cells(vault.list())),
+        Request::Open {
+            cell,
+            owner,
+            capacity,
+        } => vault
+            .open(cell, &owner, capacity.unwrap_or(DEFAULT_CELL_CAPACITY))
+            .map(|token| {
+                keys.insert(cell, token.clone());
+                Response::Token(token)
+            }),
+        Request::Unlock { cell, token } => vault.unlock(cell, &token).map(|_| {
+            keys.insert(cell, token);
+            Response::Done
+        }),
+        Request::Move { from, to, name } => vault
+            .move_item(from, to, &name, keys)
+            .map(|_| Response::Done),
+        Request::Resize { cell, capacity } => {
+            vault.resize(cell, capacity, keys).map(|_| Response::Done)
+        }
+        Request::Stats => Ok(Response::Stats(vault.stats())),
+        Request::Ping => {
+            let mut rng = rand::rng();
+
+            // Случайная задержка от 1 до 4 секунд
+            let delay_ms = rng.random_range(50..=4000);
+            std::thread::sleep(Duration::from_millis(delay_ms));
+            Ok(Response::Pong)
+        }
+        Request::Exit => Ok(Response::Bye),
+    };
+    result.unwrap_or_else(Response::Error)
+}
@@ -0,0 +1,252 @@
+use crate::vault::{Cell, Item};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+const SNAPSHOT_FILE: &str = "cells.json";
+const JOURNAL_FILE: &str = "journal.log";
+
+/// Изменение хранилища, одна строка JSON в журнале
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Record {
+    Open {
+        cell: u32,
+        capacity: u32,
+        owner: Option<String>,
+        token: Option<String>,
+    },
+    Put {
+        cell: u32,
+        item: Item,
+    },
+    Take {
+        cell: u32,
+        name: String,
+    },
+    Move {
+        from: u32,
+        to: u32,
+        name: String,
+    },
+    Resize {
+        cell: u32,
+        capacity: u32,
+    },
+}
+
+impl Record {
+    /// Повторить изменение при восстановлении
+    fn apply(self, cells: &mut HashMap<u32, Cell>) -> Result<(), String> {
+        let missing = |id: u32| format!("нет ячейки {}", id);
+        match self {
+            Record::Open {
+                cell,
+                capacity,
+                owner,
+                token,
+            } => {
+                cells.insert(
+                    cell,
+                    Cell {
+                        owner,
+                        token,
+                        ..Cell::new(capacity)
+                    },
+                );
+                Ok(())
+            }
+            Record::Put { cell, item } => cells
+                .get_mut(&cell)
+                .ok_or_else(|| missing(cell))?
+                .put_item(item)
+                .map_err(|e| format!("{:?}", e)),
+            Record::Take { cell, name } => cells
+                .get_mut(&cell)
+                .ok_or_else(|| missing(cell))?
+                .take(&name)
+                .map(|_| ())
+                .map_err(|e| format!("{:?}", e)),
+            Record::Move { from, to, name } => {
+                let item = cells
+                    .get_mut(&from)
+                    .ok_or_else(|| missing(from))?
+                    .take(&name)
+                    .map_err(|e| format!("{:?}", e))?;
+                cells
+                    .get_mut(&to)
+                    .ok_or_else(|| missing(to))?
+                    .put_item(item)
+                    .map_err(|e| format!("{:?}", e))
+            }
+            Record::Resize { cell, capacity } => cells
+                .get_mut(&cell)
+                .ok_or_else(|| missing(cell))?
+                .resize(capacity)
+                .map_err(|e| format!("{:?}", e)),
+        }
+    }
+}
+
+/// ## Хранилище на диске.
+///
+/// Снимок всех ячеек (`cells.json`) и журнал изменений после него (`journal.log`).
+/// Изменение считается сделанным, когда его строка записана в журнал и сброшена на диск.
+/// При запуске снимок и журнал сворачиваются в новый снимок, журнал начинается заново.
+pub struct Storage {
+    journal: Mutex<File>,
+}
+
+impl Storage {
+    /// Открыть хранилище в папке и восстановить ячейки
+    pub fn open(dir: &Path) -> io::Result<(Self, HashMap<u32, Cell>)> {
+        fs::create_dir_all(dir)?;
+        let mut cells: HashMap<u32, Cell> = match fs::read_to_string(dir.join(SNAPSHOT_FILE)) {
+            Ok(text) => serde_json::from_str(&text).map_err(io::Error::other)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+
+        let journal_path = dir.join(JOURNAL_FILE);
+        if journal_path.exists() {
+            let mut lines = BufReader::new(File::open(&journal_path)?)
+                .lines()
+                .peekable();
+            let mut n = 0;
+            while let Some(line) = lines.next() {
+                n += 1;
+                let line = line?;
+                let record = match serde_json::from_str::<Record>(&line) {
+                    Ok(record) => record,
+                    // Недописанная последняя строка: сбой во время записи, изменения не было
+                    Err(_) if lines.peek().is_none() => {
+                        println!("Journal: skip torn line {}", n);
+                        break;
+                    }
+                    Err(e) => return Err(io::Error::other(format!("journal line {}: {}", n, e))),
+                };
+                record
+                    .apply(&mut cells)
+                    .map_err(|e| io::Error::other(format!("journal line {}: {}", n, e)))?;
+            }
+        }
+
+        Self::write_snapshot(dir, &cells)?;
+        let journal = File::create(&journal_path)?;
+        journal.sync_all()?;
+        Ok((
+            Self {
+                journal: Mutex::new(journal),
+            },
+            cells,
+        ))
+    }
+
+    /// Записать изменение в журнал и дождаться диска
+    pub fn append(&self, record: &Record) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record).map_err(io::Error::other)?;
+        line.push(b'\n');
+        let mut journal = self.journal.lock().unwrap();
+        journal.write_all(&line)?;
+        journal.sync_data()
+    }
+
+    /// Снимок через временный файл: старый снимок заменяется только целиком
+    fn write_snapshot(dir: &Path, cells: &HashMap<u32, Cell>) -> io::Result<()> {
+        let tmp = dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+        let mut file = File::create(&tmp)?;
+        file.write_all(&serde_json::to_vec(cells).map_err(io::Error::other)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp, dir.join(SNAPSHOT_FILE))?;
+        // Переименование тоже должно дойти до диска
+        File::open(dir)?.sync_all()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::{Keys, Vault};
+    use std::fs::OpenOptions;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("vault-{}-{}", name, rand::random::<u32>()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn item(name: &str, size: u32) -> Item {
+        Item {
+            name: name.to_string(),
+            size,
+        }
+    }
+
+    /// Журнал на дозапись, как его оставил упавший сервер
+    fn open_journal(dir: &Path) -> File {
+        OpenOptions::new()
+            .append(true)
+            .open(dir.join(JOURNAL_FILE))
+            .unwrap()
+    }
+
+    fn reopen(dir: &Path) -> Vault {
+        let (storage, cells) = Storage::open(dir).unwrap();
+        Vault::with_storage(10, storage, cells)
+    }
+
+    #[test]
+    fn test_recovery() {
+        let dir = temp_dir("recovery");
+        let keys = Keys::new();
+        let token = {
+            let vault = reopen(&dir);
+            let token = vault.open(1, "alice", 50).unwrap();
+            let keys = Keys::from([(1, token.clone())]);
+            vault.put(1, item("gold", 10), &keys).unwrap();
+            vault.put(2, item("iron", 20), &keys).unwrap();
+            vault.move_item(2, 1, "iron", &keys).unwrap();
+            vault.resize(2, 30, &keys).unwrap();
+            vault.take(1, "gold", &keys).unwrap();
+            token
+        };
+
+        let vault = reopen(&dir);
+        assert_eq!(vault.list(), [1, 2]);
+        assert_eq!(
+            vault.get(1, &keys),
+            Err(crate::vault::VaultError::AccessDenied)
+        );
+        let cell = vault.get(1, &Keys::from([(1, token)])).unwrap();
+        assert_eq!((cell.items, cell.used_space), (vec![item("iron", 20)], 20));
+        assert_eq!(vault.get(2, &keys).unwrap().capacity, 30);
+
+        // Свернутый снимок читается так же
+        vault.put(2, item("sand", 1), &keys).unwrap();
+        drop(vault);
+        assert_eq!(reopen(&dir).stats().items, 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_torn_journal() {
+        let dir = temp_dir("torn");
+        reopen(&dir).put(1, item("gold", 10), &Keys::new()).unwrap();
+        // Сбой посреди записи последней строки
+        open_journal(&dir)
+            .write_all(br#"{"Put":{"cell":1,"item":{"na"#)
+            .unwrap();
+        assert_eq!(reopen(&dir).stats().items, 1);
+
+        // Испорченная строка в середине - ошибка, а не молча потерянные данные
+        let mut journal = open_journal(&dir);
+        journal.write_all(b"garbage\n").unwrap();
+        journal
+            .write_all(br#"{"Take":{"cell":1,"name":"gold"}}"#)
+            .unwrap();
+        assert!(Storage::open(&dir).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
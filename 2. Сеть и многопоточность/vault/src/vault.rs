@@ -1,16 +1,32 @@
+use crate::storage::{Record, Storage};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, RwLock};
 
-#[derive(Debug, Clone)]
+/// Вместимость ячейки, созданной через `PUT`
+pub const DEFAULT_CELL_CAPACITY: u32 = 100;
+
+/// Токены ячеек, открытых клиентом в сессии
+pub type Keys = HashMap<u32, String>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Item {
     pub name: String,
     pub size: u32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Cell {
     pub items: Vec<Item>,
     pub capacity: u32,   // вместимость ячейки
     pub used_space: u32, // сколько занято
+    /// Владелец, `None` - общая ячейка
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// Токен доступа владельца, у общей ячейки нет
+    #[serde(default)]
+    pub token: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -26,11 +42,29 @@ impl Cell {
             items: Vec::new(),
             capacity,
             used_space: 0,
+            owner: None,
+            token: None,
+        }
+    }
+
+    /// Ячейка владельца с токеном доступа
+    pub fn owned(capacity: u32, owner: String, token: String) -> Self {
+        Self {
+            owner: Some(owner),
+            token: Some(token),
+            ..Self::new(capacity)
         }
     }
 
+    // Влезет ли предмет: размер приходит от клиента, сумма не должна переполниться
+    pub fn fits(&self, size: u32) -> bool {
+        self.used_space
+            .checked_add(size)
+            .is_some_and(|used| used <= self.capacity)
+    }
+
     pub fn put_item(&mut self, item: Item) -> Result<(), CellError> {
-        if self.used_space + item.size > self.capacity {
+        if !self.fits(item.size) {
             return Err(CellError::Full);
         }
         self.used_space += item.size;
@@ -72,81 +106,298 @@ impl Cell {
             Err(CellError::NotFound)
         }
     }
+
+    /// Изменить вместимость, не меньше занятого места
+    pub fn resize(&mut self, capacity: u32) -> Result<(), CellError> {
+        if capacity < self.used_space {
+            return Err(CellError::OutOfRange);
+        }
+        self.capacity = capacity;
+        Ok(())
+    }
+
+    /// Есть ли у клиента доступ к ячейке
+    fn allows(&self, token: Option<&String>) -> bool {
+        match &self.token {
+            None => true,
+            Some(token_cell) => token == Some(token_cell),
+        }
+    }
 }
 
+/// ## Статистика хранилища
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub cells: usize,
+    pub max_cells: usize,
+    pub items: usize,
+    pub used_space: u64,
+    pub capacity: u64,
+}
+
+/// ## Хранилище ячеек.
+///
+/// Блокировка на каждую ячейку: команды с разными ячейками не ждут друг друга,
+/// карта ячеек блокируется на запись только при создании ячейки. Если есть [Storage],
+/// каждое изменение сначала пишется в журнал.
 pub struct Vault {
-    cells: HashMap<u32, Cell>,
+    cells: RwLock<HashMap<u32, Arc<Mutex<Cell>>>>,
     capacity: usize, // максимальное количество ячеек
+    storage: Option<Storage>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum VaultError {
     VaultFull,
     CellFull,
     CellNotFound,
     ItemNotFound,
+    CellExists,
+    /// Ячейка владельца, токен не открыт в сессии
+    AccessDenied,
+    /// Новая вместимость меньше занятого места
+    CellTooSmall,
+    SameCell,
+    /// Не удалось записать изменение на диск, ничего не изменилось
+    Storage(String),
+}
+
+impl fmt::Display for VaultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VaultError::VaultFull => write!(f, "vault full"),
+            VaultError::CellFull => write!(f, "cell full"),
+            VaultError::CellNotFound => write!(f, "cell not found"),
+            VaultError::ItemNotFound => write!(f, "item in cell not found"),
+            VaultError::CellExists => write!(f, "cell already exists"),
+            VaultError::AccessDenied => write!(f, "access denied"),
+            VaultError::CellTooSmall => write!(f, "capacity less than used space"),
+            VaultError::SameCell => write!(f, "source and target cell are the same"),
+            VaultError::Storage(e) => write!(f, "storage: {}", e),
+        }
+    }
 }
 
 impl Vault {
     pub fn new(capacity: usize) -> Self {
         Self {
-            cells: HashMap::new(),
+            cells: RwLock::new(HashMap::new()),
+            capacity,
+            storage: None,
+        }
+    }
+
+    /// Хранилище с журналом на диске и восстановленными из него ячейками
+    pub fn with_storage(capacity: usize, storage: Storage, cells: HashMap<u32, Cell>) -> Self {
+        let cells = cells
+            .into_iter()
+            .map(|(id, cell)| (id, Arc::new(Mutex::new(cell))))
+            .collect();
+        Self {
+            cells: RwLock::new(cells),
             capacity,
+            storage: Some(storage),
         }
     }
 
-    // Положить предмет в ячейку
-    pub fn put(&mut self, id: u32, item: Item, cell_capacity: u32) -> Result<(), VaultError> {
-        if self.cells.len() >= self.capacity && !self.cells.contains_key(&id) {
+    fn cell(&self, id: u32) -> Result<Arc<Mutex<Cell>>, VaultError> {
+        let cells = self.cells.read().unwrap();
+        cells.get(&id).cloned().ok_or(VaultError::CellNotFound)
+    }
+
+    /// Записать изменение в журнал до применения
+    fn log(&self, record: Record) -> Result<(), VaultError> {
+        match &self.storage {
+            Some(storage) => storage
+                .append(&record)
+                .map_err(|e| VaultError::Storage(e.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Создать ячейку, если ее нет. Повторная проверка под блокировкой на запись:
+    /// ячейку мог создать другой клиент.
+    fn create(
+        &self,
+        id: u32,
+        make: impl FnOnce() -> Cell,
+    ) -> Result<(Arc<Mutex<Cell>>, bool), VaultError> {
+        let mut cells = self.cells.write().unwrap();
+        if let Some(cell) = cells.get(&id) {
+            return Ok((cell.clone(), false));
+        }
+        if cells.len() >= self.capacity {
             return Err(VaultError::VaultFull);
         }
+        let cell = make();
+        self.log(Record::Open {
+            cell: id,
+            capacity: cell.capacity,
+            owner: cell.owner.clone(),
+            token: cell.token.clone(),
+        })?;
+        let cell = Arc::new(Mutex::new(cell));
+        cells.insert(id, cell.clone());
+        Ok((cell, true))
+    }
 
-        let cell = self
-            .cells
-            .entry(id)
-            .or_insert_with(|| Cell::new(cell_capacity));
+    // Создать ячейку владельца, вернуть токен доступа
+    pub fn open(&self, id: u32, owner: &str, capacity: u32) -> Result<String, VaultError> {
+        let token = gen_token();
+        let (_, created) = self.create(id, || {
+            Cell::owned(capacity, owner.to_string(), token.clone())
+        })?;
+        if !created {
+            return Err(VaultError::CellExists);
+        }
+        Ok(token)
+    }
+
+    // Проверить токен ячейки
+    pub fn unlock(&self, id: u32, token: &str) -> Result<(), VaultError> {
+        let cell = self.cell(id)?;
+        let cell = cell.lock().unwrap();
+        if !cell.allows(Some(&token.to_string())) {
+            return Err(VaultError::AccessDenied);
+        }
+        Ok(())
+    }
+
+    // Положить предмет в ячейку, новая ячейка - общая
+    pub fn put(&self, id: u32, item: Item, keys: &Keys) -> Result<(), VaultError> {
+        let cell = match self.cell(id) {
+            Ok(cell) => cell,
+            Err(_) => self.create(id, || Cell::new(DEFAULT_CELL_CAPACITY))?.0,
+        };
+        let mut cell = cell.lock().unwrap();
+        if !cell.allows(keys.get(&id)) {
+            return Err(VaultError::AccessDenied);
+        }
+        if !cell.fits(item.size) {
+            return Err(VaultError::CellFull);
+        }
+        self.log(Record::Put {
+            cell: id,
+            item: item.clone(),
+        })?;
         cell.put_item(item).map_err(|_| VaultError::CellFull)
     }
 
     // Показать содержимое ячейки
-    pub fn get(&self, id: u32) -> Result<Option<String>, VaultError> {
-        match self.cells.get(&id) {
-            Some(cell) => Ok(cell.list_items()),
-            None => Err(VaultError::CellNotFound),
+    pub fn get(&self, id: u32, keys: &Keys) -> Result<Cell, VaultError> {
+        let cell = self.cell(id)?;
+        let cell = cell.lock().unwrap();
+        if !cell.allows(keys.get(&id)) {
+            return Err(VaultError::AccessDenied);
         }
+        Ok(cell.clone())
     }
 
     // Показать список занятых ячеек
-    pub fn list(&self) -> Option<String> {
-        if self.cells.is_empty() {
-            None
-        } else {
-            let keys: Vec<String> = self.cells.keys().map(|id| id.to_string()).collect();
-            Some(format!("Occupied cells: {}\n", keys.join(", ")))
+    pub fn list(&self) -> Vec<u32> {
+        let mut ids: Vec<u32> = self.cells.read().unwrap().keys().copied().collect();
+        ids.sort();
+        ids
+    }
+
+    pub fn take(&self, id: u32, name: &str, keys: &Keys) -> Result<Item, VaultError> {
+        let cell = self.cell(id)?;
+        let mut cell = cell.lock().unwrap();
+        if !cell.allows(keys.get(&id)) {
+            return Err(VaultError::AccessDenied);
+        }
+        if !cell.items.iter().any(|i| i.name == name) {
+            return Err(VaultError::ItemNotFound);
         }
+        self.log(Record::Take {
+            cell: id,
+            name: name.to_string(),
+        })?;
+        cell.take(name).map_err(|err| match err {
+            CellError::NotFound => VaultError::ItemNotFound,
+            _ => VaultError::CellNotFound,
+        })
     }
 
-    pub fn take(&mut self, id: u32, name: &str) -> Result<Item, VaultError> {
-        if let Some(cell) = self.cells.get_mut(&id) {
-            cell.take(name).map_err(|err| match err {
-                CellError::NotFound => VaultError::ItemNotFound,
-                _ => VaultError::CellNotFound,
-            })
+    // Переложить предмет в другую ячейку одним изменением
+    pub fn move_item(&self, from: u32, to: u32, name: &str, keys: &Keys) -> Result<(), VaultError> {
+        if from == to {
+            return Err(VaultError::SameCell);
+        }
+        let (source, target) = (self.cell(from)?, self.cell(to)?);
+        // Ячейки блокируются по возрастанию id, чтобы встречные MOVE не ждали друг друга вечно
+        let (mut source, mut target) = if from < to {
+            let source = source.lock().unwrap();
+            (source, target.lock().unwrap())
         } else {
-            Err(VaultError::CellNotFound)
+            let target = target.lock().unwrap();
+            (source.lock().unwrap(), target)
+        };
+        if !source.allows(keys.get(&from)) || !target.allows(keys.get(&to)) {
+            return Err(VaultError::AccessDenied);
         }
+        let Some(item) = source.items.iter().find(|i| i.name == name) else {
+            return Err(VaultError::ItemNotFound);
+        };
+        if !target.fits(item.size) {
+            return Err(VaultError::CellFull);
+        }
+        self.log(Record::Move {
+            from,
+            to,
+            name: name.to_string(),
+        })?;
+        let item = source.take(name).map_err(|_| VaultError::ItemNotFound)?;
+        target.put_item(item).map_err(|_| VaultError::CellFull)
+    }
+
+    // Изменить вместимость ячейки
+    pub fn resize(&self, id: u32, capacity: u32, keys: &Keys) -> Result<(), VaultError> {
+        let cell = self.cell(id)?;
+        let mut cell = cell.lock().unwrap();
+        if !cell.allows(keys.get(&id)) {
+            return Err(VaultError::AccessDenied);
+        }
+        if capacity < cell.used_space {
+            return Err(VaultError::CellTooSmall);
+        }
+        self.log(Record::Resize { cell: id, capacity })?;
+        cell.resize(capacity).map_err(|_| VaultError::CellTooSmall)
+    }
+
+    pub fn stats(&self) -> Stats {
+        let cells: Vec<Arc<Mutex<Cell>>> = self.cells.read().unwrap().values().cloned().collect();
+        let mut stats = Stats {
+            cells: cells.len(),
+            max_cells: self.capacity,
+            items: 0,
+            used_space: 0,
+            capacity: 0,
+        };
+        for cell in cells {
+            let cell = cell.lock().unwrap();
+            stats.items += cell.items.len();
+            stats.used_space += cell.used_space as u64;
+            stats.capacity += cell.capacity as u64;
+        }
+        stats
     }
 }
 
+/// Случайный токен доступа, hex
+fn gen_token() -> String {
+    let bytes: [u8; 16] = rand::random();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 // src/vault.rs
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_take_item_from_cell() {
-        let mut cell = Cell {
+    fn gold_and_silver() -> Cell {
+        Cell {
             items: vec![
                 Item {
                     name: "gold".to_string(),
@@ -157,9 +408,14 @@ mod tests {
                     size: 5,
                 },
             ],
-            capacity: 100,
             used_space: 15,
-        };
+            ..Cell::new(100)
+        }
+    }
+
+    #[test]
+    fn test_take_item_from_cell() {
+        let mut cell = gold_and_silver();
 
         // берём предмет, который есть
         let item = cell.take("gold").expect("should take gold");
@@ -182,45 +438,138 @@ mod tests {
 
     #[test]
     fn test_take_item_from_vault() {
-        let mut vault = Vault {
-            cells: std::collections::HashMap::new(),
-            capacity: 100,
-        };
-
-        vault.cells.insert(
-            1,
-            Cell {
-                items: vec![
-                    Item {
-                        name: "gold".to_string(),
-                        size: 10,
-                    },
-                    Item {
-                        name: "silver".to_string(),
-                        size: 5,
-                    },
-                ],
-                capacity: 100,
-                used_space: 15,
-            },
-        );
+        let vault = Vault::new(100);
+        vault
+            .cells
+            .write()
+            .unwrap()
+            .insert(1, Arc::new(Mutex::new(gold_and_silver())));
+        let keys = Keys::new();
 
         // забираем существующий предмет
-        let item = vault.take(1, "gold").expect("should take gold");
+        let item = vault.take(1, "gold", &keys).expect("should take gold");
         assert_eq!(item.name, "gold");
         assert_eq!(item.size, 10);
 
         // забираем второй предмет
-        let item2 = vault.take(1, "silver").expect("should take silver");
+        let item2 = vault.take(1, "silver", &keys).expect("should take silver");
         assert_eq!(item2.name, "silver");
         assert_eq!(item2.size, 5);
 
         // пытаемся взять из пустой ячейки
-        let res = vault.take(1, "diamond");
+        let res = vault.take(1, "diamond", &keys);
         assert!(matches!(res, Err(VaultError::ItemNotFound)));
 
         // пытаемся взять из несуществующей ячейки
-        let res = vault.take(2, "gold");
+        let res = vault.take(2, "gold", &keys);
         assert!(matches!(res, Err(VaultError::CellNotFound)));
     }
+
+    fn item(name: &str, size: u32) -> Item {
+        Item {
+            name: name.to_string(),
+            size,
+        }
+    }
+
+    #[test]
+    fn test_owner_token() {
+        let vault = Vault::new(10);
+        let token = vault.open(1, "alice", 50).unwrap();
+        assert_eq!(vault.open(1, "bob", 50), Err(VaultError::CellExists));
+
+        let mut keys = Keys::new();
+        assert_eq!(
+            vault.put(1, item("gold", 10), &keys),
+            Err(VaultError::AccessDenied)
+        );
+        assert_eq!(vault.unlock(1, "wrong"), Err(VaultError::AccessDenied));
+        assert_eq!(vault.unlock(1, &token), Ok(()));
+        keys.insert(1, token);
+        vault.put(1, item("gold", 10), &keys).unwrap();
+        assert_eq!(vault.get(1, &keys).unwrap().owner.as_deref(), Some("alice"));
+
+        // Общая ячейка доступна всем
+        vault.put(2, item("sand", 1), &Keys::new()).unwrap();
+        assert_eq!(vault.list(), [1, 2]);
+    }
+
+    #[test]
+    fn test_move_and_resize() {
+        let vault = Vault::new(10);
+        let keys = Keys::new();
+        vault.put(1, item("gold", 60), &keys).unwrap();
+        vault.put(2, item("iron", 50), &keys).unwrap();
+
+        assert_eq!(
+            vault.move_item(1, 2, "gold", &keys),
+            Err(VaultError::CellFull)
+        );
+        assert_eq!(vault.resize(2, 40, &keys), Err(VaultError::CellTooSmall));
+        vault.resize(2, 200, &keys).unwrap();
+        vault.move_item(1, 2, "gold", &keys).unwrap();
+        assert_eq!(
+            vault.move_item(1, 2, "gold", &keys),
+            Err(VaultError::ItemNotFound)
+        );
+        assert_eq!(vault.get(2, &keys).unwrap().used_space, 110);
+
+        let stats = vault.stats();
+        assert_eq!((stats.cells, stats.items, stats.used_space), (2, 2, 110));
+        assert_eq!(stats.capacity, 300);
+    }
+
+    /// Огромный размер от клиента не переполняет used_space и не отравляет ячейку
+    #[test]
+    fn test_oversize_item() {
+        let vault = Vault::new(10);
+        let keys = Keys::new();
+        vault.put(1, item("sand", 1), &keys).unwrap();
+        assert_eq!(
+            vault.put(1, item("huge", u32::MAX), &keys),
+            Err(VaultError::CellFull)
+        );
+
+        vault.put(2, item("empty", 0), &keys).unwrap();
+        vault.resize(2, u32::MAX, &keys).unwrap();
+        vault.put(2, item("huge", u32::MAX - 1), &keys).unwrap();
+        assert_eq!(
+            vault.move_item(2, 1, "huge", &keys),
+            Err(VaultError::CellFull)
+        );
+
+        let mut cell = vault.get(1, &keys).unwrap();
+        assert!(matches!(
+            cell.put_item(item("huge", u32::MAX)),
+            Err(CellError::Full)
+        ));
+        assert_eq!(vault.get(1, &keys).unwrap().used_space, 1);
+        assert_eq!(vault.stats().items, 3);
+    }
+
+    /// Встречные MOVE из разных потоков не блокируют друг друга
+    #[test]
+    fn test_concurrent_moves() {
+        let vault = Arc::new(Vault::new(10));
+        let keys = Keys::new();
+        vault.put(1, item("a", 1), &keys).unwrap();
+        vault.put(2, item("b", 1), &keys).unwrap();
+
+        let threads: Vec<_> = [(1, 2, "a"), (2, 1, "b")]
+            .into_iter()
+            .map(|(from, to, name)| {
+                let vault = vault.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        vault.move_item(from, to, name, &Keys::new()).unwrap();
+                        vault.move_item(to, from, name, &Keys::new()).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(vault.stats().items, 2);
+    }
 }
@@ -0,0 +1,32 @@
+-- Деньги в NUMERIC вместо DOUBLE PRECISION.
+-- Старые суммы округляются до знаков своей валюты, как domain::money::minor_units.
+CREATE OR REPLACE FUNCTION pg_temp.minor_units(code VARCHAR) RETURNS INTEGER AS $$
+    SELECT CASE
+        WHEN code IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF',
+                      'UGX', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 0
+        WHEN code IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 3
+        ELSE 2
+    END
+$$ LANGUAGE SQL IMMUTABLE;
+
+ALTER TABLE accounts
+    ALTER COLUMN balance TYPE NUMERIC
+        USING round(balance::numeric, pg_temp.minor_units(currency));
+
+ALTER TABLE transactions
+    ALTER COLUMN amount TYPE NUMERIC
+        USING round(amount::numeric, pg_temp.minor_units(currency)),
+    ALTER COLUMN rate TYPE NUMERIC
+        USING round(rate::numeric, 8),
+    ADD COLUMN IF NOT EXISTS converted_amount NUMERIC;
+
+-- Зачисленная сумма в валюте получателя
+UPDATE transactions t
+SET converted_amount = round(
+    t.amount * t.rate,
+    pg_temp.minor_units(COALESCE((SELECT a.currency FROM accounts a WHERE a.id = t.to_id), t.currency))
+)
+WHERE converted_amount IS NULL;
+
+ALTER TABLE transactions
+    ALTER COLUMN converted_amount SET NOT NULL;
@@ -0,0 +1,28 @@
+-- Ответы денежных операций по ключу Idempotency-Key.
+-- response IS NULL, пока первый запрос с ключом выполняется.
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    user_id UUID NOT NULL,
+    idempotency_key VARCHAR(255) NOT NULL,
+    request_hash TEXT NOT NULL,
+    response TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+    PRIMARY KEY (user_id, idempotency_key),
+
+    CONSTRAINT fk_idempotency_keys_user
+        FOREIGN KEY (user_id)
+        REFERENCES users(id)
+        ON DELETE CASCADE
+);
+
+-- Баланс не уходит в минус даже при гонке обновлений
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM pg_constraint WHERE conname = 'accounts_balance_non_negative'
+    ) THEN
+        ALTER TABLE accounts
+            ADD CONSTRAINT accounts_balance_non_negative CHECK (balance >= 0);
+    END IF;
+END
+$$;
@@ -0,0 +1,77 @@
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM pg_type WHERE typname = 'system_account'
+    ) THEN
+        CREATE TYPE system_account AS ENUM (
+            'cash',
+            'exchange'
+        );
+    END IF;
+END
+$$;
+
+-- Проводки двойной записи: у каждой транзакции сумма проводок в каждой валюте равна нулю,
+-- баланс счета равен сумме его проводок
+CREATE TABLE IF NOT EXISTS postings (
+    id UUID PRIMARY KEY,
+    transaction_id UUID NOT NULL,
+    account_id UUID,
+    system_account system_account,
+    currency VARCHAR(3) NOT NULL,
+    amount NUMERIC NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+    CONSTRAINT fk_postings_transaction
+        FOREIGN KEY (transaction_id)
+        REFERENCES transactions(id),
+
+    CONSTRAINT fk_postings_account
+        FOREIGN KEY (account_id)
+        REFERENCES accounts(id),
+
+    CONSTRAINT postings_one_account
+        CHECK ((account_id IS NULL) <> (system_account IS NULL))
+);
+
+CREATE INDEX IF NOT EXISTS postings_account_id_idx ON postings (account_id);
+CREATE INDEX IF NOT EXISTS postings_transaction_id_idx ON postings (transaction_id);
+
+-- Проводки для транзакций, записанных до журнала. Балансы не трогаются:
+-- если они уже разошлись с историей, это покажет сверка
+WITH legacy AS (
+    SELECT t.id, t.operation, t.amount, t.currency, t.converted_amount,
+           t.from_id, t.to_id, t.created_at,
+           COALESCE(dst.currency, t.currency) AS to_currency
+    FROM transactions t
+    LEFT JOIN accounts dst ON dst.id = t.to_id
+    WHERE NOT EXISTS (SELECT 1 FROM postings p WHERE p.transaction_id = t.id)
+)
+INSERT INTO postings (id, transaction_id, account_id, system_account, currency, amount, created_at)
+SELECT gen_random_uuid(), id, account_id, system_account, currency, amount, created_at
+FROM (
+    SELECT id, NULL::UUID AS account_id, 'cash'::system_account AS system_account,
+           currency, -amount AS amount, created_at
+    FROM legacy WHERE operation = 'deposit'
+    UNION ALL
+    SELECT id, to_id, NULL, currency, amount, created_at
+    FROM legacy WHERE operation = 'deposit'
+    UNION ALL
+    SELECT id, from_id, NULL, currency, -amount, created_at
+    FROM legacy WHERE operation = 'withdrawal'
+    UNION ALL
+    SELECT id, NULL, 'cash', currency, amount, created_at
+    FROM legacy WHERE operation = 'withdrawal'
+    UNION ALL
+    SELECT id, from_id, NULL, currency, -amount, created_at
+    FROM legacy WHERE operation = 'transfer'
+    UNION ALL
+    SELECT id, to_id, NULL, to_currency, converted_amount, created_at
+    FROM legacy WHERE operation = 'transfer'
+    UNION ALL
+    SELECT id, NULL, 'exchange', currency, amount, created_at
+    FROM legacy WHERE operation = 'transfer' AND currency <> to_currency
+    UNION ALL
+    SELECT id, NULL, 'exchange', to_currency, -converted_amount, created_at
+    FROM legacy WHERE operation = 'transfer' AND currency <> to_currency
+) AS entries;
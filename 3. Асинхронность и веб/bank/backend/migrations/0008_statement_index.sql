@@ -0,0 +1,3 @@
+-- Выписка читает проводки счета по (created_at, id), этот индекс заменяет индекс по account_id
+CREATE INDEX IF NOT EXISTS postings_account_created_idx ON postings (account_id, created_at, id);
+DROP INDEX IF EXISTS postings_account_id_idx;
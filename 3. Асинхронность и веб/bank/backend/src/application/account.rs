@@ -1,21 +1,49 @@
+use rust_decimal::Decimal;
 use std::sync::Arc;
 use tracing::info;
 use uuid::Uuid;
 
 use crate::{
     data::Database,
-    domain::{account::Account, user::User},
+    domain::{
+        account::{Account, DEFAULT_CURRENCY},
+        money::Money,
+        user::User,
+    },
     infrastructure::error::ErrorApi,
 };
 
 pub async fn create_account(
     db: Arc<Database>,
     user: &User,
-    amount: Option<f64>,
+    amount: Option<Decimal>,
+    currency: Option<String>,
 ) -> Result<Account, ErrorApi> {
-    info!("Creating account for user {}", user.id());
-    let mut repo = db.get_account_repo();
-    repo.create(user, amount).await
+    let currency = currency
+        .map(|c| c.trim().to_uppercase())
+        .unwrap_or_else(|| DEFAULT_CURRENCY.to_string());
+    info!("Creating {} account for user {}", currency, user.id());
+    let amount = match amount {
+        Some(amount) => Money::new(amount, &currency)?,
+        None => Money::zero(&currency),
+    };
+    if amount.is_negative() {
+        return Err(ErrorApi::Validation(
+            "Account balance cannot be negative".to_string(),
+        ));
+    }
+    let mut repo = db.clone().get_account_repo();
+    let account = repo.create(user, currency).await?;
+    if !amount.is_positive() {
+        return Ok(account);
+    }
+
+    // Начальный баланс - обычное пополнение, чтобы он попал в проводки
+    let mut repo_tran = db.get_transaction_repo();
+    repo_tran.create_deposit(amount, &account, None).await?;
+    repo.get_by_id(*account.id())
+        .await
+        .ok_or(ErrorApi::NotFound("Account not found".to_string()))
 }
 
 pub async fn get_account_by_id(db: Arc<Database>, id: Uuid) -> Option<Account> {
@@ -0,0 +1,92 @@
+use chrono::Utc;
+use serde::Serialize;
+use std::{future::Future, sync::Arc};
+use tracing::info;
+
+use crate::{
+    data::Database,
+    domain::{
+        idempotency::{self, Completion},
+        transaction::Transaction,
+        user::User,
+    },
+    infrastructure::error::ErrorApi,
+};
+
+/// Ответ операции в JSON
+pub struct Idempotent {
+    pub body: String,
+    /// Ответ сохранен первым запросом с тем же ключом
+    pub replayed: bool,
+}
+
+/// Выполнить денежную операцию один раз на ключ, ответ - `R` из транзакции.
+///
+/// Повтор с тем же ключом и запросом получает сохраненный ответ, пока первый
+/// запрос выполняется - конфликт. Ответ сохраняется в одной транзакции с движением
+/// денег, неудачная операция ключ освобождает. Ключ, брошенный прерванным
+/// запросом, можно занять снова через `IDEMPOTENCY_LEASE`.
+pub async fn execute<R, F, Fut>(
+    db: Arc<Database>,
+    user: &User,
+    key: Option<String>,
+    request: &str,
+    operation: F,
+) -> Result<Idempotent, ErrorApi>
+where
+    R: Serialize + From<Transaction>,
+    F: FnOnce(Option<Completion>) -> Fut,
+    Fut: Future<Output = Result<Transaction, ErrorApi>>,
+{
+    let Some(key) = key else {
+        return Ok(Idempotent {
+            body: respond::<R>(&operation(None).await?)?,
+            replayed: false,
+        });
+    };
+
+    let record = idempotency::factory::create(*user.id(), key, request, Utc::now())?;
+    let mut repo = db.get_idempotency_repo();
+    if let Some(existing) = repo.acquire(&record).await? {
+        if existing.request_hash() != record.request_hash() {
+            return Err(ErrorApi::Validation(
+                "Idempotency key was used with a different request".to_string(),
+            ));
+        }
+        let Some(body) = existing.response() else {
+            return Err(ErrorApi::Conflict(
+                "Request with this idempotency key is in progress".to_string(),
+            ));
+        };
+        info!("Replaying response for idempotency key {}", record.key());
+        return Ok(Idempotent {
+            body: body.clone(),
+            replayed: true,
+        });
+    }
+
+    let completion = Completion {
+        key: record.clone(),
+        respond: respond::<R>,
+    };
+    // Тело то же, что сохранено с операцией: оба из одной транзакции
+    match operation(Some(completion))
+        .await
+        .and_then(|transaction| respond::<R>(&transaction))
+    {
+        Ok(body) => Ok(Idempotent {
+            body,
+            replayed: false,
+        }),
+        Err(err) => {
+            repo.release(&record).await?;
+            Err(err)
+        }
+    }
+}
+
+fn respond<R: Serialize + From<Transaction>>(
+    transaction: &Transaction,
+) -> Result<String, ErrorApi> {
+    serde_json::to_string(&R::from(transaction.clone())).map_err(|e| ErrorApi::Inner(e.to_string()))
+}
@@ -0,0 +1,26 @@
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::{data::Database, domain::ledger::Reconciliation, infrastructure::error::ErrorApi};
+
+/// Сверить балансы счетов с проводками
+pub async fn reconcile(db: Arc<Database>) -> Result<Reconciliation, ErrorApi> {
+    let repo = db.get_ledger_repo();
+    let report = repo.reconcile().await?;
+    info!(
+        "Reconciled {} accounts: {} mismatches, {} unbalanced transactions",
+        report.accounts_checked,
+        report.mismatches.len(),
+        report.unbalanced_transactions.len()
+    );
+    for mismatch in &report.mismatches {
+        warn!(
+            "Account {} balance {} {} differs from ledger {}",
+            mismatch.account_id,
+            mismatch.stored_balance,
+            mismatch.currency,
+            mismatch.ledger_balance
+        );
+    }
+    Ok(report)
+}
@@ -1,4 +1,7 @@
 pub mod account;
 pub mod course;
+pub mod idempotency;
+pub mod ledger;
+pub mod statement;
 pub mod transaction;
 pub mod user;
@@ -0,0 +1,61 @@
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    data::Database,
+    domain::{
+        account::Account,
+        statement::{StatementFilter, StatementLine, StatementPage, MAX_PAGE_SIZE},
+        user::User,
+    },
+    infrastructure::error::ErrorApi,
+};
+
+/// Страница выписки по счету пользователя
+pub async fn statement(
+    db: Arc<Database>,
+    user: &User,
+    account_id: Uuid,
+    filter: &StatementFilter,
+) -> Result<(Account, StatementPage), ErrorApi> {
+    info!("Getting statement of account {}", account_id);
+    filter.validate()?;
+    let Some(account) = db.clone().get_account_repo().get_by_id(account_id).await else {
+        return Err(ErrorApi::NotFound(format!("Account {}", account_id)));
+    };
+    if account.user_id() != user.id() {
+        return Err(ErrorApi::Forbidden(format!(
+            "Account {} does not belong to user {}",
+            account_id,
+            user.id()
+        )));
+    }
+    let page = db
+        .get_transaction_repo()
+        .statement(&account, filter)
+        .await?;
+    Ok((account, page))
+}
+
+/// Вся выписка по фильтру, начиная с курсора фильтра: для выгрузки в файл
+pub async fn export(
+    db: Arc<Database>,
+    user: &User,
+    account_id: Uuid,
+    filter: StatementFilter,
+) -> Result<(Account, Vec<StatementLine>), ErrorApi> {
+    let mut filter = StatementFilter {
+        limit: MAX_PAGE_SIZE,
+        ..filter
+    };
+    let mut lines = Vec::new();
+    loop {
+        let (account, page) = statement(db.clone(), user, account_id, &filter).await?;
+        lines.extend(page.lines);
+        match page.next_cursor {
+            Some(cursor) => filter.after = Some(cursor),
+            None => return Ok((account, lines)),
+        }
+    }
+}
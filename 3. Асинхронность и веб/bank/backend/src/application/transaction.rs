@@ -1,23 +1,27 @@
+use reqwest::Client;
+use rust_decimal::Decimal;
 use std::sync::Arc;
 use tracing::info;
 use uuid::Uuid;
 
 use crate::{
+    application::course,
     data::Database,
-    domain::{transaction::Transaction, user::User},
-    infrastructure::error::ErrorApi,
+    domain::{idempotency::Completion, money::Money, transaction::Transaction, user::User},
+    infrastructure::{config::Config, error::ErrorApi},
 };
 
 pub async fn deposit(
     db: Arc<Database>,
     user: &User,
     account_id: Uuid,
-    amount: f64,
+    amount: Decimal,
+    completion: Option<Completion>,
 ) -> Result<Transaction, ErrorApi> {
     info!("Depositing {} to account {}", amount, account_id);
-    let mut repo_acc = db.clone().get_account_repo();
+    let repo_acc = db.clone().get_account_repo();
     let mut repo_tran = db.clone().get_transaction_repo();
-    let Some(mut account) = repo_acc.get_by_id(account_id).await else {
+    let Some(account) = repo_acc.get_by_id(account_id).await else {
         return Err(ErrorApi::Validation("Account not found".to_string()));
     };
     if account.user_id() != user.id() {
@@ -27,26 +31,21 @@ pub async fn deposit(
             user.id()
         )));
     }
-    account.set_balance(*account.balance() + amount);
-
-    let tx = db.transaction().await?;
-    repo_acc.update(&account).await?;
-    let transaction = repo_tran.create_deposit(amount, &account).await?;
-    tx.commit().await?;
-    Ok(transaction)
+    let amount = operation_amount(amount, account.currency())?;
+    repo_tran.create_deposit(amount, &account, completion).await
 }
 
 pub async fn withdraw(
     db: Arc<Database>,
     user: &User,
     account_id: Uuid,
-    amount: f64,
+    amount: Decimal,
+    completion: Option<Completion>,
 ) -> Result<Transaction, ErrorApi> {
     info!("Withdrawing {} from account {}", amount, account_id);
-    let mut tx = db.clone().transaction().await?;
-    let mut repo_acc = db.clone().get_account_repo();
+    let repo_acc = db.clone().get_account_repo();
     let mut repo_tran = db.clone().get_transaction_repo();
-    let Some(mut account) = repo_acc.get_by_id(account_id).await else {
+    let Some(account) = repo_acc.get_by_id(account_id).await else {
         return Err(ErrorApi::Validation("Account not found".to_string()));
     };
     if account.user_id() != user.id() {
@@ -56,36 +55,31 @@ pub async fn withdraw(
             user.id()
         )));
     }
-    if *account.balance() < amount {
-        return Err(ErrorApi::Validation(format!(
-            "Account balance is not enough: {}",
-            account.balance()
-        )));
-    }
-    account.set_balance(*account.balance() - amount);
-
-    let mut tx = db.transaction().await?;
-    repo_acc.update(&account).await?;
-    let transaction = repo_tran.create_withdrawal(amount, &account).await?;
-    tx.commit().await?;
-
-    Ok(transaction)
+    let amount = operation_amount(amount, account.currency())?;
+    repo_tran
+        .create_withdrawal(amount, &account, completion)
+        .await
 }
 
+/// Перевод в валюте счета-источника, между разными валютами - по текущему курсу
+#[allow(clippy::too_many_arguments)]
 pub async fn transfer(
     db: Arc<Database>,
+    client: Arc<Client>,
+    config: Arc<Config>,
     user: &User,
     from_account_id: Uuid,
     to_account_id: Uuid,
-    amount: f64,
+    amount: Decimal,
+    completion: Option<Completion>,
 ) -> Result<Transaction, ErrorApi> {
     info!(
         "Transferring {} from {} to {}",
         amount, from_account_id, to_account_id
     );
-    let mut repo_acc = db.clone().get_account_repo();
+    let repo_acc = db.clone().get_account_repo();
     let mut repo_tran = db.clone().get_transaction_repo();
-    let Some(mut from_account) = repo_acc.get_by_id(from_account_id).await else {
+    let Some(from_account) = repo_acc.get_by_id(from_account_id).await else {
         return Err(ErrorApi::Validation("Account not found".to_string()));
     };
     if from_account.user_id() != user.id() {
@@ -93,26 +87,523 @@ pub async fn transfer(
             "Account does not belong to user".to_string(),
         ));
     }
-    if *from_account.balance() < amount {
-        return Err(ErrorApi::Validation(
-            "Account balance is not enough".to_string(),
-        ));
-    }
+    let amount = operation_amount(amount, from_account.currency())?;
 
-    let Some(mut to_account) = repo_acc.get_by_id(to_account_id).await else {
+    let Some(to_account) = repo_acc.get_by_id(to_account_id).await else {
         return Err(ErrorApi::Validation("Account not found".to_string()));
     };
 
-    from_account.set_balance(*from_account.balance() - amount);
-    to_account.set_balance(*to_account.balance() + amount);
+    let rate = if from_account.currency() == to_account.currency() {
+        Decimal::ONE
+    } else {
+        let course = course::get_course(db.clone(), client, config).await?;
+        course.rate(from_account.currency(), to_account.currency())?
+    };
+    info!(
+        "Transfer rate {} -> {}: {}",
+        from_account.currency(),
+        to_account.currency(),
+        rate
+    );
+
+    repo_tran
+        .create_transfer(amount, rate, &from_account, &to_account, completion)
+        .await
+}
+
+/// Сумма операции в валюте счета, только положительная
+fn operation_amount(amount: Decimal, currency: &str) -> Result<Money, ErrorApi> {
+    let amount = Money::new(amount, currency)?;
+    if !amount.is_positive() {
+        return Err(ErrorApi::Validation("Amount must be positive".to_string()));
+    }
+    Ok(amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        application::{account, idempotency, ledger, statement},
+        domain::{
+            idempotency::{self as idempotency_key, IDEMPOTENCY_LEASE},
+            statement::StatementFilter,
+            transaction::Operation,
+        },
+        infrastructure::state::State,
+    };
+    use chrono::Utc;
+    use serde::Serialize;
+    use sqlx::postgres::PgPoolOptions;
+    use std::str::FromStr;
+
+    const TASKS: usize = 200;
+
+    /// Ответ операции в тестах - id транзакции
+    #[derive(Serialize)]
+    struct Created(Uuid);
+
+    impl From<Transaction> for Created {
+        fn from(transaction: Transaction) -> Self {
+            Created(*transaction.id())
+        }
+    }
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    fn config() -> Arc<Config> {
+        Arc::new(Config {
+            database_url: String::new(),
+            host: String::new(),
+            port: 0,
+            jwt_secret: String::new(),
+            cors_origin: String::new(),
+            api_key: String::new(),
+            admin_key: None,
+        })
+    }
+
+    /// Пользователь с двумя пустыми счетами в долларах
+    async fn setup(db: &Arc<Database>) -> (User, Uuid, Uuid) {
+        let user = db
+            .clone()
+            .get_user_repo()
+            .create(
+                format!("{}@test.io", Uuid::new_v4().simple()),
+                "password123".to_string(),
+            )
+            .await
+            .unwrap();
+        let mut repo = db.clone().get_account_repo();
+        let from = repo.create(&user, "USD".to_string()).await.unwrap();
+        let to = repo.create(&user, "USD".to_string()).await.unwrap();
+        (user, *from.id(), *to.id())
+    }
+
+    async fn balance(db: &Arc<Database>, id: Uuid) -> Decimal {
+        let account = db.clone().get_account_repo().get_by_id(id).await.unwrap();
+        account.balance().amount()
+    }
+
+    /// Много задач одновременно пополняют, списывают и переводят с одного счета
+    async fn hammer(db: Arc<Database>) {
+        let (user, from, to) = setup(&db).await;
+        deposit(db.clone(), &user, from, dec("100"), None)
+            .await
+            .unwrap();
+
+        let tasks: Vec<_> = (0..TASKS)
+            .map(|i| {
+                let (db, user) = (db.clone(), user.clone());
+                tokio::spawn(async move {
+                    let client = Arc::new(Client::new());
+                    match i % 3 {
+                        0 => deposit(db, &user, from, dec("1"), None).await,
+                        1 => withdraw(db, &user, from, dec("3"), None).await,
+                        _ => transfer(db, client, config(), &user, from, to, dec("2"), None).await,
+                    }
+                })
+            })
+            .collect();
+        let mut succeeded = [0; 3];
+        for (i, task) in tasks.into_iter().enumerate() {
+            match task.await.unwrap() {
+                Ok(_) => succeeded[i % 3] += 1,
+                Err(ErrorApi::Validation(message)) => {
+                    assert_eq!(message, "Account balance is not enough")
+                }
+                Err(e) => panic!("{}", e),
+            }
+        }
+
+        let (from_balance, to_balance) = (balance(&db, from).await, balance(&db, to).await);
+        let expected = dec("100") + Decimal::from(succeeded[0])
+            - Decimal::from(succeeded[1] * 3)
+            - Decimal::from(succeeded[2] * 2);
+        assert_eq!(from_balance, expected);
+        assert_eq!(to_balance, Decimal::from(succeeded[2] * 2));
+        assert!(from_balance >= Decimal::ZERO);
+
+        // Каждая успешная операция записана, и только она
+        let account = db.clone().get_account_repo().get_by_id(from).await.unwrap();
+        let (_, recorded) = statement::export(db.clone(), &user, from, Default::default())
+            .await
+            .unwrap();
+        assert_eq!(recorded.len(), 1 + succeeded.iter().sum::<usize>());
+        assert_eq!(recorded.last().unwrap().balance, *account.balance());
+
+        // Списаний на сумму больше баланса не бывает: проходит ровно столько, сколько хватает
+        let drain: Vec<_> = (0..TASKS)
+            .map(|_| {
+                let (db, user) = (db.clone(), user.clone());
+                tokio::spawn(async move { withdraw(db, &user, to, dec("1"), None).await })
+            })
+            .collect();
+        let mut drained = 0;
+        for task in drain {
+            drained += task.await.unwrap().is_ok() as usize;
+        }
+        assert_eq!(Decimal::from(drained), to_balance);
+        assert_eq!(balance(&db, to).await, Decimal::ZERO);
+
+        // Балансы сходятся с проводками
+        let report = ledger::reconcile(db.clone()).await.unwrap();
+        assert!(report
+            .mismatches
+            .iter()
+            .all(|mismatch| mismatch.account_id != from && mismatch.account_id != to));
+        assert!(report.unbalanced_transactions.is_empty());
+    }
+
+    /// Сверка находит баланс, измененный в обход проводок
+    async fn drift(db: Arc<Database>) {
+        let (user, from, to) = setup(&db).await;
+        let opened = account::create_account(
+            db.clone(),
+            &user,
+            Some(dec("7.50")),
+            Some("EUR".to_string()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(opened.balance().amount(), dec("7.50"));
+        deposit(db.clone(), &user, from, dec("10"), None)
+            .await
+            .unwrap();
+        let client = Arc::new(Client::new());
+        transfer(
+            db.clone(),
+            client,
+            config(),
+            &user,
+            from,
+            to,
+            dec("4"),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let ours = |report: &crate::domain::ledger::Reconciliation| {
+            report
+                .mismatches
+                .iter()
+                .filter(|m| [from, to, *opened.id()].contains(&m.account_id))
+                .map(|m| {
+                    (
+                        m.account_id,
+                        m.stored_balance.amount(),
+                        m.ledger_balance.amount(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let report = ledger::reconcile(db.clone()).await.unwrap();
+        assert!(ours(&report).is_empty());
+
+        let mut repo = db.clone().get_account_repo();
+        let mut account = repo.get_by_id(to).await.unwrap();
+        account.set_balance(Money::new(dec("100"), "USD").unwrap());
+        repo.update(&account).await.unwrap();
+
+        let report = ledger::reconcile(db.clone()).await.unwrap();
+        assert_eq!(ours(&report), [(to, dec("100.00"), dec("4.00"))]);
+    }
+
+    /// Выписка листается курсором без пропусков, остаток считается по всем строкам
+    async fn lines(db: Arc<Database>) {
+        let (user, from, to) = setup(&db).await;
+        for amount in ["10", "20", "30"] {
+            deposit(db.clone(), &user, from, dec(amount), None)
+                .await
+                .unwrap();
+        }
+        withdraw(db.clone(), &user, from, dec("5"), None)
+            .await
+            .unwrap();
+        let client = Arc::new(Client::new());
+        transfer(
+            db.clone(),
+            client,
+            config(),
+            &user,
+            from,
+            to,
+            dec("15"),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let mut filter = StatementFilter {
+            limit: 2,
+            ..Default::default()
+        };
+        let mut pages = Vec::new();
+        loop {
+            let (_, page) = statement::statement(db.clone(), &user, from, &filter)
+                .await
+                .unwrap();
+            pages.push(page.lines);
+            match page.next_cursor {
+                Some(cursor) => filter.after = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), [2, 2, 1]);
+        let all: Vec<_> = pages.into_iter().flatten().collect();
+        let amounts: Vec<_> = all.iter().map(|l| l.amount.amount()).collect();
+        let balances: Vec<_> = all.iter().map(|l| l.balance.amount()).collect();
+        assert_eq!(amounts, ["10", "20", "30", "-5", "-15"].map(dec));
+        assert_eq!(balances, ["10", "30", "60", "55", "40"].map(dec));
+        assert_eq!(all[4].counterparty, Some(to));
+
+        // Фильтры не меняют остаток строки
+        let filter = StatementFilter {
+            operation: Some(Operation::DEPOSIT),
+            min_amount: Some(dec("15")),
+            ..Default::default()
+        };
+        let (_, page) = statement::statement(db.clone(), &user, from, &filter)
+            .await
+            .unwrap();
+        let found: Vec<_> = page
+            .lines
+            .iter()
+            .map(|l| (l.amount.amount(), l.balance.amount()))
+            .collect();
+        assert_eq!(found, [(dec("20"), dec("30")), (dec("30"), dec("60"))]);
+        assert!(page.next_cursor.is_none());
+
+        let (_, page) = statement::statement(
+            db.clone(),
+            &user,
+            to,
+            &StatementFilter {
+                to: Some(all[0].created_at),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(page.lines.is_empty());
+
+        let stranger = db
+            .clone()
+            .get_user_repo()
+            .create(
+                format!("{}@test.io", Uuid::new_v4().simple()),
+                "password123".to_string(),
+            )
+            .await
+            .unwrap();
+        let denied = statement::statement(db.clone(), &stranger, from, &Default::default()).await;
+        assert!(matches!(denied, Err(ErrorApi::Forbidden(_))));
+    }
+
+    /// Повторы одного запроса с одним ключом зачисляют деньги один раз
+    async fn retry(db: Arc<Database>) {
+        let (user, account, _) = setup(&db).await;
+        let key = Uuid::new_v4().to_string();
+        let request = format!("deposit {} 5", account);
+
+        let tasks: Vec<_> = (0..TASKS / 10)
+            .map(|_| {
+                let (db, user, key, request) =
+                    (db.clone(), user.clone(), key.clone(), request.clone());
+                tokio::spawn(async move {
+                    idempotency::execute::<Created, _, _>(
+                        db.clone(),
+                        &user,
+                        Some(key),
+                        &request,
+                        |completion| deposit(db, &user, account, dec("5"), completion),
+                    )
+                    .await
+                })
+            })
+            .collect();
+        let mut bodies = Vec::new();
+        for task in tasks {
+            match task.await.unwrap() {
+                Ok(response) => bodies.push((response.body, response.replayed)),
+                Err(ErrorApi::Conflict(_)) => {}
+                Err(e) => panic!("{}", e),
+            }
+        }
+        assert_eq!(bodies.iter().filter(|(_, replayed)| !replayed).count(), 1);
+        assert!(bodies.iter().all(|(body, _)| *body == bodies[0].0));
+        assert_eq!(balance(&db, account).await, dec("5"));
+
+        // Повтор после завершения отдает тот же ответ
+        let replay = idempotency::execute::<Created, _, _>(
+            db.clone(),
+            &user,
+            Some(key.clone()),
+            &request,
+            |completion| deposit(db.clone(), &user, account, dec("5"), completion),
+        )
+        .await
+        .unwrap();
+        assert!(replay.replayed);
+        assert_eq!(replay.body, bodies[0].0);
+        assert_eq!(balance(&db, account).await, dec("5"));
+
+        // Тот же ключ с другим запросом - ошибка
+        let other = idempotency::execute::<Created, _, _>(
+            db.clone(),
+            &user,
+            Some(key),
+            "deposit 6",
+            |completion| deposit(db.clone(), &user, account, dec("6"), completion),
+        )
+        .await;
+        assert!(matches!(other, Err(ErrorApi::Validation(_))));
+        assert_eq!(balance(&db, account).await, dec("5"));
+    }
+
+    /// Ответ не сохранился - деньги не двигаются, и ключ не зависает
+    async fn abandoned(db: Arc<Database>) {
+        let (user, account, _) = setup(&db).await;
+        let key = Uuid::new_v4().to_string();
+        let request = format!("deposit {} 5", account);
+        let execute = |key: String, fail: bool| {
+            let (db, user, request) = (db.clone(), &user, &request);
+            async move {
+                idempotency::execute::<Created, _, _>(
+                    db.clone(),
+                    user,
+                    Some(key),
+                    request,
+                    |completion| async move {
+                        // Ключ пропал до завершения: сохранить ответ не выйдет
+                        if let (true, Some(completion)) = (fail, &completion) {
+                            let mut repo = db.clone().get_idempotency_repo();
+                            repo.release(&completion.key).await.unwrap();
+                        }
+                        deposit(db, user, account, dec("5"), completion).await
+                    },
+                )
+                .await
+            }
+        };
+
+        let failed = execute(key.clone(), true).await;
+        assert!(matches!(failed, Err(ErrorApi::Conflict(_))));
+        assert_eq!(balance(&db, account).await, dec("0"));
+
+        let retried = execute(key.clone(), false).await.unwrap();
+        assert!(!retried.replayed);
+        assert_eq!(balance(&db, account).await, dec("5"));
+        let replay = execute(key, false).await.unwrap();
+        assert!(replay.replayed);
+        assert_eq!(replay.body, retried.body);
+        assert_eq!(balance(&db, account).await, dec("5"));
+
+        // Ключ занят недавно - конфликт, занят прерванным запросом давно - занимается заново
+        let mut repo = db.clone().get_idempotency_repo();
+        let busy = Uuid::new_v4().to_string();
+        let claim =
+            idempotency_key::factory::create(*user.id(), busy.clone(), &request, Utc::now())
+                .unwrap();
+        assert!(repo.acquire(&claim).await.unwrap().is_none());
+        assert!(matches!(
+            execute(busy, false).await,
+            Err(ErrorApi::Conflict(_))
+        ));
+
+        let stale_key = Uuid::new_v4().to_string();
+        let stale = idempotency_key::factory::create(
+            *user.id(),
+            stale_key.clone(),
+            &request,
+            Utc::now() - IDEMPOTENCY_LEASE * 2,
+        )
+        .unwrap();
+        assert!(repo.acquire(&stale).await.unwrap().is_none());
+        assert!(!execute(stale_key, false).await.unwrap().replayed);
+        assert_eq!(balance(&db, account).await, dec("10"));
+
+        // Прерванный запрос уже не может сохранить ответ по чужому ключу
+        let late = Completion {
+            key: stale,
+            respond: |transaction| Ok(transaction.id().to_string()),
+        };
+        let late = deposit(db.clone(), &user, account, dec("5"), Some(late)).await;
+        assert!(matches!(late, Err(ErrorApi::Conflict(_))));
+        assert_eq!(balance(&db, account).await, dec("10"));
+    }
+
+    fn state() -> Arc<Database> {
+        Arc::new(Database::STATE(Arc::new(State::new())))
+    }
+
+    async fn postgres() -> Arc<Database> {
+        let url = std::env::var("DATABASE_URL").expect("DATABASE_URL is required");
+        let pool = PgPoolOptions::new()
+            .max_connections(20)
+            .connect(&url)
+            .await
+            .unwrap();
+        crate::infrastructure::migrate::run(&pool).await.unwrap();
+        Arc::new(Database::PgSQL(Arc::new(pool)))
+    }
 
-    let mut tx = db.transaction().await?;
-    repo_acc.update(&from_account).await?;
-    repo_acc.update(&to_account).await?;
-    let transaction = repo_tran
-        .create_transfer(amount, &from_account, &to_account)
-        .await?;
-    tx.commit().await?;
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_concurrent_state() {
+        hammer(state()).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_idempotent_retry_state() {
+        retry(state()).await;
+    }
 
-    Ok(transaction)
+    #[tokio::test]
+    async fn test_abandoned_key_state() {
+        abandoned(state()).await;
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_state() {
+        drift(state()).await;
+    }
+
+    #[tokio::test]
+    async fn test_statement_state() {
+        lines(state()).await;
+    }
+
+    // Нужен Postgres: DATABASE_URL=... cargo test -- --ignored
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    #[ignore]
+    async fn test_concurrent_postgres() {
+        hammer(postgres().await).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    #[ignore]
+    async fn test_idempotent_retry_postgres() {
+        retry(postgres().await).await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_abandoned_key_postgres() {
+        abandoned(postgres().await).await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_reconcile_postgres() {
+        drift(postgres().await).await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_statement_postgres() {
+        lines(postgres().await).await;
+    }
 }
@@ -1,26 +1,25 @@
 pub mod sql;
 pub mod state;
-pub mod transaction;
 
 use crate::{
     data::{sql::course::CourseSQLRepo, state::course::CourseStateRepo},
     domain::{
-        account::AccountRepository, course::CourseRepository, token::RefreshTokenRepository,
+        account::AccountRepository, course::CourseRepository, idempotency::IdempotencyRepository,
+        ledger::LedgerRepository, token::RefreshTokenRepository,
         transaction::TransactionRepository, user::UserRepository,
     },
-    infrastructure::{error::ErrorApi, state::State},
+    infrastructure::state::State,
 };
 use sql::{
-    account::AccountSQLRepo, token::RefreshTokenSQLRepo, transactions::TransactionSQLRepo,
-    user::UserSQLRepo, DBTransactionSQL,
+    account::AccountSQLRepo, idempotency::IdempotencySQLRepo, ledger::LedgerSQLRepo,
+    token::RefreshTokenSQLRepo, transactions::TransactionSQLRepo, user::UserSQLRepo,
 };
 use sqlx::PgPool;
 use state::{
-    account::AccountStateRepo, token::RefreshTokenStateRepo, transactions::TransactionStateRepo,
-    user::UserStateRepo, DBTransactionState,
+    account::AccountStateRepo, idempotency::IdempotencyStateRepo, ledger::LedgerStateRepo,
+    token::RefreshTokenStateRepo, transactions::TransactionStateRepo, user::UserStateRepo,
 };
 use std::sync::Arc;
-use transaction::DBTransaction;
 
 #[derive(Clone, Debug)]
 pub enum Database {
@@ -39,18 +38,6 @@ macro_rules! fn_get_repo {
     };
 }
 
-impl Database {
-    pub async fn transaction(self: Arc<Self>) -> Result<DBTransaction, ErrorApi> {
-        match self.as_ref() {
-            Database::PgSQL(pool) => match DBTransactionSQL::new(pool.clone()).await {
-                Ok(tx) => Ok(DBTransaction::SQL(tx)),
-                Err(err) => Err(err),
-            },
-            Database::STATE(_) => Ok(DBTransaction::STATE(DBTransactionState::new())),
-        }
-    }
-}
-
 impl Database {
     fn_get_repo!(get_user_repo, UserRepository, UserSQLRepo, UserStateRepo);
     fn_get_repo!(
@@ -77,4 +64,16 @@ impl Database {
         CourseSQLRepo,
         CourseStateRepo
     );
+    fn_get_repo!(
+        get_idempotency_repo,
+        IdempotencyRepository,
+        IdempotencySQLRepo,
+        IdempotencyStateRepo
+    );
+    fn_get_repo!(
+        get_ledger_repo,
+        LedgerRepository,
+        LedgerSQLRepo,
+        LedgerStateRepo
+    );
 }
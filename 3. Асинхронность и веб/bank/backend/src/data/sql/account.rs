@@ -1,11 +1,13 @@
 use crate::{
     domain::{
         account::{self, Account, AccountRepository},
+        money::Money,
         user::User,
     },
     infrastructure::error::ErrorApi,
 };
 use async_trait::async_trait;
+use rust_decimal::Decimal;
 use sqlx::PgPool;
 use std::sync::Arc;
 use uuid::Uuid;
@@ -14,13 +16,15 @@ use uuid::Uuid;
 struct AccountRow {
     id: Uuid,
     user_id: Uuid,
-    balance: f64,
+    balance: Decimal,
+    currency: String,
 }
 
 impl From<AccountRow> for Account {
     fn from(row: AccountRow) -> Self {
         let token = account::get_token();
-        Account::new(token, row.id, row.user_id, row.balance)
+        let balance = Money::round(row.balance, &row.currency);
+        Account::new(token, row.id, row.user_id, balance, row.currency)
     }
 }
 
@@ -28,24 +32,22 @@ pub struct AccountSQLRepo(pub Arc<PgPool>);
 
 #[async_trait]
 impl AccountRepository for AccountSQLRepo {
-    async fn create(
-        &mut self,
-        user: &User,
-        init_balance: Option<f64>,
-    ) -> Result<Account, ErrorApi> {
+    async fn create(&mut self, user: &User, currency: String) -> Result<Account, ErrorApi> {
         let id = Uuid::new_v4();
-        let account = account::factory::create(id, *user.id(), init_balance.unwrap_or(0.0))?;
+        let balance = Money::zero(&currency);
+        let account = account::factory::create(id, *user.id(), balance, currency)?;
 
         let row = sqlx::query_as!(
             AccountRow,
             r#"
-            INSERT INTO accounts (id, user_id, balance)
-            VALUES ($1, $2, $3)
-            RETURNING id, user_id, balance
+            INSERT INTO accounts (id, user_id, balance, currency)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, user_id, balance, currency
             "#,
             account.id(),
             account.user_id(),
-            account.balance()
+            account.balance().amount(),
+            account.currency()
         )
         .fetch_one(self.0.as_ref())
         .await
@@ -61,7 +63,7 @@ impl AccountRepository for AccountSQLRepo {
             SET balance = $1
             WHERE id = $2
             "#,
-            account.balance(),
+            account.balance().amount(),
             account.id()
         )
         .execute(self.0.as_ref())
@@ -100,7 +102,7 @@ impl AccountRepository for AccountSQLRepo {
         sqlx::query_as!(
             AccountRow,
             r#"
-            SELECT id, user_id, balance
+            SELECT id, user_id, balance, currency
             FROM accounts
             WHERE id = $1
             "#,
@@ -116,7 +118,7 @@ impl AccountRepository for AccountSQLRepo {
         let rows = sqlx::query_as!(
             AccountRow,
             r#"
-            SELECT id, user_id, balance
+            SELECT id, user_id, balance, currency
             FROM accounts
             WHERE user_id = $1
             "#,
@@ -0,0 +1,152 @@
+use crate::{
+    domain::{
+        idempotency::{
+            self, Completion, IdempotencyKey, IdempotencyRepository, IDEMPOTENCY_KEY_TTL,
+            IDEMPOTENCY_LEASE,
+        },
+        transaction::Transaction,
+    },
+    infrastructure::error::ErrorApi,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgPool};
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(sqlx::FromRow)]
+struct IdempotencyRow {
+    user_id: Uuid,
+    idempotency_key: String,
+    request_hash: String,
+    response: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<IdempotencyRow> for IdempotencyKey {
+    fn from(row: IdempotencyRow) -> Self {
+        let token = idempotency::get_token();
+        IdempotencyKey::new(
+            token,
+            row.user_id,
+            row.idempotency_key,
+            row.request_hash,
+            row.response,
+            row.created_at,
+        )
+    }
+}
+
+pub struct IdempotencySQLRepo(pub Arc<PgPool>);
+
+/// Сохранить ответ в транзакции операции. Если ключ уже не наш,
+/// ошибка откатывает и саму операцию
+pub(super) async fn complete_in(
+    conn: &mut PgConnection,
+    completion: &Completion,
+    transaction: &Transaction,
+) -> Result<(), ErrorApi> {
+    let body = (completion.respond)(transaction)?;
+    let key = &completion.key;
+    let affected = sqlx::query!(
+        r#"
+        UPDATE idempotency_keys
+        SET response = $4
+        WHERE user_id = $1 AND idempotency_key = $2 AND created_at = $3
+            AND response IS NULL
+        "#,
+        key.user_id(),
+        key.key(),
+        key.created_at(),
+        body
+    )
+    .execute(conn)
+    .await
+    .map_err(|e| ErrorApi::DataBase(e.to_string()))?
+    .rows_affected();
+
+    if affected == 0 {
+        return Err(ErrorApi::Conflict(
+            "Idempotency key was released before the request completed".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl IdempotencyRepository for IdempotencySQLRepo {
+    async fn acquire(&mut self, key: &IdempotencyKey) -> Result<Option<IdempotencyKey>, ErrorApi> {
+        sqlx::query!(
+            r#"
+            DELETE FROM idempotency_keys
+            WHERE user_id = $1 AND idempotency_key = $2
+                AND (created_at < $3 OR (response IS NULL AND created_at < $4))
+            "#,
+            key.user_id(),
+            key.key(),
+            Utc::now() - IDEMPOTENCY_KEY_TTL,
+            Utc::now() - IDEMPOTENCY_LEASE
+        )
+        .execute(self.0.as_ref())
+        .await
+        .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
+
+        // Первичный ключ не даст занять ключ двум запросам сразу
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO idempotency_keys (user_id, idempotency_key, request_hash, created_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id, idempotency_key) DO NOTHING
+            "#,
+            key.user_id(),
+            key.key(),
+            key.request_hash(),
+            key.created_at()
+        )
+        .execute(self.0.as_ref())
+        .await
+        .map_err(|e| ErrorApi::DataBase(e.to_string()))?
+        .rows_affected();
+
+        if inserted == 1 {
+            return Ok(None);
+        }
+
+        let row = sqlx::query_as!(
+            IdempotencyRow,
+            r#"
+            SELECT user_id, idempotency_key, request_hash, response, created_at
+            FROM idempotency_keys
+            WHERE user_id = $1 AND idempotency_key = $2
+            "#,
+            key.user_id(),
+            key.key()
+        )
+        .fetch_optional(self.0.as_ref())
+        .await
+        .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
+
+        // Ключ успели освободить между INSERT и SELECT
+        row.map(|row| Some(row.into())).ok_or(ErrorApi::Conflict(
+            "Request with this idempotency key is in progress".to_string(),
+        ))
+    }
+
+    async fn release(&mut self, key: &IdempotencyKey) -> Result<(), ErrorApi> {
+        sqlx::query!(
+            r#"
+            DELETE FROM idempotency_keys
+            WHERE user_id = $1 AND idempotency_key = $2 AND created_at = $3
+                AND response IS NULL
+            "#,
+            key.user_id(),
+            key.key(),
+            key.created_at()
+        )
+        .execute(self.0.as_ref())
+        .await
+        .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{
+    domain::{
+        ledger::{
+            LedgerAccount, LedgerRepository, Mismatch, Posting, Reconciliation, SystemAccount,
+        },
+        money::Money,
+    },
+    infrastructure::error::ErrorApi,
+};
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use sqlx::{PgConnection, PgPool};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Записать проводки транзакции, в той же транзакции БД, что и она сама
+pub(super) async fn insert_postings(
+    conn: &mut PgConnection,
+    postings: &[Posting],
+) -> Result<(), ErrorApi> {
+    for posting in postings {
+        let (account_id, system_account) = match *posting.account() {
+            LedgerAccount::Account(id) => (Some(id), None),
+            LedgerAccount::System(system) => (None, Some(system)),
+        };
+        sqlx::query!(
+            r#"
+            INSERT INTO postings
+                (id, transaction_id, account_id, system_account, currency, amount, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            posting.id(),
+            posting.transaction_id(),
+            account_id,
+            system_account as Option<SystemAccount>,
+            posting.currency(),
+            posting.amount().amount(),
+            posting.created_at()
+        )
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
+    }
+    Ok(())
+}
+
+struct MismatchRow {
+    account_id: Uuid,
+    currency: String,
+    stored_balance: Decimal,
+    ledger_balance: Decimal,
+}
+
+impl From<MismatchRow> for Mismatch {
+    fn from(row: MismatchRow) -> Self {
+        Mismatch {
+            account_id: row.account_id,
+            stored_balance: Money::round(row.stored_balance, &row.currency),
+            ledger_balance: Money::round(row.ledger_balance, &row.currency),
+            currency: row.currency,
+        }
+    }
+}
+
+pub struct LedgerSQLRepo(pub Arc<PgPool>);
+
+#[async_trait]
+impl LedgerRepository for LedgerSQLRepo {
+    async fn reconcile(&self) -> Result<Reconciliation, ErrorApi> {
+        // Снимок на одно время для всех трех запросов
+        let mut tx = self
+            .0
+            .begin()
+            .await
+            .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
+        sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY")
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
+
+        let accounts_checked = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM accounts"#)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
+
+        let mismatches = sqlx::query_as!(
+            MismatchRow,
+            r#"
+            SELECT a.id AS account_id,
+                   a.currency,
+                   a.balance AS stored_balance,
+                   COALESCE(SUM(p.amount), 0) AS "ledger_balance!"
+            FROM accounts a
+            LEFT JOIN postings p ON p.account_id = a.id
+            GROUP BY a.id
+            HAVING a.balance <> COALESCE(SUM(p.amount), 0)
+            ORDER BY a.id
+            "#
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
+
+        let unbalanced_transactions = sqlx::query_scalar!(
+            r#"
+            SELECT t.id AS "id!"
+            FROM transactions t
+            WHERE NOT EXISTS (SELECT 1 FROM postings p WHERE p.transaction_id = t.id)
+               OR EXISTS (
+                   SELECT 1 FROM postings p
+                   WHERE p.transaction_id = t.id
+                   GROUP BY p.currency
+                   HAVING SUM(p.amount) <> 0
+               )
+            ORDER BY t.id
+            "#
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
+
+        tx.commit()
+            .await
+            .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
+
+        Ok(Reconciliation {
+            accounts_checked: accounts_checked as usize,
+            mismatches: mismatches.into_iter().map(Into::into).collect(),
+            unbalanced_transactions,
+        })
+    }
+}
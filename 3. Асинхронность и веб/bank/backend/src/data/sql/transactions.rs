@@ -1,13 +1,19 @@
+use super::{idempotency::complete_in, ledger::insert_postings};
 use crate::{
     domain::{
         account::Account,
+        idempotency::Completion,
+        ledger,
+        money::Money,
+        statement::{StatementFilter, StatementLine, StatementPage},
         transaction::{self, Transaction, TransactionRepository},
     },
     infrastructure::error::ErrorApi,
 };
 use async_trait::async_trait;
 use chrono::Utc;
-use sqlx::{prelude::FromRow, PgPool};
+use rust_decimal::Decimal;
+use sqlx::{prelude::FromRow, PgConnection, PgPool};
 use std::sync::Arc;
 use tracing::info;
 use uuid::Uuid;
@@ -16,7 +22,12 @@ use uuid::Uuid;
 pub struct TransactionRow {
     id: Uuid,
     operation: transaction::Operation,
-    amount: f64,
+    amount: Decimal,
+    currency: String,
+    rate: Decimal,
+    converted_amount: Decimal,
+    /// Валюта счета получателя, для знаков зачисленной суммы
+    to_currency: Option<String>,
     from_id: Option<Uuid>,
     to_id: Option<Uuid>,
     created_at: chrono::DateTime<chrono::Utc>,
@@ -25,11 +36,16 @@ pub struct TransactionRow {
 impl From<TransactionRow> for Transaction {
     fn from(row: TransactionRow) -> Self {
         let token = transaction::get_token();
+        let to_currency = row.to_currency.as_deref().unwrap_or(&row.currency);
+        let converted_amount = Money::round(row.converted_amount, to_currency);
         Transaction::new(
             token,
             row.id,
             row.operation,
-            row.amount,
+            Money::round(row.amount, &row.currency),
+            row.currency,
+            row.rate,
+            converted_amount,
             row.from_id,
             row.to_id,
             row.created_at,
@@ -37,98 +53,268 @@ impl From<TransactionRow> for Transaction {
     }
 }
 
+struct StatementRow {
+    id: Uuid,
+    transaction_id: Uuid,
+    operation: transaction::Operation,
+    counterparty: Option<Uuid>,
+    amount: Decimal,
+    balance: Decimal,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
 pub struct TransactionSQLRepo(pub Arc<PgPool>);
 
+/// Зачислить на счет
+async fn credit(conn: &mut PgConnection, account_id: Uuid, amount: &Money) -> Result<(), ErrorApi> {
+    let affected = sqlx::query!(
+        r#"
+        UPDATE accounts
+        SET balance = balance + $1
+        WHERE id = $2
+        "#,
+        amount.amount(),
+        account_id
+    )
+    .execute(conn)
+    .await
+    .map_err(|e| ErrorApi::DataBase(e.to_string()))?
+    .rows_affected();
+
+    if affected == 0 {
+        return Err(ErrorApi::NotFound("Account not found".into()));
+    }
+    Ok(())
+}
+
+/// Списать со счета, только если хватает денег: проверка и списание одним запросом
+async fn debit(conn: &mut PgConnection, account_id: Uuid, amount: &Money) -> Result<(), ErrorApi> {
+    let affected = sqlx::query!(
+        r#"
+        UPDATE accounts
+        SET balance = balance - $1
+        WHERE id = $2 AND balance >= $1
+        "#,
+        amount.amount(),
+        account_id
+    )
+    .execute(conn)
+    .await
+    .map_err(|e| ErrorApi::DataBase(e.to_string()))?
+    .rows_affected();
+
+    if affected == 0 {
+        return Err(ErrorApi::Validation(
+            "Account balance is not enough".to_string(),
+        ));
+    }
+    Ok(())
+}
+
 #[async_trait]
 impl TransactionRepository for TransactionSQLRepo {
-    async fn create_deposit(&mut self, amount: f64, to: &Account) -> Result<Transaction, ErrorApi> {
+    async fn create_deposit(
+        &mut self,
+        amount: Money,
+        to: &Account,
+        completion: Option<Completion>,
+    ) -> Result<Transaction, ErrorApi> {
         let id = Uuid::new_v4();
         let created_at = Utc::now();
 
-        let tx = transaction::factory::create_deposit(id, amount, *to.id(), created_at)?;
+        let tx = transaction::factory::create_deposit(
+            id,
+            amount,
+            to.currency().clone(),
+            *to.id(),
+            created_at,
+        )?;
         info!("{:#?}", tx);
+        let postings = ledger::factory::postings(&tx, to.currency())?;
+
+        let mut db_tx = self
+            .0
+            .begin()
+            .await
+            .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
+        credit(&mut db_tx, *to.id(), tx.converted_amount()).await?;
 
         let row = sqlx::query_as!(
             TransactionRow,
             r#"
-        INSERT INTO transactions (id, operation, amount, from_id, to_id, created_at)
-        VALUES ($1, 'deposit', $2, NULL, $3, $4)
+        INSERT INTO transactions
+            (id, operation, amount, currency, rate, converted_amount, from_id, to_id, created_at)
+        VALUES ($1, 'deposit', $2, $3, $4, $5, NULL, $6, $7)
         RETURNING id, operation as "operation!: transaction::Operation",
-            amount, from_id, to_id, created_at
+            amount, currency, rate, converted_amount,
+            (SELECT currency FROM accounts WHERE accounts.id = to_id) AS to_currency,
+            from_id, to_id, created_at
         "#,
             tx.id(),
-            tx.amount(),
+            tx.amount().amount(),
+            tx.currency(),
+            tx.rate(),
+            tx.converted_amount().amount(),
             *tx.to_id(),
             tx.created_at()
         )
-        .fetch_one(self.0.as_ref())
+        .fetch_one(&mut *db_tx)
         .await
         .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
+        insert_postings(&mut db_tx, &postings).await?;
+        let transaction = Transaction::from(row);
+        if let Some(completion) = &completion {
+            complete_in(&mut db_tx, completion, &transaction).await?;
+        }
 
-        Ok(row.into())
+        db_tx
+            .commit()
+            .await
+            .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
+
+        Ok(transaction)
     }
 
     async fn create_withdrawal(
         &mut self,
-        amount: f64,
+        amount: Money,
         from: &Account,
+        completion: Option<Completion>,
     ) -> Result<Transaction, ErrorApi> {
         let id = Uuid::new_v4();
         let created_at = Utc::now();
 
-        let tx = transaction::factory::create_withdrawal(id, amount, *from.id(), created_at)?;
+        let tx = transaction::factory::create_withdrawal(
+            id,
+            amount,
+            from.currency().clone(),
+            *from.id(),
+            created_at,
+        )?;
+        let postings = ledger::factory::postings(&tx, from.currency())?;
+
+        let mut db_tx = self
+            .0
+            .begin()
+            .await
+            .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
+        debit(&mut db_tx, *from.id(), tx.amount()).await?;
 
         let row = sqlx::query_as!(
             TransactionRow,
             r#"
-        INSERT INTO transactions (id, operation, amount, from_id, to_id, created_at)
-        VALUES ($1, 'withdrawal', $2, $3, NULL, $4)
+        INSERT INTO transactions
+            (id, operation, amount, currency, rate, converted_amount, from_id, to_id, created_at)
+        VALUES ($1, 'withdrawal', $2, $3, $4, $5, $6, NULL, $7)
         RETURNING id, operation as "operation!: transaction::Operation",
-                  amount, from_id, to_id, created_at
+                  amount, currency, rate, converted_amount,
+                  (SELECT currency FROM accounts WHERE accounts.id = to_id) AS to_currency,
+                  from_id, to_id, created_at
         "#,
             tx.id(),
-            tx.amount(),
+            tx.amount().amount(),
+            tx.currency(),
+            tx.rate(),
+            tx.converted_amount().amount(),
             *tx.from_id(),
             tx.created_at()
         )
-        .fetch_one(self.0.as_ref())
+        .fetch_one(&mut *db_tx)
         .await
         .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
+        insert_postings(&mut db_tx, &postings).await?;
+        let transaction = Transaction::from(row);
+        if let Some(completion) = &completion {
+            complete_in(&mut db_tx, completion, &transaction).await?;
+        }
+
+        db_tx
+            .commit()
+            .await
+            .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
 
-        Ok(row.into())
+        Ok(transaction)
     }
 
     async fn create_transfer(
         &mut self,
-        amount: f64,
+        amount: Money,
+        rate: Decimal,
         from: &Account,
         to: &Account,
+        completion: Option<Completion>,
     ) -> Result<Transaction, ErrorApi> {
         let id = Uuid::new_v4();
         let created_at = Utc::now();
 
-        let tx =
-            transaction::factory::create_transfer(id, amount, *from.id(), *to.id(), created_at)?;
+        let tx = transaction::factory::create_transfer(
+            id,
+            amount,
+            from.currency().clone(),
+            rate,
+            to.currency(),
+            *from.id(),
+            *to.id(),
+            created_at,
+        )?;
+        let postings = ledger::factory::postings(&tx, to.currency())?;
+
+        let mut db_tx = self
+            .0
+            .begin()
+            .await
+            .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
+        // Оба счета блокируются в одном порядке, встречные переводы не ждут друг друга вечно
+        sqlx::query!(
+            r#"
+        SELECT id FROM accounts
+        WHERE id = ANY($1)
+        ORDER BY id
+        FOR UPDATE
+        "#,
+            &[*from.id(), *to.id()][..]
+        )
+        .fetch_all(&mut *db_tx)
+        .await
+        .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
+        debit(&mut db_tx, *from.id(), tx.amount()).await?;
+        credit(&mut db_tx, *to.id(), tx.converted_amount()).await?;
 
         let row = sqlx::query_as!(
             TransactionRow,
             r#"
-        INSERT INTO transactions (id, operation, amount, from_id, to_id, created_at)
-        VALUES ($1, 'transfer', $2, $3, $4, $5)
+        INSERT INTO transactions
+            (id, operation, amount, currency, rate, converted_amount, from_id, to_id, created_at)
+        VALUES ($1, 'transfer', $2, $3, $4, $5, $6, $7, $8)
         RETURNING id, operation as "operation!: transaction::Operation",
-                  amount, from_id, to_id, created_at
+                  amount, currency, rate, converted_amount,
+                  (SELECT currency FROM accounts WHERE accounts.id = to_id) AS to_currency,
+                  from_id, to_id, created_at
         "#,
             tx.id(),
-            tx.amount(),
+            tx.amount().amount(),
+            tx.currency(),
+            tx.rate(),
+            tx.converted_amount().amount(),
             *tx.from_id(),
             *tx.to_id(),
             tx.created_at()
         )
-        .fetch_one(self.0.as_ref())
+        .fetch_one(&mut *db_tx)
         .await
         .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
+        insert_postings(&mut db_tx, &postings).await?;
+        let transaction = Transaction::from(row);
+        if let Some(completion) = &completion {
+            complete_in(&mut db_tx, completion, &transaction).await?;
+        }
 
-        Ok(row.into())
+        db_tx
+            .commit()
+            .await
+            .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
+
+        Ok(transaction)
     }
 
     async fn delete(&mut self, transaction: &Transaction) -> Result<(), ErrorApi> {
@@ -157,6 +343,10 @@ impl TransactionRepository for TransactionSQLRepo {
         SELECT id,
                operation as "operation!: transaction::Operation",
                amount,
+               currency,
+               rate,
+               converted_amount,
+               (SELECT currency FROM accounts WHERE accounts.id = to_id) AS to_currency,
                from_id,
                to_id,
                created_at
@@ -172,30 +362,71 @@ impl TransactionRepository for TransactionSQLRepo {
         Some(row.into())
     }
 
-    async fn gets_by_account(&self, account: &Account) -> Option<Vec<Transaction>> {
+    async fn statement(
+        &self,
+        account: &Account,
+        filter: &StatementFilter,
+    ) -> Result<StatementPage, ErrorApi> {
+        // Остаток считается окном по всем проводкам счета до `to`, фильтры - поверх него
         let rows = sqlx::query_as!(
-            TransactionRow,
+            StatementRow,
             r#"
-        SELECT id,
-               operation as "operation!: transaction::Operation",
-               amount,
-               from_id,
-               to_id,
-               created_at
-        FROM transactions
-        WHERE from_id = $1 OR to_id = $1
-        ORDER BY created_at DESC
+        SELECT id AS "id!",
+               transaction_id AS "transaction_id!",
+               operation AS "operation!: transaction::Operation",
+               counterparty,
+               amount AS "amount!",
+               balance AS "balance!",
+               created_at AS "created_at!"
+        FROM (
+            SELECT p.id,
+                   p.transaction_id,
+                   t.operation,
+                   CASE WHEN t.from_id = p.account_id THEN t.to_id ELSE t.from_id END
+                       AS counterparty,
+                   p.amount,
+                   SUM(p.amount) OVER (ORDER BY p.created_at, p.id) AS balance,
+                   p.created_at
+            FROM postings p
+            JOIN transactions t ON t.id = p.transaction_id
+            WHERE p.account_id = $1
+              AND ($3::TIMESTAMPTZ IS NULL OR p.created_at < $3)
+        ) AS lines
+        WHERE ($2::TIMESTAMPTZ IS NULL OR created_at >= $2)
+          AND ($4::operation IS NULL OR operation = $4)
+          AND ($5::NUMERIC IS NULL OR ABS(amount) >= $5)
+          AND ($6::NUMERIC IS NULL OR ABS(amount) <= $6)
+          AND ($7::TIMESTAMPTZ IS NULL OR (created_at, id) > ($7, $8))
+        ORDER BY created_at, id
+        LIMIT $9
         "#,
-            account.id()
+            account.id(),
+            filter.from,
+            filter.to,
+            filter.operation.clone() as Option<transaction::Operation>,
+            filter.min_amount,
+            filter.max_amount,
+            filter.after.map(|cursor| cursor.created_at),
+            filter.after.map(|cursor| cursor.id),
+            filter.limit as i64 + 1
         )
         .fetch_all(self.0.as_ref())
         .await
-        .ok()?;
-
-        if rows.is_empty() {
-            return None;
-        }
+        .map_err(|e| ErrorApi::DataBase(e.to_string()))?;
 
-        Some(rows.into_iter().map(Into::into).collect())
+        let currency = account.currency();
+        let lines = rows
+            .into_iter()
+            .map(|row| StatementLine {
+                id: row.id,
+                transaction_id: row.transaction_id,
+                operation: row.operation,
+                counterparty: row.counterparty,
+                amount: Money::round(row.amount, currency),
+                balance: Money::round(row.balance, currency),
+                created_at: row.created_at,
+            })
+            .collect();
+        Ok(StatementPage::from_lines(lines, filter.limit))
     }
 }
@@ -1,6 +1,7 @@
 use crate::{
     domain::{
         account::{self, Account, AccountRepository},
+        money::Money,
         user::User,
     },
     infrastructure::{error::ErrorApi, state::State},
@@ -13,14 +14,11 @@ pub struct AccountStateRepo(pub Arc<State>);
 
 #[async_trait]
 impl AccountRepository for AccountStateRepo {
-    async fn create(
-        &mut self,
-        user: &User,
-        init_balance: Option<f64>,
-    ) -> Result<Account, ErrorApi> {
+    async fn create(&mut self, user: &User, currency: String) -> Result<Account, ErrorApi> {
         let id = Uuid::new_v4();
         let mut accounts = self.0.accounts().await;
-        let account = account::factory::create(id, *user.id(), init_balance.unwrap_or(0.0))?;
+        let balance = Money::zero(&currency);
+        let account = account::factory::create(id, *user.id(), balance, currency)?;
         accounts
             .entry(*user.id())
             .or_default()
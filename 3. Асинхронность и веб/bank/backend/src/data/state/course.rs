@@ -22,7 +22,14 @@ impl CourseRepository for CourseStateRepo {
         Ok(course)
     }
     async fn get_by_time(&self, time_update_utc: DateTime<Utc>) -> Option<Course> {
+        // Ближайший курс в пределах 12 часов, как в SQL
+        let window = chrono::Duration::hours(12);
         let courses = self.0.courses().await;
-        courses.get(&time_update_utc).cloned()
+        courses
+            .iter()
+            .map(|(time, course)| ((*time - time_update_utc).abs(), course))
+            .filter(|(distance, _)| *distance <= window)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, course)| course.clone())
     }
 }
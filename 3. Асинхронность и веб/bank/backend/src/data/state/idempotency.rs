@@ -0,0 +1,61 @@
+use crate::{
+    domain::{
+        idempotency::{Completion, IdempotencyKey, IdempotencyRepository},
+        transaction::Transaction,
+    },
+    infrastructure::{error::ErrorApi, state::State},
+};
+use async_trait::async_trait;
+use std::{collections::HashMap, sync::Arc};
+use uuid::Uuid;
+
+pub struct IdempotencyStateRepo(pub Arc<State>);
+
+/// Сохранить ответ под замком счетов операции. Если ключ уже не наш,
+/// операция не выполняется
+pub(super) fn complete_in(
+    keys: &mut HashMap<(Uuid, String), IdempotencyKey>,
+    completion: &Completion,
+    transaction: &Transaction,
+) -> Result<(), ErrorApi> {
+    let key = &completion.key;
+    let Some(existing) = keys
+        .get_mut(&(*key.user_id(), key.key().clone()))
+        .filter(|existing| existing.is_same_claim(key) && existing.response().is_none())
+    else {
+        return Err(ErrorApi::Conflict(
+            "Idempotency key was released before the request completed".to_string(),
+        ));
+    };
+    existing.set_response(Some((completion.respond)(transaction)?));
+    Ok(())
+}
+
+#[async_trait]
+impl IdempotencyRepository for IdempotencyStateRepo {
+    async fn acquire(&mut self, key: &IdempotencyKey) -> Result<Option<IdempotencyKey>, ErrorApi> {
+        let mut keys = self.0.idempotency_keys().await;
+        let id = (*key.user_id(), key.key().clone());
+        match keys.get(&id) {
+            Some(existing) if !existing.is_expired() && !existing.is_abandoned() => {
+                Ok(Some(existing.clone()))
+            }
+            _ => {
+                keys.insert(id, key.clone());
+                Ok(None)
+            }
+        }
+    }
+
+    async fn release(&mut self, key: &IdempotencyKey) -> Result<(), ErrorApi> {
+        let mut keys = self.0.idempotency_keys().await;
+        let id = (*key.user_id(), key.key().clone());
+        if keys
+            .get(&id)
+            .is_some_and(|existing| existing.is_same_claim(key) && existing.response().is_none())
+        {
+            keys.remove(&id);
+        }
+        Ok(())
+    }
+}
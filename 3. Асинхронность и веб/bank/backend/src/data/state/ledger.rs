@@ -0,0 +1,71 @@
+use crate::{
+    domain::{
+        ledger::{self, LedgerAccount, LedgerRepository, Mismatch, Reconciliation},
+        money::Money,
+    },
+    infrastructure::{error::ErrorApi, state::State},
+};
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use std::{collections::HashMap, sync::Arc};
+use uuid::Uuid;
+
+pub struct LedgerStateRepo(pub Arc<State>);
+
+#[async_trait]
+impl LedgerRepository for LedgerStateRepo {
+    async fn reconcile(&self) -> Result<Reconciliation, ErrorApi> {
+        // Тот же порядок замков, что у операций: счета, транзакции, проводки
+        let accounts = self.0.accounts().await;
+        let transactions = self.0.transactions().await;
+        let postings = self.0.postings().await;
+
+        let mut by_account: HashMap<Uuid, Decimal> = HashMap::new();
+        let mut by_transaction: HashMap<Uuid, Vec<ledger::Posting>> = HashMap::new();
+        for posting in postings.iter() {
+            if let LedgerAccount::Account(id) = posting.account() {
+                *by_account.entry(*id).or_default() += posting.amount().amount();
+            }
+            by_transaction
+                .entry(*posting.transaction_id())
+                .or_default()
+                .push(posting.clone());
+        }
+
+        let mut accounts_checked = 0;
+        let mut mismatches = Vec::new();
+        for account in accounts.values().flat_map(|accs| accs.values()) {
+            accounts_checked += 1;
+            let ledger_balance = by_account.get(account.id()).copied().unwrap_or_default();
+            if account.balance().amount() != ledger_balance {
+                mismatches.push(Mismatch {
+                    account_id: *account.id(),
+                    currency: account.currency().clone(),
+                    stored_balance: *account.balance(),
+                    ledger_balance: Money::round(ledger_balance, account.currency()),
+                });
+            }
+        }
+        mismatches.sort_by_key(|mismatch| mismatch.account_id);
+
+        let mut unbalanced_transactions: Vec<Uuid> = transactions
+            .values()
+            .flat_map(|trans| trans.keys())
+            .filter(|id| {
+                by_transaction
+                    .get(id)
+                    .is_none_or(|postings| !ledger::is_balanced(postings))
+            })
+            .copied()
+            .collect();
+        // Перевод лежит под обоими счетами
+        unbalanced_transactions.sort();
+        unbalanced_transactions.dedup();
+
+        Ok(Reconciliation {
+            accounts_checked,
+            mismatches,
+            unbalanced_transactions,
+        })
+    }
+}
@@ -1,58 +1,162 @@
+use super::idempotency::complete_in;
 use crate::{
     domain::{
         account::Account,
+        idempotency::Completion,
+        ledger,
+        money::Money,
+        statement::{StatementFilter, StatementLine, StatementPage},
         transaction::{self, Transaction, TransactionRepository},
     },
     infrastructure::{error::ErrorApi, state::State},
 };
 use async_trait::async_trait;
 use chrono::Utc;
-use std::sync::Arc;
+use rust_decimal::Decimal;
+use std::{collections::HashMap, sync::Arc};
 use uuid::Uuid;
 
 pub struct TransactionStateRepo(pub Arc<State>);
 
+/// Счет по id среди счетов всех пользователей
+fn account_mut<'a>(
+    accounts: &'a mut HashMap<Uuid, HashMap<Uuid, Account>>,
+    id: &Uuid,
+) -> Result<&'a mut Account, ErrorApi> {
+    accounts
+        .values_mut()
+        .find_map(|accs| accs.get_mut(id))
+        .ok_or(ErrorApi::NotFound("Account not found".to_string()))
+}
+
+/// Баланс после списания, если денег хватает
+fn debited(account: &Account, amount: Money) -> Result<Money, ErrorApi> {
+    let balance = account.balance().checked_sub(amount)?;
+    if balance.is_negative() {
+        return Err(ErrorApi::Validation(
+            "Account balance is not enough".to_string(),
+        ));
+    }
+    Ok(balance)
+}
+
 #[async_trait]
 impl TransactionRepository for TransactionStateRepo {
-    async fn create_deposit(&mut self, amount: f64, to: &Account) -> Result<Transaction, ErrorApi> {
+    async fn create_deposit(
+        &mut self,
+        amount: Money,
+        to: &Account,
+        completion: Option<Completion>,
+    ) -> Result<Transaction, ErrorApi> {
         let id = Uuid::new_v4();
         let created_at = Utc::now();
-        let transaction = transaction::factory::create_deposit(id, amount, *to.id(), created_at)?;
+        let transaction = transaction::factory::create_deposit(
+            id,
+            amount,
+            to.currency().clone(),
+            *to.id(),
+            created_at,
+        )?;
+        let postings = ledger::factory::postings(&transaction, to.currency())?;
+        // Замок счетов держится до записи транзакции: операции идут по очереди
+        let mut accounts = self.0.accounts().await;
+        let account = account_mut(&mut accounts, to.id())?;
+        let balance = account
+            .balance()
+            .checked_add(*transaction.converted_amount())?;
+        // Ответ по ключу сохраняется последней проверкой, дальше изменения не падают
+        if let Some(completion) = &completion {
+            complete_in(
+                &mut *self.0.idempotency_keys().await,
+                completion,
+                &transaction,
+            )?;
+        }
+        account.set_balance(balance);
         let mut transactions = self.0.transactions().await;
         transactions
             .entry(*to.id())
             .or_default()
             .insert(id, transaction.clone());
+        self.0.postings().await.extend(postings);
 
         Ok(transaction)
     }
     async fn create_withdrawal(
         &mut self,
-        amount: f64,
+        amount: Money,
         from: &Account,
+        completion: Option<Completion>,
     ) -> Result<Transaction, ErrorApi> {
         let id = Uuid::new_v4();
         let created_at = Utc::now();
-        let transaction =
-            transaction::factory::create_withdrawal(id, amount, *from.id(), created_at)?;
+        let transaction = transaction::factory::create_withdrawal(
+            id,
+            amount,
+            from.currency().clone(),
+            *from.id(),
+            created_at,
+        )?;
+        let postings = ledger::factory::postings(&transaction, from.currency())?;
+        let mut accounts = self.0.accounts().await;
+        let account = account_mut(&mut accounts, from.id())?;
+        let balance = debited(account, *transaction.amount())?;
+        if let Some(completion) = &completion {
+            complete_in(
+                &mut *self.0.idempotency_keys().await,
+                completion,
+                &transaction,
+            )?;
+        }
+        account.set_balance(balance);
         let mut transactions = self.0.transactions().await;
         transactions
             .entry(*from.id())
             .or_default()
             .insert(id, transaction.clone());
+        self.0.postings().await.extend(postings);
 
         Ok(transaction)
     }
     async fn create_transfer(
         &mut self,
-        amount: f64,
+        amount: Money,
+        rate: Decimal,
         from: &Account,
         to: &Account,
+        completion: Option<Completion>,
     ) -> Result<Transaction, ErrorApi> {
         let id = Uuid::new_v4();
         let created_at = Utc::now();
-        let transaction =
-            transaction::factory::create_transfer(id, amount, *from.id(), *to.id(), created_at)?;
+        let transaction = transaction::factory::create_transfer(
+            id,
+            amount,
+            from.currency().clone(),
+            rate,
+            to.currency(),
+            *from.id(),
+            *to.id(),
+            created_at,
+        )?;
+        let postings = ledger::factory::postings(&transaction, to.currency())?;
+        let mut accounts = self.0.accounts().await;
+        // Новые балансы считаются до изменения, чтобы ошибка не оставила перевод наполовину
+        let from_balance = debited(
+            account_mut(&mut accounts, from.id())?,
+            *transaction.amount(),
+        )?;
+        let to_balance = account_mut(&mut accounts, to.id())?
+            .balance()
+            .checked_add(*transaction.converted_amount())?;
+        if let Some(completion) = &completion {
+            complete_in(
+                &mut *self.0.idempotency_keys().await,
+                completion,
+                &transaction,
+            )?;
+        }
+        account_mut(&mut accounts, from.id())?.set_balance(from_balance);
+        account_mut(&mut accounts, to.id())?.set_balance(to_balance);
         let mut transactions = self.0.transactions().await;
         transactions
             .entry(*from.id())
@@ -62,6 +166,7 @@ impl TransactionRepository for TransactionStateRepo {
             .entry(*to.id())
             .or_default()
             .insert(id, transaction.clone());
+        self.0.postings().await.extend(postings);
 
         Ok(transaction)
     }
@@ -93,11 +198,53 @@ impl TransactionRepository for TransactionStateRepo {
         };
         res.get(&id).cloned()
     }
-    async fn gets_by_account(&self, account: &Account) -> Option<Vec<Transaction>> {
+    async fn statement(
+        &self,
+        account: &Account,
+        filter: &StatementFilter,
+    ) -> Result<StatementPage, ErrorApi> {
         let transactions = self.0.transactions().await;
         let Some(trans) = transactions.get(account.id()) else {
-            return None;
+            return Ok(StatementPage::from_lines(Vec::new(), filter.limit));
         };
-        Some(trans.values().cloned().collect())
+        let mut postings: Vec<ledger::Posting> = self
+            .0
+            .postings()
+            .await
+            .iter()
+            .filter(|posting| *posting.account() == ledger::LedgerAccount::Account(*account.id()))
+            .filter(|posting| trans.contains_key(posting.transaction_id()))
+            .cloned()
+            .collect();
+        postings.sort_by_key(|posting| (*posting.created_at(), *posting.id()));
+
+        // Остаток копится по всем проводкам, фильтры применяются к готовым строкам
+        let mut balance = Money::zero(account.currency());
+        let mut lines = Vec::new();
+        for posting in postings {
+            balance = balance.checked_add(*posting.amount())?;
+            let transaction = &trans[posting.transaction_id()];
+            let counterparty = if *transaction.from_id() == Some(*account.id()) {
+                *transaction.to_id()
+            } else {
+                *transaction.from_id()
+            };
+            let line = StatementLine {
+                id: *posting.id(),
+                transaction_id: *posting.transaction_id(),
+                operation: transaction.operation().clone(),
+                counterparty,
+                amount: *posting.amount(),
+                balance,
+                created_at: *posting.created_at(),
+            };
+            if filter.matches(&line) {
+                lines.push(line);
+                if lines.len() > filter.limit {
+                    break;
+                }
+            }
+        }
+        Ok(StatementPage::from_lines(lines, filter.limit))
     }
 }
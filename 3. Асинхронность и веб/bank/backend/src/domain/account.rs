@@ -4,7 +4,11 @@ use serde::Serialize;
 use sqlx::prelude::FromRow;
 use uuid::Uuid;
 
-use crate::{domain::user::User, impl_constructor, infrastructure::error::ErrorApi};
+use crate::{
+    domain::{money::Money, user::User},
+    impl_constructor,
+    infrastructure::error::ErrorApi,
+};
 
 #[derive(Debug, Serialize, Getters, Setters, Clone, FromRow)]
 pub struct Account {
@@ -18,13 +22,31 @@ pub struct Account {
 
     /// Account balance
     #[getset(get = "pub", set = "pub")]
-    balance: f64,
+    balance: Money,
+
+    /// Account currency code (ISO 4217)
+    #[getset(get = "pub")]
+    currency: String,
+}
+
+/// Валюта счета по умолчанию, в ней же базовый курс
+pub const DEFAULT_CURRENCY: &str = "USD";
+
+/// Код валюты: три заглавные латинские буквы
+pub fn validate_currency(currency: &str) -> Result<(), ErrorApi> {
+    if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_uppercase()) {
+        return Err(ErrorApi::Validation(format!(
+            "Invalid currency code: {}",
+            currency
+        )));
+    }
+    Ok(())
 }
 
 #[async_trait]
 pub trait AccountRepository: Send + Sync {
-    async fn create(&mut self, user: &User, init_balance: Option<f64>)
-        -> Result<Account, ErrorApi>;
+    /// Счет открывается пустым, деньги на него приходят только транзакциями
+    async fn create(&mut self, user: &User, currency: String) -> Result<Account, ErrorApi>;
     async fn update(&mut self, account: &Account) -> Result<(), ErrorApi>;
     async fn delete(&mut self, account: &Account) -> Result<(), ErrorApi>;
 
@@ -32,22 +54,29 @@ pub trait AccountRepository: Send + Sync {
     async fn gets_by_user(&self, user: &User) -> Option<Vec<Account>>;
 }
 
-impl_constructor!(token: AccountToken, Account, (id: Uuid, user_id: Uuid, balance: f64));
+impl_constructor!(token: AccountToken, Account, (id: Uuid, user_id: Uuid, balance: Money, currency: String));
 
 pub mod factory {
     use super::*;
 
-    pub fn create(id: Uuid, user_id: Uuid, balance: f64) -> Result<Account, ErrorApi> {
-        if balance < 0.0 {
+    pub fn create(
+        id: Uuid,
+        user_id: Uuid,
+        balance: Money,
+        currency: String,
+    ) -> Result<Account, ErrorApi> {
+        if balance.is_negative() {
             return Err(ErrorApi::Validation(
                 "Account balance cannot be negative".to_string(),
             ));
         }
+        validate_currency(&currency)?;
 
         Ok(Account {
             id,
             user_id,
             balance,
+            currency,
         })
     }
 }
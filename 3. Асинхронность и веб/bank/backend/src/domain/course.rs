@@ -1,9 +1,10 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, str::FromStr};
 
-use crate::{impl_constructor, infrastructure::error::ErrorApi};
+use crate::{domain::money::round_rate, impl_constructor, infrastructure::error::ErrorApi};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use getset::Getters;
+use rust_decimal::Decimal;
 use serde::Serialize;
 
 #[derive(Debug, Serialize, Getters, Clone)]
@@ -18,6 +19,33 @@ pub struct Course {
     conversion_rates: HashMap<String, f64>,
 }
 
+impl Course {
+    /// Сколько единиц `to` дают за единицу `from`, округлено по `money::round_rate`
+    pub fn rate(&self, from: &str, to: &str) -> Result<Decimal, ErrorApi> {
+        let rate = self
+            .base_rate(to)?
+            .checked_div(self.base_rate(from)?)
+            .ok_or(ErrorApi::Inner("Conversion rate overflow".to_string()))?;
+        Ok(round_rate(rate))
+    }
+
+    /// Сколько единиц валюты за единицу базовой
+    fn base_rate(&self, currency: &str) -> Result<Decimal, ErrorApi> {
+        if currency == self.base_code {
+            return Ok(Decimal::ONE);
+        }
+        match self.conversion_rates.get(currency) {
+            // Через строку: курс из JSON без хвоста двоичной дроби
+            Some(rate) if *rate > 0.0 => Decimal::from_str(&rate.to_string())
+                .map_err(|_| ErrorApi::Inner(format!("Invalid conversion rate for {}", currency))),
+            _ => Err(ErrorApi::Validation(format!(
+                "No conversion rate for {}",
+                currency
+            ))),
+        }
+    }
+}
+
 #[async_trait]
 pub trait CourseRepository: Send + Sync {
     async fn create(
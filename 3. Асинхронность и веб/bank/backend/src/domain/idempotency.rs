@@ -0,0 +1,113 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, SubsecRound, Utc};
+use getset::{Getters, Setters};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{domain::transaction::Transaction, impl_constructor, infrastructure::error::ErrorApi};
+
+/// Сколько хранится ответ по ключу
+pub const IDEMPOTENCY_KEY_TTL: Duration = Duration::hours(24);
+/// Через сколько ключ без ответа считается брошенным и его можно занять заново
+pub const IDEMPOTENCY_LEASE: Duration = Duration::minutes(1);
+const MAX_KEY_LEN: usize = 255;
+
+/// Ключ повторяемого запроса и сохраненный ответ на него
+#[derive(Debug, Serialize, Getters, Setters, Clone)]
+pub struct IdempotencyKey {
+    #[getset(get = "pub")]
+    user_id: Uuid,
+
+    /// Значение заголовка Idempotency-Key
+    #[getset(get = "pub")]
+    key: String,
+
+    /// Хеш запроса: с тем же ключом нельзя прислать другой запрос
+    #[getset(get = "pub")]
+    request_hash: String,
+
+    /// Тело ответа, None пока первый запрос выполняется
+    #[getset(get = "pub", set = "pub")]
+    response: Option<String>,
+
+    #[getset(get = "pub")]
+    created_at: DateTime<Utc>,
+}
+
+impl IdempotencyKey {
+    pub fn is_expired(&self) -> bool {
+        Utc::now() > self.created_at + IDEMPOTENCY_KEY_TTL
+    }
+
+    /// Запрос занял ключ и не завершился: упал или был прерван
+    pub fn is_abandoned(&self) -> bool {
+        self.response.is_none() && Utc::now() > self.created_at + IDEMPOTENCY_LEASE
+    }
+
+    /// Та же запись: повторно занятый ключ получает новое время
+    pub fn is_same_claim(&self, other: &IdempotencyKey) -> bool {
+        self.user_id == other.user_id
+            && self.key == other.key
+            && self.created_at == other.created_at
+    }
+}
+
+/// Ответ по ключу, который сохраняется в той же транзакции, что и движение денег:
+/// либо есть и операция, и ответ, либо ни того, ни другого
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub key: IdempotencyKey,
+    /// Тело ответа по записанной транзакции
+    pub respond: fn(&Transaction) -> Result<String, ErrorApi>,
+}
+
+#[async_trait]
+pub trait IdempotencyRepository: Send + Sync {
+    /// Занять ключ. Если он уже занят, возвращает прежнюю запись.
+    /// Истекший или брошенный ключ занимается заново
+    async fn acquire(&mut self, key: &IdempotencyKey) -> Result<Option<IdempotencyKey>, ErrorApi>;
+    /// Освободить свой ключ без ответа, чтобы запрос можно было повторить
+    async fn release(&mut self, key: &IdempotencyKey) -> Result<(), ErrorApi>;
+}
+
+impl_constructor!(token: IdempotencyKeyToken, IdempotencyKey, (
+    user_id: Uuid,
+    key: String,
+    request_hash: String,
+    response: Option<String>,
+    created_at: DateTime<Utc>
+));
+
+pub mod factory {
+    use super::*;
+
+    /// `request` - описание запроса, одинаковое для одинаковых запросов
+    pub fn create(
+        user_id: Uuid,
+        key: String,
+        request: &str,
+        created_at: DateTime<Utc>,
+    ) -> Result<IdempotencyKey, ErrorApi> {
+        if key.is_empty() || key.len() > MAX_KEY_LEN {
+            return Err(ErrorApi::Validation(format!(
+                "Idempotency key must be 1 to {} characters",
+                MAX_KEY_LEN
+            )));
+        }
+        if !key.chars().all(|c| c.is_ascii_graphic()) {
+            return Err(ErrorApi::Validation(
+                "Idempotency key must be printable ASCII".to_string(),
+            ));
+        }
+
+        Ok(IdempotencyKey {
+            user_id,
+            key,
+            request_hash: hex::encode(Sha256::digest(request.as_bytes())),
+            response: None,
+            // Точность как в БД, чтобы запись можно было узнать по времени
+            created_at: created_at.trunc_subsecs(6),
+        })
+    }
+}
@@ -0,0 +1,240 @@
+use async_trait::async_trait;
+use getset::Getters;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::{
+    domain::{
+        money::Money,
+        transaction::{Operation, Transaction},
+    },
+    infrastructure::error::ErrorApi,
+};
+
+/// Системные счета банка, по одному на валюту
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash, sqlx::Type)]
+#[sqlx(type_name = "system_account", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum SystemAccount {
+    /// Касса: источник пополнений и получатель снятий
+    Cash,
+    /// Обмен валют при переводах между счетами в разных валютах
+    Exchange,
+}
+
+/// Счет проводки: счет клиента или системный
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum LedgerAccount {
+    Account(Uuid),
+    System(SystemAccount),
+}
+
+/// Проводка - изменение баланса одного счета в одной валюте.
+/// Положительная сумма - кредит (баланс растет), отрицательная - дебет.
+/// Создается только из транзакции через [factory::postings]
+#[derive(Debug, Serialize, Getters, Clone)]
+pub struct Posting {
+    #[getset(get = "pub")]
+    id: Uuid,
+
+    #[getset(get = "pub")]
+    transaction_id: Uuid,
+
+    #[getset(get = "pub")]
+    account: LedgerAccount,
+
+    #[getset(get = "pub")]
+    currency: String,
+
+    #[getset(get = "pub")]
+    amount: Money,
+
+    #[getset(get = "pub")]
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Счет, баланс которого не равен сумме его проводок
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub account_id: Uuid,
+    pub currency: String,
+    pub stored_balance: Money,
+    pub ledger_balance: Money,
+}
+
+/// Результат сверки балансов с проводками
+#[derive(Debug, Clone)]
+pub struct Reconciliation {
+    pub accounts_checked: usize,
+    pub mismatches: Vec<Mismatch>,
+    /// Транзакции без проводок или с ненулевой суммой проводок в какой-то валюте
+    pub unbalanced_transactions: Vec<Uuid>,
+}
+
+/// Проводки пишут репозитории транзакций вместе с самой транзакцией
+#[async_trait]
+pub trait LedgerRepository: Send + Sync {
+    async fn reconcile(&self) -> Result<Reconciliation, ErrorApi>;
+}
+
+/// Сумма проводок в каждой валюте равна нулю
+pub fn is_balanced(postings: &[Posting]) -> bool {
+    let mut sums: HashMap<&str, Decimal> = HashMap::new();
+    for posting in postings {
+        *sums.entry(posting.currency()).or_default() += posting.amount().amount();
+    }
+    !postings.is_empty() && sums.values().all(|sum| sum.is_zero())
+}
+
+pub mod factory {
+    use super::*;
+
+    /// Проводки транзакции, `to_currency` - валюта счета получателя
+    pub fn postings(
+        transaction: &Transaction,
+        to_currency: &str,
+    ) -> Result<Vec<Posting>, ErrorApi> {
+        use LedgerAccount::{Account, System};
+        use SystemAccount::{Cash, Exchange};
+
+        let currency = transaction.currency().as_str();
+        let amount = *transaction.amount();
+        let converted = *transaction.converted_amount();
+        let entries = match (
+            transaction.operation(),
+            *transaction.from_id(),
+            *transaction.to_id(),
+        ) {
+            (Operation::DEPOSIT, None, Some(to)) => vec![
+                (System(Cash), currency, -amount),
+                (Account(to), currency, amount),
+            ],
+            (Operation::WITHDRAWAL, Some(from), None) => vec![
+                (Account(from), currency, -amount),
+                (System(Cash), currency, amount),
+            ],
+            (Operation::TRANSFER, Some(from), Some(to)) if currency == to_currency => vec![
+                (Account(from), currency, -amount),
+                (Account(to), currency, converted),
+            ],
+            // Обмен принимает одну валюту и выдает другую, каждая валюта сходится отдельно
+            (Operation::TRANSFER, Some(from), Some(to)) => vec![
+                (Account(from), currency, -amount),
+                (System(Exchange), currency, amount),
+                (System(Exchange), to_currency, -converted),
+                (Account(to), to_currency, converted),
+            ],
+            _ => {
+                return Err(ErrorApi::Validation(
+                    "Transaction accounts do not match its operation".to_string(),
+                ))
+            }
+        };
+
+        let postings: Vec<Posting> = entries
+            .into_iter()
+            .map(|(account, currency, amount)| Posting {
+                id: Uuid::new_v4(),
+                transaction_id: *transaction.id(),
+                account,
+                currency: currency.to_string(),
+                amount,
+                created_at: *transaction.created_at(),
+            })
+            .collect();
+        if !is_balanced(&postings) {
+            return Err(ErrorApi::Inner(format!(
+                "Unbalanced postings for transaction {}",
+                transaction.id()
+            )));
+        }
+        Ok(postings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::transaction;
+    use chrono::Utc;
+    use std::str::FromStr;
+
+    fn money(s: &str, currency: &str) -> Money {
+        Money::new(Decimal::from_str(s).unwrap(), currency).unwrap()
+    }
+
+    fn changes(postings: &[Posting]) -> Vec<(LedgerAccount, &str, String)> {
+        postings
+            .iter()
+            .map(|p| (*p.account(), p.currency().as_str(), p.amount().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_postings() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let cash = LedgerAccount::System(SystemAccount::Cash);
+        let exchange = LedgerAccount::System(SystemAccount::Exchange);
+
+        let deposit = transaction::factory::create_deposit(
+            Uuid::new_v4(),
+            money("10", "USD"),
+            "USD".to_string(),
+            a,
+            Utc::now(),
+        )
+        .unwrap();
+        let postings = factory::postings(&deposit, "USD").unwrap();
+        assert_eq!(
+            changes(&postings),
+            [
+                (cash, "USD", "-10.00".to_string()),
+                (LedgerAccount::Account(a), "USD", "10.00".to_string())
+            ]
+        );
+        assert!(postings.iter().all(|p| p.transaction_id() == deposit.id()));
+
+        let transfer = transaction::factory::create_transfer(
+            Uuid::new_v4(),
+            money("10.05", "USD"),
+            "USD".to_string(),
+            Decimal::from_str("0.5").unwrap(),
+            "EUR",
+            a,
+            b,
+            Utc::now(),
+        )
+        .unwrap();
+        let postings = factory::postings(&transfer, "EUR").unwrap();
+        assert_eq!(
+            changes(&postings),
+            [
+                (LedgerAccount::Account(a), "USD", "-10.05".to_string()),
+                (exchange, "USD", "10.05".to_string()),
+                (exchange, "EUR", "-5.02".to_string()),
+                (LedgerAccount::Account(b), "EUR", "5.02".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_balanced() {
+        let transaction = transaction::factory::create_withdrawal(
+            Uuid::new_v4(),
+            money("3", "JPY"),
+            "JPY".to_string(),
+            Uuid::new_v4(),
+            Utc::now(),
+        )
+        .unwrap();
+        let mut postings = factory::postings(&transaction, "JPY").unwrap();
+        assert!(is_balanced(&postings));
+
+        postings.pop();
+        assert!(!is_balanced(&postings));
+        assert!(!is_balanced(&[]));
+    }
+}
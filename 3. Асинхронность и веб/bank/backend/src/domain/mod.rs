@@ -1,6 +1,10 @@
 pub(crate) mod _macros;
 pub mod account;
 pub mod course;
+pub mod idempotency;
+pub mod ledger;
+pub mod money;
+pub mod statement;
 pub mod token;
 pub mod transaction;
 pub mod user;
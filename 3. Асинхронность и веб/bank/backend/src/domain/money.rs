@@ -0,0 +1,193 @@
+use std::fmt;
+use std::ops::Neg;
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+
+use crate::infrastructure::error::ErrorApi;
+
+/// Единственное правило округления денег и курсов: банковское, к ближайшему четному
+pub const ROUNDING: RoundingStrategy = RoundingStrategy::MidpointNearestEven;
+
+/// Знаков после запятой в курсе, записанном в транзакцию
+pub const RATE_DECIMALS: u32 = 8;
+
+/// Валюты без копеек
+const ZERO_DECIMAL_CURRENCIES: &[&str] = &[
+    "BIF", "CLP", "DJF", "GNF", "ISK", "JPY", "KMF", "KRW", "PYG", "RWF", "UGX", "VND", "VUV",
+    "XAF", "XOF", "XPF",
+];
+
+/// Валюты с тремя знаками после запятой
+const THREE_DECIMAL_CURRENCIES: &[&str] = &["BHD", "IQD", "JOD", "KWD", "LYD", "OMR", "TND"];
+
+/// Число знаков после запятой в валюте (ISO 4217)
+pub fn minor_units(currency: &str) -> u32 {
+    if ZERO_DECIMAL_CURRENCIES.contains(&currency) {
+        0
+    } else if THREE_DECIMAL_CURRENCIES.contains(&currency) {
+        3
+    } else {
+        2
+    }
+}
+
+/// Курс с точностью [RATE_DECIMALS]
+pub fn round_rate(rate: Decimal) -> Decimal {
+    rate.round_dp_with_strategy(RATE_DECIMALS, ROUNDING)
+}
+
+/// Сумма денег, всегда с числом знаков своей валюты.
+///
+/// Валюта хранится рядом (в счете или транзакции), поэтому суммы создаются
+/// через [Money::new] или [Money::round] с ее кодом.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Money(Decimal);
+
+impl Money {
+    /// Точная сумма: дробных знаков не больше, чем у валюты
+    pub fn new(amount: Decimal, currency: &str) -> Result<Self, ErrorApi> {
+        let units = minor_units(currency);
+        if amount.normalize().scale() > units {
+            return Err(ErrorApi::Validation(format!(
+                "Amount {} has more than {} decimal places for {}",
+                amount, units, currency
+            )));
+        }
+        Ok(Self::round(amount, currency))
+    }
+
+    /// Сумма, округленная до знаков валюты по [ROUNDING]
+    pub fn round(amount: Decimal, currency: &str) -> Self {
+        let units = minor_units(currency);
+        let mut amount = amount.round_dp_with_strategy(units, ROUNDING);
+        amount.rescale(units);
+        Self(amount)
+    }
+
+    pub fn zero(currency: &str) -> Self {
+        Self::round(Decimal::ZERO, currency)
+    }
+
+    pub fn amount(&self) -> Decimal {
+        self.0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0.is_sign_negative() && !self.0.is_zero()
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.0.is_sign_positive() && !self.0.is_zero()
+    }
+
+    pub fn checked_add(self, other: Money) -> Result<Self, ErrorApi> {
+        self.0
+            .checked_add(other.0)
+            .map(Self)
+            .ok_or(ErrorApi::Validation("Amount overflow".to_string()))
+    }
+
+    pub fn checked_sub(self, other: Money) -> Result<Self, ErrorApi> {
+        self.0
+            .checked_sub(other.0)
+            .map(Self)
+            .ok_or(ErrorApi::Validation("Amount overflow".to_string()))
+    }
+
+    /// Пересчет по курсу в валюту `to`, с округлением
+    pub fn convert(self, rate: Decimal, to: &str) -> Result<Self, ErrorApi> {
+        let amount = self
+            .0
+            .checked_mul(rate)
+            .ok_or(ErrorApi::Validation("Amount overflow".to_string()))?;
+        Ok(Self::round(amount, to))
+    }
+}
+
+impl Neg for Money {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self(-self.0)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_minor_units_and_rounding() {
+        assert_eq!(Money::zero("USD").to_string(), "0.00");
+        assert_eq!(Money::zero("JPY").to_string(), "0");
+        assert_eq!(Money::new(dec("1.5"), "KWD").unwrap().to_string(), "1.500");
+
+        // Банковское округление: половина - к четному
+        assert_eq!(Money::round(dec("0.125"), "USD").to_string(), "0.12");
+        assert_eq!(Money::round(dec("0.135"), "USD").to_string(), "0.14");
+        assert_eq!(Money::round(dec("-0.125"), "EUR").to_string(), "-0.12");
+        assert_eq!(Money::round(dec("100.5"), "JPY").to_string(), "100");
+        assert_eq!(round_rate(dec("0.123456785")), dec("0.12345678"));
+
+        assert!(Money::new(dec("0.001"), "USD").is_err());
+        assert!(Money::new(dec("10.5"), "JPY").is_err());
+        assert!(Money::new(dec("10.5000"), "USD").is_ok());
+    }
+
+    #[test]
+    fn test_convert() {
+        let usd = Money::new(dec("40"), "USD").unwrap();
+        let eur = usd.convert(dec("0.91234567"), "EUR").unwrap();
+        assert_eq!(eur.to_string(), "36.49");
+        let jpy = usd.convert(dec("151.255"), "JPY").unwrap();
+        assert_eq!(jpy.to_string(), "6050");
+
+        let max = Money::round(Decimal::MAX, "JPY");
+        assert!(max.convert(dec("2"), "JPY").is_err());
+        assert!(max.checked_add(max).is_err());
+    }
+
+    #[test]
+    fn test_no_drift() {
+        const OPERATIONS: usize = 1_000_000;
+        let cent = Money::new(dec("0.01"), "USD").unwrap();
+        let mut balance = Money::zero("USD");
+        let mut float = 0.0_f64;
+        for _ in 0..OPERATIONS {
+            balance = balance.checked_add(cent).unwrap();
+            float += 0.01;
+        }
+        assert_eq!(balance, Money::new(dec("10000"), "USD").unwrap());
+        // То же на f64 уже разошлось
+        assert_ne!(float, 10000.0);
+
+        for _ in 0..OPERATIONS {
+            balance = balance.checked_sub(cent).unwrap();
+        }
+        assert_eq!(balance, Money::zero("USD"));
+
+        // Каждый перевод округляется отдельно: сумма равна количеству одинаковых сумм
+        let rate = round_rate(dec("1") / dec("3"));
+        let mut credited = Money::zero("EUR");
+        let one = cent.convert(rate, "EUR").unwrap();
+        for _ in 0..OPERATIONS {
+            credited = credited
+                .checked_add(cent.convert(rate, "EUR").unwrap())
+                .unwrap();
+        }
+        assert_eq!(credited.amount(), one.amount() * Decimal::from(OPERATIONS));
+    }
+}
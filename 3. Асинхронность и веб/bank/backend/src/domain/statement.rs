@@ -0,0 +1,220 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::{fmt, str::FromStr};
+use uuid::Uuid;
+
+use crate::{
+    domain::{money::Money, transaction::Operation},
+    infrastructure::error::ErrorApi,
+};
+
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+pub const MAX_PAGE_SIZE: usize = 500;
+
+/// Позиция в выписке: строки идут по (created_at, id), следующая страница - строго после
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+/// Для клиента курсор непрозрачен: `<наносекунды>.<id>`
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nanos = self.created_at.timestamp_nanos_opt().unwrap_or_default();
+        write!(f, "{}.{}", nanos, self.id.simple())
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = ErrorApi;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ErrorApi::Validation("Invalid cursor".to_string());
+        let (nanos, id) = s.split_once('.').ok_or_else(invalid)?;
+        let nanos: i64 = nanos.parse().map_err(|_| invalid())?;
+        Ok(Cursor {
+            created_at: DateTime::from_timestamp_nanos(nanos),
+            id: Uuid::try_parse(id).map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Фильтры выписки. Сумма сравнивается по модулю, `to` не включается
+#[derive(Debug, Clone)]
+pub struct StatementFilter {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub operation: Option<Operation>,
+    pub min_amount: Option<Decimal>,
+    pub max_amount: Option<Decimal>,
+    pub after: Option<Cursor>,
+    pub limit: usize,
+}
+
+impl Default for StatementFilter {
+    fn default() -> Self {
+        Self {
+            from: None,
+            to: None,
+            operation: None,
+            min_amount: None,
+            max_amount: None,
+            after: None,
+            limit: DEFAULT_PAGE_SIZE,
+        }
+    }
+}
+
+impl StatementFilter {
+    pub fn validate(&self) -> Result<(), ErrorApi> {
+        if self.limit == 0 || self.limit > MAX_PAGE_SIZE {
+            return Err(ErrorApi::Validation(format!(
+                "Limit must be from 1 to {}",
+                MAX_PAGE_SIZE
+            )));
+        }
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from >= to {
+                return Err(ErrorApi::Validation(
+                    "Date range is empty: from must be before to".to_string(),
+                ));
+            }
+        }
+        if self.min_amount.is_some_and(|min| min.is_sign_negative())
+            || self.max_amount.is_some_and(|max| max.is_sign_negative())
+        {
+            return Err(ErrorApi::Validation(
+                "Amount range cannot be negative".to_string(),
+            ));
+        }
+        if let (Some(min), Some(max)) = (self.min_amount, self.max_amount) {
+            if min > max {
+                return Err(ErrorApi::Validation(
+                    "Amount range is empty: min_amount is greater than max_amount".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Строка проходит фильтры и стоит после курсора
+    pub fn matches(&self, line: &StatementLine) -> bool {
+        let amount = line.amount.amount().abs();
+        self.from.is_none_or(|from| line.created_at >= from)
+            && self.to.is_none_or(|to| line.created_at < to)
+            && self
+                .operation
+                .as_ref()
+                .is_none_or(|operation| *operation == line.operation)
+            && self.min_amount.is_none_or(|min| amount >= min)
+            && self.max_amount.is_none_or(|max| amount <= max)
+            && self
+                .after
+                .is_none_or(|after| (line.created_at, line.id) > (after.created_at, after.id))
+    }
+}
+
+/// Строка выписки - проводка по счету
+#[derive(Debug, Clone)]
+pub struct StatementLine {
+    /// id проводки
+    pub id: Uuid,
+    pub transaction_id: Uuid,
+    pub operation: Operation,
+    /// Второй счет перевода
+    pub counterparty: Option<Uuid>,
+    /// Изменение баланса: зачисление положительное, списание отрицательное
+    pub amount: Money,
+    /// Баланс после строки, по всем проводкам счета без учета фильтров
+    pub balance: Money,
+    pub created_at: DateTime<Utc>,
+}
+
+impl StatementLine {
+    pub fn cursor(&self) -> Cursor {
+        Cursor {
+            created_at: self.created_at,
+            id: self.id,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StatementPage {
+    pub lines: Vec<StatementLine>,
+    /// Курсор следующей страницы, None на последней
+    pub next_cursor: Option<Cursor>,
+}
+
+impl StatementPage {
+    /// Страница из `limit + 1` строк: лишняя строка только говорит, что есть продолжение
+    pub fn from_lines(mut lines: Vec<StatementLine>, limit: usize) -> Self {
+        let has_more = lines.len() > limit;
+        lines.truncate(limit);
+        let next_cursor = if has_more {
+            lines.last().map(StatementLine::cursor)
+        } else {
+            None
+        };
+        Self { lines, next_cursor }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor() {
+        let cursor = Cursor {
+            created_at: Utc::now(),
+            id: Uuid::new_v4(),
+        };
+        assert_eq!(cursor.to_string().parse::<Cursor>().unwrap(), cursor);
+        assert!("abc".parse::<Cursor>().is_err());
+        assert!("1.not-a-uuid".parse::<Cursor>().is_err());
+    }
+
+    #[test]
+    fn test_filter() {
+        let line = StatementLine {
+            id: Uuid::new_v4(),
+            transaction_id: Uuid::new_v4(),
+            operation: Operation::WITHDRAWAL,
+            counterparty: None,
+            amount: Money::new(Decimal::from(-30), "USD").unwrap(),
+            balance: Money::zero("USD"),
+            created_at: Utc::now(),
+        };
+        let filter = StatementFilter {
+            operation: Some(Operation::WITHDRAWAL),
+            min_amount: Some(Decimal::from(10)),
+            max_amount: Some(Decimal::from(30)),
+            ..Default::default()
+        };
+        assert!(filter.validate().is_ok());
+        assert!(filter.matches(&line));
+        assert!(!StatementFilter {
+            after: Some(line.cursor()),
+            ..filter.clone()
+        }
+        .matches(&line));
+        assert!(!StatementFilter {
+            operation: Some(Operation::DEPOSIT),
+            ..filter.clone()
+        }
+        .matches(&line));
+
+        let invalid = StatementFilter {
+            min_amount: Some(Decimal::from(40)),
+            ..filter
+        };
+        assert!(invalid.validate().is_err());
+        let invalid = StatementFilter {
+            limit: MAX_PAGE_SIZE + 1,
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+}
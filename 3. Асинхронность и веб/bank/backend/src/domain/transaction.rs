@@ -1,11 +1,23 @@
 use async_trait::async_trait;
 use getset::Getters;
 use serde::Serialize;
+use std::{fmt, str::FromStr};
 use uuid::Uuid;
 
-use crate::{domain::account::Account, impl_constructor, infrastructure::error::ErrorApi};
+use rust_decimal::Decimal;
 
-#[derive(Debug, Serialize, Clone, sqlx::Type)]
+use crate::{
+    domain::{
+        account::Account,
+        idempotency::Completion,
+        money::Money,
+        statement::{StatementFilter, StatementPage},
+    },
+    impl_constructor,
+    infrastructure::error::ErrorApi,
+};
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq, sqlx::Type)]
 #[sqlx(type_name = "operation", rename_all = "lowercase")]
 pub enum Operation {
     DEPOSIT,
@@ -13,6 +25,31 @@ pub enum Operation {
     TRANSFER,
 }
 
+/// Как в БД: deposit, withdrawal, transfer
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Operation::DEPOSIT => "deposit",
+            Operation::WITHDRAWAL => "withdrawal",
+            Operation::TRANSFER => "transfer",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Operation {
+    type Err = ErrorApi;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "deposit" => Ok(Operation::DEPOSIT),
+            "withdrawal" => Ok(Operation::WITHDRAWAL),
+            "transfer" => Ok(Operation::TRANSFER),
+            _ => Err(ErrorApi::Validation(format!("Unknown operation: {}", s))),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Getters, Clone)]
 pub struct Transaction {
     #[getset(get = "pub")]
@@ -21,8 +58,21 @@ pub struct Transaction {
     #[getset(get = "pub")]
     operation: Operation,
 
+    /// Amount in `currency`
+    #[getset(get = "pub")]
+    amount: Money,
+
+    /// Currency of the source account (of the target one for deposits)
     #[getset(get = "pub")]
-    amount: f64,
+    currency: String,
+
+    /// Conversion rate to the target account currency, 1 for same currency
+    #[getset(get = "pub")]
+    rate: Decimal,
+
+    /// Amount credited in the target account currency
+    #[getset(get = "pub")]
+    converted_amount: Money,
 
     /// uuid account
     #[getset(get = "pub")]
@@ -36,28 +86,48 @@ pub struct Transaction {
     created_at: chrono::DateTime<chrono::Utc>,
 }
 
+/// Операции записывают транзакцию и меняют балансы счетов атомарно:
+/// параллельные операции над одним счетом не теряют изменений,
+/// а списание сверх баланса возвращает ошибку валидации.
+/// `completion` сохраняет ответ по ключу идемпотентности вместе с операцией
 #[async_trait]
 pub trait TransactionRepository: Send + Sync {
-    async fn create_deposit(&mut self, amount: f64, to: &Account) -> Result<Transaction, ErrorApi>;
+    async fn create_deposit(
+        &mut self,
+        amount: Money,
+        to: &Account,
+        completion: Option<Completion>,
+    ) -> Result<Transaction, ErrorApi>;
     async fn create_withdrawal(
         &mut self,
-        amount: f64,
+        amount: Money,
         from: &Account,
+        completion: Option<Completion>,
     ) -> Result<Transaction, ErrorApi>;
     async fn create_transfer(
         &mut self,
-        amount: f64,
+        amount: Money,
+        rate: Decimal,
         from: &Account,
         to: &Account,
+        completion: Option<Completion>,
     ) -> Result<Transaction, ErrorApi>;
     async fn delete(&mut self, transaction: &Transaction) -> Result<(), ErrorApi>;
     async fn get_by_id(&self, id: Uuid) -> Option<Transaction>;
-    async fn gets_by_account(&self, account: &Account) -> Option<Vec<Transaction>>;
+    /// Выписка по счету из проводок, по возрастанию времени
+    async fn statement(
+        &self,
+        account: &Account,
+        filter: &StatementFilter,
+    ) -> Result<StatementPage, ErrorApi>;
 }
 impl_constructor!(token: TransactionToken, Transaction, (
     id: Uuid,
     operation: Operation,
-    amount: f64,
+    amount: Money,
+    currency: String,
+    rate: Decimal,
+    converted_amount: Money,
     from_id: Option<Uuid>,
     to_id: Option<Uuid>,
     created_at: chrono::DateTime<chrono::Utc>
@@ -68,11 +138,12 @@ pub mod factory {
 
     pub fn create_deposit(
         id: Uuid,
-        amount: f64,
+        amount: Money,
+        currency: String,
         to: Uuid,
         created_at: chrono::DateTime<chrono::Utc>,
     ) -> Result<Transaction, ErrorApi> {
-        if amount < 0.0 {
+        if amount.is_negative() {
             return Err(ErrorApi::Validation(
                 "Deposit amount cannot be negative".to_string(),
             ));
@@ -82,6 +153,9 @@ pub mod factory {
             id,
             operation: Operation::DEPOSIT,
             amount,
+            currency,
+            rate: Decimal::ONE,
+            converted_amount: amount,
             from_id: None,
             to_id: Some(to),
             created_at,
@@ -90,11 +164,12 @@ pub mod factory {
 
     pub fn create_withdrawal(
         id: Uuid,
-        amount: f64,
+        amount: Money,
+        currency: String,
         from: Uuid,
         created_at: chrono::DateTime<chrono::Utc>,
     ) -> Result<Transaction, ErrorApi> {
-        if amount < 0.0 {
+        if amount.is_negative() {
             return Err(ErrorApi::Validation(
                 "Deposit amount cannot be negative".to_string(),
             ));
@@ -103,25 +178,38 @@ pub mod factory {
             id,
             operation: Operation::WITHDRAWAL,
             amount,
+            currency,
+            rate: Decimal::ONE,
+            converted_amount: amount,
             from_id: Some(from),
             to_id: None,
             created_at,
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn create_transfer(
         id: Uuid,
-        amount: f64,
+        amount: Money,
+        currency: String,
+        rate: Decimal,
+        to_currency: &str,
         from: Uuid,
         to: Uuid,
         created_at: chrono::DateTime<chrono::Utc>,
     ) -> Result<Transaction, ErrorApi> {
-        if amount < 0.0 {
+        if amount.is_negative() {
             return Err(ErrorApi::Validation(
                 "Deposit amount cannot be negative".to_string(),
             ));
         }
 
+        if rate <= Decimal::ZERO {
+            return Err(ErrorApi::Validation(
+                "Conversion rate must be positive".to_string(),
+            ));
+        }
+
         if to == from {
             return Err(ErrorApi::Validation(
                 "Transfer to and from cannot be the same".to_string(),
@@ -132,6 +220,9 @@ pub mod factory {
             id,
             operation: Operation::TRANSFER,
             amount,
+            currency,
+            rate,
+            converted_amount: amount.convert(rate, to_currency)?,
             from_id: Some(from),
             to_id: Some(to),
             created_at,
@@ -8,6 +8,8 @@ pub struct Config {
     pub jwt_secret: String,
     pub cors_origin: String,
     pub api_key: String,
+    /// Ключ для /api/admin, без него админские запросы запрещены
+    pub admin_key: Option<String>,
 }
 
 impl Config {
@@ -20,6 +22,9 @@ impl Config {
         let jwt_secret = std::env::var("JWT_SECRET")?;
         let cors_origin = std::env::var("CORS_ORIGIN").unwrap_or_else(|_| "*".into());
         let api_key = std::env::var("API_KEY")?;
+        let admin_key = std::env::var("ADMIN_KEY")
+            .ok()
+            .filter(|key| !key.is_empty());
 
         Ok(Self {
             database_url,
@@ -28,6 +33,7 @@ impl Config {
             jwt_secret,
             cors_origin,
             api_key,
+            admin_key,
         })
     }
 }
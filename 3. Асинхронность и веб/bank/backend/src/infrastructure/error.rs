@@ -12,6 +12,9 @@ pub enum ErrorApi {
     #[error("Not found: {0}")]
     NotFound(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("Unauthorized")]
     Unauthorized(String),
 
@@ -28,6 +31,7 @@ impl ResponseError for ErrorApi {
             ErrorApi::Forbidden(_) => StatusCode::FORBIDDEN,
             ErrorApi::Validation(_) => StatusCode::BAD_REQUEST,
             ErrorApi::NotFound(_) => StatusCode::NOT_FOUND,
+            ErrorApi::Conflict(_) => StatusCode::CONFLICT,
             ErrorApi::Unauthorized(_) => StatusCode::UNAUTHORIZED,
             ErrorApi::DataBase(_) => StatusCode::INTERNAL_SERVER_ERROR,
             ErrorApi::Inner(_) => StatusCode::INTERNAL_SERVER_ERROR,
@@ -1,5 +1,6 @@
 use crate::domain::{
-    account::Account, course::Course, token::RefreshToken, transaction::Transaction, user::User,
+    account::Account, course::Course, idempotency::IdempotencyKey, ledger::Posting,
+    token::RefreshToken, transaction::Transaction, user::User,
 };
 use chrono::{DateTime, Utc};
 use std::{collections::HashMap, sync::Arc};
@@ -13,10 +14,14 @@ pub struct State {
     accounts: Arc<Mutex<HashMap<uuid::Uuid, HashMap<uuid::Uuid, Account>>>>,
     /// HashMap<account_id, HashMap<transaction_id, transaction>>
     transactions: Arc<Mutex<HashMap<uuid::Uuid, HashMap<uuid::Uuid, Transaction>>>>,
+    /// Проводки всех транзакций по порядку записи
+    postings: Arc<Mutex<Vec<Posting>>>,
     /// HashMap<refresh_token_hash, user_id>
     refresh_tokens: Arc<Mutex<HashMap<String, RefreshToken>>>,
     /// HashMap<time_update_utc, course>
     courses: Arc<Mutex<HashMap<DateTime<Utc>, Course>>>,
+    /// HashMap<(user_id, idempotency_key), key>
+    idempotency_keys: Arc<Mutex<HashMap<(uuid::Uuid, String), IdempotencyKey>>>,
 }
 
 impl State {
@@ -25,8 +30,10 @@ impl State {
             users: Arc::new(Mutex::new(HashMap::new())),
             accounts: Arc::new(Mutex::new(HashMap::new())),
             transactions: Arc::new(Mutex::new(HashMap::new())),
+            postings: Arc::new(Mutex::new(Vec::new())),
             refresh_tokens: Arc::new(Mutex::new(HashMap::new())),
             courses: Arc::new(Mutex::new(HashMap::new())),
+            idempotency_keys: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
@@ -46,6 +53,10 @@ impl State {
         self.transactions.lock().await
     }
 
+    pub async fn postings(&self) -> MutexGuard<'_, Vec<Posting>> {
+        self.postings.lock().await
+    }
+
     pub async fn refresh_tokens(&self) -> MutexGuard<'_, HashMap<String, RefreshToken>> {
         self.refresh_tokens.lock().await
     }
@@ -53,4 +64,10 @@ impl State {
     pub async fn courses(&self) -> MutexGuard<'_, HashMap<DateTime<Utc>, Course>> {
         self.courses.lock().await
     }
+
+    pub async fn idempotency_keys(
+        &self,
+    ) -> MutexGuard<'_, HashMap<(uuid::Uuid, String), IdempotencyKey>> {
+        self.idempotency_keys.lock().await
+    }
 }
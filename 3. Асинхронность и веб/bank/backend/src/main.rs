@@ -17,7 +17,12 @@ use infrastructure::{config::Config, logging::init_logging, migrate};
 use presentation::middleware::{RequestIdMiddleware, TimingMiddleware};
 
 use crate::{
-    data::Database, infrastructure::state::State, presentation::middleware::csrf::CsrfMiddleware,
+    data::Database,
+    infrastructure::state::State,
+    presentation::{
+        consts::{IDEMPOTENCY_KEY_HEADER, IDEMPOTENT_REPLAYED_HEADER},
+        middleware::csrf::CsrfMiddleware,
+    },
 };
 
 #[actix_web::main]
@@ -59,7 +64,11 @@ async fn main() -> std::io::Result<()> {
             .allowed_headers(vec![
                 actix_web::http::header::CONTENT_TYPE,
                 actix_web::http::header::AUTHORIZATION,
+                actix_web::http::header::HeaderName::from_static(IDEMPOTENCY_KEY_HEADER),
             ])
+            .expose_headers(vec![actix_web::http::header::HeaderName::from_static(
+                IDEMPOTENT_REPLAYED_HEADER,
+            )])
             .supports_credentials()
             .max_age(3600);
 
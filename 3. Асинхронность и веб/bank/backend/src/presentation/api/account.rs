@@ -1,22 +1,30 @@
 use crate::{
-    application::{account, user},
+    application::{account, statement, user},
     data::Database,
     infrastructure::error::ErrorApi,
-    presentation::{dto::account::AccountDto, extractor::user::UserExtractor},
+    presentation::{
+        dto::{
+            account::{AccountDto, CreateAccountDto},
+            statement::{self as dto, StatementDto, StatementFormat, StatementQuery},
+        },
+        extractor::user::UserExtractor,
+    },
 };
-use actix_web::{get, post, web, HttpResponse, Responder};
+use actix_web::{get, http::header, post, web, HttpResponse, Responder};
 use uuid::Uuid;
 
 #[post("/account/create")]
 async fn create_account(
     db: web::Data<Database>,
     user: UserExtractor,
+    body: Option<web::Json<CreateAccountDto>>,
 ) -> actix_web::Result<impl Responder> {
     let user = user::get_user_by_id(db.clone().into_inner(), user.id)
         .await
         .ok_or(ErrorApi::NotFound("User not found".to_string()))?;
 
-    let account = account::create_account(db.into_inner(), &user, None).await?;
+    let CreateAccountDto { currency } = body.map(|b| b.into_inner()).unwrap_or_default();
+    let account = account::create_account(db.into_inner(), &user, None, currency).await?;
     Ok(HttpResponse::Created().json(serde_json::json!(AccountDto::from(account))))
 }
 
@@ -36,6 +44,50 @@ async fn get_account_by_id(
     Ok(HttpResponse::Ok().json(serde_json::json!(AccountDto::from(account))))
 }
 
+/// Выписка по счету: страница в JSON, а с `format=csv|jsonl` - вся выписка по фильтрам файлом
+#[get("/account/{id}/statement")]
+async fn get_statement(
+    db: web::Data<Database>,
+    user: UserExtractor,
+    path: web::Path<Uuid>,
+    query: web::Query<StatementQuery>,
+) -> actix_web::Result<impl Responder> {
+    let account_id = path.into_inner();
+    let user = user::get_user_by_id(db.clone().into_inner(), user.id)
+        .await
+        .ok_or(ErrorApi::NotFound("User not found".to_string()))?;
+    let filter = query.filter()?;
+
+    let (content_type, extension, body) = match query.format {
+        StatementFormat::Json => {
+            let (account, page) =
+                statement::statement(db.into_inner(), &user, account_id, &filter).await?;
+            return Ok(HttpResponse::Ok().json(StatementDto::new(&account, page)));
+        }
+        StatementFormat::Csv => {
+            let (account, lines) =
+                statement::export(db.into_inner(), &user, account_id, filter).await?;
+            ("text/csv", "csv", dto::to_csv(&account, lines))
+        }
+        StatementFormat::Jsonl => {
+            let (_, lines) = statement::export(db.into_inner(), &user, account_id, filter).await?;
+            ("application/x-ndjson", "jsonl", dto::to_json_lines(lines)?)
+        }
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"statement-{}.{}\"",
+                account_id, extension
+            ),
+        ))
+        .body(body))
+}
+
 pub fn configure(cfg: &mut actix_web::web::ServiceConfig) {
-    cfg.service(create_account).service(get_account_by_id);
+    cfg.service(create_account)
+        .service(get_account_by_id)
+        .service(get_statement);
 }
@@ -0,0 +1,20 @@
+use crate::{
+    application::ledger,
+    data::Database,
+    presentation::{dto::ledger::ReconciliationDto, extractor::admin::AdminExtractor},
+};
+use actix_web::{get, web, HttpResponse, Responder};
+
+/// Счета, чей баланс расходится с проводками, и несбалансированные транзакции
+#[get("/admin/reconciliation")]
+async fn reconciliation(
+    db: web::Data<Database>,
+    _admin: AdminExtractor,
+) -> actix_web::Result<impl Responder> {
+    let report = ledger::reconcile(db.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(ReconciliationDto::from(report)))
+}
+
+pub fn configure(cfg: &mut actix_web::web::ServiceConfig) {
+    cfg.service(reconciliation);
+}
@@ -1,7 +1,12 @@
-use crate::{application, infrastructure::config::Config};
+use crate::{
+    application,
+    domain::money::Money,
+    infrastructure::{config::Config, error::ErrorApi},
+    presentation::dto::course::{ConvertDto, ConvertQuery},
+};
 use actix_web::{
     get,
-    web::{Data, ServiceConfig},
+    web::{Data, Query, ServiceConfig},
 };
 use reqwest::Client;
 
@@ -17,6 +22,36 @@ async fn get_course(
     Ok(actix_web::HttpResponse::Ok().json(course))
 }
 
+/// Сколько получится при переводе `amount` из `from` в `to` по текущему курсу
+#[get("/course/convert")]
+async fn convert(
+    db: Data<crate::data::Database>,
+    client: Data<Client>,
+    config: Data<Config>,
+    query: Query<ConvertQuery>,
+) -> Result<actix_web::HttpResponse, actix_web::Error> {
+    let ConvertQuery { from, to, amount } = query.into_inner();
+    let (from, to) = (from.trim().to_uppercase(), to.trim().to_uppercase());
+    let amount = Money::new(amount, &from)?;
+    if amount.is_negative() {
+        return Err(ErrorApi::Validation("Amount cannot be negative".to_string()).into());
+    }
+
+    let course =
+        application::course::get_course(db.into_inner(), client.into_inner(), config.into_inner())
+            .await?;
+    let rate = course.rate(&from, &to)?;
+    let converted_amount = amount.convert(rate, &to)?;
+    Ok(actix_web::HttpResponse::Ok().json(ConvertDto {
+        from,
+        to,
+        amount,
+        rate,
+        converted_amount,
+        time_update_utc: *course.time_update_utc(),
+    }))
+}
+
 pub fn configure(cfg: &mut ServiceConfig) {
-    cfg.service(get_course);
+    cfg.service(get_course).service(convert);
 }
@@ -1,4 +1,5 @@
 mod account;
+mod admin;
 pub mod course;
 mod general;
 pub mod transaction;
@@ -6,6 +7,7 @@ mod user;
 
 use account::configure as account_configure;
 use actix_web::web;
+use admin::configure as admin_configure;
 use course::configure as course_configure;
 use general::configure as general_configure;
 use transaction::configure as transaction_configure;
@@ -18,6 +20,7 @@ pub fn configure(cfg: &mut actix_web::web::ServiceConfig) {
             .configure(user_configure)
             .configure(account_configure)
             .configure(course_configure)
-            .configure(transaction_configure),
+            .configure(transaction_configure)
+            .configure(admin_configure),
     );
 }
@@ -1,19 +1,36 @@
 use crate::{
-    application::{transaction, user},
+    application::{
+        idempotency::{self, Idempotent},
+        transaction, user,
+    },
     data::Database,
-    infrastructure::error::ErrorApi,
+    infrastructure::{config::Config, error::ErrorApi},
     presentation::{
+        consts::IDEMPOTENT_REPLAYED_HEADER,
         dto::transaction::{DepositDto, TransactionDto, TransferDto, WithdrawalDto},
-        extractor::user::UserExtractor,
+        extractor::{idempotency::IdempotencyKeyExtractor, user::UserExtractor},
     },
 };
-use actix_web::{post, web, HttpResponse, Responder};
+use actix_web::{http::header::ContentType, post, web, HttpResponse, Responder};
+use reqwest::Client;
 use uuid::Uuid;
 
+/// 201 с телом операции, повтор по Idempotency-Key помечается заголовком
+fn created(response: Idempotent) -> HttpResponse {
+    let mut builder = HttpResponse::Created();
+    if response.replayed {
+        builder.insert_header((IDEMPOTENT_REPLAYED_HEADER, "true"));
+    }
+    builder
+        .content_type(ContentType::json())
+        .body(response.body)
+}
+
 #[post("/account/{id}/deposit")]
 async fn deposit(
     db: web::Data<Database>,
     user: UserExtractor,
+    idempotency_key: IdempotencyKeyExtractor,
     body: web::Json<DepositDto>,
     path: web::Path<Uuid>,
 ) -> actix_web::Result<impl Responder> {
@@ -24,14 +41,23 @@ async fn deposit(
         .await
         .ok_or(ErrorApi::NotFound("User not found".to_string()))?;
 
-    let transaction = transaction::deposit(db.into_inner(), &user, account_id, amount).await?;
-    Ok(HttpResponse::Created().json(serde_json::json!(TransactionDto::from(transaction))))
+    let request = format!("deposit {} {}", account_id, amount.normalize());
+    let response = idempotency::execute::<TransactionDto, _, _>(
+        db.clone().into_inner(),
+        &user,
+        idempotency_key.0,
+        &request,
+        |completion| transaction::deposit(db.into_inner(), &user, account_id, amount, completion),
+    )
+    .await?;
+    Ok(created(response))
 }
 
 #[post("/account/{id}/withdrawal")]
 async fn withdrawal(
     db: web::Data<Database>,
     user: UserExtractor,
+    idempotency_key: IdempotencyKeyExtractor,
     body: web::Json<WithdrawalDto>,
     path: web::Path<Uuid>,
 ) -> actix_web::Result<impl Responder> {
@@ -42,14 +68,25 @@ async fn withdrawal(
         .await
         .ok_or(ErrorApi::NotFound("User not found".to_string()))?;
 
-    let transaction = transaction::withdraw(db.into_inner(), &user, account_id, amount).await?;
-    Ok(HttpResponse::Created().json(serde_json::json!(TransactionDto::from(transaction))))
+    let request = format!("withdrawal {} {}", account_id, amount.normalize());
+    let response = idempotency::execute::<TransactionDto, _, _>(
+        db.clone().into_inner(),
+        &user,
+        idempotency_key.0,
+        &request,
+        |completion| transaction::withdraw(db.into_inner(), &user, account_id, amount, completion),
+    )
+    .await?;
+    Ok(created(response))
 }
 
 #[post("/account/{id}/transfer")]
 async fn transfer(
     db: web::Data<Database>,
+    client: web::Data<Client>,
+    config: web::Data<Config>,
     user: UserExtractor,
+    idempotency_key: IdempotencyKeyExtractor,
     body: web::Json<TransferDto>,
     path: web::Path<Uuid>,
 ) -> actix_web::Result<impl Responder> {
@@ -63,9 +100,32 @@ async fn transfer(
         .await
         .ok_or(ErrorApi::NotFound("User not found".to_string()))?;
 
-    let transaction =
-        transaction::transfer(db.into_inner(), &user, account_id, to_account_id, amount).await?;
-    Ok(HttpResponse::Created().json(serde_json::json!(TransactionDto::from(transaction))))
+    let request = format!(
+        "transfer {} {} {}",
+        account_id,
+        to_account_id,
+        amount.normalize()
+    );
+    let response = idempotency::execute::<TransactionDto, _, _>(
+        db.clone().into_inner(),
+        &user,
+        idempotency_key.0,
+        &request,
+        |completion| {
+            transaction::transfer(
+                db.into_inner(),
+                client.into_inner(),
+                config.into_inner(),
+                &user,
+                account_id,
+                to_account_id,
+                amount,
+                completion,
+            )
+        },
+    )
+    .await?;
+    Ok(created(response))
 }
 
 pub fn configure(cfg: &mut actix_web::web::ServiceConfig) {
@@ -2,6 +2,9 @@ pub const USER_ID_HEADER: &str = "x-user-id";
 pub const REFRESH_COOKIE: &str = "refresh-token";
 pub const CSRF_TOKEN_COOKIE: &str = "csrf-token";
 pub const CSRF_TOKEN_HEADER: &str = "x-csrf-token";
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+pub const ADMIN_KEY_HEADER: &str = "x-admin-key";
 
 pub(super) const EXCLUDE_PATHS: &[&str] =
     &["/api/auth/login", "/api/auth/register", "/api/auth/refresh"];
@@ -1,9 +1,17 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::money::Money;
+
+#[derive(Deserialize, Default)]
+pub struct CreateAccountDto {
+    pub currency: Option<String>,
+}
 
 #[derive(Serialize)]
 pub struct AccountDto {
     pub id: uuid::Uuid,
-    pub balance: f64,
+    pub balance: Money,
+    pub currency: String,
 }
 
 impl From<crate::domain::account::Account> for AccountDto {
@@ -11,6 +19,7 @@ impl From<crate::domain::account::Account> for AccountDto {
         Self {
             id: *account.id(),
             balance: *account.balance(),
+            currency: account.currency().clone(),
         }
     }
 }
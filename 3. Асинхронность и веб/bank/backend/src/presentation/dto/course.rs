@@ -0,0 +1,23 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::money::Money;
+
+#[derive(Deserialize)]
+pub struct ConvertQuery {
+    pub from: String,
+    pub to: String,
+    pub amount: Decimal,
+}
+
+#[derive(Serialize)]
+pub struct ConvertDto {
+    pub from: String,
+    pub to: String,
+    pub amount: Money,
+    pub rate: Decimal,
+    pub converted_amount: Money,
+    /// Время курса, по которому посчитано
+    pub time_update_utc: DateTime<Utc>,
+}
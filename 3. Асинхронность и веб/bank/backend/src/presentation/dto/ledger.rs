@@ -0,0 +1,46 @@
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::domain::{
+    ledger::{Mismatch, Reconciliation},
+    money::Money,
+};
+
+#[derive(Serialize)]
+pub struct MismatchDto {
+    pub account_id: Uuid,
+    pub currency: String,
+    pub stored_balance: Money,
+    pub ledger_balance: Money,
+}
+
+#[derive(Serialize)]
+pub struct ReconciliationDto {
+    pub accounts_checked: usize,
+    /// Сверка прошла без расхождений
+    pub consistent: bool,
+    pub mismatches: Vec<MismatchDto>,
+    pub unbalanced_transactions: Vec<Uuid>,
+}
+
+impl From<Mismatch> for MismatchDto {
+    fn from(mismatch: Mismatch) -> Self {
+        Self {
+            account_id: mismatch.account_id,
+            currency: mismatch.currency,
+            stored_balance: mismatch.stored_balance,
+            ledger_balance: mismatch.ledger_balance,
+        }
+    }
+}
+
+impl From<Reconciliation> for ReconciliationDto {
+    fn from(report: Reconciliation) -> Self {
+        Self {
+            accounts_checked: report.accounts_checked,
+            consistent: report.mismatches.is_empty() && report.unbalanced_transactions.is_empty(),
+            mismatches: report.mismatches.into_iter().map(Into::into).collect(),
+            unbalanced_transactions: report.unbalanced_transactions,
+        }
+    }
+}
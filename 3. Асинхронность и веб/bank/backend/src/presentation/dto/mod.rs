@@ -1,3 +1,6 @@
 pub mod account;
+pub mod course;
+pub mod ledger;
+pub mod statement;
 pub mod transaction;
 pub mod user;
@@ -0,0 +1,128 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    domain::{
+        account::Account,
+        money::Money,
+        statement::{StatementFilter, StatementLine, StatementPage, DEFAULT_PAGE_SIZE},
+    },
+    infrastructure::error::ErrorApi,
+};
+
+/// Формат ответа: страница в JSON или вся выписка файлом
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StatementFormat {
+    #[default]
+    Json,
+    Csv,
+    Jsonl,
+}
+
+#[derive(Deserialize)]
+pub struct StatementQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub operation: Option<String>,
+    pub min_amount: Option<Decimal>,
+    pub max_amount: Option<Decimal>,
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub format: StatementFormat,
+}
+
+impl StatementQuery {
+    pub fn filter(&self) -> Result<StatementFilter, ErrorApi> {
+        Ok(StatementFilter {
+            from: self.from,
+            to: self.to,
+            operation: self.operation.as_deref().map(str::parse).transpose()?,
+            min_amount: self.min_amount,
+            max_amount: self.max_amount,
+            after: self.cursor.as_deref().map(str::parse).transpose()?,
+            limit: self.limit.unwrap_or(DEFAULT_PAGE_SIZE),
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct StatementLineDto {
+    pub id: Uuid,
+    pub transaction_id: Uuid,
+    pub operation: String,
+    pub counterparty: Option<Uuid>,
+    pub amount: Money,
+    pub balance: Money,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct StatementDto {
+    pub account_id: Uuid,
+    pub currency: String,
+    pub lines: Vec<StatementLineDto>,
+    /// Передать в `cursor` за следующей страницей, null на последней
+    pub next_cursor: Option<String>,
+}
+
+impl From<StatementLine> for StatementLineDto {
+    fn from(line: StatementLine) -> Self {
+        Self {
+            id: line.id,
+            transaction_id: line.transaction_id,
+            operation: line.operation.to_string(),
+            counterparty: line.counterparty,
+            amount: line.amount,
+            balance: line.balance,
+            created_at: line.created_at,
+        }
+    }
+}
+
+impl StatementDto {
+    pub fn new(account: &Account, page: StatementPage) -> Self {
+        Self {
+            account_id: *account.id(),
+            currency: account.currency().clone(),
+            lines: page.lines.into_iter().map(Into::into).collect(),
+            next_cursor: page.next_cursor.map(|cursor| cursor.to_string()),
+        }
+    }
+}
+
+/// Выписка в CSV: в полях только uuid, числа и даты, экранирование не нужно
+pub fn to_csv(account: &Account, lines: Vec<StatementLine>) -> String {
+    let mut csv =
+        String::from("created_at,transaction_id,operation,counterparty,amount,balance,currency\n");
+    for line in lines {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            line.created_at.to_rfc3339(),
+            line.transaction_id,
+            line.operation,
+            line.counterparty
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            line.amount,
+            line.balance,
+            account.currency()
+        ));
+    }
+    csv
+}
+
+/// Выписка в JSON Lines: по объекту [StatementLineDto] на строку
+pub fn to_json_lines(lines: Vec<StatementLine>) -> Result<String, ErrorApi> {
+    let mut jsonl = String::new();
+    for line in lines {
+        let json = serde_json::to_string(&StatementLineDto::from(line))
+            .map_err(|e| ErrorApi::Inner(e.to_string()))?;
+        jsonl.push_str(&json);
+        jsonl.push('\n');
+    }
+    Ok(jsonl)
+}
@@ -1,28 +1,32 @@
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::domain::transaction::Operation;
+use crate::domain::{money::Money, transaction::Operation};
 
 #[derive(Deserialize)]
 pub struct WithdrawalDto {
-    pub amount: f64,
+    pub amount: Decimal,
 }
 
 #[derive(Deserialize)]
 pub struct DepositDto {
-    pub amount: f64,
+    pub amount: Decimal,
 }
 
 #[derive(Deserialize)]
 pub struct TransferDto {
     pub to_account_id: Uuid,
-    pub amount: f64,
+    pub amount: Decimal,
 }
 
 #[derive(Serialize)]
 pub struct TransactionDto {
     pub id: Uuid,
-    pub amount: f64,
+    pub amount: Money,
+    pub currency: String,
+    pub rate: Decimal,
+    pub converted_amount: Money,
     pub from: Option<Uuid>,
     pub to: Option<Uuid>,
     pub opeation: Operation,
@@ -34,6 +38,9 @@ impl From<crate::domain::transaction::Transaction> for TransactionDto {
         Self {
             id: *transaction.id(),
             amount: *transaction.amount(),
+            currency: transaction.currency().clone(),
+            rate: *transaction.rate(),
+            converted_amount: *transaction.converted_amount(),
             from: *transaction.from_id(),
             to: *transaction.to_id(),
             opeation: transaction.operation().clone(),
@@ -0,0 +1,40 @@
+use actix_web::{dev::Payload, web::Data, Error, FromRequest, HttpRequest};
+use std::future::{ready, Ready};
+
+use crate::{
+    infrastructure::{config::Config, error::ErrorApi},
+    presentation::consts::ADMIN_KEY_HEADER,
+};
+
+/// Запрос с ключом администратора из ADMIN_KEY
+#[derive(Debug, Clone)]
+pub struct AdminExtractor;
+
+impl FromRequest for AdminExtractor {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let Some(admin_key) = req
+            .app_data::<Data<Config>>()
+            .and_then(|config| config.admin_key.clone())
+        else {
+            return ready(Err(ErrorApi::Forbidden(
+                "Admin API is disabled".to_string(),
+            )
+            .into()));
+        };
+
+        let key = req
+            .headers()
+            .get(ADMIN_KEY_HEADER)
+            .and_then(|h| h.to_str().ok());
+        match key {
+            Some(key) if key == admin_key => ready(Ok(AdminExtractor)),
+            _ => ready(Err(ErrorApi::Unauthorized(
+                "Missing or invalid admin key".to_string(),
+            )
+            .into())),
+        }
+    }
+}
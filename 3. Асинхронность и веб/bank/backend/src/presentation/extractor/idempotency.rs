@@ -0,0 +1,27 @@
+use actix_web::{dev::Payload, Error, FromRequest, HttpRequest};
+use serde::Serialize;
+use std::future::{ready, Ready};
+
+use crate::{infrastructure::error::ErrorApi, presentation::consts::IDEMPOTENCY_KEY_HEADER};
+
+/// Необязательный заголовок Idempotency-Key
+#[derive(Debug, Clone, Serialize)]
+pub struct IdempotencyKeyExtractor(pub Option<String>);
+
+impl FromRequest for IdempotencyKeyExtractor {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let Some(header) = req.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+            return ready(Ok(IdempotencyKeyExtractor(None)));
+        };
+        match header.to_str() {
+            Ok(key) => ready(Ok(IdempotencyKeyExtractor(Some(key.to_string())))),
+            Err(_) => ready(Err(ErrorApi::Validation(
+                "Invalid Idempotency-Key header".to_string(),
+            )
+            .into())),
+        }
+    }
+}
@@ -1,2 +1,4 @@
+pub mod admin;
+pub mod idempotency;
 pub mod refresh;
 pub mod user;